@@ -0,0 +1,132 @@
+//! Probing whether the current process may act on a service without elevation.
+use crate::{Result, ServiceManager, ServiceOp};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// What the calling user can change for one service with its own privileges.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ControlCapability {
+    /// `supervise/ok` and `supervise/control` are writable, so runsv accepts commands.
+    pub control: bool,
+    /// Links can be added to or removed from the enabled directory.
+    pub enable: bool,
+}
+
+impl ControlCapability {
+    /// Whether `op` can be carried out without going through pkexec.
+    pub fn allows(self, op: ServiceOp) -> bool {
+        match op {
+            ServiceOp::Enable | ServiceOp::Disable => self.enable,
+            _ => self.control,
+        }
+    }
+}
+
+impl ServiceManager {
+    /// Check write access to the service's control FIFOs and to the enabled directory.
+    ///
+    /// A service whose supervisor is not running reports `control: false`.
+    pub fn can_control(&self, service: &str) -> Result<ControlCapability> {
+        self.validate_service_name(service)?;
+        let supervise = self.enabled_dir.join(service).join("supervise");
+        Ok(ControlCapability {
+            control: writable(&supervise.join("ok")) && writable(&supervise.join("control")),
+            enable: writable(&self.enabled_dir),
+        })
+    }
+
+    /// Whether the service's state can be read without elevation: runsv's status record
+    /// is readable, or there is no supervisor to ask. runsv creates `supervise` with
+    /// mode 0700, so this usually needs root for enabled system services.
+    pub fn can_read_state(&self, service: &str) -> Result<bool> {
+        self.validate_service_name(service)?;
+        Ok(state_readable(&self.enabled_dir.join(service)))
+    }
+
+    /// [`ServiceManager::can_read_state`] for every enabled service, plus both
+    /// directories being listable, so a listing matches what root would see.
+    pub fn can_read_states(&self) -> bool {
+        if !readable(&self.definitions_dir) || !readable(&self.enabled_dir) {
+            return false;
+        }
+        let Ok(entries) = std::fs::read_dir(&self.enabled_dir) else {
+            return false;
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .all(|entry| state_readable(&entry.path()))
+    }
+
+    /// Whether the service's current svlogd file can be read without elevation.
+    pub fn can_read_logs(&self, service: &str) -> Result<bool> {
+        Ok(self
+            .log_dir(service)?
+            .is_some_and(|dir| readable(&dir.join("current"))))
+    }
+}
+
+fn state_readable(service_dir: &Path) -> bool {
+    let supervise = service_dir.join("supervise");
+    !supervise.exists() || readable(&supervise.join("status"))
+}
+
+fn readable(path: &Path) -> bool {
+    accessible(path, libc::R_OK)
+}
+
+fn writable(path: &Path) -> bool {
+    accessible(path, libc::W_OK)
+}
+
+fn accessible(path: &Path, mode: libc::c_int) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // access(2) checks against the real uid, which is what a later open would use here.
+    unsafe { libc::access(path.as_ptr(), mode) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::FakeRunit;
+    use crate::{ServiceManager, ServiceOp};
+    use std::time::SystemTime;
+
+    #[test]
+    fn reports_missing_supervisor_as_uncontrollable() {
+        let root = std::env::temp_dir().join(format!("runkit-access-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("service/sshd")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        let capability = manager.can_control("sshd").unwrap();
+        assert!(!capability.control);
+        assert!(capability.enable);
+        assert!(capability.allows(ServiceOp::Enable));
+        assert!(!capability.allows(ServiceOp::Restart));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn reads_states_and_logs_it_can_open() {
+        let tree = FakeRunit::new("access-read");
+        tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        tree.enable("sshd");
+        tree.set_running("sshd", 42, SystemTime::now());
+        tree.add_service("ntpd", "#!/bin/sh\nexec ntpd\n");
+
+        let manager = tree.manager();
+        assert!(manager.can_read_state("sshd").unwrap());
+        // Nothing supervises it, so there is nothing privileged to read.
+        assert!(manager.can_read_state("ntpd").unwrap());
+        assert!(manager.can_read_states());
+        // svlogd has not written `current` yet.
+        assert!(!manager.can_read_logs("sshd").unwrap());
+        let logger = tree.add_logger("sshd");
+        std::fs::write(logger.join("main/current"), "").unwrap();
+        assert!(manager.can_read_logs("sshd").unwrap());
+        assert!(manager.can_read_state("../sshd").is_err());
+    }
+}
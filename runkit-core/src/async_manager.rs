@@ -0,0 +1,433 @@
+//! Non-blocking counterparts of the `ServiceManager` queries for tokio runtimes.
+use crate::logs::{ReverseTail, TAIL_BLOCK_SIZE};
+use crate::supervise::{SupervisePresence, has_reader};
+use crate::template::is_template_dir_name;
+use crate::{
+    BrokenLink, DOWN_REASON_FILE, DesiredState, DownReason, FinishHistory, MASK_FILE,
+    METADATA_FILE, RestartTracker, Result, ServiceError, ServiceHealth, ServiceInfo,
+    ServiceLogEntry, ServiceManager, ServiceMetadata, ServiceRuntimeState, SuperviseStatus,
+    Transition, run_script_dependencies, split_instance, validate_service_name,
+};
+use std::io::{ErrorKind, SeekFrom};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::process::Command;
+
+/// Async variant of [`ServiceManager`] built on `tokio::fs` and `tokio::process`.
+///
+/// It always talks to the runit tree directly; custom backends are not consulted.
+#[derive(Debug, Clone)]
+pub struct AsyncServiceManager {
+    definitions_dir: PathBuf,
+    enabled_dir: PathBuf,
+    sv_command: PathBuf,
+    runlevels_dir: Option<PathBuf>,
+    restarts: Arc<Mutex<RestartTracker>>,
+    finishes: Arc<Mutex<FinishHistory>>,
+}
+
+impl Default for AsyncServiceManager {
+    fn default() -> Self {
+        Self::from(&ServiceManager::default())
+    }
+}
+
+impl From<&ServiceManager> for AsyncServiceManager {
+    fn from(manager: &ServiceManager) -> Self {
+        AsyncServiceManager {
+            definitions_dir: manager.definitions_dir().to_path_buf(),
+            enabled_dir: manager.enabled_dir().to_path_buf(),
+            sv_command: manager.sv_command_path().to_path_buf(),
+            runlevels_dir: manager.runlevels_dir(),
+            restarts: Arc::clone(&manager.restarts),
+            finishes: Arc::clone(&manager.finishes),
+        }
+    }
+}
+
+impl AsyncServiceManager {
+    pub fn new(definitions_dir: impl Into<PathBuf>, enabled_dir: impl Into<PathBuf>) -> Self {
+        Self::from(&ServiceManager::new(definitions_dir, enabled_dir))
+    }
+
+    /// Async counterpart of [`ServiceManager::from_env`].
+    pub fn from_env() -> Self {
+        Self::from(&ServiceManager::from_env())
+    }
+
+    /// Copy of this manager pointed at another service directory.
+    pub fn with_service_dir(&self, dir: impl Into<PathBuf>) -> Self {
+        AsyncServiceManager {
+            enabled_dir: dir.into(),
+            ..self.clone()
+        }
+    }
+
+    pub fn with_sv_command(mut self, cmd: impl Into<PathBuf>) -> Self {
+        self.sv_command = cmd.into();
+        self
+    }
+
+    /// Enumerate all services available on the system.
+    pub async fn list_services(&self) -> Result<Vec<ServiceInfo>> {
+        let mut services = Vec::new();
+        let levels = self.runlevels().await;
+
+        let mut read_dir = tokio::fs::read_dir(&self.definitions_dir)
+            .await
+            .map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?;
+
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?
+        {
+            let path = entry.path();
+            let is_dir = tokio::fs::metadata(&path)
+                .await
+                .map(|meta| meta.is_dir())
+                .unwrap_or(false);
+            if !is_dir {
+                continue;
+            }
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name.starts_with('.')
+                || is_template_dir_name(name)
+                || validate_service_name(name).is_err()
+            {
+                continue;
+            }
+
+            let enabled = tokio::fs::try_exists(self.enabled_dir.join(name))
+                .await
+                .unwrap_or(false);
+            let runtime_state = if enabled {
+                self.status(name).await?
+            } else {
+                ServiceRuntimeState::Unknown {
+                    raw: format!("{name}: not linked into {}", self.enabled_dir.display()),
+                }
+            };
+
+            let has_down_file = tokio::fs::try_exists(path.join("down"))
+                .await
+                .unwrap_or(false);
+            let (pending, log_state) = if enabled {
+                (self.transition(name).await, self.log_status(name).await)
+            } else {
+                (None, None)
+            };
+            let run_once = enabled && self.runs_once(name).await;
+
+            let flapping = if enabled {
+                self.restarts
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .observe(name, &runtime_state)
+            } else {
+                None
+            };
+            // Only pid changes are seen here; the blocking manager also reads `finish` arguments.
+            let finishes = if enabled {
+                let mut finishes = self
+                    .finishes
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                finishes.observe(name, &runtime_state);
+                finishes.history(name)
+            } else {
+                Vec::new()
+            };
+
+            let mut runlevels = Vec::new();
+            if let Some(root) = &self.runlevels_dir {
+                for level in &levels {
+                    if tokio::fs::symlink_metadata(root.join(level).join(name))
+                        .await
+                        .is_ok()
+                    {
+                        runlevels.push(level.clone());
+                    }
+                }
+            }
+
+            let supervise = if enabled {
+                supervise_presence(&self.enabled_dir.join(name)).await
+            } else {
+                SupervisePresence::default()
+            };
+            let metadata = read_metadata(&path).await;
+            let description = match metadata.as_ref().and_then(|m| m.description.clone()) {
+                Some(description) => Some(description),
+                None => read_description(&path).await,
+            };
+            services.push(ServiceInfo {
+                name: name.to_string(),
+                definition_path: path.clone(),
+                enabled,
+                desired_state: DesiredState::from_flags(enabled, has_down_file),
+                runlevels,
+                runtime_state,
+                pending,
+                run_once,
+                log_state,
+                has_check: is_file(&path.join("check")).await,
+                has_finish: is_file(&path.join("finish")).await,
+                has_logger: tokio::fs::metadata(path.join("log"))
+                    .await
+                    .is_ok_and(|meta| meta.is_dir()),
+                masked: is_file(&path.join(MASK_FILE)).await,
+                flapping,
+                health: ServiceHealth::Ok,
+                dependencies: read_dependencies(&path, name).await,
+                template: split_instance(name).map(|(template, _)| template.to_string()),
+                metadata,
+                needs_restart: false,
+                finishes,
+                supervise_dir: supervise.dir,
+                has_ok_pipe: supervise.has_ok_pipe,
+                runsv_running: supervise.runsv_running,
+                down_reason: read_down_reason(&path).await,
+                description,
+            });
+        }
+
+        if let Ok(mut read_dir) = tokio::fs::read_dir(&self.enabled_dir).await {
+            while let Ok(Some(entry)) = read_dir.next_entry().await {
+                let link = entry.path();
+                let dangling = entry.file_type().await.is_ok_and(|t| t.is_symlink())
+                    && !tokio::fs::try_exists(&link).await.unwrap_or(true);
+                if !dangling {
+                    continue;
+                }
+                let Some(service) = link
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .filter(|name| validate_service_name(name).is_ok())
+                else {
+                    continue;
+                };
+                let Ok(target) = tokio::fs::read_link(&link).await else {
+                    continue;
+                };
+                let broken = BrokenLink {
+                    service: service.to_string(),
+                    link: link.clone(),
+                    target,
+                };
+                match services.iter_mut().find(|info| info.name == broken.service) {
+                    Some(info) => info.health = ServiceHealth::BrokenLink,
+                    None => services.push(broken.to_service_info()),
+                }
+            }
+        }
+
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(services)
+    }
+
+    /// Names of all runlevels, sorted; empty when no runlevel root is known.
+    pub async fn runlevels(&self) -> Vec<String> {
+        let mut levels = Vec::new();
+        let Some(root) = &self.runlevels_dir else {
+            return levels;
+        };
+        let Ok(mut read_dir) = tokio::fs::read_dir(root).await else {
+            return levels;
+        };
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let is_dir = entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false);
+            if let (true, Some(name)) = (is_dir, entry.file_name().to_str()) {
+                levels.push(name.to_string());
+            }
+        }
+        levels.sort();
+        levels
+    }
+
+    /// Fetch the runtime status, preferring `supervise/status` over `sv status`.
+    pub async fn status(&self, service: &str) -> Result<ServiceRuntimeState> {
+        validate_service_name(service)?;
+
+        let service_dir = self.enabled_dir.join(service);
+        if let Ok(bytes) = tokio::fs::read(service_dir.join("supervise/status")).await
+            && let Some(status) = SuperviseStatus::parse(&bytes)
+        {
+            let normally_up = !tokio::fs::try_exists(service_dir.join("down"))
+                .await
+                .unwrap_or(false);
+            return Ok(status.to_runtime_state(normally_up));
+        }
+
+        self.sv_status(service).await
+    }
+
+    /// Pending start/stop transition, read from `supervise/status` only.
+    pub async fn transition(&self, service: &str) -> Option<Transition> {
+        validate_service_name(service).ok()?;
+        let path = self.enabled_dir.join(service).join("supervise/status");
+        let bytes = tokio::fs::read(path).await.ok()?;
+        SuperviseStatus::parse(&bytes)?.transition()
+    }
+
+    /// Whether the service was started with `sv once`, read from `supervise/status` only.
+    pub async fn runs_once(&self, service: &str) -> bool {
+        if validate_service_name(service).is_err() {
+            return false;
+        }
+        let path = self.enabled_dir.join(service).join("supervise/status");
+        tokio::fs::read(path)
+            .await
+            .ok()
+            .and_then(|bytes| SuperviseStatus::parse(&bytes))
+            .is_some_and(|status| status.runs_once())
+    }
+
+    /// State of the attached logger, read from `log/supervise/status` only.
+    pub async fn log_status(&self, service: &str) -> Option<ServiceRuntimeState> {
+        validate_service_name(service).ok()?;
+        let log_dir = self.enabled_dir.join(service).join("log");
+        let bytes = tokio::fs::read(log_dir.join("supervise/status"))
+            .await
+            .ok()?;
+        let status = SuperviseStatus::parse(&bytes)?;
+        let normally_up = !tokio::fs::try_exists(log_dir.join("down"))
+            .await
+            .unwrap_or(false);
+        Some(status.to_runtime_state(normally_up))
+    }
+
+    /// Fetch the runtime status for a single service via `sv status`.
+    pub async fn sv_status(&self, service: &str) -> Result<ServiceRuntimeState> {
+        validate_service_name(service)?;
+
+        let output = Command::new(&self.sv_command)
+            .env("SVDIR", &self.enabled_dir)
+            .arg("status")
+            .arg(service)
+            .output()
+            .await
+            .map_err(|err| ServiceError::from_io(&self.sv_command, err))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+        if !stderr.is_empty() {
+            return Err(ServiceError::from_sv_message(service, stderr));
+        }
+
+        if stdout.trim().is_empty() {
+            return Err(ServiceError::SvCommand {
+                service: service.to_string(),
+                message: format!("sv status returned no output ({})", output.status),
+            });
+        }
+
+        Ok(ServiceRuntimeState::from_sv_status(&stdout))
+    }
+
+    /// Tail the newest log entries for a service without blocking the executor.
+    pub async fn tail_logs(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>> {
+        validate_service_name(service)?;
+
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let candidates = [
+            self.definitions_dir.join(service).join("log/main/current"),
+            self.enabled_dir.join(service).join("log/main/current"),
+        ];
+        for log_path in candidates {
+            match read_tail(&log_path, limit).await {
+                Ok(entries) => return Ok(entries),
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(ServiceError::from_io(&log_path, err)),
+            }
+        }
+
+        Ok(Vec::new())
+    }
+}
+
+async fn read_tail(path: &Path, limit: usize) -> std::io::Result<Vec<ServiceLogEntry>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut pos = file.seek(SeekFrom::End(0)).await?;
+    let mut tail = ReverseTail::default();
+
+    while pos > 0 && !tail.has_lines(limit) {
+        let len = TAIL_BLOCK_SIZE.min(pos);
+        pos -= len;
+        file.seek(SeekFrom::Start(pos)).await?;
+        let mut chunk = vec![0; len as usize];
+        file.read_exact(&mut chunk).await?;
+        tail.push_front(chunk);
+    }
+
+    Ok(tail.into_entries(pos == 0, limit))
+}
+
+async fn is_file(path: &Path) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|meta| meta.is_file())
+}
+
+async fn read_dependencies(definition_path: &Path, service: &str) -> Vec<String> {
+    let Ok(script) = tokio::fs::read_to_string(definition_path.join("run")).await else {
+        return Vec::new();
+    };
+    let mut deps = run_script_dependencies(&script);
+    deps.retain(|dep| dep != service);
+    deps
+}
+
+/// Async counterpart of [`SupervisePresence::read`]. Probing the FIFO cannot block, as it
+/// is opened non-blocking.
+async fn supervise_presence(service_dir: &Path) -> SupervisePresence {
+    let Ok(dir) = tokio::fs::canonicalize(service_dir.join("supervise")).await else {
+        return SupervisePresence::default();
+    };
+    let ok = dir.join("ok");
+    let has_ok_pipe = tokio::fs::metadata(&ok)
+        .await
+        .is_ok_and(|meta| meta.file_type().is_fifo());
+    SupervisePresence {
+        runsv_running: has_ok_pipe && has_reader(&ok),
+        has_ok_pipe,
+        dir: Some(dir),
+    }
+}
+
+async fn read_metadata(definition_path: &Path) -> Option<ServiceMetadata> {
+    let contents = tokio::fs::read_to_string(definition_path.join(METADATA_FILE))
+        .await
+        .ok()?;
+    ServiceMetadata::parse(&contents).ok()
+}
+
+async fn read_down_reason(definition_path: &Path) -> Option<DownReason> {
+    let path = definition_path.join(DOWN_REASON_FILE);
+    let text = tokio::fs::read_to_string(&path).await.ok()?;
+    let written = tokio::fs::metadata(&path)
+        .await
+        .and_then(|meta| meta.modified())
+        .unwrap_or(std::time::UNIX_EPOCH);
+    DownReason::parse(&text, written)
+}
+
+async fn read_description(definition_path: &Path) -> Option<String> {
+    for candidate in ["description", "README", "README.md"] {
+        if let Ok(contents) = tokio::fs::read_to_string(definition_path.join(candidate)).await {
+            let trimmed = contents.trim();
+            if !trimmed.is_empty() {
+                return Some(trimmed.lines().next().unwrap_or(trimmed).to_string());
+            }
+        }
+    }
+    None
+}
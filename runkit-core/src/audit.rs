@@ -0,0 +1,194 @@
+//! Consistency checks over supervise state left behind by removed services or dead runsv.
+use crate::supervise::has_reader;
+use crate::{Result, ServiceError, ServiceManager, validate_service_name};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::PathBuf;
+
+/// One problem found by [`ServiceManager::audit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum AuditFinding {
+    /// `supervise/` state for a service that has no definition any more.
+    OrphanedSupervise {
+        service: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        path: PathBuf,
+    },
+    /// `supervise/lock` and `supervise/ok` exist but no runsv holds them, usually
+    /// after runsv was killed.
+    StaleSupervisor {
+        service: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        path: PathBuf,
+    },
+}
+
+impl AuditFinding {
+    pub fn service(&self) -> &str {
+        match self {
+            AuditFinding::OrphanedSupervise { service, .. }
+            | AuditFinding::StaleSupervisor { service, .. } => service,
+        }
+    }
+}
+
+impl fmt::Display for AuditFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditFinding::OrphanedSupervise { service, path } => write!(
+                f,
+                "{service}: supervise state at {} has no service definition",
+                path.display()
+            ),
+            AuditFinding::StaleSupervisor { service, path } => write!(
+                f,
+                "{service}: {} is left over from a runsv that is no longer running",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Result of [`ServiceManager::audit`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditReport {
+    pub findings: Vec<AuditFinding>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl ServiceManager {
+    /// Look for supervise state that no definition accounts for and for supervise
+    /// directories whose runsv has died.
+    ///
+    /// Runtime directories such as `/run/runit` are found by following the `supervise`
+    /// symlinks of existing definitions, so no location is assumed.
+    pub fn audit(&self) -> Result<AuditReport> {
+        let definitions: BTreeSet<String> = self.backend.definition_names()?.into_iter().collect();
+        let mut findings = Vec::new();
+
+        // Void keeps supervise state in `/run/runit/supervise.<name>`, linked from the
+        // definition; entries there without a definition are leftovers.
+        let mut runtime_dirs = BTreeSet::new();
+        for name in &definitions {
+            if let Ok(target) =
+                std::fs::read_link(self.definitions_dir.join(name).join("supervise"))
+                && let Some(parent) = target.parent()
+                && target.is_absolute()
+            {
+                runtime_dirs.insert(parent.to_path_buf());
+            }
+        }
+        for dir in runtime_dirs {
+            let Ok(read_dir) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in read_dir.filter_map(|entry| entry.ok()) {
+                let file_name = entry.file_name();
+                let Some(service) = file_name
+                    .to_str()
+                    .and_then(|name| name.strip_prefix("supervise."))
+                else {
+                    continue;
+                };
+                // `supervise.foo.log` belongs to foo's logger.
+                let owner = service.strip_suffix(".log").unwrap_or(service);
+                if !definitions.contains(owner) {
+                    findings.push(AuditFinding::OrphanedSupervise {
+                        service: service.to_string(),
+                        path: entry.path(),
+                    });
+                }
+            }
+        }
+
+        let read_dir = std::fs::read_dir(&self.enabled_dir)
+            .map_err(|err| ServiceError::from_io(&self.enabled_dir, err))?;
+        let mut enabled: Vec<(String, PathBuf)> = read_dir
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_string();
+                validate_service_name(&name).ok()?;
+                Some((name, entry.path()))
+            })
+            .collect();
+        enabled.sort();
+
+        for (service, dir) in enabled {
+            let supervise = dir.join("supervise");
+            if !supervise.is_dir() {
+                continue;
+            }
+            // A plain directory in the enabled dir with no definition behind it.
+            let is_link = std::fs::symlink_metadata(&dir).is_ok_and(|m| m.file_type().is_symlink());
+            if !is_link && !definitions.contains(&service) {
+                findings.push(AuditFinding::OrphanedSupervise {
+                    service: service.clone(),
+                    path: supervise.clone(),
+                });
+            }
+            if supervise.join("lock").exists() && !has_reader(&supervise.join("ok")) {
+                findings.push(AuditFinding::StaleSupervisor {
+                    service,
+                    path: supervise,
+                });
+            }
+        }
+
+        Ok(AuditReport { findings })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditFinding;
+    use crate::ServiceManager;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn finds_orphaned_and_stale_supervise_state() {
+        let root = std::env::temp_dir().join(format!("runkit-audit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd")).unwrap();
+        std::fs::create_dir_all(root.join("run/supervise.sshd")).unwrap();
+        std::fs::create_dir_all(root.join("run/supervise.ntpd")).unwrap();
+        std::fs::create_dir_all(root.join("run/supervise.sshd.log")).unwrap();
+        symlink(
+            root.join("run/supervise.sshd"),
+            root.join("sv/sshd/supervise"),
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("service")).unwrap();
+        symlink(root.join("sv/sshd"), root.join("service/sshd")).unwrap();
+
+        let ok = root.join("run/supervise.sshd/ok");
+        let path = CString::new(ok.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+        std::fs::write(root.join("run/supervise.sshd/lock"), b"").unwrap();
+
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        let report = manager.audit().unwrap();
+        assert_eq!(report.findings.len(), 2, "{:?}", report.findings);
+        assert!(matches!(
+            &report.findings[0],
+            AuditFinding::OrphanedSupervise { service, .. } if service == "ntpd"
+        ));
+        assert!(matches!(
+            &report.findings[1],
+            AuditFinding::StaleSupervisor { service, .. } if service == "sshd"
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
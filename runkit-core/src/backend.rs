@@ -0,0 +1,271 @@
+//! Pluggable access to service definitions and the runit supervisor.
+use crate::supervise::read_supervise_status;
+use crate::{
+    Result, ServiceError, ServiceLogEntry, ServiceRuntimeState, StatusReport, Transition,
+    read_svlogd_tail,
+};
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::ErrorKind;
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Operations `ServiceManager` delegates to the underlying service tree.
+///
+/// The default implementation is [`RunitBackend`]; tests and frontends can provide their
+/// own implementation through [`crate::ServiceManager::with_backend`]. Service names are
+/// validated by the manager before they reach the backend.
+pub trait ServiceBackend: fmt::Debug + Send + Sync {
+    /// Names of every service definition, in no particular order.
+    fn definition_names(&self) -> Result<Vec<String>>;
+
+    /// Whether the service is linked into the enabled directory.
+    fn is_enabled(&self, service: &str) -> bool;
+
+    /// Whether the definition carries a `down` file, so runsv leaves it stopped at boot.
+    fn has_down_file(&self, _service: &str) -> bool {
+        false
+    }
+
+    /// Current runtime state of an enabled service.
+    fn status(&self, service: &str) -> Result<ServiceRuntimeState>;
+
+    /// Runtime state plus any non-fatal diagnostics encountered while reading it.
+    fn status_lenient(&self, service: &str) -> Result<StatusReport> {
+        self.status(service).map(StatusReport::new)
+    }
+
+    /// Pending start/stop transition, if runsv has not reached the wanted state yet.
+    fn transition(&self, _service: &str) -> Result<Option<Transition>> {
+        Ok(None)
+    }
+
+    /// State of the attached `log/` service; `None` when the service has no logger.
+    fn log_status(&self, _service: &str) -> Result<Option<ServiceRuntimeState>> {
+        Ok(None)
+    }
+
+    /// Newest `limit` log entries, oldest first.
+    fn tail_logs(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>>;
+
+    /// Link the service into the enabled directory.
+    fn enable(&self, service: &str) -> Result<()>;
+
+    /// Remove the service from the enabled directory.
+    fn disable(&self, service: &str) -> Result<()>;
+}
+
+/// Backend that talks to a live runit installation through the filesystem and `sv`.
+#[derive(Debug, Clone)]
+pub struct RunitBackend {
+    definitions_dir: PathBuf,
+    enabled_dir: PathBuf,
+    sv_command: PathBuf,
+}
+
+impl RunitBackend {
+    pub fn new(
+        definitions_dir: impl Into<PathBuf>,
+        enabled_dir: impl Into<PathBuf>,
+        sv_command: impl Into<PathBuf>,
+    ) -> Self {
+        RunitBackend {
+            definitions_dir: definitions_dir.into(),
+            enabled_dir: enabled_dir.into(),
+            sv_command: sv_command.into(),
+        }
+    }
+
+    pub fn definitions_dir(&self) -> &Path {
+        &self.definitions_dir
+    }
+
+    pub fn enabled_dir(&self) -> &Path {
+        &self.enabled_dir
+    }
+
+    pub fn sv_command_path(&self) -> &Path {
+        &self.sv_command
+    }
+
+    /// Fetch the runtime status for a single service via `sv status`.
+    pub fn sv_status(&self, service: &str) -> Result<ServiceRuntimeState> {
+        self.sv_status_output(service)
+            .map(|stdout| ServiceRuntimeState::from_sv_status(&stdout))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    fn sv_status_output(&self, service: &str) -> Result<String> {
+        // Point `sv` at our service directory so it resolves the name the same way we do.
+        let output = Command::new(&self.sv_command)
+            .env("SVDIR", &self.enabled_dir)
+            .arg("status")
+            .arg(service)
+            .output()
+            .map_err(|err| ServiceError::from_io(&self.sv_command, err))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        trace_debug!(status = %output.status, stdout, stderr, "sv status finished");
+
+        if !stderr.is_empty() {
+            return Err(ServiceError::from_sv_message(service, stderr));
+        }
+
+        if stdout.trim().is_empty() {
+            let status_desc = output
+                .status
+                .code()
+                .map(|code| format!("exit status {code}"))
+                .unwrap_or_else(|| output.status.to_string());
+            return Err(ServiceError::SvCommand {
+                service: service.to_string(),
+                message: format!("sv status returned no output ({status_desc})"),
+            });
+        }
+
+        Ok(stdout)
+    }
+
+    fn log_path(&self, service: &str) -> Option<PathBuf> {
+        let definition_candidate = self.definitions_dir.join(service).join("log/main/current");
+        let enabled_candidate = self.enabled_dir.join(service).join("log/main/current");
+
+        if definition_candidate.exists() {
+            Some(definition_candidate)
+        } else if enabled_candidate.exists() {
+            Some(enabled_candidate)
+        } else {
+            None
+        }
+    }
+}
+
+impl ServiceBackend for RunitBackend {
+    fn definition_names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+
+        let read_dir = std::fs::read_dir(&self.definitions_dir)
+            .map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?;
+
+        for entry in read_dir {
+            let entry = entry.map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?;
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+                names.push(name.to_string());
+            }
+        }
+
+        Ok(names)
+    }
+
+    fn is_enabled(&self, service: &str) -> bool {
+        self.enabled_dir.join(service).exists()
+    }
+
+    fn has_down_file(&self, service: &str) -> bool {
+        self.definitions_dir.join(service).join("down").exists()
+    }
+
+    /// Prefer the `supervise/status` record and only spawn `sv status` when it is unreadable.
+    fn status(&self, service: &str) -> Result<ServiceRuntimeState> {
+        let service_dir = self.enabled_dir.join(service);
+        match read_supervise_status(&service_dir) {
+            Ok(status) => {
+                let normally_up = !service_dir.join("down").exists();
+                Ok(status.to_runtime_state(normally_up))
+            }
+            Err(_) => self.sv_status(service),
+        }
+    }
+
+    fn status_lenient(&self, service: &str) -> Result<StatusReport> {
+        let service_dir = self.enabled_dir.join(service);
+        if let Ok(status) = read_supervise_status(&service_dir) {
+            let normally_up = !service_dir.join("down").exists();
+            return Ok(StatusReport::new(status.to_runtime_state(normally_up)));
+        }
+
+        let output = Command::new(&self.sv_command)
+            .env("SVDIR", &self.enabled_dir)
+            .arg("status")
+            .arg(service)
+            .output()
+            .map_err(|err| ServiceError::from_io(&self.sv_command, err))?;
+        StatusReport::from_sv_output(
+            service,
+            &String::from_utf8_lossy(&output.stdout),
+            &String::from_utf8_lossy(&output.stderr),
+        )
+    }
+
+    fn transition(&self, service: &str) -> Result<Option<Transition>> {
+        let service_dir = self.enabled_dir.join(service);
+        match read_supervise_status(&service_dir) {
+            Ok(status) => Ok(status.transition()),
+            Err(_) => self
+                .sv_status_output(service)
+                .map(|stdout| Transition::from_sv_status(&stdout)),
+        }
+    }
+
+    fn log_status(&self, service: &str) -> Result<Option<ServiceRuntimeState>> {
+        let log_dir = self.enabled_dir.join(service).join("log");
+        if !log_dir.is_dir() {
+            return Ok(None);
+        }
+
+        match read_supervise_status(&log_dir) {
+            Ok(status) => {
+                let normally_up = !log_dir.join("down").exists();
+                Ok(Some(status.to_runtime_state(normally_up)))
+            }
+            Err(_) => self
+                .sv_status_output(service)
+                .map(|stdout| ServiceRuntimeState::log_from_sv_status(&stdout)),
+        }
+    }
+
+    fn tail_logs(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>> {
+        let Some(log_path) = self.log_path(service) else {
+            return Ok(Vec::new());
+        };
+
+        match read_svlogd_tail(&log_path, limit) {
+            Ok(entries) => Ok(entries),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(ServiceError::from_io(&log_path, err)),
+        }
+    }
+
+    fn enable(&self, service: &str) -> Result<()> {
+        let src = self.definitions_dir.join(service);
+        if !src.exists() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: src,
+            });
+        }
+
+        let dest = self.enabled_dir.join(service);
+        if dest.exists() {
+            return Err(ServiceError::AlreadyEnabled(service.to_string()));
+        }
+
+        unix_fs::symlink(&src, &dest).map_err(|err| ServiceError::from_io(&dest, err))
+    }
+
+    fn disable(&self, service: &str) -> Result<()> {
+        let dest = self.enabled_dir.join(service);
+        // A dangling link still counts as enabled and must be removable.
+        if std::fs::symlink_metadata(&dest).is_err() {
+            return Err(ServiceError::NotEnabled(service.to_string()));
+        }
+
+        std::fs::remove_file(&dest).map_err(|err| ServiceError::from_io(&dest, err))
+    }
+}
@@ -0,0 +1,125 @@
+//! Apply one action to many services and collect the outcome of each.
+use crate::{ControlCommand, Result, ServiceManager};
+use std::fmt;
+
+/// Action that can be part of a bulk request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceOp {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+    Once,
+    Enable,
+    Disable,
+}
+
+impl ServiceOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ServiceOp::Start => "start",
+            ServiceOp::Stop => "stop",
+            ServiceOp::Restart => "restart",
+            ServiceOp::Reload => "reload",
+            ServiceOp::Once => "once",
+            ServiceOp::Enable => "enable",
+            ServiceOp::Disable => "disable",
+        }
+    }
+
+    /// Control bytes written to runsv; `None` for operations on the enabled directory.
+    fn control_commands(self) -> Option<&'static [ControlCommand]> {
+        match self {
+            ServiceOp::Start => Some(&[ControlCommand::Up]),
+            ServiceOp::Stop => Some(&[ControlCommand::Down]),
+            ServiceOp::Restart => Some(&[
+                ControlCommand::Term,
+                ControlCommand::Continue,
+                ControlCommand::Up,
+            ]),
+            ServiceOp::Reload => Some(&[ControlCommand::Hangup]),
+            ServiceOp::Once => Some(&[ControlCommand::Once]),
+            ServiceOp::Enable | ServiceOp::Disable => None,
+        }
+    }
+}
+
+impl fmt::Display for ServiceOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Outcome of one entry of [`ServiceManager::apply_bulk`].
+#[derive(Debug)]
+pub struct OpResult {
+    pub service: String,
+    pub op: ServiceOp,
+    pub result: Result<()>,
+}
+
+impl OpResult {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl ServiceManager {
+    /// Run a single operation against one service.
+    pub fn apply(&self, op: ServiceOp, service: &str) -> Result<()> {
+        match op.control_commands() {
+            Some(commands) => self.send_control(service, commands),
+            None if op == ServiceOp::Enable => self.enable(service),
+            None => self.disable(service),
+        }
+    }
+
+    /// Run every operation in order, continuing past failures.
+    ///
+    /// The result list has one entry per input, in the same order.
+    pub fn apply_bulk<S: AsRef<str>>(&self, ops: &[(ServiceOp, S)]) -> Vec<OpResult> {
+        ops.iter()
+            .map(|(op, service)| OpResult {
+                service: service.as_ref().to_string(),
+                op: *op,
+                result: self.apply(*op, service.as_ref()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceOp;
+    use crate::{ServiceError, ServiceManager};
+
+    #[test]
+    fn reports_each_operation_separately() {
+        let root = std::env::temp_dir().join(format!("runkit-bulk-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd")).unwrap();
+        std::fs::create_dir_all(root.join("sv/cron")).unwrap();
+        std::fs::create_dir_all(root.join("service")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        let results = manager.apply_bulk(&[
+            (ServiceOp::Enable, "sshd"),
+            (ServiceOp::Enable, "missing"),
+            (ServiceOp::Start, "cron"),
+            (ServiceOp::Enable, "cron"),
+        ]);
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1].result,
+            Err(ServiceError::DefinitionMissing { .. })
+        ));
+        assert!(matches!(
+            &results[2].result,
+            Err(ServiceError::SupervisorNotRunning(name)) if name == "cron"
+        ));
+        assert!(results[3].is_ok());
+        assert!(root.join("service/cron").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
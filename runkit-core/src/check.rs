@@ -0,0 +1,187 @@
+//! Running a service's `check` script directly and capturing what it reports.
+use crate::{Result, ServiceError, ServiceManager};
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long output is still collected after the script exits. Anything it left running
+/// in the background may hold the pipes open far longer.
+const DRAIN_GRACE: Duration = Duration::from_millis(500);
+
+/// Result of one `check` run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckOutcome {
+    /// `None` when the script was killed by a signal, including on timeout.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::millis"))]
+    pub duration: Duration,
+    pub timed_out: bool,
+}
+
+impl CheckOutcome {
+    /// runsv treats exit status 0 as "service is available".
+    pub fn passed(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+impl ServiceManager {
+    /// Execute the service's `./check` the way runsv does, from the service directory,
+    /// and capture its output. The script runs in its own process group, which is killed
+    /// as a whole when it runs longer than `timeout`.
+    ///
+    /// A failing check is reported in the outcome, not as an error.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    pub fn run_check(&self, service: &str, timeout: Duration) -> Result<CheckOutcome> {
+        self.validate_service_name(service)?;
+        let service_dir = self.definitions_dir.join(service);
+        let script = service_dir.join("check");
+        if !script.is_file() {
+            return Err(ServiceError::MissingCheckScript(service.to_string()));
+        }
+
+        let started = Instant::now();
+        let mut child = Command::new(&script)
+            .current_dir(&service_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(|err| ServiceError::from_io(&script, err))?;
+
+        // Drain both pipes concurrently so a chatty script cannot block on a full pipe.
+        let stdout = child.stdout.take().map(drain);
+        let stderr = child.stderr.take().map(drain);
+
+        let mut timed_out = false;
+        let status = loop {
+            if let Some(status) = child
+                .try_wait()
+                .map_err(|err| ServiceError::from_io(&script, err))?
+            {
+                break status;
+            }
+            if started.elapsed() >= timeout {
+                timed_out = true;
+                // SAFETY: the child leads its own group, so this reaches nothing else.
+                unsafe {
+                    libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+                }
+                let _ = child.kill();
+                break child
+                    .wait()
+                    .map_err(|err| ServiceError::from_io(&script, err))?;
+            }
+            thread::sleep(Duration::from_millis(20));
+        };
+
+        let deadline = Instant::now() + DRAIN_GRACE;
+        let collect = |output: Option<Drained>| output.map(|output| output.collect(deadline));
+        Ok(CheckOutcome {
+            exit_code: status.code(),
+            stdout: collect(stdout).unwrap_or_default(),
+            stderr: collect(stderr).unwrap_or_default(),
+            duration: started.elapsed(),
+            timed_out,
+        })
+    }
+}
+
+/// Output read so far from one pipe, and word from the reader once it hits EOF.
+struct Drained {
+    bytes: Arc<Mutex<Vec<u8>>>,
+    done: Receiver<()>,
+}
+
+impl Drained {
+    /// Everything read by EOF, or by `deadline` when the pipe is still open then.
+    fn collect(self, deadline: Instant) -> String {
+        let _ = self
+            .done
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        let bytes = self
+            .bytes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+fn drain(mut pipe: impl Read + Send + 'static) -> Drained {
+    let bytes = Arc::new(Mutex::new(Vec::new()));
+    let (done, receiver) = mpsc::channel();
+    let buffer = Arc::clone(&bytes);
+    thread::spawn(move || {
+        let mut chunk = [0u8; 4096];
+        while let Ok(read @ 1..) = pipe.read(&mut chunk) {
+            buffer
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .extend_from_slice(&chunk[..read]);
+        }
+        let _ = done.send(());
+    });
+    Drained {
+        bytes,
+        done: receiver,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ServiceManager;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    #[test]
+    fn captures_output_and_enforces_timeout() {
+        let root = std::env::temp_dir().join(format!("runkit-check-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd")).unwrap();
+        std::fs::create_dir_all(root.join("sv/slow")).unwrap();
+        let write_script = |path: std::path::PathBuf, body: &str| {
+            std::fs::write(&path, body).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        write_script(
+            root.join("sv/sshd/check"),
+            "#!/bin/sh\necho probing\necho 'port 22 closed' >&2\nexit 3\n",
+        );
+        write_script(root.join("sv/slow/check"), "#!/bin/sh\nexec sleep 5\n");
+        std::fs::create_dir_all(root.join("sv/forking")).unwrap();
+        write_script(
+            root.join("sv/forking/check"),
+            "#!/bin/sh\necho started\nsleep 30\n",
+        );
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        let outcome = manager.run_check("sshd", Duration::from_secs(5)).unwrap();
+        assert_eq!(outcome.exit_code, Some(3));
+        assert!(!outcome.passed());
+        assert_eq!(outcome.stdout, "probing\n");
+        assert_eq!(outcome.stderr, "port 22 closed\n");
+
+        let outcome = manager
+            .run_check("slow", Duration::from_millis(100))
+            .unwrap();
+        assert!(outcome.timed_out);
+        assert_eq!(outcome.exit_code, None);
+
+        // `sleep` without `exec` is a grandchild holding the pipes.
+        let outcome = manager
+            .run_check("forking", Duration::from_millis(200))
+            .unwrap();
+        assert!(outcome.timed_out);
+        assert_eq!(outcome.stdout, "started\n");
+        assert!(outcome.duration < Duration::from_secs(5));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
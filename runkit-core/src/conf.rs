@@ -0,0 +1,232 @@
+//! The `conf` file many Void services source for `OPTS` and similar variables.
+use crate::definition::write_atomic;
+use crate::{Result, ServiceError, ServiceManager};
+use std::fmt;
+use std::os::unix::fs::PermissionsExt;
+
+/// Shell variable assignments from a `conf` file, with every other line kept verbatim.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceConf {
+    lines: Vec<ConfLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ConfLine {
+    Assignment {
+        key: String,
+        value: String,
+        export: bool,
+        raw: Option<String>,
+    },
+    Other(String),
+}
+
+impl ServiceConf {
+    pub fn parse(contents: &str) -> Self {
+        let lines = contents
+            .lines()
+            .map(|line| match parse_assignment(line) {
+                Some((key, value, export)) => ConfLine::Assignment {
+                    key,
+                    value,
+                    export,
+                    raw: Some(line.to_string()),
+                },
+                None => ConfLine::Other(line.to_string()),
+            })
+            .collect();
+        ServiceConf { lines }
+    }
+
+    /// Value of the last assignment to `key`, as the shell would see it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().rev().find_map(|line| match line {
+            ConfLine::Assignment { key: k, value, .. } if k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Variable names in file order, without duplicates.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = Vec::new();
+        for line in &self.lines {
+            if let ConfLine::Assignment { key, .. } = line
+                && !keys.contains(&key.as_str())
+            {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    /// Update the last assignment to `key` in place, or append a new one.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if !is_valid_name(key) {
+            return Err(ServiceError::Other(
+                format!("invalid variable name: {key}").into(),
+            ));
+        }
+
+        let existing = self.lines.iter_mut().rev().find_map(|line| match line {
+            ConfLine::Assignment {
+                key: k, value, raw, ..
+            } if k == key => Some((value, raw)),
+            _ => None,
+        });
+        match existing {
+            Some((current, raw)) => {
+                *current = value.to_string();
+                *raw = None;
+            }
+            None => self.lines.push(ConfLine::Assignment {
+                key: key.to_string(),
+                value: value.to_string(),
+                export: false,
+                raw: None,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Drop every assignment to `key`. Returns whether anything was removed.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.lines.len();
+        self.lines
+            .retain(|line| !matches!(line, ConfLine::Assignment { key: k, .. } if k == key));
+        self.lines.len() != before
+    }
+}
+
+impl fmt::Display for ServiceConf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                ConfLine::Assignment { raw: Some(raw), .. } | ConfLine::Other(raw) => {
+                    writeln!(f, "{raw}")?
+                }
+                ConfLine::Assignment {
+                    key,
+                    value,
+                    export,
+                    raw: None,
+                } => {
+                    let prefix = if *export { "export " } else { "" };
+                    writeln!(f, "{prefix}{key}=\"{}\"", escape_double_quoted(value))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ServiceManager {
+    /// Parsed `conf` file of a service; empty when the service has none.
+    pub fn conf(&self, service: &str) -> Result<ServiceConf> {
+        let path = self.definition_file(service, "conf")?;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(ServiceConf::parse(&contents)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(ServiceConf::default()),
+            Err(err) => Err(ServiceError::from_io(&path, err)),
+        }
+    }
+
+    /// Write the `conf` file back atomically, keeping the old one as `conf.bak`.
+    pub fn write_conf(&self, service: &str, conf: &ServiceConf) -> Result<()> {
+        let path = self.definition_file(service, "conf")?;
+        let mode = std::fs::metadata(&path)
+            .map(|meta| meta.permissions().mode() & 0o7777)
+            .unwrap_or(0o644);
+        write_atomic(&path, conf.to_string().as_bytes(), mode, true, |_| Ok(()))
+    }
+}
+
+fn is_valid_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_assignment(line: &str) -> Option<(String, String, bool)> {
+    let trimmed = line.trim_start();
+    let (rest, export) = match trimmed.strip_prefix("export ") {
+        Some(rest) => (rest.trim_start(), true),
+        None => (trimmed, false),
+    };
+    let (key, value) = rest.split_once('=')?;
+    if !is_valid_name(key) {
+        return None;
+    }
+    Some((key.to_string(), unquote(value.trim_end()), export))
+}
+
+fn unquote(value: &str) -> String {
+    if let Some(inner) = value
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+    {
+        return inner.to_string();
+    }
+    if let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        let mut out = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('\\', Some(next @ ('"' | '\\' | '$' | '`'))) => {
+                    out.push(next);
+                    chars.next();
+                }
+                _ => out.push(c),
+            }
+        }
+        return out;
+    }
+    // Unquoted: the value ends at an unescaped comment.
+    match value.find(" #") {
+        Some(idx) => value[..idx].to_string(),
+        None => value.to_string(),
+    }
+}
+
+fn escape_double_quoted(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceConf;
+
+    #[test]
+    fn edits_assignments_and_keeps_comments() {
+        let mut conf = ServiceConf::parse(
+            "# options for the daemon\nOPTS=\"-f --port 22\"\nexport LANG='C.UTF-8'\n",
+        );
+        assert_eq!(conf.get("OPTS"), Some("-f --port 22"));
+        assert_eq!(conf.get("LANG"), Some("C.UTF-8"));
+        assert_eq!(conf.keys(), ["OPTS", "LANG"]);
+
+        conf.set("OPTS", "-f \"$HOME\"").unwrap();
+        conf.set("EXTRA", "1").unwrap();
+        assert!(conf.set("1BAD", "x").is_err());
+        assert_eq!(
+            conf.to_string(),
+            "# options for the daemon\nOPTS=\"-f \\\"\\$HOME\\\"\"\nexport LANG='C.UTF-8'\nEXTRA=\"1\"\n"
+        );
+        assert_eq!(
+            ServiceConf::parse(&conf.to_string()).get("OPTS"),
+            Some("-f \"$HOME\"")
+        );
+
+        assert!(conf.remove("LANG"));
+        assert_eq!(conf.get("LANG"), None);
+    }
+}
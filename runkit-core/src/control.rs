@@ -0,0 +1,294 @@
+//! Direct control of runsv through the `supervise/control` FIFO.
+use crate::{Result, ServiceError};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// Single-byte commands runsv accepts on `supervise/control`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    Up,
+    Down,
+    Once,
+    Pause,
+    Continue,
+    Hangup,
+    Alarm,
+    Interrupt,
+    Quit,
+    User1,
+    User2,
+    Term,
+    Kill,
+    Exit,
+}
+
+impl ControlCommand {
+    pub fn as_byte(self) -> u8 {
+        match self {
+            ControlCommand::Up => b'u',
+            ControlCommand::Down => b'd',
+            ControlCommand::Once => b'o',
+            ControlCommand::Pause => b'p',
+            ControlCommand::Continue => b'c',
+            ControlCommand::Hangup => b'h',
+            ControlCommand::Alarm => b'a',
+            ControlCommand::Interrupt => b'i',
+            ControlCommand::Quit => b'q',
+            ControlCommand::User1 => b'1',
+            ControlCommand::User2 => b'2',
+            ControlCommand::Term => b't',
+            ControlCommand::Kill => b'k',
+            ControlCommand::Exit => b'x',
+        }
+    }
+}
+
+/// Signals runsv can deliver to the supervised process on request.
+///
+/// `Stop` and `Cont` correspond to `sv pause` / `sv cont`; the rest forward the
+/// signal of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SvSignal {
+    Hup,
+    Alrm,
+    Int,
+    Quit,
+    Usr1,
+    Usr2,
+    Term,
+    Kill,
+    Stop,
+    Cont,
+}
+
+impl SvSignal {
+    pub const ALL: [SvSignal; 10] = [
+        SvSignal::Hup,
+        SvSignal::Alrm,
+        SvSignal::Int,
+        SvSignal::Quit,
+        SvSignal::Usr1,
+        SvSignal::Usr2,
+        SvSignal::Term,
+        SvSignal::Kill,
+        SvSignal::Stop,
+        SvSignal::Cont,
+    ];
+
+    /// Conventional upper-case name without the `SIG` prefix.
+    pub fn name(self) -> &'static str {
+        match self {
+            SvSignal::Hup => "HUP",
+            SvSignal::Alrm => "ALRM",
+            SvSignal::Int => "INT",
+            SvSignal::Quit => "QUIT",
+            SvSignal::Usr1 => "USR1",
+            SvSignal::Usr2 => "USR2",
+            SvSignal::Term => "TERM",
+            SvSignal::Kill => "KILL",
+            SvSignal::Stop => "STOP",
+            SvSignal::Cont => "CONT",
+        }
+    }
+
+    /// Parse a signal name such as `hup`, `USR1` or `SIGTERM`, or one of the command
+    /// names `sv` uses for it (`alarm`, `interrupt`, `1`, `2`, `pause`, `cont`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        let upper = name.trim().to_ascii_uppercase();
+        let bare = upper.strip_prefix("SIG").unwrap_or(&upper);
+        let alias = match bare {
+            "HANGUP" => Some(SvSignal::Hup),
+            "ALARM" => Some(SvSignal::Alrm),
+            "INTERRUPT" => Some(SvSignal::Int),
+            "1" => Some(SvSignal::Usr1),
+            "2" => Some(SvSignal::Usr2),
+            "PAUSE" => Some(SvSignal::Stop),
+            "CONTINUE" => Some(SvSignal::Cont),
+            _ => None,
+        };
+        alias.or_else(|| {
+            SvSignal::ALL
+                .into_iter()
+                .find(|signal| signal.name() == bare)
+        })
+    }
+
+    pub fn control_command(self) -> ControlCommand {
+        match self {
+            SvSignal::Hup => ControlCommand::Hangup,
+            SvSignal::Alrm => ControlCommand::Alarm,
+            SvSignal::Int => ControlCommand::Interrupt,
+            SvSignal::Quit => ControlCommand::Quit,
+            SvSignal::Usr1 => ControlCommand::User1,
+            SvSignal::Usr2 => ControlCommand::User2,
+            SvSignal::Term => ControlCommand::Term,
+            SvSignal::Kill => ControlCommand::Kill,
+            SvSignal::Stop => ControlCommand::Pause,
+            SvSignal::Cont => ControlCommand::Continue,
+        }
+    }
+}
+
+impl fmt::Display for SvSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Handle for sending control bytes to the runsv instance supervising one service.
+#[derive(Debug, Clone)]
+pub struct ServiceControl {
+    service: String,
+    control_path: PathBuf,
+}
+
+impl ServiceControl {
+    /// `service_dir` is the supervised directory, usually `/var/service/<name>`.
+    pub fn new(service: impl Into<String>, service_dir: &Path) -> Self {
+        ServiceControl {
+            service: service.into(),
+            control_path: service_dir.join("supervise/control"),
+        }
+    }
+
+    pub fn control_path(&self) -> &Path {
+        &self.control_path
+    }
+
+    pub fn send(&self, command: ControlCommand) -> Result<()> {
+        self.send_all(&[command])
+    }
+
+    /// Write several commands in one go, the way `sv restart` sends `tcu`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip(self), fields(service = %self.service), err)
+    )]
+    pub fn send_all(&self, commands: &[ControlCommand]) -> Result<()> {
+        let bytes: Vec<u8> = commands.iter().map(|cmd| cmd.as_byte()).collect();
+
+        // Opening a FIFO for writing without blocking fails with ENXIO when no
+        // runsv holds the read end, which is exactly "supervisor not running".
+        let mut fifo = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.control_path)
+            .map_err(|err| match err.raw_os_error() {
+                Some(libc::ENXIO) => ServiceError::SupervisorNotRunning(self.service.clone()),
+                // The service directory exists but runsv never created `supervise/`.
+                _ if err.kind() == ErrorKind::NotFound
+                    && self.control_path.parent().is_some_and(|dir| {
+                        !dir.exists() && dir.parent().is_some_and(Path::is_dir)
+                    }) =>
+                {
+                    ServiceError::NotSupervised(self.service.clone())
+                }
+                _ if err.kind() == ErrorKind::NotFound => {
+                    ServiceError::SupervisorNotRunning(self.service.clone())
+                }
+                _ => ServiceError::from_io(&self.control_path, err),
+            })?;
+
+        fifo.write_all(&bytes)
+            .map_err(|err| ServiceError::from_io(&self.control_path, err))
+    }
+
+    pub fn up(&self) -> Result<()> {
+        self.send(ControlCommand::Up)
+    }
+
+    pub fn down(&self) -> Result<()> {
+        self.send(ControlCommand::Down)
+    }
+
+    pub fn once(&self) -> Result<()> {
+        self.send(ControlCommand::Once)
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        self.send(ControlCommand::Pause)
+    }
+
+    pub fn cont(&self) -> Result<()> {
+        self.send(ControlCommand::Continue)
+    }
+
+    pub fn signal(&self, signal: SvSignal) -> Result<()> {
+        self.send(signal.control_command())
+    }
+
+    pub fn restart(&self) -> Result<()> {
+        self.send_all(&[
+            ControlCommand::Term,
+            ControlCommand::Continue,
+            ControlCommand::Up,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ControlCommand, ServiceControl, SvSignal};
+    use crate::ServiceError;
+
+    #[test]
+    fn parses_signal_names() {
+        assert_eq!(SvSignal::from_name("hup"), Some(SvSignal::Hup));
+        assert_eq!(SvSignal::from_name("SIGUSR2"), Some(SvSignal::Usr2));
+        assert_eq!(SvSignal::from_name("winch"), None);
+        assert_eq!(SvSignal::from_name("alarm"), Some(SvSignal::Alrm));
+        assert_eq!(SvSignal::from_name("interrupt"), Some(SvSignal::Int));
+        assert_eq!(SvSignal::from_name("1"), Some(SvSignal::Usr1));
+        assert_eq!(SvSignal::from_name("pause"), Some(SvSignal::Stop));
+        assert_eq!(SvSignal::from_name("cont"), Some(SvSignal::Cont));
+        assert_eq!(SvSignal::Stop.control_command(), ControlCommand::Pause);
+        assert_eq!(SvSignal::Cont.control_command().as_byte(), b'c');
+    }
+
+    #[test]
+    fn reports_unsupervised_service() {
+        let dir = std::env::temp_dir().join(format!("runkit-unsupervised-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let control = ServiceControl::new("sshd", &dir);
+        assert!(matches!(
+            control.up(),
+            Err(ServiceError::NotSupervised(name)) if name == "sshd"
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn classifies_sv_diagnostics() {
+        assert!(matches!(
+            ServiceError::from_sv_message("sshd", "warning: sshd: runsv not running"),
+            ServiceError::SupervisorNotRunning(_)
+        ));
+        assert!(matches!(
+            ServiceError::from_sv_message(
+                "sshd",
+                "warning: sshd: unable to open supervise/ok: file does not exist"
+            ),
+            ServiceError::NotSupervised(_)
+        ));
+        assert!(matches!(
+            ServiceError::from_sv_message(
+                "sshd",
+                "fail: sshd: unable to open supervise/control: access denied"
+            ),
+            ServiceError::PermissionDenied(_)
+        ));
+    }
+
+    #[test]
+    fn reports_missing_supervisor() {
+        let dir = std::env::temp_dir().join(format!("runkit-control-{}", std::process::id()));
+        let control = ServiceControl::new("ghost", &dir);
+        assert!(matches!(
+            control.up(),
+            Err(ServiceError::SupervisorNotRunning(name)) if name == "ghost"
+        ));
+    }
+}
@@ -0,0 +1,225 @@
+//! Crash-loop detection from successive status observations.
+use crate::{Result, ServiceManager, ServiceRuntimeState};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// A service that runsv keeps restarting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flapping {
+    /// Restarts seen inside `window`.
+    pub restarts: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "window_seconds", with = "crate::serde_support::seconds")
+    )]
+    pub window: Duration,
+}
+
+impl fmt::Display for Flapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "restarting rapidly, {} restarts in {} seconds",
+            self.restarts,
+            self.window.as_secs()
+        )
+    }
+}
+
+/// Counts pid changes per service over a sliding window.
+///
+/// A single status read cannot tell a crash loop from a service that was just started,
+/// so the tracker has to see the same service several times. Restarts that happen
+/// between two observations are counted once.
+#[derive(Debug, Clone)]
+pub struct RestartTracker {
+    window: Duration,
+    threshold: u32,
+    services: HashMap<String, History>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct History {
+    last_pid: Option<u32>,
+    restarts: VecDeque<Instant>,
+}
+
+impl Default for RestartTracker {
+    /// Three restarts within a minute.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60), 3)
+    }
+}
+
+impl RestartTracker {
+    pub fn new(window: Duration, threshold: u32) -> Self {
+        RestartTracker {
+            window,
+            threshold: threshold.max(1),
+            services: HashMap::new(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Record the state seen now and report whether the service is flapping.
+    pub fn observe(&mut self, service: &str, state: &ServiceRuntimeState) -> Option<Flapping> {
+        self.observe_at(service, state, Instant::now())
+    }
+
+    pub fn observe_at(
+        &mut self,
+        service: &str,
+        state: &ServiceRuntimeState,
+        at: Instant,
+    ) -> Option<Flapping> {
+        let history = self.services.entry(service.to_string()).or_default();
+        let pid = match state {
+            ServiceRuntimeState::Running { pid, .. }
+            | ServiceRuntimeState::Paused { pid, .. }
+            | ServiceRuntimeState::Failed { pid, .. } => Some(*pid),
+            _ => None,
+        };
+        if let Some(pid) = pid {
+            // The first pid seen is the baseline, not a restart.
+            if history.last_pid.is_some_and(|last| last != pid) {
+                history.restarts.push_back(at);
+            }
+            history.last_pid = Some(pid);
+        }
+        while history
+            .restarts
+            .front()
+            .is_some_and(|first| at.saturating_duration_since(*first) > self.window)
+        {
+            history.restarts.pop_front();
+        }
+        self.flapping(service)
+    }
+
+    /// Result of the latest observation, without recording a new one.
+    pub fn flapping(&self, service: &str) -> Option<Flapping> {
+        let restarts = self.services.get(service)?.restarts.len() as u32;
+        (restarts >= self.threshold).then_some(Flapping {
+            restarts,
+            window: self.window,
+        })
+    }
+
+    /// Drop the history of a service, e.g. after the user restarted it on purpose.
+    pub fn forget(&mut self, service: &str) {
+        self.services.remove(service);
+    }
+}
+
+impl ServiceManager {
+    /// Watch the service for `window` and report whether it restarted `threshold` times.
+    ///
+    /// Blocks for the whole window; status is re-read whenever runsv rewrites
+    /// `supervise/status`, so short-lived processes are not missed between polls.
+    pub fn detect_flapping(
+        &self,
+        service: &str,
+        window: Duration,
+        threshold: u32,
+    ) -> Result<Option<Flapping>> {
+        self.validate_service_name(service)?;
+        let mut tracker = RestartTracker::new(window, threshold);
+        let deadline = Instant::now() + window;
+        let mut watcher = self.watch().ok();
+
+        loop {
+            tracker.observe(service, &self.backend.status(service)?);
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(tracker.flapping(service));
+            }
+            let nap = Duration::from_millis(250).min(deadline - now);
+            match watcher.as_mut() {
+                Some(watcher) => {
+                    watcher.wait(nap)?;
+                }
+                None => std::thread::sleep(nap),
+            }
+        }
+    }
+
+    /// Crash-loop tracker shared by this manager and its clones; `list_services` feeds it.
+    pub fn restart_tracker(&self) -> std::sync::MutexGuard<'_, RestartTracker> {
+        self.restarts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Flapping, RestartTracker};
+    use crate::ServiceRuntimeState;
+    use crate::testutil::FakeRunit;
+    use std::time::{Duration, Instant, SystemTime};
+
+    fn running(pid: u32) -> ServiceRuntimeState {
+        ServiceRuntimeState::Running {
+            pid,
+            uptime: Duration::from_secs(1),
+            started_at: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn flags_repeated_pid_changes_inside_window() {
+        let mut tracker = RestartTracker::new(Duration::from_secs(60), 3);
+        let start = Instant::now();
+        assert_eq!(tracker.observe_at("sshd", &running(10), start), None);
+        assert_eq!(
+            tracker.observe_at("sshd", &running(10), start + Duration::from_secs(5)),
+            None
+        );
+        for (offset, pid) in [(10, 11), (15, 12), (20, 13)] {
+            tracker.observe_at("sshd", &running(pid), start + Duration::from_secs(offset));
+        }
+        let flapping = tracker.flapping("sshd").expect("three restarts");
+        assert_eq!(
+            flapping,
+            Flapping {
+                restarts: 3,
+                window: Duration::from_secs(60)
+            }
+        );
+        assert_eq!(
+            flapping.to_string(),
+            "restarting rapidly, 3 restarts in 60 seconds"
+        );
+
+        // Once the restarts age out of the window the warning clears.
+        assert_eq!(
+            tracker.observe_at("sshd", &running(13), start + Duration::from_secs(75)),
+            None
+        );
+    }
+
+    #[test]
+    fn requested_restarts_do_not_count() {
+        let tree = FakeRunit::new("requested");
+        let manager = tree.manager();
+        for pid in [10, 11, 12, 13] {
+            manager.restart_tracker().observe("sshd", &running(pid));
+        }
+        assert!(manager.restart_tracker().flapping("sshd").is_some());
+
+        manager.record_request("sshd");
+        assert_eq!(
+            manager.restart_tracker().observe("sshd", &running(14)),
+            None
+        );
+        assert_eq!(
+            manager.restart_tracker().observe("sshd", &running(15)),
+            None
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Safe in-place editing of the files inside a service definition.
+use crate::scaffold::check_syntax;
+use crate::{Result, ServiceError, ServiceManager};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+impl ServiceManager {
+    /// Current contents of the service's `run` script.
+    pub fn read_run_script(&self, service: &str) -> Result<String> {
+        let path = self.definition_file(service, "run")?;
+        fs::read_to_string(&path).map_err(|err| ServiceError::from_io(&path, err))
+    }
+
+    /// Replace the `run` script, keeping the previous version as `run.bak`.
+    ///
+    /// The new contents are syntax-checked with `sh -n` before they replace the old
+    /// script, and the swap is a single rename so runsv never sees a partial file.
+    pub fn write_run_script(&self, service: &str, contents: &str) -> Result<()> {
+        let path = self.definition_file(service, "run")?;
+        let mode = fs::metadata(&path)
+            .map(|meta| meta.permissions().mode() & 0o7777)
+            .unwrap_or(0o755);
+
+        write_atomic(&path, contents.as_bytes(), mode, true, |staged| {
+            check_syntax(staged, service)
+        })
+    }
+
+    /// Path of a file inside an existing definition.
+    pub(crate) fn definition_file(&self, service: &str, file: &str) -> Result<PathBuf> {
+        self.validate_service_name(service)?;
+        let dir = self.definitions_dir.join(service);
+        if !dir.is_dir() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: dir,
+            });
+        }
+        Ok(dir.join(file))
+    }
+}
+
+/// Write `contents` to a sibling temp file, run `validate` on it, optionally back up the
+/// current file to `<name>.bak`, then rename the temp file over `path`.
+pub(crate) fn write_atomic(
+    path: &Path,
+    contents: &[u8],
+    mode: u32,
+    backup: bool,
+    validate: impl FnOnce(&Path) -> Result<()>,
+) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let staged = path.with_file_name(format!(".{file_name}.tmp"));
+    let backup_path = path.with_file_name(format!("{file_name}.bak"));
+
+    let result = stage(&staged, contents, mode)
+        .and_then(|()| validate(&staged))
+        .and_then(|()| {
+            if backup && path.exists() {
+                fs::copy(path, &backup_path)
+                    .map_err(|err| ServiceError::from_io(&backup_path, err))?;
+            }
+            fs::rename(&staged, path).map_err(|err| ServiceError::from_io(path, err))
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    result
+}
+
+fn stage(staged: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(staged)
+        .map_err(|err| ServiceError::from_io(staged, err))?;
+    file.write_all(contents)
+        .and_then(|()| file.sync_all())
+        .map_err(|err| ServiceError::from_io(staged, err))?;
+    // `mode` on open is masked by the umask; set it explicitly.
+    fs::set_permissions(staged, fs::Permissions::from_mode(mode))
+        .map_err(|err| ServiceError::from_io(staged, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RunScriptSpec, ServiceError, ServiceManager};
+
+    #[test]
+    fn rewrites_run_script_with_backup() {
+        let root = std::env::temp_dir().join(format!("runkit-definition-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        manager
+            .create_service("app", &RunScriptSpec::new("sleep 1"))
+            .unwrap();
+        let original = manager.read_run_script("app").unwrap();
+
+        manager
+            .write_run_script("app", "#!/bin/sh\nexec sleep 2\n")
+            .unwrap();
+        assert_eq!(
+            manager.read_run_script("app").unwrap(),
+            "#!/bin/sh\nexec sleep 2\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("sv/app/run.bak")).unwrap(),
+            original
+        );
+
+        assert!(matches!(
+            manager.write_run_script("app", "if\n"),
+            Err(ServiceError::InvalidRunScript { .. })
+        ));
+        assert_eq!(
+            manager.read_run_script("app").unwrap(),
+            "#!/bin/sh\nexec sleep 2\n"
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,145 @@
+//! Start-order hints recovered from run scripts; runit itself never records dependencies.
+use crate::{Result, ServiceError, ServiceManager, validate_service_name};
+use std::path::Path;
+
+/// `sv` subcommands a run script uses to wait for, or bring up, another service.
+const WAITING_COMMANDS: &[&str] = &["check", "start", "up", "u"];
+
+/// Services a run script declares it needs, in order of first mention.
+///
+/// Recognizes `sv check dbus`, `sv start dbus` (with any `sv` options, several names, or
+/// `/var/service/dbus` style paths) and comments such as `# depends: dbus, elogind`.
+/// Names that involve shell expansion are skipped since they cannot be resolved statically.
+pub fn run_script_dependencies(script: &str) -> Vec<String> {
+    let mut deps = Vec::new();
+    let mut push = |name: &str| {
+        if validate_service_name(name).is_ok() && !deps.iter().any(|dep| dep == name) {
+            deps.push(name.to_string());
+        }
+    };
+
+    for line in script.lines() {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('#') {
+            for name in depends_comment(comment) {
+                push(name);
+            }
+            continue;
+        }
+        let code = match line.find(" #") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+        for command in code.split([';', '&', '|']) {
+            for name in sv_waits_for(command) {
+                push(name);
+            }
+        }
+    }
+    deps
+}
+
+/// Names listed in a `depends:` / `depends on` comment body.
+fn depends_comment(comment: &str) -> Vec<&str> {
+    let comment = comment.trim_start();
+    let Some(prefix) = comment
+        .get(..7)
+        .filter(|p| p.eq_ignore_ascii_case("depends"))
+    else {
+        return Vec::new();
+    };
+    let mut rest = &comment[prefix.len()..];
+    if rest.starts_with(['s', 'S']) {
+        rest = &rest[1..];
+    }
+    let rest = rest.trim_start();
+    let rest = match rest.get(..2) {
+        Some(on) if on.eq_ignore_ascii_case("on") => &rest[2..],
+        _ => rest,
+    };
+    let Some(list) = rest.trim_start().strip_prefix([':', '=']) else {
+        return Vec::new();
+    };
+    list.split([',', ' ', '\t'])
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Services named by a single `sv check|start ...` command, if it is one.
+fn sv_waits_for(command: &str) -> Vec<&str> {
+    let mut words = command.split_whitespace().skip_while(|word| {
+        // Allow `exec`, `env` and similar wrappers in front of `sv`.
+        Path::new(word).file_name().is_none_or(|name| name != "sv")
+    });
+    if words.next().is_none() {
+        return Vec::new();
+    }
+
+    let mut subcommand = None;
+    while let Some(word) = words.next() {
+        match word {
+            "-w" => {
+                words.next();
+            }
+            option if option.starts_with('-') => {}
+            other => {
+                subcommand = Some(other);
+                break;
+            }
+        }
+    }
+    if !subcommand.is_some_and(|cmd| WAITING_COMMANDS.contains(&cmd)) {
+        return Vec::new();
+    }
+
+    words
+        .take_while(|word| !word.starts_with(['>', '<']) && !word.starts_with("2>"))
+        .map(|word| word.trim_matches(['"', '\'']))
+        .filter(|word| !word.contains('$'))
+        .filter_map(|word| Path::new(word).file_name()?.to_str())
+        .collect()
+}
+
+impl ServiceManager {
+    /// Services the definition's `run` script waits for or starts before it execs.
+    pub fn dependencies(&self, service: &str) -> Result<Vec<String>> {
+        let path = self.definition_file(service, "run")?;
+        let script = match std::fs::read_to_string(&path) {
+            Ok(script) => script,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(&path, err)),
+        };
+        let mut deps = run_script_dependencies(&script);
+        deps.retain(|dep| dep != service);
+        Ok(deps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_script_dependencies;
+
+    #[test]
+    fn finds_sv_check_and_start_calls() {
+        let script = "#!/bin/sh\n\
+            sv check dbus >/dev/null || exit 1\n\
+            sv -w 10 start /var/service/elogind udevd; exec 2>&1\n\
+            [ -x /usr/bin/foo ] && sv up \"$DEP\"\n\
+            sv status ignored\n\
+            exec NetworkManager -n # sv check not-a-dep\n";
+        assert_eq!(
+            run_script_dependencies(script),
+            ["dbus", "elogind", "udevd"]
+        );
+    }
+
+    #[test]
+    fn reads_depends_comments() {
+        let script =
+            "#!/bin/sh\n# Depends on: dbus, polkitd\n# depends=dbus seatd\n# dependency notes\n";
+        assert_eq!(
+            run_script_dependencies(script),
+            ["dbus", "polkitd", "seatd"]
+        );
+    }
+}
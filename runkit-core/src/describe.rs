@@ -0,0 +1,430 @@
+//! Package descriptions for services: batch lookups through xbps and their cache.
+#[cfg(feature = "serde")]
+use crate::definition::write_atomic;
+use crate::{Result, ServiceInfo, ServiceManager, strip_package_version};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a cached description is trusted when the run script does not change.
+pub const DEFAULT_DESCRIPTION_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Package descriptions keyed by service, invalidated when the run script's mtime changes
+/// or the entry is older than the TTL.
+///
+/// With the `serde` feature the cache can be persisted as JSON; without it, it lives only
+/// as long as the process.
+#[derive(Debug, Clone)]
+pub struct DescriptionCache {
+    path: Option<PathBuf>,
+    ttl: Duration,
+    entries: HashMap<String, CachedDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct CachedDescription {
+    description: Option<String>,
+    /// Seconds since the epoch of the run script's mtime when the entry was stored.
+    script_mtime: u64,
+    /// Seconds since the epoch when the entry was stored.
+    cached_at: u64,
+}
+
+impl Default for DescriptionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DescriptionCache {
+    /// Empty in-memory cache.
+    pub fn new() -> Self {
+        DescriptionCache {
+            path: None,
+            ttl: DEFAULT_DESCRIPTION_TTL,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// `$XDG_CACHE_HOME/runkit/descriptions.json`, falling back to `~/.cache`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(base.join("runkit").join("descriptions.json"))
+    }
+
+    /// Cached description for the service, if the entry is still valid.
+    ///
+    /// The outer `None` means "look it up"; `Some(None)` means the lookup was done and
+    /// found nothing.
+    pub fn get(&self, service: &str, definition_path: &Path) -> Option<Option<String>> {
+        let entry = self.entries.get(service)?;
+        let fresh = unix_now().saturating_sub(entry.cached_at) <= self.ttl.as_secs();
+        let unchanged = script_mtime(definition_path) == Some(entry.script_mtime);
+        (fresh && unchanged).then(|| entry.description.clone())
+    }
+
+    /// Remember a lookup result. Nothing is stored when the service has no script to key on.
+    pub fn insert(&mut self, service: &str, definition_path: &Path, description: Option<String>) {
+        let Some(script_mtime) = script_mtime(definition_path) else {
+            return;
+        };
+        self.entries.insert(
+            service.to_string(),
+            CachedDescription {
+                description,
+                script_mtime,
+                cached_at: unix_now(),
+            },
+        );
+    }
+
+    pub fn remove(&mut self, service: &str) {
+        self.entries.remove(service);
+    }
+
+    /// File the cache is loaded from and saved to, if persistent.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl DescriptionCache {
+    /// Load the cache stored at `path`. A missing or unreadable file yields an empty
+    /// cache that will be written to `path` on save.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = std::fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        DescriptionCache {
+            path: Some(path),
+            ttl: DEFAULT_DESCRIPTION_TTL,
+            entries,
+        }
+    }
+
+    /// [`DescriptionCache::load`] from [`DescriptionCache::default_path`], or an in-memory
+    /// cache when no cache directory can be determined.
+    pub fn load_default() -> Self {
+        Self::default_path().map(Self::load).unwrap_or_default()
+    }
+
+    /// Write the cache back to its file through a temp file renamed over it, so a
+    /// concurrent load never sees half of it; a no-op for in-memory caches.
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(&self.entries).map_err(std::io::Error::other)?;
+        write_atomic(path, &data, 0o644, false, |_| Ok(())).map_err(std::io::Error::other)
+    }
+}
+
+impl ServiceManager {
+    /// Cache package descriptions looked up by [`ServiceManager::service_description`].
+    pub fn with_description_cache(mut self, cache: DescriptionCache) -> Self {
+        self.description_cache = Some(Arc::new(Mutex::new(cache)));
+        self
+    }
+
+    /// Descriptions for many services at once.
+    ///
+    /// Local `description`/`README` files win as in [`ServiceManager::service_description`].
+    /// For the rest, package ownership is resolved with one `xbps-query -o` glob per script
+    /// name and descriptions come from a single `xbps-query -l`, instead of two
+    /// subprocesses per service. Services without a definition are left out.
+    pub fn describe_all<S: AsRef<str>>(
+        &self,
+        services: &[S],
+    ) -> Result<HashMap<String, Option<String>>> {
+        let mut descriptions = HashMap::new();
+        let mut unresolved: Vec<(String, PathBuf)> = Vec::new();
+
+        for service in services {
+            let service = service.as_ref();
+            self.validate_service_name(service)?;
+            let definition_path = self.definitions_dir.join(service);
+            if !definition_path.exists() {
+                continue;
+            }
+            if let Some(description) = self.read_description(&definition_path) {
+                descriptions.insert(service.to_string(), Some(description));
+                continue;
+            }
+            let cached = self.description_cache.as_ref().and_then(|cache| {
+                let cache = cache
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                cache.get(service, &definition_path)
+            });
+            match cached {
+                Some(description) => {
+                    descriptions.insert(service.to_string(), description);
+                }
+                None => unresolved.push((service.to_string(), definition_path)),
+            }
+        }
+        if unresolved.is_empty() {
+            return Ok(descriptions);
+        }
+
+        let mut owners = HashMap::new();
+        let mut script_names: Vec<OsString> = unresolved
+            .iter()
+            .filter_map(|(_, path)| description_script(path)?.file_name().map(OsString::from))
+            .collect();
+        script_names.sort_unstable();
+        script_names.dedup();
+        for name in script_names {
+            let pattern = self.definitions_dir.join("*").join(name);
+            if let Some(output) = xbps_query([OsStr::new("-o"), pattern.as_os_str()]) {
+                owners.extend(parse_owned_by(&output));
+            }
+        }
+        let package_descriptions = xbps_query(["-l"])
+            .map(|output| parse_package_list(&output))
+            .unwrap_or_default();
+
+        for (service, definition_path) in unresolved {
+            let description = description_script(&definition_path)
+                .and_then(|script| owners.get(&script))
+                .and_then(|pkgver| package_descriptions.get(strip_package_version(pkgver)))
+                .cloned();
+            if let Some(cache) = &self.description_cache {
+                let mut cache = cache
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                cache.insert(&service, &definition_path, description.clone());
+            }
+            descriptions.insert(service, description);
+        }
+        #[cfg(feature = "serde")]
+        if let Some(cache) = &self.description_cache {
+            let _ = cache
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .save();
+        }
+        Ok(descriptions)
+    }
+
+    /// Package descriptions for listed services that have none of their own, from one
+    /// [`ServiceManager::describe_all`] batch. Only done with a description cache, so a
+    /// listing forks xbps-query for new or changed services alone and saves once.
+    pub(crate) fn fill_package_descriptions(&self, services: &mut [ServiceInfo]) {
+        if self.description_cache.is_none() {
+            return;
+        }
+        let missing: Vec<&str> = services
+            .iter()
+            .filter(|info| info.description.is_none())
+            .map(|info| info.name.as_str())
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        let Ok(mut descriptions) = self.describe_all(&missing) else {
+            return;
+        };
+        for info in services
+            .iter_mut()
+            .filter(|info| info.description.is_none())
+        {
+            if let Some(description) = descriptions.remove(&info.name) {
+                info.description = description;
+            }
+        }
+    }
+
+    pub(crate) fn cached_package_description(&self, definition_path: &Path) -> Option<String> {
+        let Some(cache) = &self.description_cache else {
+            return self.lookup_package_description(definition_path);
+        };
+        let service = definition_path.file_name()?.to_str()?;
+        let lock = || {
+            cache
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        };
+        if let Some(description) = lock().get(service, definition_path) {
+            return description;
+        }
+
+        // Not under the lock: other threads keep reading the cache while xbps-query runs.
+        let description = self.lookup_package_description(definition_path);
+        let mut cache = lock();
+        cache.insert(service, definition_path, description.clone());
+        // The cache is an optimisation; failing to persist it must not fail the lookup.
+        #[cfg(feature = "serde")]
+        let _ = cache.save();
+        description
+    }
+}
+
+/// The script the package lookup is keyed on: the same one `xbps-query -o` is asked about.
+pub(crate) fn description_script(definition_path: &Path) -> Option<PathBuf> {
+    ["run", "finish", "check"]
+        .into_iter()
+        .map(|candidate| definition_path.join(candidate))
+        .find(|path| path.exists())
+}
+
+fn xbps_query<I, A>(args: I) -> Option<String>
+where
+    I: IntoIterator<Item = A>,
+    A: AsRef<OsStr>,
+{
+    let output = Command::new("xbps-query").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Parse `xbps-query -o` lines of the form `pkgver: /path` (optionally followed by
+/// ` -> target` for symlinks or ` (type)`) into path -> pkgver.
+pub(crate) fn parse_owned_by(output: &str) -> HashMap<PathBuf, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (pkgver, rest) = line.trim().split_once(": ")?;
+            let path = rest.split(" -> ").next()?;
+            let path = path.split(" (").next()?.trim();
+            Some((PathBuf::from(path), pkgver.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Parse `xbps-query -l` lines (`ii pkgver short description`) into name -> description.
+fn parse_package_list(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _state = fields.next()?;
+            let pkgver = fields.next()?;
+            let description = fields.collect::<Vec<_>>().join(" ");
+            (!description.is_empty())
+                .then(|| (strip_package_version(pkgver).to_string(), description))
+        })
+        .collect()
+}
+
+fn script_mtime(definition_path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(description_script(definition_path)?)
+        .and_then(|meta| meta.modified())
+        .ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DescriptionCache, parse_owned_by, parse_package_list};
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_batch_xbps_output() {
+        let owners = parse_owned_by(
+            "openssh-9.6p1_1: /etc/sv/sshd/run\n\
+             runit-void-20231124_1: /etc/sv/agetty-tty1/run -> /etc/sv/agetty-generic/run\n",
+        );
+        assert_eq!(owners[Path::new("/etc/sv/sshd/run")], "openssh-9.6p1_1");
+        assert_eq!(
+            owners[Path::new("/etc/sv/agetty-tty1/run")],
+            "runit-void-20231124_1"
+        );
+
+        let packages = parse_package_list(
+            "ii openssh-9.6p1_1        The OpenBSD Secure Shell\n\
+             ii runit-void-20231124_1  Void Linux runit scripts\n",
+        );
+        assert_eq!(packages["openssh"], "The OpenBSD Secure Shell");
+        assert_eq!(packages["runit-void"], "Void Linux runit scripts");
+    }
+
+    #[test]
+    fn invalidates_on_script_change_and_ttl() {
+        let root = std::env::temp_dir().join(format!("runkit-describe-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let sshd = root.join("sshd");
+        std::fs::create_dir_all(&sshd).unwrap();
+
+        let mut cache = DescriptionCache::new();
+        cache.insert("sshd", &sshd, Some("OpenSSH".into()));
+        assert_eq!(cache.get("sshd", &sshd), None, "no run script to key on");
+
+        std::fs::write(sshd.join("run"), "#!/bin/sh\n").unwrap();
+        cache.insert("sshd", &sshd, Some("OpenSSH".into()));
+        assert_eq!(cache.get("sshd", &sshd), Some(Some("OpenSSH".into())));
+
+        let run = std::fs::File::options()
+            .write(true)
+            .open(sshd.join("run"))
+            .unwrap();
+        run.set_modified(std::time::UNIX_EPOCH + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(cache.get("sshd", &sshd), None);
+
+        cache.insert("sshd", &sshd, None);
+        assert_eq!(cache.get("sshd", &sshd), Some(None));
+        let expired = cache.with_ttl(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(1100));
+        assert_eq!(expired.get("sshd", &sshd), None);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn saves_through_a_temp_file() {
+        let root =
+            std::env::temp_dir().join(format!("runkit-describe-save-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let sshd = root.join("sv/sshd");
+        std::fs::create_dir_all(&sshd).unwrap();
+        std::fs::write(sshd.join("run"), "#!/bin/sh\n").unwrap();
+        let path = root.join("cache/descriptions.json");
+
+        let mut cache = DescriptionCache::load(&path);
+        cache.insert("sshd", &sshd, Some("OpenSSH".into()));
+        cache.save().unwrap();
+        cache.insert("sshd", &sshd, Some("OpenSSH daemon".into()));
+        cache.save().unwrap();
+
+        let loaded = DescriptionCache::load(&path);
+        assert_eq!(
+            loaded.get("sshd", &sshd),
+            Some(Some("OpenSSH daemon".into()))
+        );
+        let files: Vec<_> = std::fs::read_dir(root.join("cache"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["descriptions.json"]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
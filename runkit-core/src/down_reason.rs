@@ -0,0 +1,210 @@
+//! Notes explaining why a service was stopped or disabled, kept next to its definition.
+use crate::journal::default_actor;
+use crate::{Result, ServiceError, ServiceManager};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// File in the service definition holding the last recorded [`DownReason`].
+pub const DOWN_REASON_FILE: &str = "runkit.down-reason";
+
+/// Why a service was taken down, and by whom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DownReason {
+    pub reason: String,
+    /// Who recorded the reason; `None` for notes written by hand.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub actor: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "at_unix", with = "crate::serde_support::unix_seconds")
+    )]
+    pub at: SystemTime,
+}
+
+impl DownReason {
+    /// Read the `reason:`, `actor:` and `at:` lines of a down-reason file. Other lines
+    /// count as reason text, so a note written by hand is kept as it is; `written` stands
+    /// in for a missing `at:` line.
+    pub fn parse(text: &str, written: SystemTime) -> Option<Self> {
+        let mut reason = Vec::new();
+        let mut actor = None;
+        let mut at = None;
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match line.split_once(':').map(|(key, value)| (key, value.trim())) {
+                Some(("reason", value)) => reason.push(value),
+                Some(("actor", value)) if !value.is_empty() => actor = Some(value.to_string()),
+                Some(("at", value)) => match value.parse() {
+                    Ok(seconds) => at = Some(UNIX_EPOCH + Duration::from_secs(seconds)),
+                    Err(_) => reason.push(line),
+                },
+                _ => reason.push(line),
+            }
+        }
+        if reason.is_empty() {
+            return None;
+        }
+        Some(DownReason {
+            reason: reason.join(" "),
+            actor,
+            at: at.unwrap_or(written),
+        })
+    }
+
+    /// Contents of the file [`DownReason::parse`] reads back.
+    pub fn to_file_contents(&self) -> String {
+        let seconds = self.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut contents = format!("reason: {}\n", one_line(&self.reason));
+        if let Some(actor) = &self.actor {
+            contents.push_str(&format!("actor: {}\n", one_line(actor)));
+        }
+        contents.push_str(&format!("at: {}\n", seconds.as_secs()));
+        contents
+    }
+}
+
+impl fmt::Display for DownReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.actor {
+            Some(actor) => write!(f, "{} ({actor})", self.reason),
+            None => f.write_str(&self.reason),
+        }
+    }
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The reason stored in `definition`, if there is a readable one.
+pub(crate) fn read_down_reason(definition: &Path) -> Option<DownReason> {
+    let path = definition.join(DOWN_REASON_FILE);
+    let text = std::fs::read_to_string(&path).ok()?;
+    let written = std::fs::metadata(&path)
+        .and_then(|meta| meta.modified())
+        .unwrap_or(UNIX_EPOCH);
+    DownReason::parse(&text, written)
+}
+
+impl ServiceManager {
+    fn down_reason_path(&self, service: &str) -> PathBuf {
+        self.definitions_dir.join(service).join(DOWN_REASON_FILE)
+    }
+
+    /// The note left by the last [`ServiceManager::stop_with_reason`] or
+    /// [`ServiceManager::disable_with_reason`], until the service is started or enabled.
+    pub fn down_reason(&self, service: &str) -> Result<Option<DownReason>> {
+        self.validate_service_name(service)?;
+        Ok(read_down_reason(&self.definitions_dir.join(service)))
+    }
+
+    /// Store `reason` for the service, attributed to the journal's actor or else the
+    /// invoking user.
+    pub fn set_down_reason(&self, service: &str, reason: &str) -> Result<DownReason> {
+        self.validate_service_name(service)?;
+        let definition = self.definitions_dir.join(service);
+        if !definition.is_dir() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: definition,
+            });
+        }
+        let actor = match self.event_journal() {
+            Some(journal) => journal.actor().to_string(),
+            None => default_actor(),
+        };
+        // The file keeps whole seconds; match what a later read returns.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let note = DownReason {
+            reason: one_line(reason),
+            actor: Some(actor),
+            at: UNIX_EPOCH + Duration::from_secs(now.as_secs()),
+        };
+        let path = self.down_reason_path(service);
+        std::fs::write(&path, note.to_file_contents())
+            .map_err(|err| ServiceError::from_io(&path, err))?;
+        Ok(note)
+    }
+
+    pub fn clear_down_reason(&self, service: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        let path = self.down_reason_path(service);
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(ServiceError::from_io(&path, err)),
+        }
+    }
+
+    /// [`ServiceManager::stop`], then record why.
+    pub fn stop_with_reason(&self, service: &str, reason: &str) -> Result<DownReason> {
+        self.stop(service)?;
+        self.set_down_reason(service, reason)
+    }
+
+    /// [`ServiceManager::disable`], then record why.
+    pub fn disable_with_reason(&self, service: &str, reason: &str) -> Result<DownReason> {
+        self.disable(service)?;
+        self.set_down_reason(service, reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DOWN_REASON_FILE, DownReason};
+    use crate::testutil::FakeRunit;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn parses_recorded_and_handwritten_notes() {
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let note = DownReason {
+            reason: "migrating the\ndatabase".to_string(),
+            actor: Some("alice".to_string()),
+            at,
+        };
+        let contents = note.to_file_contents();
+        assert_eq!(
+            contents,
+            "reason: migrating the database\nactor: alice\nat: 1700000000\n"
+        );
+        let parsed = DownReason::parse(&contents, UNIX_EPOCH).unwrap();
+        assert_eq!(parsed.to_string(), "migrating the database (alice)");
+        assert_eq!(parsed.at, at);
+
+        let handwritten = DownReason::parse("broken NIC, see ticket 42\n", at).unwrap();
+        assert_eq!(handwritten.reason, "broken NIC, see ticket 42");
+        assert_eq!(handwritten.actor, None);
+        assert_eq!(handwritten.at, at);
+        assert_eq!(DownReason::parse("\n  \n", at), None);
+    }
+
+    #[test]
+    fn disabling_with_a_reason_shows_up_in_the_listing() {
+        let tree = FakeRunit::new("down-reason");
+        tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        tree.enable("sshd");
+        tree.set_down("sshd", SystemTime::now());
+        let manager = tree.manager();
+
+        let note = manager
+            .disable_with_reason("sshd", "replaced by dropbear")
+            .unwrap();
+        assert!(note.actor.is_some());
+        let services = manager.list_services().unwrap();
+        assert_eq!(services[0].down_reason.as_ref(), Some(&note));
+
+        manager.enable("sshd").unwrap();
+        assert_eq!(manager.down_reason("sshd").unwrap(), None);
+        assert!(
+            !tree
+                .definitions_dir()
+                .join("sshd")
+                .join(DOWN_REASON_FILE)
+                .exists()
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Environment directories read by `chpst -e`: one file per variable.
+use crate::definition::write_atomic;
+use crate::{Result, ServiceError, ServiceManager};
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// What `chpst -e` does with one file of an environment directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvValue {
+    /// The variable is set to this value.
+    Set(String),
+    /// The file is empty, so the variable is removed from the environment.
+    Unset,
+}
+
+/// An `env/` directory inside a service definition.
+#[derive(Debug, Clone)]
+pub struct EnvDir {
+    path: PathBuf,
+}
+
+impl EnvDir {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        EnvDir { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every variable in the directory, sorted by name. A missing directory is empty.
+    pub fn list(&self) -> Result<Vec<(String, EnvValue)>> {
+        let read_dir = match std::fs::read_dir(&self.path) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(&self.path, err)),
+        };
+
+        let mut vars = Vec::new();
+        for entry in read_dir {
+            let entry = entry.map_err(|e| ServiceError::from_io(&self.path, e))?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            // chpst skips dot files, which is also where staged writes live.
+            if name.starts_with('.') || !entry.path().is_file() {
+                continue;
+            }
+            if let Some(value) = self.get(&name)? {
+                vars.push((name, value));
+            }
+        }
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(vars)
+    }
+
+    /// Decode a variable the way chpst does: first line only, trailing blanks
+    /// dropped, NUL bytes turned into newlines.
+    pub fn get(&self, name: &str) -> Result<Option<EnvValue>> {
+        let path = self.var_path(name)?;
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(ServiceError::from_io(&path, err)),
+        };
+        if bytes.is_empty() {
+            return Ok(Some(EnvValue::Unset));
+        }
+
+        let line = bytes.split(|b| *b == b'\n').next().unwrap_or_default();
+        let end = line
+            .iter()
+            .rposition(|b| *b != b' ' && *b != b'\t')
+            .map_or(0, |idx| idx + 1);
+        let value: Vec<u8> = line[..end]
+            .iter()
+            .map(|b| if *b == 0 { b'\n' } else { *b })
+            .collect();
+        Ok(Some(EnvValue::Set(
+            String::from_utf8_lossy(&value).into_owned(),
+        )))
+    }
+
+    /// Store a value. Newlines are encoded as NUL bytes so chpst restores them, and
+    /// trailing blanks are dropped because chpst would strip them anyway.
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        let encoded = value.trim_end_matches([' ', '\t']).replace('\n', "\0");
+        self.write(name, format!("{encoded}\n").as_bytes())
+    }
+
+    /// Write an empty file so chpst removes the variable from the inherited environment.
+    pub fn unset(&self, name: &str) -> Result<()> {
+        self.write(name, b"")
+    }
+
+    /// Delete the variable's file so the inherited value passes through untouched.
+    /// Returns whether a file was removed.
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let path = self.var_path(name)?;
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(ServiceError::from_io(&path, err)),
+        }
+    }
+
+    /// Set and remove several variables, checking every name before touching any file.
+    pub fn update(&self, set: &[(String, String)], remove: &[String]) -> Result<()> {
+        for name in set.iter().map(|(name, _)| name).chain(remove) {
+            self.var_path(name)?;
+        }
+        for (name, value) in set {
+            self.set(name, value)?;
+        }
+        for name in remove {
+            self.remove(name)?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, name: &str, contents: &[u8]) -> Result<()> {
+        let path = self.var_path(name)?;
+        if !self.path.is_dir() {
+            // Match the definition directory, whatever umask the helper runs with.
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o755)
+                .create(&self.path)
+                .and_then(|()| {
+                    std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o755))
+                })
+                .map_err(|e| ServiceError::from_io(&self.path, e))?;
+        }
+        // Values are often credentials, so new files are readable by root only; a mode
+        // someone chose for an existing one is kept.
+        let mode = std::fs::metadata(&path)
+            .map(|meta| meta.permissions().mode() & 0o7777)
+            .unwrap_or(0o600);
+        // No `.bak` here: chpst would pick it up as another variable.
+        write_atomic(&path, contents, mode, false, |_| Ok(()))
+    }
+
+    fn var_path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty() && !name.starts_with('.') && !name.contains(['=', '/', '\0']);
+        if !valid {
+            return Err(ServiceError::Other(
+                format!("invalid environment variable name: {name}").into(),
+            ));
+        }
+        Ok(self.path.join(name))
+    }
+}
+
+impl ServiceManager {
+    /// The `env/` directory of a service, for use with `chpst -e env`.
+    pub fn env_dir(&self, service: &str) -> Result<EnvDir> {
+        self.definition_file(service, "env").map(EnvDir::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EnvDir, EnvValue};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn round_trips_chpst_values() {
+        let dir = std::env::temp_dir().join(format!("runkit-envdir-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let env = EnvDir::new(&dir);
+
+        env.set("GREETING", "hello\nworld  ").unwrap();
+        env.unset("TZ").unwrap();
+        std::fs::write(dir.join("PATH"), "/usr/bin\t\nignored\n").unwrap();
+
+        assert_eq!(
+            env.list().unwrap(),
+            [
+                (
+                    "GREETING".to_string(),
+                    EnvValue::Set("hello\nworld".to_string())
+                ),
+                ("PATH".to_string(), EnvValue::Set("/usr/bin".to_string())),
+                ("TZ".to_string(), EnvValue::Unset),
+            ]
+        );
+        assert!(env.set("A=B", "x").is_err());
+        assert!(env.remove("TZ").unwrap());
+        assert_eq!(env.get("TZ").unwrap(), None);
+
+        let set = [("LANG".to_string(), "C".to_string())];
+        assert!(env.update(&set, &["bad/name".to_string()]).is_err());
+        assert_eq!(env.get("LANG").unwrap(), None);
+        env.update(&set, &["PATH".to_string()]).unwrap();
+        assert_eq!(
+            env.get("LANG").unwrap(),
+            Some(EnvValue::Set("C".to_string()))
+        );
+        assert_eq!(env.get("PATH").unwrap(), None);
+
+        let mode = |name: &str| {
+            let meta = std::fs::metadata(dir.join(name)).unwrap();
+            meta.permissions().mode() & 0o777
+        };
+        assert_eq!(mode("LANG"), 0o600);
+        std::fs::set_permissions(dir.join("LANG"), std::fs::Permissions::from_mode(0o640)).unwrap();
+        env.set("LANG", "C.UTF-8").unwrap();
+        assert_eq!(mode("LANG"), 0o640);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
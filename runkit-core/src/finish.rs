@@ -0,0 +1,439 @@
+//! Recent exits of a service, pieced together from successive status observations.
+#[cfg(feature = "serde")]
+use crate::definition::write_atomic;
+use crate::supervise::{SupervisedProcessState, read_supervise_status};
+use crate::{EventJournal, Result, ServiceManager, ServiceRuntimeState};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Observations closer together than this describe the same exit.
+const SAME_EXIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// One exit of the `run` process, as passed to the `finish` script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FinishRecord {
+    /// When runsv noticed the exit.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "at_unix", with = "crate::serde_support::unix_seconds")
+    )]
+    pub at: SystemTime,
+    /// Exit status; `None` when it was killed by a signal or the exit was only inferred.
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the process.
+    pub signal: Option<i32>,
+}
+
+impl FinishRecord {
+    /// Exit whose status was not observed, e.g. because the `finish` script was too quick.
+    pub fn inferred(at: SystemTime) -> Self {
+        FinishRecord {
+            at,
+            exit_code: None,
+            signal: None,
+        }
+    }
+
+    /// Build a record from the `finish` command line. runsv runs `./finish <code> <signal>`
+    /// with a code of -1 when the process was killed and a signal of 0 when it exited.
+    pub fn from_finish_args(at: SystemTime, argv: &[String]) -> Self {
+        let number = |index: usize| argv.get(index).and_then(|arg| arg.parse::<i32>().ok());
+        FinishRecord {
+            at,
+            exit_code: number(1).filter(|code| *code >= 0),
+            signal: number(2).filter(|signal| *signal > 0),
+        }
+    }
+
+    /// The process died from a signal or exited with a non-zero status.
+    pub fn is_crash(&self) -> bool {
+        self.signal.is_some() || self.exit_code.is_some_and(|code| code != 0)
+    }
+}
+
+impl fmt::Display for FinishRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.exit_code, self.signal) {
+            (_, Some(signal)) => write!(f, "killed by signal {signal}"),
+            (Some(code), None) => write!(f, "exited with code {code}"),
+            (None, None) => f.write_str("exited"),
+        }
+    }
+}
+
+/// Keeps the last few exits per service.
+///
+/// runsv only remembers the current state, so exits are inferred from pid changes between
+/// observations and completed with the `finish` arguments when a `finish` script is seen
+/// running. Exits that happen between two observations without a pid change are missed.
+///
+/// With the `serde` feature the history can be persisted as JSON, so short-lived
+/// processes such as runkitd see exits between runs; without it, it lives only as long
+/// as the process.
+#[derive(Debug, Clone)]
+pub struct FinishHistory {
+    path: Option<PathBuf>,
+    capacity: usize,
+    services: HashMap<String, Exits>,
+    /// Services whose entry changed since it was loaded or last saved.
+    changed: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Exits {
+    last_pid: Option<u32>,
+    records: VecDeque<FinishRecord>,
+}
+
+impl Default for FinishHistory {
+    /// The last ten exits.
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+impl FinishHistory {
+    pub fn new(capacity: usize) -> Self {
+        FinishHistory {
+            path: None,
+            capacity: capacity.max(1),
+            services: HashMap::new(),
+            changed: HashSet::new(),
+        }
+    }
+
+    /// `finishes.json` next to [`EventJournal::default_path`].
+    pub fn default_path() -> Option<PathBuf> {
+        Some(EventJournal::default_path()?.with_file_name("finishes.json"))
+    }
+
+    /// File the history is loaded from and saved to, if persistent.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Record the state seen now and return the exit it reveals, if any.
+    pub fn observe(&mut self, service: &str, state: &ServiceRuntimeState) -> Option<FinishRecord> {
+        let exits = self.services.entry(service.to_string()).or_default();
+        let (pid, exit) = match state {
+            ServiceRuntimeState::Running {
+                pid, started_at, ..
+            }
+            | ServiceRuntimeState::Paused {
+                pid, started_at, ..
+            } => (
+                Some(*pid),
+                exits
+                    .last_pid
+                    .is_some_and(|last| last != *pid)
+                    .then(|| FinishRecord::inferred(*started_at)),
+            ),
+            ServiceRuntimeState::Down { started_at, .. } => (
+                None,
+                exits
+                    .last_pid
+                    .is_some()
+                    .then(|| FinishRecord::inferred(*started_at)),
+            ),
+            ServiceRuntimeState::Failed { pid, .. } => (Some(*pid), None),
+            ServiceRuntimeState::Unknown { .. } => (exits.last_pid, None),
+        };
+        if exits.last_pid != pid {
+            exits.last_pid = pid;
+            self.changed.insert(service.to_string());
+        }
+        let exit = exit?;
+        self.record(service, exit);
+        Some(exit)
+    }
+
+    /// Add an exit learned elsewhere. A record close to a known one fills in its status
+    /// instead of being added twice.
+    pub fn record(&mut self, service: &str, record: FinishRecord) {
+        let exits = self.services.entry(service.to_string()).or_default();
+        let same = exits.records.iter_mut().find(|known| {
+            let gap = known
+                .at
+                .duration_since(record.at)
+                .or_else(|_| record.at.duration_since(known.at))
+                .unwrap_or_default();
+            gap < SAME_EXIT_WINDOW
+        });
+        match same {
+            Some(known) => {
+                let filled = FinishRecord {
+                    exit_code: known.exit_code.or(record.exit_code),
+                    signal: known.signal.or(record.signal),
+                    ..*known
+                };
+                if filled == *known {
+                    return;
+                }
+                *known = filled;
+            }
+            None => {
+                let index = exits.records.partition_point(|known| known.at > record.at);
+                exits.records.insert(index, record);
+                exits.records.truncate(self.capacity);
+            }
+        }
+        self.changed.insert(service.to_string());
+    }
+
+    /// Known exits, newest first.
+    pub fn history(&self, service: &str) -> Vec<FinishRecord> {
+        self.services
+            .get(service)
+            .map(|exits| exits.records.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn forget(&mut self, service: &str) {
+        if self.services.remove(service).is_some() {
+            self.changed.insert(service.to_string());
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl FinishHistory {
+    /// Load the history stored at `path`, keeping the last ten exits. A missing or
+    /// unreadable file yields an empty history that will be written to `path` on save.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let services = read_services(&path);
+        FinishHistory {
+            path: Some(path),
+            services,
+            ..Self::default()
+        }
+    }
+
+    /// [`FinishHistory::load`] from [`FinishHistory::default_path`], or an in-memory
+    /// history when no state directory can be determined.
+    pub fn load_default() -> Self {
+        Self::default_path().map(Self::load).unwrap_or_default()
+    }
+
+    /// Write the services that changed back to the file, on top of whatever other
+    /// processes saved meanwhile. A no-op when nothing changed or for in-memory histories.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.changed.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Held from reading to renaming, so two savers cannot drop each other's changes.
+        let lock = std::fs::File::create(path.with_extension("lock"))?;
+        lock.lock()?;
+        let mut services = read_services(path);
+        for service in &self.changed {
+            match self.services.get(service) {
+                Some(exits) => services.insert(service.clone(), exits.clone()),
+                None => services.remove(service),
+            };
+        }
+        let data = serde_json::to_vec(&services).map_err(std::io::Error::other)?;
+        write_atomic(path, &data, 0o644, false, |_| Ok(())).map_err(std::io::Error::other)?;
+        self.services = services;
+        self.changed.clear();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+fn read_services(path: &Path) -> HashMap<String, Exits> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+impl ServiceManager {
+    /// Track exits in `history` instead of a fresh in-memory one.
+    pub fn with_finish_history(mut self, history: FinishHistory) -> Self {
+        self.finishes = Arc::new(Mutex::new(history));
+        self
+    }
+
+    /// Exits of the service seen by this manager and its clones, newest first.
+    ///
+    /// An in-memory history only covers the lifetime of the manager; call this or
+    /// [`ServiceManager::list_services`] periodically to build it up, or give the
+    /// manager a persistent [`FinishHistory`].
+    pub fn finish_history(&self, service: &str) -> Result<Vec<FinishRecord>> {
+        self.validate_service_name(service)?;
+        self.observe_finish(service, &self.backend.status(service)?);
+        self.save_finishes();
+        Ok(self.finish_tracker().history(service))
+    }
+
+    /// Persist the history after a round of observations. Like the description cache, it
+    /// is an optimisation that must not fail the operation.
+    pub(crate) fn save_finishes(&self) {
+        #[cfg(feature = "serde")]
+        let _ = self.finish_tracker().save();
+    }
+
+    /// Exit history shared by this manager and its clones.
+    pub fn finish_tracker(&self) -> std::sync::MutexGuard<'_, FinishHistory> {
+        self.finishes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Feed the tracker, picking up the exit status while a `finish` script runs.
+    pub(crate) fn observe_finish(&self, service: &str, state: &ServiceRuntimeState) {
+        let finishing = read_supervise_status(&self.enabled_dir.join(service))
+            .ok()
+            .filter(|status| status.state == SupervisedProcessState::Finishing)
+            .and_then(|status| {
+                let argv = finish_args(Path::new("/proc"), status.pid?)?;
+                Some(FinishRecord::from_finish_args(status.changed_at(), &argv))
+            });
+        let mut tracker = self.finish_tracker();
+        tracker.observe(service, state);
+        if let Some(record) = finishing {
+            tracker.record(service, record);
+        }
+    }
+}
+
+/// Command line of the running `finish` script.
+fn finish_args(proc_root: &Path, pid: u32) -> Option<Vec<String>> {
+    let raw = std::fs::read(proc_root.join(pid.to_string()).join("cmdline")).ok()?;
+    let argv: Vec<String> = raw
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    argv.first()
+        .is_some_and(|program| program.ends_with("finish"))
+        .then_some(argv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FinishHistory, FinishRecord};
+    use crate::ServiceRuntimeState;
+    use std::time::{Duration, SystemTime};
+
+    fn running(pid: u32, started_at: SystemTime) -> ServiceRuntimeState {
+        ServiceRuntimeState::Running {
+            pid,
+            uptime: Duration::from_secs(1),
+            started_at,
+        }
+    }
+
+    #[test]
+    fn infers_exits_and_merges_finish_arguments() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut history = FinishHistory::new(2);
+        assert_eq!(history.observe("sshd", &running(10, start)), None);
+        assert_eq!(history.observe("sshd", &running(10, start)), None);
+
+        let restarted = start + Duration::from_secs(30);
+        let exit = history.observe("sshd", &running(11, restarted)).unwrap();
+        assert_eq!(exit, FinishRecord::inferred(restarted));
+        assert_eq!(exit.to_string(), "exited");
+
+        let args = ["./finish", "-1", "9"].map(String::from);
+        history.record("sshd", FinishRecord::from_finish_args(restarted, &args));
+        let down = start + Duration::from_secs(60);
+        history.observe(
+            "sshd",
+            &ServiceRuntimeState::Down {
+                since: Duration::ZERO,
+                normally_up: true,
+                started_at: down,
+            },
+        );
+        let args = ["./finish", "3", "0"].map(String::from);
+        history.record("sshd", FinishRecord::from_finish_args(down, &args));
+
+        let records = history.history("sshd");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].at, down);
+        assert_eq!(records[0].to_string(), "exited with code 3");
+        assert_eq!(records[1].to_string(), "killed by signal 9");
+        assert!(records.iter().all(FinishRecord::is_crash));
+
+        // Starting after a stop is not an exit; capacity keeps only the newest exits.
+        assert_eq!(
+            history.observe("sshd", &running(12, down + Duration::from_secs(5))),
+            None
+        );
+        history.observe("sshd", &running(13, down + Duration::from_secs(10)));
+        let records = history.history("sshd");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].at, down + Duration::from_secs(10));
+        assert_eq!(records[1].at, down);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn keeps_exits_across_loads() {
+        let root = std::env::temp_dir().join(format!("runkit-finish-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let path = root.join("finishes.json");
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let mut history = FinishHistory::load(&path);
+        history.save().unwrap();
+        assert!(!path.exists(), "nothing to save yet");
+        history.observe("sshd", &running(10, start));
+        history.save().unwrap();
+
+        // A later run still knows the old pid, so the restart shows up as an exit.
+        let restarted = start + Duration::from_secs(30);
+        let mut history = FinishHistory::load(&path);
+        assert!(history.observe("sshd", &running(11, restarted)).is_some());
+        history.save().unwrap();
+        assert_eq!(
+            FinishHistory::load(&path).history("sshd"),
+            [FinishRecord::inferred(restarted)]
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn saves_merge_with_other_processes() {
+        let root = std::env::temp_dir().join(format!("runkit-finish-merge-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let path = root.join("finishes.json");
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let restarted = start + Duration::from_secs(30);
+
+        let mut seed = FinishHistory::load(&path);
+        seed.observe("sshd", &running(10, start));
+        seed.observe("cronie", &running(20, start));
+        seed.save().unwrap();
+
+        // Two runs started from the same file, each seeing one service restart.
+        let mut first = FinishHistory::load(&path);
+        let mut second = FinishHistory::load(&path);
+        first.observe("sshd", &running(11, restarted));
+        second.observe("cronie", &running(21, restarted));
+        first.save().unwrap();
+        second.save().unwrap();
+
+        let merged = FinishHistory::load(&path);
+        assert_eq!(merged.history("sshd"), [FinishRecord::inferred(restarted)]);
+        assert_eq!(
+            merged.history("cronie"),
+            [FinishRecord::inferred(restarted)]
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
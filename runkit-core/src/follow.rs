@@ -0,0 +1,244 @@
+//! Streaming `tail -f` over an svlogd `current` file.
+use crate::inotify::Inotify;
+use crate::{Result, ServiceError, ServiceLogEntry, ServiceManager, parse_svlogd_line};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often to re-check the file when inotify is unavailable.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Yields log entries as svlogd appends them, following `current` across rotations.
+///
+/// New lines are picked up through inotify on the log directory; when that cannot be
+/// set up the follower falls back to checking the file every half second.
+#[derive(Debug)]
+pub struct LogFollower {
+    path: PathBuf,
+    file: Option<File>,
+    inode: Option<(u64, u64)>,
+    pos: u64,
+    partial: Vec<u8>,
+    pending: VecDeque<ServiceLogEntry>,
+    inotify: Option<Inotify>,
+}
+
+impl LogFollower {
+    /// Follow `path`, starting at its current end.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let inotify = path.parent().and_then(watch_dir);
+        let mut follower = LogFollower {
+            path,
+            file: None,
+            inode: None,
+            pos: 0,
+            partial: Vec::new(),
+            pending: VecDeque::new(),
+            inotify,
+        };
+        if follower.reopen()? {
+            let file = follower.file.as_mut().expect("file was just opened");
+            follower.pos = file
+                .seek(SeekFrom::End(0))
+                .map_err(|err| ServiceError::from_io(&follower.path, err))?;
+        }
+        Ok(follower)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether changes are detected through inotify rather than polling.
+    pub fn uses_inotify(&self) -> bool {
+        self.inotify.is_some()
+    }
+
+    /// Entries appended since the last call, without blocking.
+    pub fn poll(&mut self) -> Result<Vec<ServiceLogEntry>> {
+        let mut entries: Vec<ServiceLogEntry> = self.pending.drain(..).collect();
+        entries.extend(self.read_available()?);
+
+        // svlogd renames `current` away and creates a fresh one. Whatever it wrote
+        // before the rename was read above through the old handle.
+        if self.rotated() && self.reopen()? {
+            entries.extend(self.read_available()?);
+        }
+        Ok(entries)
+    }
+
+    /// Block until new entries arrive or `timeout` passes; empty on timeout.
+    pub fn wait(&mut self, timeout: Duration) -> Result<Vec<ServiceLogEntry>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let entries = self.poll()?;
+            let now = Instant::now();
+            if !entries.is_empty() || now >= deadline {
+                return Ok(entries);
+            }
+            self.sleep(deadline - now);
+        }
+    }
+
+    fn sleep(&self, remaining: Duration) {
+        match &self.inotify {
+            // The events themselves do not matter; the next `poll` re-reads the file anyway.
+            Some(inotify) if inotify.wait(remaining) => {
+                let _ = inotify.read_events();
+            }
+            Some(_) => {}
+            None => std::thread::sleep(remaining.min(POLL_INTERVAL)),
+        }
+    }
+
+    fn rotated(&self) -> bool {
+        match std::fs::metadata(&self.path) {
+            Ok(meta) => self.inode != Some((meta.dev(), meta.ino())),
+            Err(_) => false,
+        }
+    }
+
+    /// Open `path` from the start. Returns false when it does not exist (yet).
+    fn reopen(&mut self) -> Result<bool> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(ServiceError::from_io(&self.path, err)),
+        };
+        let meta = file
+            .metadata()
+            .map_err(|err| ServiceError::from_io(&self.path, err))?;
+        self.inode = Some((meta.dev(), meta.ino()));
+        self.file = Some(file);
+        self.pos = 0;
+        self.partial.clear();
+        Ok(true)
+    }
+
+    fn read_available(&mut self) -> Result<Vec<ServiceLogEntry>> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(Vec::new());
+        };
+        let len = file
+            .metadata()
+            .map_err(|err| ServiceError::from_io(&self.path, err))?
+            .len();
+        if len < self.pos {
+            // Truncated in place: start over.
+            self.pos = 0;
+            self.partial.clear();
+        }
+        if len == self.pos {
+            return Ok(Vec::new());
+        }
+
+        file.seek(SeekFrom::Start(self.pos))
+            .map_err(|err| ServiceError::from_io(&self.path, err))?;
+        let mut buf = Vec::new();
+        let read = file
+            .read_to_end(&mut buf)
+            .map_err(|err| ServiceError::from_io(&self.path, err))?;
+        self.pos += read as u64;
+        self.partial.extend_from_slice(&buf);
+
+        let Some(last_newline) = self.partial.iter().rposition(|b| *b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.partial.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        Ok(complete[..last_newline]
+            .split(|b| *b == b'\n')
+            .map(parse_svlogd_line)
+            .collect())
+    }
+}
+
+impl Iterator for LogFollower {
+    type Item = Result<ServiceLogEntry>;
+
+    /// Blocks until the next entry is written.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.pending.pop_front() {
+                return Some(Ok(entry));
+            }
+            match self.wait(Duration::from_secs(60)) {
+                Ok(entries) => self.pending.extend(entries),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl ServiceManager {
+    /// Follow the service's `log/main/current`, starting at its end.
+    pub fn follow_logs(&self, service: &str) -> Result<LogFollower> {
+        let dir = self
+            .log_dir(service)?
+            .ok_or_else(|| ServiceError::LogUnavailable(service.to_string()))?;
+        LogFollower::new(dir.join("current"))
+    }
+}
+
+/// Watch a log directory for writes, creations and renames.
+fn watch_dir(dir: &Path) -> Option<Inotify> {
+    let inotify = Inotify::new().ok()?;
+    let mask = libc::IN_MODIFY | libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_MOVED_FROM;
+    inotify.add_watch(dir, mask).ok()?;
+    Some(inotify)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogFollower;
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn append(path: &std::path::Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn follows_appends_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("runkit-follow-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let current = dir.join("current");
+        append(&current, "old\n");
+
+        let mut follower = LogFollower::new(&current).unwrap();
+        assert!(follower.poll().unwrap().is_empty());
+
+        append(&current, "first\nsec");
+        let messages: Vec<_> = follower
+            .poll()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, ["first"]);
+
+        append(&current, "ond\n");
+        std::fs::rename(&current, dir.join("@400000006500000000000000.s")).unwrap();
+        append(&current, "rotated\n");
+        let messages: Vec<_> = follower
+            .wait(Duration::from_secs(1))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, ["second", "rotated"]);
+
+        assert!(follower.wait(Duration::from_millis(20)).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,239 @@
+//! Dependency graph over service definitions, used to order bulk operations.
+use crate::template::is_template_dir_name;
+use crate::{OpResult, Result, ServiceError, ServiceInfo, ServiceManager, ServiceOp};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Which services each service needs running first, as declared by its run script.
+///
+/// Dependencies on names without a definition are kept as leaf nodes so ordering still
+/// works for partially installed systems.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceGraph {
+    deps: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Visiting,
+    Done,
+}
+
+impl ServiceGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Graph from listed services, using their `dependencies` field.
+    pub fn from_services(services: &[ServiceInfo]) -> Self {
+        let mut graph = Self::new();
+        for info in services {
+            graph.add_service(&info.name, info.dependencies.iter().cloned());
+        }
+        graph
+    }
+
+    /// Record `service` and the services it depends on, replacing earlier edges.
+    pub fn add_service(&mut self, service: &str, deps: impl IntoIterator<Item = String>) {
+        let deps: Vec<String> = deps.into_iter().filter(|dep| dep != service).collect();
+        for dep in &deps {
+            self.deps.entry(dep.clone()).or_default();
+        }
+        self.deps.insert(service.to_string(), deps);
+    }
+
+    pub fn contains(&self, service: &str) -> bool {
+        self.deps.contains_key(service)
+    }
+
+    /// Direct dependencies of `service`.
+    pub fn dependencies(&self, service: &str) -> &[String] {
+        self.deps
+            .get(service)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Services that directly depend on `service`, sorted.
+    pub fn dependents(&self, service: &str) -> Vec<String> {
+        self.deps
+            .iter()
+            .filter(|(_, deps)| deps.iter().any(|dep| dep == service))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Every service that needs `service`, directly or through others: what breaks if
+    /// it stops. Sorted, and never includes `service` itself.
+    pub fn reverse_dependencies(&self, service: &str) -> Vec<String> {
+        let mut seen = BTreeSet::new();
+        let mut queue = vec![service.to_string()];
+        while let Some(current) = queue.pop() {
+            for dependent in self.dependents(&current) {
+                if dependent != service && seen.insert(dependent.clone()) {
+                    queue.push(dependent);
+                }
+            }
+        }
+        seen.into_iter().collect()
+    }
+
+    /// A dependency cycle, if any, as the services along it with the first repeated last.
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        self.order(self.deps.keys().map(String::as_str)).err()
+    }
+
+    /// Every service with dependencies before their dependents.
+    pub fn topo_order(&self) -> Result<Vec<String>> {
+        self.order(self.deps.keys().map(String::as_str))
+            .map_err(ServiceError::DependencyCycle)
+    }
+
+    /// `services` reordered so each comes after whatever it (transitively) depends on.
+    ///
+    /// Services unknown to the graph keep their relative position. Only cycles reachable
+    /// from the requested services are reported.
+    pub fn start_order<S: AsRef<str>>(&self, services: &[S]) -> Result<Vec<String>> {
+        let wanted: BTreeSet<&str> = services.iter().map(AsRef::as_ref).collect();
+        let order = self
+            .order(services.iter().map(AsRef::as_ref))
+            .map_err(ServiceError::DependencyCycle)?;
+        Ok(order
+            .into_iter()
+            .filter(|name| wanted.contains(name.as_str()))
+            .collect())
+    }
+
+    /// Reverse of [`ServiceGraph::start_order`]: dependents stop before what they need.
+    pub fn stop_order<S: AsRef<str>>(&self, services: &[S]) -> Result<Vec<String>> {
+        let mut order = self.start_order(services)?;
+        order.reverse();
+        Ok(order)
+    }
+
+    /// Depth-first post-order from `roots`; on a cycle, returns the path around it.
+    fn order<'a>(
+        &'a self,
+        roots: impl IntoIterator<Item = &'a str>,
+    ) -> std::result::Result<Vec<String>, Vec<String>> {
+        let mut marks: HashMap<&str, Mark> = HashMap::new();
+        let mut out = Vec::new();
+        for root in roots {
+            let mut path = Vec::new();
+            self.visit(root, &mut marks, &mut path, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    fn visit<'a>(
+        &'a self,
+        node: &'a str,
+        marks: &mut HashMap<&'a str, Mark>,
+        path: &mut Vec<&'a str>,
+        out: &mut Vec<String>,
+    ) -> std::result::Result<(), Vec<String>> {
+        match marks.get(node) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let start = path.iter().position(|n| *n == node).unwrap_or(0);
+                let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+                cycle.push(node.to_string());
+                return Err(cycle);
+            }
+            None => {}
+        }
+
+        marks.insert(node, Mark::Visiting);
+        path.push(node);
+        for dep in self.dependencies(node) {
+            self.visit(dep, marks, path, out)?;
+        }
+        path.pop();
+        marks.insert(node, Mark::Done);
+        out.push(node.to_string());
+        Ok(())
+    }
+}
+
+impl ServiceManager {
+    /// Dependency graph of every service definition.
+    pub fn dependency_graph(&self) -> Result<ServiceGraph> {
+        let mut graph = ServiceGraph::new();
+        for name in self.backend.definition_names()? {
+            if name.starts_with('.')
+                || is_template_dir_name(&name)
+                || self.validate_service_name(&name).is_err()
+            {
+                continue;
+            }
+            let deps = self.dependencies(&name).unwrap_or_default();
+            graph.add_service(&name, deps);
+        }
+        Ok(graph)
+    }
+
+    /// Apply `op` to every service in dependency order, continuing past failures.
+    ///
+    /// Stop and disable run dependents first; every other operation runs dependencies
+    /// first. Fails before touching anything if the services form a cycle.
+    pub fn apply_ordered<S: AsRef<str>>(
+        &self,
+        op: ServiceOp,
+        services: &[S],
+    ) -> Result<Vec<OpResult>> {
+        let graph = self.dependency_graph()?;
+        let order = match op {
+            ServiceOp::Stop | ServiceOp::Disable => graph.stop_order(services)?,
+            _ => graph.start_order(services)?,
+        };
+        let ops: Vec<(ServiceOp, String)> = order.into_iter().map(|name| (op, name)).collect();
+        Ok(self.apply_bulk(&ops))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceGraph;
+    use crate::ServiceError;
+
+    fn graph(edges: &[(&str, &[&str])]) -> ServiceGraph {
+        let mut graph = ServiceGraph::new();
+        for (service, deps) in edges {
+            graph.add_service(service, deps.iter().map(|dep| dep.to_string()));
+        }
+        graph
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let graph = graph(&[
+            ("NetworkManager", &["dbus"]),
+            ("elogind", &["dbus"]),
+            ("sddm", &["elogind", "dbus"]),
+            ("cron", &[]),
+        ]);
+        assert_eq!(
+            graph.start_order(&["sddm", "cron", "dbus"]).unwrap(),
+            ["dbus", "sddm", "cron"]
+        );
+        assert_eq!(
+            graph.stop_order(&["dbus", "elogind", "sddm"]).unwrap(),
+            ["sddm", "elogind", "dbus"]
+        );
+        assert_eq!(
+            graph.reverse_dependencies("dbus"),
+            ["NetworkManager", "elogind", "sddm"]
+        );
+        assert!(graph.find_cycle().is_none());
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let graph = graph(&[("a", &["b"]), ("b", &["c"]), ("c", &["a"]), ("d", &[])]);
+        assert_eq!(graph.find_cycle().unwrap(), ["a", "b", "c", "a"]);
+        assert!(matches!(
+            graph.start_order(&["a"]),
+            Err(ServiceError::DependencyCycle(cycle)) if cycle.len() == 4
+        ));
+        assert_eq!(graph.start_order(&["d"]).unwrap(), ["d"]);
+    }
+}
@@ -0,0 +1,158 @@
+//! Enablement links in the service directory whose definition has disappeared.
+use crate::{
+    DesiredState, Result, ServiceError, ServiceInfo, ServiceManager, ServiceRuntimeState,
+    validate_service_name,
+};
+use std::path::{Path, PathBuf};
+
+/// Integrity of a listed service, independent of its runtime state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ServiceHealth {
+    #[default]
+    Ok,
+    /// The service is linked into the enabled directory but the link target is gone,
+    /// typically because its package was removed.
+    BrokenLink,
+}
+
+/// Dangling symlink in the enabled directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BrokenLink {
+    pub service: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+    pub link: PathBuf,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+    pub target: PathBuf,
+}
+
+impl BrokenLink {
+    /// Inspect one entry of the enabled directory; `None` unless it is a dangling symlink.
+    pub(crate) fn probe(link: &Path) -> Option<Self> {
+        let meta = std::fs::symlink_metadata(link).ok()?;
+        if !meta.file_type().is_symlink() || link.exists() {
+            return None;
+        }
+        let service = link.file_name()?.to_str()?.to_string();
+        validate_service_name(&service).ok()?;
+        let target = std::fs::read_link(link).ok()?;
+        Some(BrokenLink {
+            service,
+            link: link.to_path_buf(),
+            target,
+        })
+    }
+
+    /// Listing entry standing in for the missing definition.
+    pub fn to_service_info(&self) -> ServiceInfo {
+        ServiceInfo {
+            name: self.service.clone(),
+            definition_path: self.target.clone(),
+            enabled: true,
+            desired_state: DesiredState::from_flags(true, false),
+            runlevels: Vec::new(),
+            runtime_state: ServiceRuntimeState::Unknown {
+                raw: format!(
+                    "{}: link target {} does not exist",
+                    self.service,
+                    self.target.display()
+                ),
+            },
+            pending: None,
+            run_once: false,
+            log_state: None,
+            has_check: false,
+            has_finish: false,
+            has_logger: false,
+            masked: false,
+            flapping: None,
+            health: ServiceHealth::BrokenLink,
+            dependencies: Vec::new(),
+            metadata: None,
+            needs_restart: false,
+            finishes: Vec::new(),
+            supervise_dir: None,
+            has_ok_pipe: false,
+            runsv_running: false,
+            down_reason: None,
+            template: crate::split_instance(&self.service)
+                .map(|(template, _)| template.to_string()),
+            description: None,
+        }
+    }
+}
+
+impl ServiceManager {
+    /// Symlinks in the enabled directory whose target no longer exists, sorted by name.
+    pub fn broken_links(&self) -> Result<Vec<BrokenLink>> {
+        let read_dir = std::fs::read_dir(&self.enabled_dir)
+            .map_err(|err| ServiceError::from_io(&self.enabled_dir, err))?;
+        let mut links: Vec<BrokenLink> = read_dir
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| BrokenLink::probe(&entry.path()))
+            .collect();
+        links.sort_by(|a, b| a.service.cmp(&b.service));
+        Ok(links)
+    }
+
+    /// Remove the service's enablement link if it is dangling. Returns `false`, leaving
+    /// the link alone, when it is missing or still points at a definition.
+    pub fn remove_broken_link(&self, service: &str) -> Result<bool> {
+        self.validate_service_name(service)?;
+        let link = self.enabled_dir.join(service);
+        if BrokenLink::probe(&link).is_none() {
+            return Ok(false);
+        }
+        std::fs::remove_file(&link).map_err(|err| ServiceError::from_io(&link, err))?;
+        Ok(true)
+    }
+
+    /// Remove every dangling enablement link and report what was removed.
+    pub fn prune_broken_links(&self) -> Result<Vec<BrokenLink>> {
+        let mut removed = Vec::new();
+        for link in self.broken_links()? {
+            std::fs::remove_file(&link.link)
+                .map_err(|err| ServiceError::from_io(&link.link, err))?;
+            removed.push(link);
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceHealth;
+    use crate::ServiceManager;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn lists_and_prunes_dangling_links() {
+        let root = std::env::temp_dir().join(format!("runkit-health-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd")).unwrap();
+        std::fs::create_dir_all(root.join("service")).unwrap();
+        symlink(root.join("sv/sshd"), root.join("service/sshd")).unwrap();
+        symlink(root.join("sv/dhcpcd"), root.join("service/dhcpcd")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        let broken = manager.broken_links().unwrap();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].service, "dhcpcd");
+        assert_eq!(broken[0].target, root.join("sv/dhcpcd"));
+        assert_eq!(
+            broken[0].to_service_info().health,
+            ServiceHealth::BrokenLink
+        );
+
+        assert!(!manager.remove_broken_link("sshd").unwrap());
+        assert_eq!(manager.prune_broken_links().unwrap().len(), 1);
+        assert!(manager.broken_links().unwrap().is_empty());
+        assert!(root.join("service/sshd").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,103 @@
+//! Minimal inotify wrapper shared by the log follower and the service watcher.
+use std::ffi::{CString, OsString};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::time::Duration;
+
+/// One event read from the inotify descriptor.
+#[derive(Debug, Clone)]
+pub(crate) struct InotifyEvent {
+    pub wd: i32,
+    pub mask: u32,
+    pub name: Option<OsString>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Inotify {
+    fd: OwnedFd,
+}
+
+impl Inotify {
+    pub(crate) fn new() -> io::Result<Self> {
+        // SAFETY: plain syscall; on success the descriptor is handed to `OwnedFd`.
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a freshly created descriptor nobody else owns.
+        Ok(Inotify {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    pub(crate) fn add_watch(&self, path: &Path, mask: u32) -> io::Result<i32> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        // SAFETY: `path` is NUL-terminated and outlives the call.
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask) };
+        if wd < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(wd)
+        }
+    }
+
+    pub(crate) fn rm_watch(&self, wd: i32) {
+        // SAFETY: plain syscall; an unknown `wd` only yields EINVAL.
+        unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), wd) };
+    }
+
+    /// Wait up to `timeout` for events. Returns whether any are ready.
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        // SAFETY: `pollfd` is a valid, initialized array of one element.
+        unsafe { libc::poll(&mut pollfd, 1, millis) > 0 }
+    }
+
+    /// Drain every queued event without blocking.
+    pub(crate) fn read_events(&self) -> io::Result<Vec<InotifyEvent>> {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+        let mut events = Vec::new();
+        let mut buf = vec![0u8; 64 * 1024];
+
+        loop {
+            // SAFETY: the fd is non-blocking and `buf` is writable for its full length.
+            let read =
+                unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if read < 0 {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    io::ErrorKind::WouldBlock => Ok(events),
+                    _ => Err(err),
+                };
+            }
+            if read == 0 {
+                return Ok(events);
+            }
+
+            let mut offset = 0;
+            let read = read as usize;
+            while offset + HEADER <= read {
+                let field = |at: usize| {
+                    u32::from_ne_bytes(buf[offset + at..offset + at + 4].try_into().unwrap())
+                };
+                let wd = field(0) as i32;
+                let mask = field(4);
+                let len = field(12) as usize;
+                let name_bytes = &buf[offset + HEADER..offset + HEADER + len];
+                let name_len = name_bytes.iter().position(|b| *b == 0).unwrap_or(len);
+                let name =
+                    (name_len > 0).then(|| OsString::from_vec(name_bytes[..name_len].to_vec()));
+                events.push(InotifyEvent { wd, mask, name });
+                offset += HEADER + len;
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Services that repository packages ship but that are not set up locally.
+use crate::describe::parse_owned_by;
+use crate::{
+    DEFAULT_SERVICE_DIR, Result, ServiceError, ServiceManager, ServiceScope, strip_package_version,
+    validate_service_name,
+};
+use std::path::Path;
+use std::process::Command;
+
+/// A service definition provided by a package from the configured xbps repositories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstallableService {
+    pub service: String,
+    /// Package name without its version, as passed to `xbps-install`.
+    pub package: String,
+    /// Full `name-version_revision` of the package that provides the service.
+    pub pkgver: String,
+}
+
+impl ServiceManager {
+    /// Services shipped by repository packages that have no definition in the
+    /// definitions directory, sorted by name.
+    ///
+    /// Asks `xbps-query -Ro` which remote packages own `/etc/sv/*/run`; this reads the
+    /// repository file indexes and can take a few seconds. User services do not come from
+    /// packages, so the user scope always yields an empty list.
+    pub fn installable_services(&self) -> Result<Vec<InstallableService>> {
+        if self.scope() == ServiceScope::User {
+            return Ok(Vec::new());
+        }
+
+        let pattern = Path::new(DEFAULT_SERVICE_DIR).join("*").join("run");
+        let output = Command::new("xbps-query")
+            .arg("-Ro")
+            .arg(&pattern)
+            .output()
+            .map_err(|err| ServiceError::from_io("xbps-query", err))?;
+        if !output.status.success() {
+            return Err(ServiceError::SystemCommand {
+                command: "xbps-query".to_string(),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        Ok(
+            repository_services(&String::from_utf8_lossy(&output.stdout))
+                .into_iter()
+                .filter(|candidate| !self.definitions_dir.join(&candidate.service).exists())
+                .collect(),
+        )
+    }
+}
+
+/// Top-level services in `xbps-query -o` output, one per name, sorted. `log/run` scripts
+/// and other nested matches are skipped.
+pub(crate) fn repository_services(output: &str) -> Vec<InstallableService> {
+    let root = Path::new(DEFAULT_SERVICE_DIR);
+    let mut services: Vec<InstallableService> = parse_owned_by(output)
+        .into_iter()
+        .filter_map(|(path, pkgver)| {
+            let dir = path.parent()?;
+            if dir.parent()? != root || path.file_name()? != "run" {
+                return None;
+            }
+            let service = dir.file_name()?.to_str()?;
+            validate_service_name(service).ok()?;
+            Some(InstallableService {
+                service: service.to_string(),
+                package: strip_package_version(&pkgver).to_string(),
+                pkgver,
+            })
+        })
+        .collect();
+    services.sort_by(|a, b| a.service.cmp(&b.service).then(a.pkgver.cmp(&b.pkgver)));
+    services.dedup_by(|later, earlier| later.service == earlier.service);
+    services
+}
+
+#[cfg(test)]
+mod tests {
+    use super::repository_services;
+
+    #[test]
+    fn keeps_top_level_run_scripts() {
+        let services = repository_services(
+            "tor-0.4.8.10_1: /etc/sv/tor/run (regular file)\n\
+             tor-0.4.8.10_1: /etc/sv/tor/log/run -> /usr/bin/vlogger\n\
+             cups-2.4.7_2: /etc/sv/cupsd/run (regular file)\n\
+             broken line without owner\n",
+        );
+        let names: Vec<_> = services
+            .iter()
+            .map(|service| (service.service.as_str(), service.package.as_str()))
+            .collect();
+        assert_eq!(names, [("cupsd", "cups"), ("tor", "tor")]);
+        assert_eq!(services[1].pkgver, "tor-0.4.8.10_1");
+    }
+}
@@ -0,0 +1,356 @@
+//! Append-only record of service state changes, kept across runs for timelines and
+//! post-mortems.
+use crate::{Result, RuntimeStateKind, ServiceError, ServiceManager, ServiceRuntimeState};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Actor recorded for changes noticed while listing rather than caused by a request.
+pub const OBSERVED_ACTOR: &str = "runsv";
+
+/// One line of the journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JournalEvent {
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "at_unix", with = "crate::serde_support::unix_seconds")
+    )]
+    pub at: SystemTime,
+    pub service: String,
+    pub from: RuntimeStateKind,
+    pub to: RuntimeStateKind,
+    /// Who caused the change: a user for requested changes, [`OBSERVED_ACTOR`] otherwise.
+    pub actor: String,
+}
+
+impl JournalEvent {
+    /// Parse a `<unix seconds>.<nanos>\t<service>\t<from>\t<to>\t<actor>` line.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(5, '\t');
+        let (seconds, nanos) = fields.next()?.split_once('.')?;
+        let at =
+            UNIX_EPOCH.checked_add(Duration::new(seconds.parse().ok()?, nanos.parse().ok()?))?;
+        Some(JournalEvent {
+            at,
+            service: fields.next()?.to_string(),
+            from: RuntimeStateKind::from_name(fields.next()?)?,
+            to: RuntimeStateKind::from_name(fields.next()?)?,
+            actor: fields.next()?.to_string(),
+        })
+    }
+
+    /// The line [`JournalEvent::parse`] reads back, without the trailing newline.
+    pub fn to_line(&self) -> String {
+        let since_epoch = self.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let actor: String = self
+            .actor
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        format!(
+            "{}.{:09}\t{}\t{}\t{}\t{actor}",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos(),
+            self.service,
+            self.from.name(),
+            self.to.name(),
+        )
+    }
+}
+
+impl fmt::Display for JournalEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} ({})",
+            self.service,
+            self.from.name(),
+            self.to.name(),
+            self.actor
+        )
+    }
+}
+
+/// Filters for [`EventJournal::events`]. The default matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct JournalQuery {
+    pub service: Option<String>,
+    /// Only events at or after this time.
+    pub since: Option<SystemTime>,
+    /// Keep only the newest `limit` matches.
+    pub limit: Option<usize>,
+}
+
+impl JournalQuery {
+    fn matches(&self, event: &JournalEvent) -> bool {
+        self.service
+            .as_deref()
+            .is_none_or(|service| event.service == service)
+            && self.since.is_none_or(|since| event.at >= since)
+    }
+}
+
+/// State-change journal stored as one tab-separated line per event.
+///
+/// The last known state of each service is taken from the file the first time it is
+/// needed, so short-lived processes such as runkitd still see changes between runs.
+#[derive(Debug, Clone)]
+pub struct EventJournal {
+    path: PathBuf,
+    actor: String,
+    last: Option<HashMap<String, RuntimeStateKind>>,
+}
+
+impl EventJournal {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        EventJournal {
+            path: path.into(),
+            actor: default_actor(),
+            last: None,
+        }
+    }
+
+    /// `$XDG_STATE_HOME/runkit/events.log`, falling back to `~/.local/state`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })?;
+        Some(base.join("runkit").join("events.log"))
+    }
+
+    /// Journal at [`EventJournal::default_path`], if a state directory can be determined.
+    pub fn open_default() -> Option<Self> {
+        Self::default_path().map(Self::new)
+    }
+
+    /// Name recorded for changes requested through this journal's manager. Defaults to
+    /// the invoking user, including the one behind pkexec.
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = actor.into();
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn actor(&self) -> &str {
+        &self.actor
+    }
+
+    /// Append the change if the service's state kind differs from the last one recorded.
+    /// The first sighting of a service only sets the baseline.
+    pub fn observe(
+        &mut self,
+        service: &str,
+        state: &ServiceRuntimeState,
+    ) -> io::Result<Option<JournalEvent>> {
+        let to = state.kind();
+        let Some(from) = self.last_states()?.insert(service.to_string(), to) else {
+            return Ok(None);
+        };
+        if from == to {
+            return Ok(None);
+        }
+        let event = JournalEvent {
+            at: state.started_at().unwrap_or_else(SystemTime::now),
+            service: service.to_string(),
+            from,
+            to,
+            actor: OBSERVED_ACTOR.to_string(),
+        };
+        self.append(&event)?;
+        Ok(Some(event))
+    }
+
+    /// Append a change requested by this journal's actor, even if the state kind stayed
+    /// the same, as it does for a restart.
+    pub fn record(
+        &mut self,
+        service: &str,
+        state: &ServiceRuntimeState,
+    ) -> io::Result<JournalEvent> {
+        let to = state.kind();
+        let from = self
+            .last_states()?
+            .insert(service.to_string(), to)
+            .unwrap_or(RuntimeStateKind::Unknown);
+        let event = JournalEvent {
+            at: SystemTime::now(),
+            service: service.to_string(),
+            from,
+            to,
+            actor: self.actor.clone(),
+        };
+        self.append(&event)?;
+        Ok(event)
+    }
+
+    pub fn append(&self, event: &JournalEvent) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", event.to_line())
+    }
+
+    /// Events matching `query`, oldest first. Unreadable lines are skipped and a missing
+    /// file is an empty journal.
+    pub fn events(&self, query: &JournalQuery) -> io::Result<Vec<JournalEvent>> {
+        let mut events: Vec<JournalEvent> = self
+            .read_all()?
+            .into_iter()
+            .filter(|event| query.matches(event))
+            .collect();
+        if let Some(limit) = query.limit {
+            events.drain(..events.len().saturating_sub(limit));
+        }
+        Ok(events)
+    }
+
+    fn read_all(&self) -> io::Result<Vec<JournalEvent>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => Ok(text.lines().filter_map(JournalEvent::parse).collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn last_states(&mut self) -> io::Result<&mut HashMap<String, RuntimeStateKind>> {
+        if self.last.is_none() {
+            let last = self
+                .read_all()?
+                .into_iter()
+                .map(|event| (event.service, event.to))
+                .collect();
+            self.last = Some(last);
+        }
+        Ok(self.last.get_or_insert_default())
+    }
+}
+
+pub(crate) fn default_actor() -> String {
+    if let Ok(uid) = std::env::var("PKEXEC_UID") {
+        return format!("uid {uid}");
+    }
+    std::env::var("USER")
+        .ok()
+        .filter(|user| !user.is_empty())
+        .unwrap_or_else(|| format!("uid {}", unsafe { libc::geteuid() }))
+}
+
+impl ServiceManager {
+    /// Record state changes in `journal`. Listings note changes made behind the manager's
+    /// back; `start`, `stop` and verified restarts are recorded under the journal's actor.
+    pub fn with_event_journal(mut self, journal: EventJournal) -> Self {
+        self.journal = Some(Arc::new(Mutex::new(journal)));
+        self
+    }
+
+    /// The journal shared by this manager and its clones, if one is configured.
+    pub fn event_journal(&self) -> Option<MutexGuard<'_, EventJournal>> {
+        self.journal.as_ref().map(|journal| {
+            journal
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        })
+    }
+
+    /// Journal events matching `query`; empty when no journal is configured.
+    pub fn journal_events(&self, query: &JournalQuery) -> Result<Vec<JournalEvent>> {
+        let Some(journal) = self.event_journal() else {
+            return Ok(Vec::new());
+        };
+        journal
+            .events(query)
+            .map_err(|err| ServiceError::from_io(journal.path(), err))
+    }
+
+    /// Failing to write the journal never fails the operation being journaled.
+    pub(crate) fn journal_observe(&self, service: &str, state: &ServiceRuntimeState) {
+        if let Some(mut journal) = self.event_journal() {
+            let _ = journal.observe(service, state);
+        }
+    }
+
+    /// A change the caller asked for: journaled under the actor, and not counted as a
+    /// crash-loop restart the next time the service is listed.
+    pub(crate) fn record_request(&self, service: &str) {
+        self.restart_tracker().forget(service);
+        if let Some(mut journal) = self.event_journal()
+            && let Ok(state) = self.backend.status(service)
+        {
+            let _ = journal.record(service, &state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventJournal, JournalEvent, JournalQuery, OBSERVED_ACTOR};
+    use crate::{RuntimeStateKind, ServiceRuntimeState};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn down(at: SystemTime) -> ServiceRuntimeState {
+        ServiceRuntimeState::Down {
+            since: Duration::ZERO,
+            normally_up: true,
+            started_at: at,
+        }
+    }
+
+    #[test]
+    fn journals_changes_across_instances() {
+        let root = std::env::temp_dir().join(format!("runkit-journal-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let path = root.join("runkit/events.log");
+        let started = UNIX_EPOCH + Duration::new(1_700_000_000, 5);
+        let running = ServiceRuntimeState::Running {
+            pid: 10,
+            uptime: Duration::ZERO,
+            started_at: started,
+        };
+
+        let mut journal = EventJournal::new(&path).with_actor("alice");
+        assert_eq!(journal.observe("sshd", &running).unwrap(), None);
+        assert_eq!(journal.observe("sshd", &running).unwrap(), None);
+        journal.record("sshd", &running).unwrap();
+
+        // A fresh instance picks the baseline up from the file.
+        let mut journal = EventJournal::new(&path);
+        let stopped = started + Duration::from_secs(60);
+        let event = journal.observe("sshd", &down(stopped)).unwrap().unwrap();
+        assert_eq!(event.to_string(), "sshd: running -> down (runsv)");
+        journal.observe("ntpd", &down(stopped)).unwrap();
+
+        let events = journal.events(&JournalQuery::default()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].actor, "alice");
+        assert_eq!(events[0].from, RuntimeStateKind::Running);
+        assert_eq!(events[1].at, stopped);
+        assert_eq!(events[1].actor, OBSERVED_ACTOR);
+        assert_eq!(
+            JournalEvent::parse(&events[1].to_line()).as_ref(),
+            Some(&events[1])
+        );
+
+        let query = JournalQuery {
+            service: Some("sshd".into()),
+            since: Some(stopped),
+            limit: Some(1),
+        };
+        assert_eq!(journal.events(&query).unwrap(), [event]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,13 +1,124 @@
 //! Core domain layer for discovering and describing Void Linux runit services.
+#[macro_use]
+mod trace;
+
+mod access;
+#[cfg(feature = "tokio")]
+mod async_manager;
+mod audit;
+mod backend;
+mod bulk;
+mod check;
+mod conf;
+mod control;
+mod crashloop;
+mod definition;
+mod depends;
+mod describe;
+mod down_reason;
+mod envdir;
+mod finish;
+mod follow;
+mod graph;
+mod health;
+mod inotify;
+mod installable;
+mod journal;
+mod lint;
+mod logs;
+mod mask;
+mod metadata;
+#[cfg(feature = "system-control")]
+mod power;
+mod preset;
+mod process;
+mod query;
+mod remove;
+mod runlevel;
+mod scaffold;
+mod scope;
+mod search;
+#[cfg(feature = "serde")]
+mod serde_support;
+mod severity;
+mod snapshot;
+mod socklog;
+mod stage;
+mod stats;
+mod status;
+mod supervise;
+mod sv;
+mod svlogd;
+mod tai64;
+mod template;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
+mod unit;
+mod upgrade;
+mod wait;
+mod watcher;
+
+pub use access::ControlCapability;
+#[cfg(feature = "tokio")]
+pub use async_manager::AsyncServiceManager;
+pub use audit::{AuditFinding, AuditReport};
+pub use backend::{RunitBackend, ServiceBackend};
+pub use bulk::{OpResult, ServiceOp};
+pub use check::CheckOutcome;
+pub use conf::ServiceConf;
+pub use control::{ControlCommand, ServiceControl, SvSignal};
+pub use crashloop::{Flapping, RestartTracker};
+pub use depends::run_script_dependencies;
+pub use describe::{DEFAULT_DESCRIPTION_TTL, DescriptionCache};
+pub use down_reason::{DOWN_REASON_FILE, DownReason};
+pub use envdir::{EnvDir, EnvValue};
+pub use finish::{FinishHistory, FinishRecord};
+pub use follow::LogFollower;
+pub use graph::ServiceGraph;
+pub use health::{BrokenLink, ServiceHealth};
+pub use installable::InstallableService;
+pub use journal::{EventJournal, JournalEvent, JournalQuery, OBSERVED_ACTOR};
+pub use lint::{LintFinding, LintReport};
+pub use logs::{LogFile, LogFileKind, LogUsage};
+pub use mask::MASK_FILE;
+pub use metadata::{
+    Criticality, METADATA_FILE, MetadataError, RestartHints, RestartPolicy, ServiceMetadata,
+};
+#[cfg(feature = "system-control")]
+pub use power::{DEFAULT_RUNIT_INIT, DEFAULT_RUNSVCHDIR, PowerAction, SystemControl};
+pub use preset::Distro;
+pub use process::ProcessInfo;
+pub use query::{RuntimeStateKind, ServiceIter, ServiceQuery};
+pub use scaffold::RunScriptSpec;
+pub use scope::ServiceScope;
+pub use search::LogQuery;
+pub use severity::{LogSeverity, SeverityRules};
+pub use snapshot::{ApplyReport, DryRun, EnabledService, EnabledSnapshot, SnapshotLink};
+pub use socklog::{SOCKLOG_DIR, SyslogLine};
+pub use stage::{RUNIT_DIR, RunitStage, SystemStage};
+pub use stats::ServiceStats;
+pub use status::{StatusReport, StatusWarning};
+pub use supervise::{SUPERVISE_STATUS_LEN, SuperviseStatus, SupervisedProcessState, WantState};
+pub use sv::DEFAULT_SV_TIMEOUT;
+pub use svlogd::{LogFilter, LogFilterKind, SvlogdConfig};
+pub use tai64::{TAI64_EPOCH_LABEL, Tai64N, encode_tai64n, tai_minus_utc};
+pub use template::{INSTANCE_PLACEHOLDER, INSTANCE_SEPARATOR, group_instances, split_instance};
+pub use unit::UnitConversion;
+pub use upgrade::deleted_mappings;
+pub use watcher::{ServiceEvent, ServiceWatcher};
+
+use logs::{ReverseTail, TAIL_BLOCK_SIZE};
+use supervise::{SupervisePresence, read_supervise_status};
+
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::collections::VecDeque;
-use std::ffi::OsStr;
 use std::fs::File;
-use std::io::{BufRead, BufReader, ErrorKind};
+use std::io::{BufRead, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 pub const DEFAULT_SERVICE_DIR: &str = "/etc/sv";
@@ -26,18 +137,79 @@ static FAIL_REGEX: Lazy<Regex> = Lazy::new(|| {
 });
 
 /// High-level state of a runit service instance.
+///
+/// With the `serde` feature it serializes internally tagged by `state`, with durations
+/// as whole seconds in `*_seconds` fields and timestamps as Unix seconds in `*_unix` fields.
+///
+/// `uptime` and `since` are snapshots taken when the state was read; `started_at` stays
+/// valid, so frontends should derive live durations from it with [`Self::elapsed`].
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "state", rename_all = "snake_case")
+)]
 pub enum ServiceRuntimeState {
     Running {
         pid: u32,
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "uptime_seconds", with = "serde_support::seconds")
+        )]
+        uptime: Duration,
+        /// When the current process was started.
+        #[cfg_attr(
+            feature = "serde",
+            serde(
+                rename = "started_at_unix",
+                with = "serde_support::unix_seconds",
+                default = "serde_support::unix_epoch"
+            )
+        )]
+        started_at: SystemTime,
+    },
+    /// runsv stopped the process with SIGSTOP (`sv pause`); `sv cont` resumes it.
+    Paused {
+        pid: u32,
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "uptime_seconds", with = "serde_support::seconds")
+        )]
         uptime: Duration,
+        #[cfg_attr(
+            feature = "serde",
+            serde(
+                rename = "started_at_unix",
+                with = "serde_support::unix_seconds",
+                default = "serde_support::unix_epoch"
+            )
+        )]
+        started_at: SystemTime,
     },
     Down {
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "since_seconds", with = "serde_support::seconds")
+        )]
         since: Duration,
         normally_up: bool,
+        /// When the service went down.
+        #[cfg_attr(
+            feature = "serde",
+            serde(
+                rename = "started_at_unix",
+                with = "serde_support::unix_seconds",
+                default = "serde_support::unix_epoch"
+            )
+        )]
+        started_at: SystemTime,
     },
     Failed {
         pid: u32,
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "uptime_seconds", with = "serde_support::seconds")
+        )]
         uptime: Duration,
         exit_code: i32,
     },
@@ -47,6 +219,54 @@ pub enum ServiceRuntimeState {
 }
 
 impl ServiceRuntimeState {
+    pub fn is_running(&self) -> bool {
+        matches!(self, ServiceRuntimeState::Running { .. })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self, ServiceRuntimeState::Paused { .. })
+    }
+
+    /// Pid of the live process, whether it is running or paused.
+    pub fn pid(&self) -> Option<u32> {
+        match self {
+            ServiceRuntimeState::Running { pid, .. } | ServiceRuntimeState::Paused { pid, .. } => {
+                Some(*pid)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_down(&self) -> bool {
+        matches!(self, ServiceRuntimeState::Down { .. })
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, ServiceRuntimeState::Failed { .. })
+    }
+
+    /// When a running service started or a stopped one went down.
+    pub fn started_at(&self) -> Option<SystemTime> {
+        match self {
+            ServiceRuntimeState::Running { started_at, .. }
+            | ServiceRuntimeState::Paused { started_at, .. }
+            | ServiceRuntimeState::Down { started_at, .. } => Some(*started_at),
+            _ => None,
+        }
+    }
+
+    /// Time spent in the current state as of now, rather than when it was read.
+    pub fn elapsed(&self) -> Option<Duration> {
+        let started_at = self.started_at()?;
+        Some(
+            SystemTime::now()
+                .duration_since(started_at)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Parse `sv status` output. `sv` only reports whole seconds, so `started_at` is
+    /// estimated from the current time; prefer `supervise/status` where it is readable.
     pub fn from_sv_status(status_output: &str) -> Self {
         let line = status_output.lines().next().unwrap_or("").trim();
 
@@ -59,7 +279,22 @@ impl ServiceRuntimeState {
                 .and_then(|m| m.as_str().parse::<u64>().ok())
                 .map(Duration::from_secs);
             if let (Some(pid), Some(uptime)) = (pid, uptime) {
-                return ServiceRuntimeState::Running { pid, uptime };
+                let started_at = ago(uptime);
+                // `sv` appends `, paused` to the service's own segment, before any `; log:`.
+                let paused = line.split(';').next().unwrap_or(line).contains(", paused");
+                return if paused {
+                    ServiceRuntimeState::Paused {
+                        pid,
+                        uptime,
+                        started_at,
+                    }
+                } else {
+                    ServiceRuntimeState::Running {
+                        pid,
+                        uptime,
+                        started_at,
+                    }
+                };
             }
         }
 
@@ -70,7 +305,11 @@ impl ServiceRuntimeState {
                 .map(Duration::from_secs)
                 .unwrap_or_default();
             let normally_up = line.contains("normally up");
-            return ServiceRuntimeState::Down { since, normally_up };
+            return ServiceRuntimeState::Down {
+                since,
+                normally_up,
+                started_at: ago(since),
+            };
         }
 
         if let Some(caps) = FAIL_REGEX.captures(line) {
@@ -94,34 +333,110 @@ impl ServiceRuntimeState {
             }
         }
 
+        trace_debug!(line, "unrecognized sv status output");
         ServiceRuntimeState::Unknown {
             raw: line.to_string(),
         }
     }
+
+    /// Parse the `; run: log: ...` segment `sv status` appends for an attached logger.
+    pub fn log_from_sv_status(status_output: &str) -> Option<Self> {
+        let line = status_output.lines().next()?.trim();
+        line.split(';')
+            .skip(1)
+            .map(str::trim)
+            .find(|segment| {
+                segment
+                    .split_once(':')
+                    .is_some_and(|(_, rest)| rest.trim_start().starts_with("log:"))
+            })
+            .map(Self::from_sv_status)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ServiceManager, ServiceRuntimeState};
+    use super::{
+        DesiredState, Result, ServiceBackend, ServiceError, ServiceLogEntry, ServiceManager,
+        ServiceRuntimeState, Transition, parse_svlogd_line, read_svlogd_tail_from,
+    };
+    use std::path::Path;
     use std::time::Duration;
 
+    #[derive(Debug)]
+    struct FakeBackend;
+
+    impl ServiceBackend for FakeBackend {
+        fn definition_names(&self) -> Result<Vec<String>> {
+            Ok(vec!["sshd".into(), ".hidden".into(), "cron".into()])
+        }
+
+        fn is_enabled(&self, service: &str) -> bool {
+            service == "sshd"
+        }
+
+        fn status(&self, _service: &str) -> Result<ServiceRuntimeState> {
+            Ok(ServiceRuntimeState::Running {
+                pid: 7,
+                uptime: Duration::from_secs(3),
+                started_at: std::time::SystemTime::now(),
+            })
+        }
+
+        fn tail_logs(&self, _service: &str, _limit: usize) -> Result<Vec<ServiceLogEntry>> {
+            Ok(Vec::new())
+        }
+
+        fn enable(&self, service: &str) -> Result<()> {
+            Err(ServiceError::AlreadyEnabled(service.to_string()))
+        }
+
+        fn disable(&self, _service: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
     #[test]
     fn parses_running_status() {
         let state = ServiceRuntimeState::from_sv_status("run: sshd: (pid 1234) 42s\n");
         match state {
-            ServiceRuntimeState::Running { pid, uptime } => {
+            ServiceRuntimeState::Running {
+                pid,
+                uptime,
+                started_at,
+            } => {
                 assert_eq!(pid, 1234);
                 assert_eq!(uptime, Duration::from_secs(42));
+                let elapsed = started_at.elapsed().unwrap();
+                assert!(elapsed >= Duration::from_secs(42) && elapsed < Duration::from_secs(60));
             }
             other => panic!("unexpected state: {:?}", other),
         }
     }
 
+    #[test]
+    fn parses_paused_status() {
+        let state = ServiceRuntimeState::from_sv_status(
+            "run: sshd: (pid 1234) 42s, paused; run: log: (pid 9) 50s\n",
+        );
+        assert!(matches!(
+            state,
+            ServiceRuntimeState::Paused { pid: 1234, .. }
+        ));
+        assert_eq!(state.pid(), Some(1234));
+        let logger_paused = ServiceRuntimeState::from_sv_status(
+            "run: sshd: (pid 1234) 42s; run: log: (pid 9) 50s, paused\n",
+        );
+        assert!(logger_paused.is_running());
+    }
+
     #[test]
     fn parses_down_status() {
         let state = ServiceRuntimeState::from_sv_status("down: cron: 5s, normally up\n");
         match state {
-            ServiceRuntimeState::Down { since, normally_up } => {
+            ServiceRuntimeState::Down {
+                since, normally_up, ..
+            } => {
                 assert_eq!(since, Duration::from_secs(5));
                 assert!(normally_up);
             }
@@ -129,14 +444,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_log_segment() {
+        let output = "run: sshd: (pid 1234) 42s; down: log: 3s, normally up\n";
+        match ServiceRuntimeState::log_from_sv_status(output) {
+            Some(ServiceRuntimeState::Down {
+                since, normally_up, ..
+            }) => {
+                assert_eq!(since, Duration::from_secs(3));
+                assert!(normally_up);
+            }
+            other => panic!("unexpected log state: {:?}", other),
+        }
+        assert!(ServiceRuntimeState::log_from_sv_status("run: cron: (pid 9) 1s\n").is_none());
+    }
+
+    #[test]
+    fn keeps_non_utf8_log_bytes() {
+        let entry = parse_svlogd_line(b"@400000006500000a00000000 caf\xe9 ready");
+        assert_eq!(entry.timestamp_unix, Some(0x6500_0000));
+        assert_eq!(entry.message_bytes, b"caf\xe9 ready");
+        assert_eq!(entry.message, "caf\u{fffd} ready");
+
+        let path = std::env::temp_dir().join(format!("runkit-bytes-{}", std::process::id()));
+        std::fs::write(&path, b"\xff\xfe\nplain\n").unwrap();
+        let entries = read_svlogd_tail_from(
+            std::io::BufReader::new(std::fs::File::open(&path).unwrap()),
+            10,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].message_bytes, b"\xff\xfe");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn detects_want_markers() {
+        assert_eq!(
+            Transition::from_sv_status("down: sshd: 1s, normally up, want up\n"),
+            Some(Transition::Starting)
+        );
+        assert_eq!(
+            Transition::from_sv_status("run: sshd: (pid 5) 9s; run: log: (pid 6) 9s\n"),
+            None
+        );
+    }
+
     #[test]
     fn decodes_tai64n_timestamp() {
-        let stamp = "400000000000000000000000";
+        let stamp = "400000000000000a00000000";
         let parsed = super::decode_tai64n(stamp).expect("failed to parse tai64n epoch");
         assert_eq!(parsed.0, 0);
         assert_eq!(parsed.1, 0);
     }
 
+    #[test]
+    fn lists_services_through_backend() {
+        let manager = ServiceManager::new("/nonexistent/sv", "/nonexistent/service")
+            .with_backend(FakeBackend);
+        let services = manager.list_services().expect("fake backend listing");
+        let names: Vec<_> = services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["cron", "sshd"]);
+        assert!(matches!(
+            services[1].runtime_state,
+            ServiceRuntimeState::Running { pid: 7, .. }
+        ));
+        assert!(matches!(
+            manager.enable("sshd"),
+            Err(ServiceError::AlreadyEnabled(_))
+        ));
+    }
+
+    #[test]
+    fn down_file_marks_enabled_service() {
+        assert_eq!(
+            DesiredState::from_flags(true, true),
+            DesiredState::EnabledButDown
+        );
+        assert_eq!(
+            DesiredState::from_flags(true, false),
+            DesiredState::AutoStart
+        );
+        assert_eq!(DesiredState::from_flags(false, true), DesiredState::Manual);
+    }
+
     #[test]
     fn validates_service_name() {
         let manager = ServiceManager::default();
@@ -144,33 +535,191 @@ mod tests {
         assert!(manager.validate_service_name("../bad").is_err());
         assert!(manager.validate_service_name("").is_err());
     }
+
+    #[test]
+    fn overrides_service_dir() {
+        let manager = ServiceManager::new("/etc/sv", "/var/service");
+        let fixture = manager.with_service_dir("/tmp/runkit-fixture");
+        assert_eq!(fixture.enabled_dir(), Path::new("/tmp/runkit-fixture"));
+        assert_eq!(fixture.definitions_dir(), manager.definitions_dir());
+        assert_eq!(manager.enabled_dir(), Path::new("/var/service"));
+    }
+}
+
+/// Direction a service is moving in while runsv acts on a `want up` / `want down`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Transition {
+    Starting,
+    Stopping,
+}
+
+impl Transition {
+    /// Detect the `want up` / `want down` markers in `sv status` output.
+    pub fn from_sv_status(status_output: &str) -> Option<Self> {
+        let line = status_output.lines().next()?;
+        let main = line.split(';').next().unwrap_or(line);
+        if main.contains("want up") {
+            Some(Transition::Starting)
+        } else if main.contains("want down") {
+            Some(Transition::Stopping)
+        } else {
+            None
+        }
+    }
 }
 
 /// Desired state of a service as configured by the user.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum DesiredState {
     AutoStart,
+    /// Linked into the enabled directory, but a `down` file keeps runsv from starting it.
+    EnabledButDown,
     Manual,
 }
 
+impl DesiredState {
+    pub fn from_flags(enabled: bool, has_down_file: bool) -> Self {
+        match (enabled, has_down_file) {
+            (true, false) => DesiredState::AutoStart,
+            (true, true) => DesiredState::EnabledButDown,
+            (false, _) => DesiredState::Manual,
+        }
+    }
+}
+
 /// Immutable snapshot of a runit service.
+///
+/// Fields added after the first wire format default when missing, so older runkitd
+/// output still deserializes.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ServiceInfo {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(with = "serde_support::lossy_path"))]
     pub definition_path: PathBuf,
     pub enabled: bool,
     pub desired_state: DesiredState,
     pub runtime_state: ServiceRuntimeState,
+    /// Runlevels under `/etc/runit/runsvdir` that link this service.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub runlevels: Vec<String>,
+    /// Set while runsv is still acting on a start or stop request.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pending: Option<Transition>,
+    /// Started with `sv once`: runsv will not restart the process when it exits.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub run_once: bool,
+    /// State of the attached `log/` service, when one exists.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub log_state: Option<ServiceRuntimeState>,
+    /// The definition has a `check` script for `sv check`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub has_check: bool,
+    /// The definition has a `finish` script.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub has_finish: bool,
+    /// The definition has a `log/` service.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub has_logger: bool,
+    /// A `masked` marker keeps the service from being enabled.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub masked: bool,
+    /// Set once the service has been seen restarting rapidly; see [`RestartTracker`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flapping: Option<Flapping>,
+    /// Problems with the service's links or definition, as opposed to its process.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub health: ServiceHealth,
+    /// Services the `run` script waits for or starts, see [`run_script_dependencies`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dependencies: Vec<String>,
+    /// Template this service was instantiated from, for `name@param` definitions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub template: Option<String>,
+    /// Contents of the definition's `runkit.toml`, when present and valid.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Option<ServiceMetadata>,
+    /// The running process maps binaries or libraries that were deleted by an upgrade.
+    /// Listings only check this with [`ServiceQuery::check_upgrades`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub needs_restart: bool,
+    /// Recent exits seen by the manager, newest first; see [`FinishHistory`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub finishes: Vec<FinishRecord>,
+    /// The service's `supervise/` directory with symlinks resolved; `None` until runsv
+    /// has created it.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "serde_support::option_lossy_path")
+    )]
+    pub supervise_dir: Option<PathBuf>,
+    /// runsv created the `supervise/ok` FIFO, so control commands have somewhere to go.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub has_ok_pipe: bool,
+    /// A runsv currently supervises the service.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub runsv_running: bool,
+    /// Why the service was last stopped or disabled; see [`DOWN_REASON_FILE`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub down_reason: Option<DownReason>,
     pub description: Option<String>,
 }
 
+fn ago(elapsed: Duration) -> SystemTime {
+    SystemTime::now()
+        .checked_sub(elapsed)
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
 /// Structured log entry emitted by a runit service logger.
+///
+/// On the wire the timestamp fields are `unix_seconds`, `nanos` and `raw`, and only the
+/// lossy `message` is carried; `message_bytes` is rebuilt from it when deserializing.
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "serde_support::LogEntryWire")
+)]
 pub struct ServiceLogEntry {
+    #[cfg_attr(feature = "serde", serde(rename = "unix_seconds"))]
     pub timestamp_unix: Option<i64>,
+    #[cfg_attr(feature = "serde", serde(rename = "nanos"))]
     pub timestamp_nanos: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(rename = "raw"))]
     pub timestamp_raw: Option<String>,
+    /// Lossy UTF-8 rendering of `message_bytes`.
     pub message: String,
+    /// The message exactly as the service logged it.
+    #[cfg_attr(feature = "serde", serde(skip_serializing))]
+    pub message_bytes: Vec<u8>,
+    /// Derived from the message by [`SeverityRules`].
+    pub severity: LogSeverity,
+}
+
+impl ServiceLogEntry {
+    /// When svlogd stamped the line, if it carried a timestamp.
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        let secs = u64::try_from(self.timestamp_unix?).ok()?;
+        let nanos = self.timestamp_nanos.unwrap_or(0).min(999_999_999);
+        Some(UNIX_EPOCH + Duration::new(secs, nanos))
+    }
+
+    /// [`ServiceLogEntry::timestamp`] as a chrono UTC date-time.
+    #[cfg(feature = "chrono")]
+    pub fn datetime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.timestamp().map(chrono::DateTime::from)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -191,15 +740,93 @@ pub enum ServiceError {
     #[error("log stream unavailable for service {0}")]
     LogUnavailable(String),
 
+    #[error("service definition missing: {service} ({path})")]
+    DefinitionMissing { service: String, path: PathBuf },
+
+    #[error("service definition already exists: {service} ({path})")]
+    DefinitionExists { service: String, path: PathBuf },
+
+    #[error("invalid run script for service {service}: {message}")]
+    InvalidRunScript { service: String, message: String },
+
+    #[error("cannot convert unit: {0}")]
+    InvalidUnit(String),
+
+    #[error("service already enabled: {0}")]
+    AlreadyEnabled(String),
+
+    #[error("service is not enabled: {0}")]
+    NotEnabled(String),
+
+    #[error("service is masked: {0}")]
+    Masked(String),
+
+    #[error("service has no check script: {0}")]
+    MissingCheckScript(String),
+
+    #[error("runsv is not running for service {0}")]
+    SupervisorNotRunning(String),
+
+    #[error("service is not supervised by runsvdir: {0}")]
+    NotSupervised(String),
+
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("invalid {path:?}: {source}")]
+    InvalidMetadata {
+        path: PathBuf,
+        #[source]
+        source: MetadataError,
+    },
+
+    #[error("dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+
+    #[error("{command} failed: {message}")]
+    SystemCommand { command: String, message: String },
+
+    #[error("unknown runlevel: {0}")]
+    UnknownRunlevel(String),
+
+    #[error("refusing to remove {service}: {reason}")]
+    RemovalRefused { service: String, reason: String },
+
+    #[error("timed out after {timeout:?} waiting for service {service}")]
+    Timeout { service: String, timeout: Duration },
+
     #[error(transparent)]
     Other(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
 
 impl ServiceError {
+    /// Wrap an I/O error, singling out `EACCES`/`EPERM` as [`ServiceError::PermissionDenied`].
     pub fn from_io(path: impl Into<PathBuf>, err: std::io::Error) -> Self {
-        ServiceError::Io {
-            source: err,
-            path: path.into(),
+        let path = path.into();
+        if err.kind() == std::io::ErrorKind::PermissionDenied {
+            return ServiceError::PermissionDenied(path.display().to_string());
+        }
+        ServiceError::Io { source: err, path }
+    }
+
+    /// Map a diagnostic printed by `sv` onto the most specific variant, falling back
+    /// to [`ServiceError::SvCommand`].
+    pub fn from_sv_message(service: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        let lower = message.to_ascii_lowercase();
+        if lower.contains("runsv not running") {
+            ServiceError::SupervisorNotRunning(service.to_string())
+        } else if lower.contains("file does not exist")
+            && (lower.contains("supervise/ok") || lower.contains("service directory"))
+        {
+            ServiceError::NotSupervised(service.to_string())
+        } else if lower.contains("access denied") || lower.contains("permission denied") {
+            ServiceError::PermissionDenied(message)
+        } else {
+            ServiceError::SvCommand {
+                service: service.to_string(),
+                message,
+            }
         }
     }
 }
@@ -212,6 +839,16 @@ pub struct ServiceManager {
     definitions_dir: PathBuf,
     enabled_dir: PathBuf,
     sv_command: PathBuf,
+    sv_timeout: Duration,
+    runlevels_dir: Option<PathBuf>,
+    scope: ServiceScope,
+    severity_rules: Option<Arc<SeverityRules>>,
+    restarts: Arc<Mutex<RestartTracker>>,
+    finishes: Arc<Mutex<FinishHistory>>,
+    journal: Option<Arc<Mutex<EventJournal>>>,
+    description_cache: Option<Arc<Mutex<DescriptionCache>>>,
+    socklog_dir: Option<PathBuf>,
+    backend: Arc<dyn ServiceBackend>,
 }
 
 impl Default for ServiceManager {
@@ -222,18 +859,67 @@ impl Default for ServiceManager {
 
 impl ServiceManager {
     pub fn new(definitions_dir: impl Into<PathBuf>, enabled_dir: impl Into<PathBuf>) -> Self {
+        let definitions_dir = definitions_dir.into();
+        let enabled_dir = enabled_dir.into();
+        let sv_command = PathBuf::from("sv");
+        let backend = Arc::new(RunitBackend::new(
+            &definitions_dir,
+            &enabled_dir,
+            &sv_command,
+        ));
         ServiceManager {
-            definitions_dir: definitions_dir.into(),
-            enabled_dir: enabled_dir.into(),
-            sv_command: PathBuf::from("sv"),
+            definitions_dir,
+            enabled_dir,
+            sv_command,
+            sv_timeout: DEFAULT_SV_TIMEOUT,
+            runlevels_dir: None,
+            scope: ServiceScope::System,
+            severity_rules: None,
+            restarts: Arc::default(),
+            finishes: Arc::default(),
+            journal: None,
+            description_cache: None,
+            socklog_dir: Some(PathBuf::from(SOCKLOG_DIR)),
+            backend,
         }
     }
 
+    /// Manager for the service directory named by `SVDIR`, falling back to
+    /// [`DEFAULT_ENABLED_DIR`] the same way `sv` does.
+    pub fn from_env() -> Self {
+        Self::new(DEFAULT_SERVICE_DIR, service_dir_from_env())
+    }
+
+    /// Copy of this manager pointed at another service directory, for one-off calls
+    /// against a second runsvdir or a test fixture. This reinstates the built-in
+    /// [`RunitBackend`].
+    pub fn with_service_dir(&self, dir: impl Into<PathBuf>) -> Self {
+        let mut manager = self.clone();
+        manager.enabled_dir = dir.into();
+        manager.with_sv_command(self.sv_command.clone())
+    }
+
+    /// Use a different `sv` binary. This reinstates the built-in [`RunitBackend`].
     pub fn with_sv_command(mut self, cmd: impl Into<PathBuf>) -> Self {
         self.sv_command = cmd.into();
+        self.backend = Arc::new(RunitBackend::new(
+            &self.definitions_dir,
+            &self.enabled_dir,
+            &self.sv_command,
+        ));
         self
     }
 
+    /// Route listing, status, log and enablement calls through a custom backend.
+    pub fn with_backend(mut self, backend: impl ServiceBackend + 'static) -> Self {
+        self.backend = Arc::new(backend);
+        self
+    }
+
+    pub fn backend(&self) -> &dyn ServiceBackend {
+        self.backend.as_ref()
+    }
+
     pub fn definitions_dir(&self) -> &Path {
         &self.definitions_dir
     }
@@ -248,92 +934,184 @@ impl ServiceManager {
 
     /// Enumerate all services available on the system.
     pub fn list_services(&self) -> Result<Vec<ServiceInfo>> {
-        let mut services = Vec::new();
-
-        let read_dir = std::fs::read_dir(&self.definitions_dir)
-            .map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?;
-
-        for entry in read_dir {
-            let entry = entry.map_err(|e| ServiceError::from_io(&self.definitions_dir, e))?;
-            let path = entry.path();
-            if !path.is_dir() {
-                continue;
-            }
-            if let Some(name) = path.file_name().and_then(OsStr::to_str) {
-                if let Some(info) = self.build_service_info(name, &path)? {
-                    services.push(info);
-                }
-            }
-        }
-
-        services.sort_by(|a, b| a.name.cmp(&b.name));
-        Ok(services)
+        self.list_services_filtered(&ServiceQuery::default())
     }
 
-    fn build_service_info(
-        &self,
-        name: &str,
-        definition_path: &Path,
-    ) -> Result<Option<ServiceInfo>> {
-        // Skip hidden directories or invalid names.
-        if name.starts_with('.') {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
+    fn build_service_info(&self, name: &str) -> Result<Option<ServiceInfo>> {
+        // Skip hidden directories, templates and invalid names.
+        if name.starts_with('.')
+            || template::is_template_dir_name(name)
+            || self.validate_service_name(name).is_err()
+        {
             return Ok(None);
         }
 
-        let enabled_path = self.enabled_dir.join(name);
-        let enabled = enabled_path.exists();
-        let desired_state = if enabled {
-            DesiredState::AutoStart
+        let definition_path = self.definitions_dir.join(name);
+        let enabled = self.backend.is_enabled(name);
+        let desired_state = DesiredState::from_flags(enabled, self.backend.has_down_file(name));
+
+        // A warning from `sv` about one service should not abort the whole listing.
+        let runtime_state = if enabled {
+            self.backend.status_lenient(name)?.state
+        } else {
+            ServiceRuntimeState::Unknown {
+                raw: format!("{name}: not linked into {}", self.enabled_dir.display()),
+            }
+        };
+        let (pending, log_state) = if enabled {
+            (
+                self.backend.transition(name).ok().flatten(),
+                self.backend.log_status(name).ok().flatten(),
+            )
         } else {
-            DesiredState::Manual
+            (None, None)
         };
-
-        let runtime_state = self.status(name)?;
-        let description = self.read_description(definition_path);
+        let metadata = metadata::read_metadata(&definition_path.join(METADATA_FILE))
+            .ok()
+            .flatten();
+        let description = metadata
+            .as_ref()
+            .and_then(|metadata| metadata.description.clone())
+            .or_else(|| self.read_description(&definition_path));
+        let has_check = definition_path.join("check").is_file();
+        let has_finish = definition_path.join("finish").is_file();
+        let has_logger = definition_path.join("log").is_dir();
+        let masked = definition_path.join(MASK_FILE).exists();
+        let down_reason = down_reason::read_down_reason(&definition_path);
+        let dependencies = self.dependencies(name).unwrap_or_default();
+        let (flapping, finishes) = if enabled {
+            self.observe_finish(name, &runtime_state);
+            self.journal_observe(name, &runtime_state);
+            (
+                self.restart_tracker().observe(name, &runtime_state),
+                self.finish_tracker().history(name),
+            )
+        } else {
+            (None, Vec::new())
+        };
+        let supervise = if enabled {
+            SupervisePresence::read(&self.enabled_dir.join(name))
+        } else {
+            SupervisePresence::default()
+        };
+        let run_once = enabled
+            && read_supervise_status(&self.enabled_dir.join(name))
+                .is_ok_and(|status| status.runs_once());
 
         Ok(Some(ServiceInfo {
             name: name.to_string(),
-            definition_path: definition_path.to_path_buf(),
+            definition_path,
             enabled,
             desired_state,
+            runlevels: Vec::new(),
             runtime_state,
+            pending,
+            run_once,
+            log_state,
+            has_check,
+            has_finish,
+            has_logger,
+            masked,
+            flapping,
+            health: ServiceHealth::Ok,
+            dependencies,
+            template: split_instance(name).map(|(template, _)| template.to_string()),
+            metadata,
+            needs_restart: false,
+            finishes,
+            supervise_dir: supervise.dir,
+            has_ok_pipe: supervise.has_ok_pipe,
+            runsv_running: supervise.runsv_running,
+            down_reason,
             description,
         }))
     }
 
-    /// Fetch the runtime status for a single service via `sv status`.
+    /// Read and decode the raw `supervise/status` record for an enabled service.
+    pub fn supervise_status(&self, service: &str) -> Result<SuperviseStatus> {
+        self.validate_service_name(service)?;
+        let service_dir = self.enabled_dir.join(service);
+        read_supervise_status(&service_dir)
+            .map_err(|err| ServiceError::from_io(service_dir.join("supervise/status"), err))
+    }
+
+    /// Fetch the runtime status by reading `supervise/status` directly instead of
+    /// spawning `sv status`.
+    pub fn status_from_supervise(&self, service: &str) -> Result<ServiceRuntimeState> {
+        let status = self.supervise_status(service)?;
+        let normally_up = !self.enabled_dir.join(service).join("down").exists();
+        Ok(status.to_runtime_state(normally_up))
+    }
+
+    /// Fetch the runtime status for a single service from the backend.
     pub fn status(&self, service: &str) -> Result<ServiceRuntimeState> {
         self.validate_service_name(service)?;
+        self.backend.status(service)
+    }
 
-        let output = Command::new(&self.sv_command)
-            .arg("status")
-            .arg(service)
-            .output()
-            .map_err(|err| ServiceError::from_io(&self.sv_command, err))?;
+    /// Control handle writing directly to the service's `supervise/control` FIFO.
+    pub fn control(&self, service: &str) -> Result<ServiceControl> {
+        self.validate_runnable_name(service)?;
+        Ok(ServiceControl::new(
+            service,
+            &self.enabled_dir.join(service),
+        ))
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    /// Send control commands to runsv without going through the `sv` binary.
+    pub fn send_control(&self, service: &str, commands: &[ControlCommand]) -> Result<()> {
+        self.control(service)?.send_all(commands)
+    }
 
-        if !stderr.is_empty() {
-            return Err(ServiceError::SvCommand {
-                service: service.to_string(),
-                message: stderr,
-            });
-        }
+    /// Ask runsv to deliver a signal to the service's main process.
+    pub fn signal(&self, service: &str, signal: SvSignal) -> Result<()> {
+        self.control(service)?.signal(signal)
+    }
 
-        if stdout.trim().is_empty() {
-            let status_desc = output
-                .status
-                .code()
-                .map(|code| format!("exit status {code}"))
-                .unwrap_or_else(|| output.status.to_string());
-            return Err(ServiceError::SvCommand {
-                service: service.to_string(),
-                message: format!("sv status returned no output ({status_desc})"),
-            });
-        }
+    /// Start the service if it is down without having runsv restart it when it exits,
+    /// like `sv once`. Listings report it through [`ServiceInfo::run_once`].
+    pub fn once(&self, service: &str) -> Result<()> {
+        self.control(service)?.once()
+    }
 
-        Ok(ServiceRuntimeState::from_sv_status(&stdout))
+    /// Stop the service's process with SIGSTOP, like `sv pause`; runsv keeps it
+    /// supervised and reports it as [`ServiceRuntimeState::Paused`].
+    pub fn pause(&self, service: &str) -> Result<()> {
+        self.control(service)?.pause()
+    }
+
+    /// Resume a paused service with SIGCONT, like `sv cont`.
+    pub fn cont(&self, service: &str) -> Result<()> {
+        self.control(service)?.cont()
+    }
+
+    /// Report whether runsv is still starting or stopping the service.
+    pub fn transition(&self, service: &str) -> Result<Option<Transition>> {
+        self.validate_service_name(service)?;
+        self.backend.transition(service)
+    }
+
+    /// Fetch the state of the service's attached logger, if it has one.
+    pub fn log_status(&self, service: &str) -> Result<Option<ServiceRuntimeState>> {
+        self.validate_service_name(service)?;
+        self.backend.log_status(service)
+    }
+
+    /// Link a service into the enabled directory so runsvdir supervises it, dropping any
+    /// recorded [`DownReason`].
+    pub fn enable(&self, service: &str) -> Result<()> {
+        self.validate_runnable_name(service)?;
+        self.ensure_not_masked(service)?;
+        self.backend.enable(service)?;
+        let _ = self.clear_down_reason(service);
+        Ok(())
+    }
+
+    /// Remove a service from the enabled directory.
+    pub fn disable(&self, service: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        self.backend.disable(service)
     }
 
     fn read_description(&self, definition_path: &Path) -> Option<String> {
@@ -350,6 +1128,7 @@ impl ServiceManager {
         None
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
     pub fn service_description(&self, service: &str) -> Result<Option<String>> {
         self.validate_service_name(service)?;
         let definition_path = self.definitions_dir.join(service);
@@ -357,18 +1136,21 @@ impl ServiceManager {
             return Ok(None);
         }
 
+        let metadata = metadata::read_metadata(&definition_path.join(METADATA_FILE))
+            .ok()
+            .flatten();
+        if let Some(description) = metadata.and_then(|metadata| metadata.description) {
+            return Ok(Some(description));
+        }
         if let Some(description) = self.read_description(&definition_path) {
             return Ok(Some(description));
         }
 
-        Ok(self.lookup_package_description(&definition_path))
+        Ok(self.cached_package_description(&definition_path))
     }
 
     fn lookup_package_description(&self, definition_path: &Path) -> Option<String> {
-        let service_file = ["run", "finish", "check"]
-            .into_iter()
-            .map(|candidate| definition_path.join(candidate))
-            .find(|path| path.exists())?;
+        let service_file = describe::description_script(definition_path)?;
 
         let owner_output = Command::new("xbps-query")
             .arg("-o")
@@ -409,18 +1191,23 @@ impl ServiceManager {
     }
 
     pub fn validate_service_name(&self, service: &str) -> Result<()> {
-        let valid = !service.is_empty()
-            && service
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
-        if valid {
-            Ok(())
-        } else {
-            Err(ServiceError::InvalidServiceName(service.to_string()))
+        validate_service_name(service)
+    }
+
+    /// [`ServiceManager::validate_service_name`] for a service that is to be enabled or
+    /// controlled. A template directory such as `agetty@` is only there to be
+    /// instantiated, so it is rejected as a name.
+    pub(crate) fn validate_runnable_name(&self, service: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        if template::is_template_dir_name(service) {
+            return Err(ServiceError::InvalidServiceName(service.to_string()));
         }
+        Ok(())
     }
 
-    /// Tail the newest log entries for a service, if its logger writes to svlogd-style files.
+    /// Tail the newest log entries for a service. Services without a `log/main` directory
+    /// fall back to what they sent to syslog, see [`ServiceManager::tail_socklog`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
     pub fn tail_logs(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>> {
         self.validate_service_name(service)?;
 
@@ -428,94 +1215,128 @@ impl ServiceManager {
             return Ok(Vec::new());
         }
 
-        let definition_candidate = self.definitions_dir.join(service).join("log/main/current");
-        let enabled_candidate = self.enabled_dir.join(service).join("log/main/current");
+        let mut entries = self.backend.tail_logs(service, limit)?;
+        if entries.is_empty() && self.log_dir(service)?.is_none() {
+            return self.tail_socklog(service, limit);
+        }
+        self.classify_entries(&mut entries);
+        Ok(entries)
+    }
+}
 
-        let log_path = if definition_candidate.exists() {
-            definition_candidate
-        } else if enabled_candidate.exists() {
-            enabled_candidate
-        } else {
-            return Ok(Vec::new());
-        };
+/// Service directory `sv` would use: `$SVDIR` when set and non-empty, else `/var/service`.
+pub(crate) fn service_dir_from_env() -> PathBuf {
+    std::env::var_os("SVDIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_ENABLED_DIR))
+}
 
-        match read_svlogd_tail(&log_path, limit) {
-            Ok(entries) => Ok(entries),
-            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
-            Err(err) => Err(ServiceError::from_io(&log_path, err)),
-        }
+pub(crate) fn validate_service_name(service: &str) -> Result<()> {
+    // `.` and `..` would resolve to the directory itself or its parent.
+    let valid = !matches!(service, "" | "." | "..")
+        && service
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.@".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(ServiceError::InvalidServiceName(service.to_string()))
+    }
+}
+
+/// Tail a log file by reading blocks backwards from its end, so only the
+/// requested lines are read no matter how large the file has grown.
+pub(crate) fn read_svlogd_tail(path: &Path, limit: usize) -> std::io::Result<Vec<ServiceLogEntry>> {
+    let mut file = File::open(path)?;
+    let mut pos = file.seek(SeekFrom::End(0))?;
+    let mut tail = ReverseTail::default();
+
+    while pos > 0 && !tail.has_lines(limit) {
+        let len = TAIL_BLOCK_SIZE.min(pos);
+        pos -= len;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = vec![0; len as usize];
+        file.read_exact(&mut chunk)?;
+        tail.push_front(chunk);
     }
+
+    Ok(tail.into_entries(pos == 0, limit))
 }
 
-fn read_svlogd_tail(path: &Path, limit: usize) -> std::io::Result<Vec<ServiceLogEntry>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+pub(crate) fn read_svlogd_tail_from(
+    reader: impl BufRead,
+    limit: usize,
+) -> std::io::Result<Vec<ServiceLogEntry>> {
     let mut entries: VecDeque<ServiceLogEntry> = VecDeque::with_capacity(limit);
 
-    for line in reader.lines() {
+    // Split on raw bytes: `lines()` would fail on the first non-UTF-8 line.
+    for line in reader.split(b'\n') {
         let line = line?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
         if entries.len() == limit {
             entries.pop_front();
         }
-        entries.push_back(parse_svlogd_line(&line));
+        entries.push_back(parse_svlogd_line(line));
     }
 
     Ok(entries.into_iter().collect())
 }
 
-fn parse_svlogd_line(line: &str) -> ServiceLogEntry {
-    if let Some(rest) = line.strip_prefix('@') {
-        if rest.len() >= 24 {
-            let stamp = &rest[..24];
-            let message = rest[24..].trim_start().to_string();
-            let (unix, nanos) = decode_tai64n(stamp).unwrap_or((-1, 0));
-            let timestamp_unix = if unix >= 0 { Some(unix) } else { None };
-            let timestamp_nanos = if unix >= 0 { Some(nanos) } else { None };
-            return ServiceLogEntry {
-                timestamp_unix,
-                timestamp_nanos,
-                timestamp_raw: Some(stamp.to_string()),
-                message,
-            };
-        }
+/// Parse one raw log line. Only the `@<tai64n>` prefix has to be ASCII; the message
+/// keeps its original bytes alongside a lossy UTF-8 rendering.
+pub(crate) fn parse_svlogd_line(line: &[u8]) -> ServiceLogEntry {
+    if let Some(rest) = line.strip_prefix(b"@")
+        && rest.len() >= 24
+        && let Ok(stamp) = std::str::from_utf8(&rest[..24])
+    {
+        let message_bytes = rest[24..].trim_ascii_start().to_vec();
+        let message = String::from_utf8_lossy(&message_bytes).into_owned();
+        let (unix, nanos) = decode_tai64n(stamp).unwrap_or((-1, 0));
+        let timestamp_unix = if unix >= 0 { Some(unix) } else { None };
+        let timestamp_nanos = if unix >= 0 { Some(nanos) } else { None };
+        return ServiceLogEntry {
+            timestamp_unix,
+            timestamp_nanos,
+            timestamp_raw: Some(stamp.to_string()),
+            severity: severity::default_severity(&message),
+            message,
+            message_bytes,
+        };
     }
 
+    let message = String::from_utf8_lossy(line).into_owned();
     ServiceLogEntry {
         timestamp_unix: None,
         timestamp_nanos: None,
         timestamp_raw: None,
-        message: line.to_string(),
+        severity: severity::default_severity(&message),
+        message,
+        message_bytes: line.to_vec(),
     }
 }
 
+/// Decode an svlogd label into Unix seconds and nanoseconds, using runit's convention.
 fn decode_tai64n(stamp: &str) -> Option<(i64, u32)> {
-    if stamp.len() != 24 {
+    let tai = Tai64N::parse(stamp)?;
+    if tai.label < tai64::RUNIT_TAI64_UNIX_OFFSET {
         return None;
     }
 
-    let secs = u64::from_str_radix(&stamp[..16], 16).ok()?;
-    let nanos = u32::from_str_radix(&stamp[16..], 16).ok()?;
-
-    const TAI64_UNIX_OFFSET: u64 = 0x4000_0000_0000_0000;
-    if secs < TAI64_UNIX_OFFSET {
-        return None;
-    }
-
-    let unix_secs = secs - TAI64_UNIX_OFFSET;
-    Some((unix_secs as i64, nanos))
+    let unix_secs = tai.label - tai64::RUNIT_TAI64_UNIX_OFFSET;
+    Some((unix_secs as i64, tai.nanos))
 }
 
-fn strip_package_version(package: &str) -> &str {
-    if let Some(pos) = package.rfind('-') {
-        if pos + 1 < package.len()
-            && package[pos + 1..]
-                .chars()
-                .next()
-                .map(|c| c.is_ascii_digit())
-                .unwrap_or(false)
-        {
-            return &package[..pos];
-        }
+pub(crate) fn strip_package_version(package: &str) -> &str {
+    if let Some(pos) = package.rfind('-')
+        && pos + 1 < package.len()
+        && package[pos + 1..]
+            .chars()
+            .next()
+            .map(|c| c.is_ascii_digit())
+            .unwrap_or(false)
+    {
+        return &package[..pos];
     }
     package
 }
@@ -0,0 +1,340 @@
+//! Static checks of service definitions that catch mistakes before runsv trips over them.
+use crate::scaffold::check_syntax;
+use crate::socklog::run_script_command;
+use crate::{BrokenLink, Result, ServiceError, ServiceManager, validate_service_name};
+use std::fmt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Search path runit hands to run scripts on Void; the helper's own `PATH` may differ.
+const RUNIT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Scripts runsv executes from a definition, relative to it.
+const SCRIPTS: [&str; 4] = ["run", "finish", "check", "log/run"];
+
+/// One problem found by [`ServiceManager::lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum LintFinding {
+    /// The definition has no `run` script, so runsv has nothing to start.
+    MissingRun {
+        service: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        path: PathBuf,
+    },
+    /// A script runsv executes lacks the executable bit.
+    NotExecutable {
+        service: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        path: PathBuf,
+    },
+    /// `sh -n` rejects a shell script.
+    SyntaxError {
+        service: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        path: PathBuf,
+        message: String,
+    },
+    /// The program a script finally execs is not installed.
+    MissingExec {
+        service: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        path: PathBuf,
+        program: String,
+    },
+    /// A symlink in the definition or the enabled directory points nowhere.
+    BrokenSymlink {
+        service: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        path: PathBuf,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::lossy_path"))]
+        target: PathBuf,
+    },
+}
+
+impl LintFinding {
+    pub fn service(&self) -> &str {
+        match self {
+            LintFinding::MissingRun { service, .. }
+            | LintFinding::NotExecutable { service, .. }
+            | LintFinding::SyntaxError { service, .. }
+            | LintFinding::MissingExec { service, .. }
+            | LintFinding::BrokenSymlink { service, .. } => service,
+        }
+    }
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintFinding::MissingRun { service, path } => {
+                write!(f, "{service}: {} does not exist", path.display())
+            }
+            LintFinding::NotExecutable { service, path } => {
+                write!(f, "{service}: {} is not executable", path.display())
+            }
+            LintFinding::SyntaxError {
+                service,
+                path,
+                message,
+            } => write!(f, "{service}: {} does not parse: {message}", path.display()),
+            LintFinding::MissingExec {
+                service,
+                path,
+                program,
+            } => write!(
+                f,
+                "{service}: {} runs {program}, which is not installed",
+                path.display()
+            ),
+            LintFinding::BrokenSymlink {
+                service,
+                path,
+                target,
+            } => write!(
+                f,
+                "{service}: {} points to missing {}",
+                path.display(),
+                target.display()
+            ),
+        }
+    }
+}
+
+/// Result of [`ServiceManager::lint`] and [`ServiceManager::lint_all`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LintReport {
+    pub findings: Vec<LintFinding>,
+}
+
+impl LintReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl ServiceManager {
+    /// Check one definition: its scripts exist, are executable and parse, the programs
+    /// they exec are installed, and none of its symlinks dangle.
+    ///
+    /// An enablement link whose definition is gone is reported rather than failing with
+    /// [`ServiceError::DefinitionMissing`].
+    pub fn lint(&self, service: &str) -> Result<LintReport> {
+        self.validate_service_name(service)?;
+        let dir = self.definitions_dir.join(service);
+        if !dir.is_dir() {
+            if let Some(link) = BrokenLink::probe(&self.enabled_dir.join(service)) {
+                return Ok(LintReport {
+                    findings: vec![broken_link_finding(link)],
+                });
+            }
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: dir,
+            });
+        }
+
+        let mut findings = Vec::new();
+        for sub in [dir.clone(), dir.join("log")] {
+            find_dangling_symlinks(service, &sub, &mut findings);
+        }
+        for script in SCRIPTS {
+            lint_script(service, &dir.join(script), script == "run", &mut findings);
+        }
+        Ok(LintReport { findings })
+    }
+
+    /// [`ServiceManager::lint`] every definition and report dangling enablement links.
+    pub fn lint_all(&self) -> Result<LintReport> {
+        let mut names = self.backend.definition_names()?;
+        names.retain(|name| validate_service_name(name).is_ok());
+        names.sort();
+        let mut findings = Vec::new();
+        for service in names {
+            findings.extend(self.lint(&service)?.findings);
+        }
+        findings.extend(self.broken_links()?.into_iter().map(broken_link_finding));
+        Ok(LintReport { findings })
+    }
+}
+
+fn broken_link_finding(link: BrokenLink) -> LintFinding {
+    LintFinding::BrokenSymlink {
+        service: link.service,
+        path: link.link,
+        target: link.target,
+    }
+}
+
+fn find_dangling_symlinks(service: &str, dir: &Path, findings: &mut Vec<LintFinding>) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = read_dir
+        .filter_map(|entry| entry.ok())
+        // `supervise` points into /run and only exists while runsv does.
+        .filter(|entry| entry.file_name() != "supervise")
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        let is_link = std::fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink());
+        if is_link
+            && !path.exists()
+            && let Ok(target) = std::fs::read_link(&path)
+        {
+            findings.push(LintFinding::BrokenSymlink {
+                service: service.to_string(),
+                path,
+                target,
+            });
+        }
+    }
+}
+
+fn lint_script(service: &str, path: &Path, required: bool, findings: &mut Vec<LintFinding>) {
+    // Dangling symlinks were already reported.
+    let Ok(meta) = std::fs::metadata(path) else {
+        if required && std::fs::symlink_metadata(path).is_err() {
+            findings.push(LintFinding::MissingRun {
+                service: service.to_string(),
+                path: path.to_path_buf(),
+            });
+        }
+        return;
+    };
+    if !meta.is_file() {
+        return;
+    }
+    if meta.permissions().mode() & 0o111 == 0 {
+        findings.push(LintFinding::NotExecutable {
+            service: service.to_string(),
+            path: path.to_path_buf(),
+        });
+    }
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return;
+    };
+    // execline and other interpreters are beyond `sh -n`.
+    if !is_shell_script(&contents) {
+        return;
+    }
+    if let Err(ServiceError::InvalidRunScript { message, .. }) = check_syntax(path, service) {
+        findings.push(LintFinding::SyntaxError {
+            service: service.to_string(),
+            path: path.to_path_buf(),
+            message,
+        });
+        return;
+    }
+    if let Some(program) = run_script_command(&contents)
+        && !program_exists(&program, path.parent().unwrap_or(Path::new("/")))
+    {
+        findings.push(LintFinding::MissingExec {
+            service: service.to_string(),
+            path: path.to_path_buf(),
+            program,
+        });
+    }
+}
+
+fn is_shell_script(contents: &str) -> bool {
+    let Some(shebang) = contents
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("#!"))
+    else {
+        return true;
+    };
+    let interpreter = shebang.split_whitespace().next().unwrap_or_default();
+    matches!(
+        Path::new(interpreter)
+            .file_name()
+            .and_then(|name| name.to_str()),
+        Some("sh" | "dash" | "bash" | "ash" | "ksh")
+    )
+}
+
+/// Whether `program` resolves the way the shell would, from `cwd` and [`RUNIT_PATH`].
+/// Anything built from variables or substitutions is assumed to exist.
+fn program_exists(program: &str, cwd: &Path) -> bool {
+    if program.contains(['$', '`', '"', '\'']) {
+        return true;
+    }
+    let is_executable = |path: &Path| {
+        std::fs::metadata(path)
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    };
+    if program.contains('/') {
+        return is_executable(&cwd.join(program));
+    }
+    RUNIT_PATH
+        .split(':')
+        .any(|dir| is_executable(&Path::new(dir).join(program)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LintFinding;
+    use crate::testutil::FakeRunit;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    #[test]
+    fn reports_broken_definitions() {
+        let tree = FakeRunit::new("lint");
+        tree.add_service(
+            "fine",
+            "#!/bin/sh\nexec 2>&1\nexec chpst -u nobody sleep 1000\n",
+        );
+        tree.add_service("ghost", "#!/bin/sh\nexec /nonexistent/ghostd -f\n");
+        tree.add_service("broken", "#!/bin/sh\nif true; then\nexec sleep 1\n");
+        let plain = tree.add_service("plain", "#!/bin/sh\nexec sleep 1000\n");
+        std::fs::set_permissions(plain.join("run"), std::fs::Permissions::from_mode(0o644))
+            .unwrap();
+        symlink("/nonexistent/conf", plain.join("conf")).unwrap();
+        std::fs::create_dir_all(tree.definitions_dir().join("empty")).unwrap();
+        symlink(
+            tree.definitions_dir().join("gone"),
+            tree.enabled_dir().join("gone"),
+        )
+        .unwrap();
+
+        let manager = tree.manager();
+        assert!(manager.lint("fine").unwrap().is_clean());
+        let report = manager.lint_all().unwrap();
+        let found: Vec<(&str, &str)> = report
+            .findings
+            .iter()
+            .map(|finding| {
+                let kind = match finding {
+                    LintFinding::MissingRun { .. } => "missing_run",
+                    LintFinding::NotExecutable { .. } => "not_executable",
+                    LintFinding::SyntaxError { .. } => "syntax_error",
+                    LintFinding::MissingExec { .. } => "missing_exec",
+                    LintFinding::BrokenSymlink { .. } => "broken_symlink",
+                };
+                (finding.service(), kind)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("broken", "syntax_error"),
+                ("empty", "missing_run"),
+                ("ghost", "missing_exec"),
+                ("plain", "broken_symlink"),
+                ("plain", "not_executable"),
+                ("gone", "broken_symlink"),
+            ]
+        );
+        assert!(matches!(
+            &manager.lint("gone").unwrap().findings[..],
+            [LintFinding::BrokenSymlink { .. }]
+        ));
+    }
+}
@@ -0,0 +1,512 @@
+//! svlogd log directories: `current` plus the `@<tai64n>.s`/`.u` files it rotates out.
+use crate::{
+    ControlCommand, Result, ServiceControl, ServiceError, ServiceLogEntry, ServiceManager,
+    decode_tai64n, parse_svlogd_line, read_svlogd_tail, read_svlogd_tail_from,
+};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Role of a file inside an svlogd log directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFileKind {
+    /// The file svlogd is writing to.
+    Current,
+    /// `@<stamp>.s`: rotated and fully processed.
+    Rotated,
+    /// `@<stamp>.u`: rotated while svlogd was interrupted; may be truncated.
+    Unfinished,
+}
+
+/// One file of a log directory, with the rotation time encoded in its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub kind: LogFileKind,
+    pub rotated_unix: Option<i64>,
+    pub rotated_nanos: Option<u32>,
+    stamp: Option<String>,
+}
+
+impl LogFile {
+    /// When svlogd rotated the file out, from the stamp in its name.
+    pub fn rotated_at(&self) -> Option<SystemTime> {
+        let secs = u64::try_from(self.rotated_unix?).ok()?;
+        Some(UNIX_EPOCH + Duration::new(secs, self.rotated_nanos.unwrap_or(0).min(999_999_999)))
+    }
+
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name == "current" {
+            return Some(LogFile {
+                path,
+                kind: LogFileKind::Current,
+                rotated_unix: None,
+                rotated_nanos: None,
+                stamp: None,
+            });
+        }
+
+        let rest = name.strip_prefix('@')?;
+        let (stamp, kind) = match rest.split_once('.')? {
+            (stamp, "s") => (stamp, LogFileKind::Rotated),
+            (stamp, "u") => (stamp, LogFileKind::Unfinished),
+            _ => return None,
+        };
+        let (unix, nanos) = decode_tai64n(stamp)?;
+        Some(LogFile {
+            stamp: Some(stamp.to_string()),
+            path,
+            kind,
+            rotated_unix: Some(unix),
+            rotated_nanos: Some(nanos),
+        })
+    }
+}
+
+/// svlogd's `s` default: rotate `current` at one million bytes.
+const SVLOGD_DEFAULT_MAX_SIZE: u64 = 1_000_000;
+
+/// Disk usage and rotation state of a service's log directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogUsage {
+    /// Size of `current` plus every rotated file.
+    pub total_bytes: u64,
+    pub current_bytes: u64,
+    /// Rotated files, finished or not.
+    pub rotated_files: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "last_rotation_unix",
+            with = "crate::serde_support::option_unix_seconds",
+            default
+        )
+    )]
+    pub last_rotation: Option<SystemTime>,
+    /// Modification time of `current`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "last_write_unix",
+            with = "crate::serde_support::option_unix_seconds",
+            default
+        )
+    )]
+    pub last_write: Option<SystemTime>,
+    /// svlogd is not keeping up: the logger is down while the service runs, or `current`
+    /// has outgrown the configured size without being rotated.
+    pub stalled: bool,
+}
+
+impl ServiceManager {
+    /// Directory svlogd writes to, checked in the same order `tail_logs` uses.
+    pub fn log_dir(&self, service: &str) -> Result<Option<PathBuf>> {
+        self.validate_service_name(service)?;
+        Ok([&self.definitions_dir, &self.enabled_dir]
+            .into_iter()
+            .map(|root| root.join(service).join("log/main"))
+            .find(|dir| dir.is_dir()))
+    }
+
+    /// Every log file of the service, oldest first, ending with `current`.
+    pub fn log_files(&self, service: &str) -> Result<Vec<LogFile>> {
+        match self.log_dir(service)? {
+            Some(dir) => list_log_files(&dir),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Size and rotation state of the service's svlogd directory; `None` without one.
+    pub fn log_usage(&self, service: &str) -> Result<Option<LogUsage>> {
+        let files = self.log_files(service)?;
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let mut usage = LogUsage {
+            total_bytes: 0,
+            current_bytes: 0,
+            rotated_files: 0,
+            last_rotation: None,
+            last_write: None,
+            stalled: false,
+        };
+        for file in &files {
+            // svlogd may prune a rotated file between listing and stat.
+            let Ok(meta) = std::fs::metadata(&file.path) else {
+                continue;
+            };
+            usage.total_bytes += meta.len();
+            if file.kind == LogFileKind::Current {
+                usage.current_bytes = meta.len();
+                usage.last_write = meta.modified().ok();
+                continue;
+            }
+            usage.rotated_files += 1;
+            let rotated = file.rotated_at();
+            if rotated > usage.last_rotation {
+                usage.last_rotation = rotated;
+            }
+        }
+
+        let max_size = self
+            .svlogd_config(service)
+            .ok()
+            .and_then(|config| config.max_size)
+            .unwrap_or(SVLOGD_DEFAULT_MAX_SIZE);
+        let oversized = max_size > 0 && usage.current_bytes > max_size;
+        let logger_down = self.status(service).is_ok_and(|state| state.is_running())
+            && self
+                .log_status(service)
+                .is_ok_and(|logger| logger.is_some_and(|logger| !logger.is_running()));
+        usage.stalled = oversized || logger_down;
+        Ok(Some(usage))
+    }
+
+    /// Newest `limit` entries across `current` and the rotated files, oldest first.
+    pub fn tail_log_history(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>> {
+        let files = self.log_files(service)?;
+        let mut chunks = Vec::new();
+        let mut collected = 0;
+
+        for file in files.iter().rev() {
+            if collected >= limit {
+                break;
+            }
+            let entries = read_log_file(file, limit - collected)?;
+            collected += entries.len();
+            chunks.push(entries);
+        }
+
+        let mut entries: Vec<ServiceLogEntry> = chunks.into_iter().rev().flatten().collect();
+        self.classify_entries(&mut entries);
+        Ok(entries)
+    }
+
+    /// Control handle for the runsv supervising the service's `log/` logger. Its state
+    /// is reported by [`ServiceManager::log_status`].
+    pub fn logger_control(&self, service: &str) -> Result<ServiceControl> {
+        self.validate_service_name(service)?;
+        let logger_dir = self.enabled_dir.join(service).join("log");
+        if !logger_dir.is_dir() {
+            return Err(ServiceError::LogUnavailable(service.to_string()));
+        }
+        Ok(ServiceControl::new(service, &logger_dir))
+    }
+
+    /// Restart the logger, e.g. after changing `log/main/config`.
+    pub fn restart_logger(&self, service: &str) -> Result<()> {
+        self.logger_control(service)?.restart()
+    }
+
+    /// Make svlogd rotate `current` right away; runsv forwards the request as SIGALRM.
+    pub fn rotate_logs(&self, service: &str) -> Result<()> {
+        self.logger_control(service)?.send(ControlCommand::Alarm)
+    }
+}
+
+pub(crate) fn list_log_files(dir: &Path) -> Result<Vec<LogFile>> {
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(ServiceError::from_io(dir, err)),
+    };
+
+    let mut files = Vec::new();
+    for entry in read_dir {
+        let entry = entry.map_err(|e| ServiceError::from_io(dir, e))?;
+        if let Some(file) = LogFile::from_path(entry.path()) {
+            files.push(file);
+        }
+    }
+    // Fixed-width hex stamps sort chronologically; `current` (no stamp) goes last.
+    files.sort_by(|a, b| match (&a.stamp, &b.stamp) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    Ok(files)
+}
+
+pub(crate) fn read_log_file(file: &LogFile, limit: usize) -> Result<Vec<ServiceLogEntry>> {
+    let result = match file.kind {
+        LogFileKind::Current => read_svlogd_tail(&file.path, limit),
+        LogFileKind::Rotated | LogFileKind::Unfinished => read_archive(&file.path, limit),
+    };
+    match result {
+        Ok(entries) => Ok(entries),
+        // svlogd may rotate or prune a file between listing and reading it.
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(ServiceError::from_io(&file.path, err)),
+    }
+}
+
+/// Size of the blocks read backwards from the end of a log file.
+pub(crate) const TAIL_BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Blocks read backwards from the end of a file, kept until they hold enough lines.
+#[derive(Debug, Default)]
+pub(crate) struct ReverseTail {
+    chunks: VecDeque<Vec<u8>>,
+    newlines: usize,
+    ends_with_newline: bool,
+}
+
+impl ReverseTail {
+    /// Add the block that precedes everything collected so far.
+    pub(crate) fn push_front(&mut self, chunk: Vec<u8>) {
+        if self.chunks.is_empty() {
+            self.ends_with_newline = chunk.last() == Some(&b'\n');
+        }
+        self.newlines += chunk.iter().filter(|b| **b == b'\n').count();
+        self.chunks.push_front(chunk);
+    }
+
+    /// Whether the oldest of the newest `limit` lines is known to be complete.
+    pub(crate) fn has_lines(&self, limit: usize) -> bool {
+        self.newlines - usize::from(self.ends_with_newline) >= limit
+    }
+
+    /// Parse the newest `limit` lines. Unless the reads reached the start of the file,
+    /// the first line is partial and dropped.
+    pub(crate) fn into_entries(self, reached_start: bool, limit: usize) -> Vec<ServiceLogEntry> {
+        let bytes: Vec<u8> = self.chunks.into_iter().flatten().collect();
+        let start = if reached_start {
+            0
+        } else {
+            bytes
+                .iter()
+                .position(|b| *b == b'\n')
+                .map_or(bytes.len(), |idx| idx + 1)
+        };
+        let text = &bytes[start..];
+        if text.is_empty() {
+            return Vec::new();
+        }
+
+        let text = text.strip_suffix(b"\n").unwrap_or(text);
+        let lines: Vec<&[u8]> = text.split(|b| *b == b'\n').collect();
+        lines[lines.len().saturating_sub(limit)..]
+            .iter()
+            .map(|line| {
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                parse_svlogd_line(line)
+            })
+            .collect()
+    }
+}
+
+/// Compression an svlogd processor (`!gzip`, `!zstd`) left on a rotated file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Processors keep the `.s` name, so sniff the magic bytes instead.
+    fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+fn read_archive(path: &Path, limit: usize) -> io::Result<Vec<ServiceLogEntry>> {
+    read_svlogd_tail_from(open_log_reader(path)?, limit)
+}
+
+/// Line reader over a log file, decompressing rotated archives when needed.
+pub(crate) fn open_log_reader(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    match Compression::detect(reader.fill_buf()?) {
+        Compression::None => Ok(Box::new(reader)),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => {
+            let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+            Ok(Box::new(BufReader::new(decoder)))
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            let decoder = zstd::stream::read::Decoder::with_buffer(reader)?;
+            Ok(Box::new(BufReader::new(decoder)))
+        }
+        #[cfg(not(feature = "gzip"))]
+        Compression::Gzip => Err(unsupported("gzip")),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => Err(unsupported("zstd")),
+    }
+}
+
+#[cfg(any(not(feature = "gzip"), not(feature = "zstd")))]
+fn unsupported(format: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        format!("{format}-compressed log; rebuild runkit-core with the `{format}` feature"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, LogFileKind};
+    use crate::{ServiceError, ServiceManager};
+
+    #[test]
+    fn logger_control_requires_a_logger() {
+        let root = std::env::temp_dir().join(format!("runkit-logger-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("service/cron")).unwrap();
+        std::fs::create_dir_all(root.join("service/sshd/log")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        assert!(matches!(
+            manager.rotate_logs("cron"),
+            Err(ServiceError::LogUnavailable(name)) if name == "cron"
+        ));
+        let control = manager.logger_control("sshd").unwrap();
+        assert_eq!(
+            control.control_path(),
+            root.join("service/sshd/log/supervise/control")
+        );
+        assert!(matches!(
+            manager.restart_logger("sshd"),
+            Err(ServiceError::NotSupervised(_))
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn reports_log_usage() {
+        let root = std::env::temp_dir().join(format!("runkit-log-usage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let log_dir = root.join("sv/app/log/main");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        std::fs::write(log_dir.join("@400000006500000000000000.s"), "one\ntwo\n").unwrap();
+        std::fs::write(log_dir.join("@400000006600000000000000.s"), "three\n").unwrap();
+        std::fs::write(log_dir.join("current"), "a line longer than ten bytes\n").unwrap();
+        std::fs::write(log_dir.join("config"), "s10\n").unwrap();
+
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        let usage = manager.log_usage("app").unwrap().unwrap();
+        assert_eq!(usage.rotated_files, 2);
+        assert_eq!(usage.current_bytes, 29);
+        assert_eq!(usage.total_bytes, 29 + 8 + 6);
+        assert_eq!(
+            usage.last_rotation,
+            manager.log_files("app").unwrap()[1].rotated_at()
+        );
+        assert!(usage.stalled);
+        assert!(manager.log_usage("missing").unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn tails_across_rotated_files() {
+        let root = std::env::temp_dir().join(format!("runkit-logs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let log_dir = root.join("sv/app/log/main");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        std::fs::write(log_dir.join("@400000006500000000000000.s"), "one\ntwo\n").unwrap();
+        std::fs::write(log_dir.join("@400000006600000000000000.u"), "three\n").unwrap();
+        std::fs::write(log_dir.join("current"), "four\n").unwrap();
+        std::fs::write(log_dir.join("lock"), "").unwrap();
+
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        let kinds: Vec<_> = manager
+            .log_files("app")
+            .unwrap()
+            .into_iter()
+            .map(|file| file.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                LogFileKind::Rotated,
+                LogFileKind::Unfinished,
+                LogFileKind::Current
+            ]
+        );
+
+        let messages: Vec<_> = manager
+            .tail_log_history("app", 3)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, ["two", "three", "four"]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn tails_large_file_from_the_end() {
+        let dir = std::env::temp_dir().join(format!("runkit-bigtail-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("current");
+        let contents: String = (0..20_000).map(|n| format!("line {n}\n")).collect();
+        std::fs::write(&path, contents.trim_end()).unwrap();
+
+        let messages: Vec<_> = crate::read_svlogd_tail(&path, 3)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, ["line 19997", "line 19998", "line 19999"]);
+        assert_eq!(
+            crate::read_svlogd_tail(&path, 30_000).unwrap().len(),
+            20_000
+        );
+        assert!(crate::read_svlogd_tail(&path, 0).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn detects_compressed_archives() {
+        assert_eq!(Compression::detect(&[0x1f, 0x8b, 8, 0]), Compression::Gzip);
+        assert_eq!(
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::detect(b"@4000"), Compression::None);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn reads_gzip_rotated_file() {
+        use flate2::{Compression as Level, write::GzEncoder};
+        use std::io::Write;
+
+        let root = std::env::temp_dir().join(format!("runkit-gzlogs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let log_dir = root.join("sv/app/log/main");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+        encoder.write_all(b"old\n").unwrap();
+        std::fs::write(
+            log_dir.join("@400000006500000000000000.s"),
+            encoder.finish().unwrap(),
+        )
+        .unwrap();
+        std::fs::write(log_dir.join("current"), "new\n").unwrap();
+
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        let messages: Vec<_> = manager
+            .tail_log_history("app", 10)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, ["old", "new"]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
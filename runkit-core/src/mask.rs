@@ -0,0 +1,124 @@
+//! Masking: keeping a service from being enabled until it is explicitly unmasked.
+use crate::{DesiredState, Result, ServiceError, ServiceManager};
+use std::path::PathBuf;
+
+/// Marker file placed in the service definition while it is masked.
+pub const MASK_FILE: &str = "masked";
+
+impl ServiceManager {
+    fn mask_path(&self, service: &str) -> PathBuf {
+        self.definitions_dir.join(service).join(MASK_FILE)
+    }
+
+    pub fn is_masked(&self, service: &str) -> bool {
+        self.validate_service_name(service).is_ok() && self.mask_path(service).exists()
+    }
+
+    /// Disable the service if needed, in every runlevel, and mark it so
+    /// [`ServiceManager::enable`] and [`ServiceManager::enable_in`] refuse it.
+    pub fn mask(&self, service: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        let definition = self.definitions_dir.join(service);
+        if !definition.is_dir() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: definition,
+            });
+        }
+        if std::fs::symlink_metadata(self.enabled_dir.join(service)).is_ok() {
+            self.disable(service)?;
+        }
+        // Otherwise switching to a level that still links it would start it anyway.
+        for level in self.service_runlevels(service)? {
+            self.disable_in(service, &level)?;
+        }
+        let marker = self.mask_path(service);
+        std::fs::write(&marker, "masked by runkit; remove to allow enabling\n")
+            .map_err(|err| ServiceError::from_io(&marker, err))
+    }
+
+    /// Remove the mask; the service stays disabled until enabled again.
+    pub fn unmask(&self, service: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        let marker = self.mask_path(service);
+        match std::fs::remove_file(&marker) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(ServiceError::from_io(&marker, err)),
+        }
+    }
+
+    /// Whether the service starts on its own once linked; masking leaves it `Manual`.
+    pub fn desired_state(&self, service: &str) -> Result<DesiredState> {
+        self.validate_service_name(service)?;
+        Ok(DesiredState::from_flags(
+            self.backend.is_enabled(service),
+            self.backend.has_down_file(service),
+        ))
+    }
+
+    pub(crate) fn ensure_not_masked(&self, service: &str) -> Result<()> {
+        if self.mask_path(service).exists() {
+            return Err(ServiceError::Masked(service.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DesiredState, ServiceError, ServiceManager};
+
+    #[test]
+    fn masked_service_cannot_be_enabled() {
+        let root = std::env::temp_dir().join(format!("runkit-mask-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd")).unwrap();
+        std::fs::create_dir_all(root.join("service")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        manager.enable("sshd").unwrap();
+
+        manager.mask("sshd").unwrap();
+        assert!(manager.is_masked("sshd"));
+        assert_eq!(manager.desired_state("sshd").unwrap(), DesiredState::Manual);
+        assert!(!manager.backend().is_enabled("sshd"));
+        assert!(matches!(
+            manager.enable("sshd"),
+            Err(ServiceError::Masked(name)) if name == "sshd"
+        ));
+
+        manager.unmask("sshd").unwrap();
+        manager.enable("sshd").unwrap();
+        assert!(!manager.is_masked("sshd"));
+        assert_eq!(
+            manager.desired_state("sshd").unwrap(),
+            DesiredState::AutoStart
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn masking_unlinks_every_runlevel() {
+        let root = std::env::temp_dir().join(format!("runkit-mask-levels-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd")).unwrap();
+        std::fs::create_dir_all(root.join("runsvdir/default")).unwrap();
+        std::fs::create_dir_all(root.join("runsvdir/single")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("runsvdir/default"))
+            .with_runlevels_dir(root.join("runsvdir"));
+        manager.enable("sshd").unwrap();
+        manager.enable_in("sshd", "single").unwrap();
+        assert_eq!(
+            manager.service_runlevels("sshd").unwrap(),
+            ["default", "single"]
+        );
+
+        manager.mask("sshd").unwrap();
+        assert!(manager.service_runlevels("sshd").unwrap().is_empty());
+        assert!(matches!(
+            manager.enable_in("sshd", "single"),
+            Err(ServiceError::Masked(name)) if name == "sshd"
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,399 @@
+//! Optional `runkit.toml` next to a service's `run` script, describing the service.
+use crate::{Result, ServiceError, ServiceManager};
+#[cfg(not(feature = "serde"))]
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+/// File name of the metadata file inside a service definition.
+pub const METADATA_FILE: &str = "runkit.toml";
+
+/// How much the system depends on a service; frontends use it to guard stop actions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Criticality {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+impl Criticality {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "low" => Some(Criticality::Low),
+            "normal" => Some(Criticality::Normal),
+            "high" => Some(Criticality::High),
+            "critical" => Some(Criticality::Critical),
+            _ => None,
+        }
+    }
+}
+
+/// When the service is meant to come back after it exits. runsv always restarts, so
+/// this only records the author's intent for tools that act on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RestartPolicy {
+    Always,
+    #[cfg_attr(feature = "serde", serde(alias = "on-failure"))]
+    OnFailure,
+    Never,
+}
+
+impl RestartPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "always" => Some(RestartPolicy::Always),
+            "on-failure" | "on_failure" => Some(RestartPolicy::OnFailure),
+            "never" => Some(RestartPolicy::Never),
+            _ => None,
+        }
+    }
+}
+
+/// The `[restart]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RestartHints {
+    pub policy: Option<RestartPolicy>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "delay_seconds",
+            alias = "delay",
+            with = "crate::serde_support::option_seconds",
+            default
+        )
+    )]
+    pub delay: Option<Duration>,
+    pub max_restarts: Option<u32>,
+}
+
+/// Contents of `runkit.toml`.
+///
+/// ```toml
+/// description = "OpenSSH server"
+/// tags = ["network", "remote-access"]
+/// criticality = "high"
+/// documentation = "https://man.voidlinux.org/sshd.8"
+///
+/// [restart]
+/// policy = "on-failure"
+/// delay = 5
+/// max_restarts = 3
+/// ```
+///
+/// With the `serde` feature the file is read with the `toml` crate; without it, only
+/// this flat subset of TOML is understood. Unknown keys are ignored so newer files still
+/// load.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServiceMetadata {
+    pub description: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tags: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub criticality: Criticality,
+    pub documentation: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub restart: RestartHints,
+}
+
+/// A line of `runkit.toml` that could not be understood.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("line {line}: {message}")]
+pub struct MetadataError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[cfg(not(feature = "serde"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Array(Vec<String>),
+}
+
+#[cfg(not(feature = "serde"))]
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Value::Str(_) => "a string",
+            Value::Int(_) => "an integer",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+        })
+    }
+}
+
+impl ServiceMetadata {
+    #[cfg(feature = "serde")]
+    pub fn parse(contents: &str) -> std::result::Result<Self, MetadataError> {
+        toml::from_str(contents).map_err(|err| {
+            let offset = err.span().map_or(0, |span| span.start);
+            MetadataError {
+                line: contents[..offset].matches('\n').count() + 1,
+                message: err.message().to_string(),
+            }
+        })
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn parse(contents: &str) -> std::result::Result<Self, MetadataError> {
+        let mut metadata = ServiceMetadata::default();
+        let mut table = String::new();
+
+        for (idx, raw) in contents.lines().enumerate() {
+            let err = |message: String| MetadataError {
+                line: idx + 1,
+                message,
+            };
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| err("unterminated table header".to_string()))?;
+                table = name.trim().to_string();
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| err(format!("expected `key = value`, found `{line}`")))?;
+            let key = key.trim().trim_matches('"');
+            let value = parse_value(value.trim()).map_err(err)?;
+            metadata.set(&table, key, value).map_err(err)?;
+        }
+        Ok(metadata)
+    }
+
+    #[cfg(not(feature = "serde"))]
+    fn set(&mut self, table: &str, key: &str, value: Value) -> std::result::Result<(), String> {
+        let mismatch = |expected: &str| format!("`{key}` must be {expected}, found {value}");
+        match (table, key, &value) {
+            ("", "description", Value::Str(text)) => self.description = Some(text.clone()),
+            ("", "documentation", Value::Str(url)) => self.documentation = Some(url.clone()),
+            ("", "tags", Value::Array(tags)) => self.tags = tags.clone(),
+            ("", "criticality", Value::Str(name)) => {
+                self.criticality = Criticality::from_name(name)
+                    .ok_or_else(|| format!("unknown criticality `{name}`"))?;
+            }
+            ("restart", "policy", Value::Str(name)) => {
+                self.restart.policy = Some(
+                    RestartPolicy::from_name(name)
+                        .ok_or_else(|| format!("unknown restart policy `{name}`"))?,
+                );
+            }
+            ("restart", "delay", Value::Int(secs)) => {
+                let secs = u64::try_from(*secs).map_err(|_| mismatch("a positive integer"))?;
+                self.restart.delay = Some(Duration::from_secs(secs));
+            }
+            ("restart", "max_restarts", Value::Int(count)) => {
+                let count = u32::try_from(*count).map_err(|_| mismatch("a positive integer"))?;
+                self.restart.max_restarts = Some(count);
+            }
+            ("", "description" | "documentation" | "criticality", _) | ("restart", "policy", _) => {
+                return Err(mismatch("a string"));
+            }
+            ("", "tags", _) => return Err(mismatch("an array of strings")),
+            ("restart", "delay" | "max_restarts", _) => return Err(mismatch("an integer")),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Drop a trailing `#` comment that is not inside a string.
+#[cfg(not(feature = "serde"))]
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..idx],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+#[cfg(not(feature = "serde"))]
+fn parse_value(raw: &str) -> std::result::Result<Value, String> {
+    match raw {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+    if let Some(items) = raw.strip_prefix('[') {
+        let items = items
+            .strip_suffix(']')
+            .ok_or_else(|| "arrays must close on the same line".to_string())?;
+        return split_array(items)?
+            .into_iter()
+            .map(parse_string)
+            .collect::<std::result::Result<_, _>>()
+            .map(Value::Array);
+    }
+    if raw.starts_with(['"', '\'']) {
+        return parse_string(raw).map(Value::Str);
+    }
+    raw.replace('_', "")
+        .parse()
+        .map(Value::Int)
+        .map_err(|_| format!("unsupported value `{raw}`"))
+}
+
+#[cfg(not(feature = "serde"))]
+fn split_array(items: &str) -> std::result::Result<Vec<&str>, String> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    let mut escaped = false;
+    for (idx, c) in items.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                parts.push(items[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    if quote.is_some() {
+        return Err("unterminated string".to_string());
+    }
+    let last = items[start..].trim();
+    if !last.is_empty() {
+        parts.push(last);
+    }
+    Ok(parts)
+}
+
+#[cfg(not(feature = "serde"))]
+fn parse_string(raw: &str) -> std::result::Result<String, String> {
+    if let Some(literal) = raw.strip_prefix('\'') {
+        return literal
+            .strip_suffix('\'')
+            .map(str::to_string)
+            .ok_or_else(|| format!("unterminated string `{raw}`"));
+    }
+    let body = raw
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| format!("expected a quoted string, found `{raw}`"))?;
+
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c @ ('"' | '\\')) => out.push(c),
+            other => return Err(format!("unsupported escape `\\{}`", other.unwrap_or(' '))),
+        }
+    }
+    Ok(out)
+}
+
+impl ServiceManager {
+    /// Parsed `runkit.toml` of the service; `None` when the definition has none.
+    pub fn metadata(&self, service: &str) -> Result<Option<ServiceMetadata>> {
+        let path = self.definition_file(service, METADATA_FILE)?;
+        read_metadata(&path)
+    }
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "trace", err(level = "warn"))
+)]
+pub(crate) fn read_metadata(path: &Path) -> Result<Option<ServiceMetadata>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(ServiceError::from_io(path, err)),
+    };
+    ServiceMetadata::parse(&contents)
+        .map(Some)
+        .map_err(|source| ServiceError::InvalidMetadata {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Criticality, RestartPolicy, ServiceMetadata};
+    use std::time::Duration;
+
+    #[test]
+    fn parses_metadata_file() {
+        let metadata = ServiceMetadata::parse(
+            "# sshd\ndescription = \"OpenSSH \\\"server\\\"\" # trailing\n\
+             tags = ['network', \"remote#access\"]\ncriticality = \"high\"\n\
+             future_key = 1\n\n[restart]\npolicy = \"on-failure\"\ndelay = 5\n",
+        )
+        .unwrap();
+        assert_eq!(metadata.description.as_deref(), Some("OpenSSH \"server\""));
+        assert_eq!(metadata.tags, ["network", "remote#access"]);
+        assert_eq!(metadata.criticality, Criticality::High);
+        assert_eq!(metadata.restart.policy, Some(RestartPolicy::OnFailure));
+        assert_eq!(metadata.restart.delay, Some(Duration::from_secs(5)));
+        assert_eq!(metadata.restart.max_restarts, None);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let err = ServiceMetadata::parse("description = \"ok\"\ncriticality = 3\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(ServiceMetadata::parse("tags = [\"a\",\n").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reads_any_toml_layout() {
+        let metadata = ServiceMetadata::parse(
+            "tags = [\n  \"network\",\n  \"remote-access\",\n]\n\
+             restart = { policy = \"on-failure\", max_restarts = 3 }\n",
+        )
+        .unwrap();
+        assert_eq!(metadata.tags, ["network", "remote-access"]);
+        assert_eq!(metadata.restart.policy, Some(RestartPolicy::OnFailure));
+        assert_eq!(metadata.restart.max_restarts, Some(3));
+        let err = ServiceMetadata::parse("[restart]\n\ndelay = -5\n").unwrap_err();
+        assert_eq!(err.line, 3);
+    }
+}
@@ -0,0 +1,195 @@
+//! Whole-system actions through runit: halting, rebooting and switching runlevels.
+use crate::stage::process_cmdlines;
+use crate::{RUNIT_DIR, Result, RunitStage, ServiceError, ServiceManager};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const DEFAULT_RUNIT_INIT: &str = "runit-init";
+pub const DEFAULT_RUNSVCHDIR: &str = "runsvchdir";
+
+/// What to ask runit's stage 3 to do once every service is down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PowerAction {
+    Poweroff,
+    Reboot,
+}
+
+impl PowerAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerAction::Poweroff => "poweroff",
+            PowerAction::Reboot => "reboot",
+        }
+    }
+
+    /// The `runit-init` argument selecting this action: `0` halts, `6` reboots.
+    pub fn init_arg(self) -> &'static str {
+        match self {
+            PowerAction::Poweroff => "0",
+            PowerAction::Reboot => "6",
+        }
+    }
+}
+
+impl fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Checked wrappers around `runit-init` and `runsvchdir`.
+///
+/// Every call verifies that it runs as root and that runit is in stage 2 before spawning
+/// anything, so a desktop frontend gets a clear error instead of a half-started shutdown.
+#[derive(Debug, Clone)]
+pub struct SystemControl {
+    runit_init: PathBuf,
+    runsvchdir: PathBuf,
+}
+
+impl Default for SystemControl {
+    fn default() -> Self {
+        SystemControl {
+            runit_init: PathBuf::from(DEFAULT_RUNIT_INIT),
+            runsvchdir: PathBuf::from(DEFAULT_RUNSVCHDIR),
+        }
+    }
+}
+
+impl SystemControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_runit_init(mut self, cmd: impl Into<PathBuf>) -> Self {
+        self.runit_init = cmd.into();
+        self
+    }
+
+    pub fn with_runsvchdir(mut self, cmd: impl Into<PathBuf>) -> Self {
+        self.runsvchdir = cmd.into();
+        self
+    }
+
+    /// Hand the system to runit's stage 3 with the given action.
+    pub fn power(&self, action: PowerAction) -> Result<()> {
+        ensure_root(action.name())?;
+        ensure_running(action.name())?;
+        run(&self.runit_init, &[action.init_arg()])
+    }
+
+    pub fn poweroff(&self) -> Result<()> {
+        self.power(PowerAction::Poweroff)
+    }
+
+    pub fn reboot(&self) -> Result<()> {
+        self.power(PowerAction::Reboot)
+    }
+
+    /// Point runsvdir at another runlevel; services missing from it are stopped.
+    ///
+    /// Only levels [`ServiceManager::runlevels`] lists are accepted, so `runsvchdir` never
+    /// sees a path such as `..`.
+    pub fn switch_runlevel(&self, manager: &ServiceManager, runlevel: &str) -> Result<()> {
+        manager.runlevel_path(runlevel)?;
+        if manager.current_runlevel().as_deref() == Some(runlevel) {
+            return Ok(());
+        }
+        ensure_root("runlevel switch")?;
+        ensure_running("runlevel switch")?;
+        run(&self.runsvchdir, &[runlevel])
+    }
+}
+
+fn ensure_root(action: &str) -> Result<()> {
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(ServiceError::PermissionDenied(format!(
+            "{action} requires root privileges"
+        )));
+    }
+    Ok(())
+}
+
+fn ensure_running(action: &str) -> Result<()> {
+    let cmdlines = process_cmdlines();
+    let stage = RunitStage::from_cmdlines(Path::new(RUNIT_DIR), cmdlines.iter().map(Vec::as_slice));
+    refuse_outside_stage_2(action, stage)
+}
+
+fn refuse_outside_stage_2(action: &str, stage: Option<RunitStage>) -> Result<()> {
+    let reason = match stage {
+        Some(RunitStage::Running) => return Ok(()),
+        Some(RunitStage::Shutdown) => "the system is already shutting down",
+        Some(RunitStage::Boot) => "the system is still booting",
+        None => "runit is not running as init",
+    };
+    Err(ServiceError::SystemCommand {
+        command: action.to_string(),
+        message: format!("refusing: {reason}"),
+    })
+}
+
+fn run(program: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|err| ServiceError::from_io(program, err))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(ServiceError::SystemCommand {
+        command: format!("{} {}", program.display(), args.join(" ")),
+        message: if stderr.is_empty() {
+            output.status.to_string()
+        } else {
+            stderr
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PowerAction, SystemControl, refuse_outside_stage_2};
+    use crate::testutil::FakeRunit;
+    use crate::{RunitStage, ServiceError};
+
+    #[test]
+    fn maps_actions_to_runit_init_levels() {
+        assert_eq!(PowerAction::Poweroff.init_arg(), "0");
+        assert_eq!(PowerAction::Reboot.init_arg(), "6");
+    }
+
+    #[test]
+    fn switches_only_to_listed_runlevels() {
+        let tree = FakeRunit::new("switch");
+        let levels = tree.root().join("runsvdir");
+        std::fs::create_dir_all(levels.join("default")).unwrap();
+        let manager = tree.manager().with_runlevels_dir(&levels);
+        let control = SystemControl::new().with_runsvchdir(tree.root().join("no-runsvchdir"));
+        for runlevel in ["..", ".", "single", "default/.."] {
+            assert!(matches!(
+                control.switch_runlevel(&manager, runlevel),
+                Err(ServiceError::UnknownRunlevel(level)) if level == runlevel
+            ));
+        }
+    }
+
+    #[test]
+    fn refuses_outside_normal_operation() {
+        assert!(refuse_outside_stage_2("reboot", Some(RunitStage::Running)).is_ok());
+        for stage in [None, Some(RunitStage::Boot), Some(RunitStage::Shutdown)] {
+            assert!(matches!(
+                refuse_outside_stage_2("reboot", stage),
+                Err(ServiceError::SystemCommand { command, .. }) if command == "reboot"
+            ));
+        }
+    }
+}
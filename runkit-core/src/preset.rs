@@ -0,0 +1,112 @@
+//! Directory layouts of the runit-based distributions runkit knows about.
+use crate::{DEFAULT_ENABLED_DIR, DEFAULT_SERVICE_DIR, ServiceManager};
+use std::path::{Path, PathBuf};
+
+/// A known runit installation layout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Distro {
+    /// `/etc/sv` and `/var/service`.
+    #[default]
+    Void,
+    /// `/etc/runit/sv` and `/run/runit/service`.
+    Artix,
+    /// `/etc/sv` and `/etc/service`, as set up by antiX's runit init.
+    AntiX,
+    /// Upstream runit packaged elsewhere, e.g. Debian: `/etc/sv` and `/etc/service`.
+    Generic,
+}
+
+impl Distro {
+    pub fn definitions_dir(self) -> &'static str {
+        match self {
+            Distro::Void | Distro::AntiX | Distro::Generic => DEFAULT_SERVICE_DIR,
+            Distro::Artix => "/etc/runit/sv",
+        }
+    }
+
+    pub fn enabled_dir(self) -> &'static str {
+        match self {
+            Distro::Void => DEFAULT_ENABLED_DIR,
+            Distro::Artix => "/run/runit/service",
+            Distro::AntiX | Distro::Generic => "/etc/service",
+        }
+    }
+
+    /// Recognise the distribution from the `ID` and `ID_LIKE` keys of os-release(5).
+    pub fn from_os_release(contents: &str) -> Option<Self> {
+        let mut ids = Vec::new();
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if matches!(key.trim(), "ID" | "ID_LIKE") {
+                let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+                ids.extend(value.split_whitespace().map(str::to_ascii_lowercase));
+            }
+        }
+        ids.iter().find_map(|id| match id.as_str() {
+            "void" => Some(Distro::Void),
+            "artix" => Some(Distro::Artix),
+            "antix" => Some(Distro::AntiX),
+            _ => None,
+        })
+    }
+
+    /// Identify the running system from os-release, falling back to whichever known
+    /// layout exists on disk, and finally to Void.
+    pub fn detect() -> Self {
+        let from_release = ["/etc/os-release", "/usr/lib/os-release"]
+            .into_iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .find_map(|contents| Self::from_os_release(&contents));
+        if let Some(distro) = from_release {
+            return distro;
+        }
+
+        [Distro::Void, Distro::Artix, Distro::Generic]
+            .into_iter()
+            .find(|distro| {
+                Path::new(distro.definitions_dir()).is_dir()
+                    && Path::new(distro.enabled_dir()).is_dir()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl ServiceManager {
+    /// Manager for the standard layout of `distro`.
+    pub fn preset(distro: Distro) -> Self {
+        Self::new(distro.definitions_dir(), distro.enabled_dir())
+    }
+
+    /// Manager for the detected distribution; `SVDIR` still overrides the enabled
+    /// directory, as it does for `sv`.
+    pub fn detect() -> Self {
+        let distro = Distro::detect();
+        let enabled_dir = std::env::var_os("SVDIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(distro.enabled_dir()));
+        Self::new(distro.definitions_dir(), enabled_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Distro;
+
+    #[test]
+    fn recognises_os_release_ids() {
+        assert_eq!(
+            Distro::from_os_release("NAME=\"Artix Linux\"\nID=artix\n"),
+            Some(Distro::Artix)
+        );
+        assert_eq!(
+            Distro::from_os_release("ID=antix\nID_LIKE=debian\n"),
+            Some(Distro::AntiX)
+        );
+        assert_eq!(Distro::from_os_release("ID=\"void\"\n"), Some(Distro::Void));
+        assert_eq!(Distro::from_os_release("ID=debian\n"), None);
+        assert_eq!(Distro::Artix.enabled_dir(), "/run/runit/service");
+    }
+}
@@ -0,0 +1,141 @@
+//! Identity of a supervised process: when it started, what it runs and as whom.
+use crate::stats::{read_stat, sysconf};
+use crate::{Result, ServiceError, ServiceManager};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Details of the main process of a running service, read from `/proc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// When the kernel started the process. Unlike the supervise timestamp this does not
+    /// move when runsv is paused or resumed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "started_at_unix",
+            with = "crate::serde_support::unix_seconds"
+        )
+    )]
+    pub started_at: SystemTime,
+    /// Arguments as the process sees them; empty for kernel threads and zombies.
+    pub command_line: Vec<String>,
+    /// Effective user id, as set by `chpst -u` in the run script.
+    pub uid: u32,
+    /// Name for `uid` from `/etc/passwd`, if it has one.
+    pub user: Option<String>,
+}
+
+impl ProcessInfo {
+    pub fn for_pid(pid: u32) -> io::Result<Self> {
+        read_process(Path::new("/proc"), Path::new("/etc/passwd"), pid)
+    }
+
+    /// The command line joined with spaces, for display.
+    pub fn command(&self) -> String {
+        self.command_line.join(" ")
+    }
+}
+
+impl ServiceManager {
+    /// Start time, command line and user of a running service; `None` when it is not
+    /// running.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    pub fn process_info(&self, service: &str) -> Result<Option<ProcessInfo>> {
+        let Some(pid) = self.status(service)?.pid() else {
+            return Ok(None);
+        };
+        match ProcessInfo::for_pid(pid) {
+            Ok(info) => Ok(Some(info)),
+            // The process exited between the status read and the /proc lookup.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(ServiceError::from_io(format!("/proc/{pid}"), err)),
+        }
+    }
+}
+
+fn read_process(proc_root: &Path, passwd: &Path, pid: u32) -> io::Result<ProcessInfo> {
+    let dir = proc_root.join(pid.to_string());
+    let stat = read_stat(proc_root, pid)?;
+    let status = std::fs::read_to_string(dir.join("status"))?;
+    let uid = effective_uid(&status).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("no Uid line in /proc/{pid}/status"),
+        )
+    })?;
+    let command_line = std::fs::read(dir.join("cmdline"))?
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+
+    let ticks_per_sec = sysconf(libc::_SC_CLK_TCK).unwrap_or(100);
+    let boot_time = std::fs::read_to_string(proc_root.join("stat"))
+        .ok()
+        .and_then(|stat| boot_time(&stat))
+        .unwrap_or(0);
+    let since_boot = Duration::from_millis(stat.start_ticks * 1000 / ticks_per_sec);
+
+    Ok(ProcessInfo {
+        pid,
+        started_at: UNIX_EPOCH + Duration::from_secs(boot_time) + since_boot,
+        command_line,
+        uid,
+        user: std::fs::read_to_string(passwd)
+            .ok()
+            .and_then(|passwd| user_name(&passwd, uid)),
+    })
+}
+
+/// Second field of the `Uid:` line in `/proc/<pid>/status`.
+fn effective_uid(status: &str) -> Option<u32> {
+    let line = status.lines().find_map(|line| line.strip_prefix("Uid:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// The `btime` line of `/proc/stat`: boot time in seconds since the epoch.
+fn boot_time(stat: &str) -> Option<u64> {
+    let line = stat.lines().find_map(|line| line.strip_prefix("btime "))?;
+    line.trim().parse().ok()
+}
+
+fn user_name(passwd: &str, uid: u32) -> Option<String> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let uid_field = fields.nth(1)?;
+        (uid_field.parse::<u32>().ok()? == uid).then(|| name.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProcessInfo, boot_time, effective_uid, user_name};
+    use std::time::SystemTime;
+
+    #[test]
+    fn parses_proc_and_passwd_fields() {
+        assert_eq!(
+            effective_uid("Name:\tsshd\nUid:\t0\t22\t22\t22\nGid:\t0\t0\t0\t0\n"),
+            Some(22)
+        );
+        assert_eq!(
+            boot_time("cpu  1 2 3\nintr 5\nbtime 1700000000\nprocesses 42\n"),
+            Some(1_700_000_000)
+        );
+        let passwd = "root:x:0:0:root:/root:/bin/sh\n_tor:x:22:22::/var/lib/tor:/sbin/nologin\n";
+        assert_eq!(user_name(passwd, 22).as_deref(), Some("_tor"));
+        assert_eq!(user_name(passwd, 1000), None);
+    }
+
+    #[test]
+    fn reads_own_process() {
+        let info = ProcessInfo::for_pid(std::process::id()).unwrap();
+        assert!(!info.command_line.is_empty());
+        assert!(info.started_at <= SystemTime::now());
+        assert_eq!(info.uid, unsafe { libc::geteuid() });
+    }
+}
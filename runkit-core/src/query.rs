@@ -0,0 +1,548 @@
+//! Filtered service listings.
+use crate::{
+    BrokenLink, Result, ServiceError, ServiceHealth, ServiceInfo, ServiceManager,
+    ServiceRuntimeState, runlevel, upgrade,
+};
+use std::iter::Peekable;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Variant of [`ServiceRuntimeState`] without its payload, for filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum RuntimeStateKind {
+    Running,
+    Paused,
+    Down,
+    Failed,
+    Unknown,
+}
+
+impl RuntimeStateKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            RuntimeStateKind::Running => "running",
+            RuntimeStateKind::Paused => "paused",
+            RuntimeStateKind::Down => "down",
+            RuntimeStateKind::Failed => "failed",
+            RuntimeStateKind::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "running" => Some(RuntimeStateKind::Running),
+            "paused" => Some(RuntimeStateKind::Paused),
+            "down" => Some(RuntimeStateKind::Down),
+            "failed" => Some(RuntimeStateKind::Failed),
+            "unknown" => Some(RuntimeStateKind::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl ServiceRuntimeState {
+    pub fn kind(&self) -> RuntimeStateKind {
+        match self {
+            ServiceRuntimeState::Running { .. } => RuntimeStateKind::Running,
+            ServiceRuntimeState::Paused { .. } => RuntimeStateKind::Paused,
+            ServiceRuntimeState::Down { .. } => RuntimeStateKind::Down,
+            ServiceRuntimeState::Failed { .. } => RuntimeStateKind::Failed,
+            ServiceRuntimeState::Unknown { .. } => RuntimeStateKind::Unknown,
+        }
+    }
+}
+
+/// Filters for [`ServiceManager::list_services_filtered`]. The default matches everything
+/// and skips the optional checks.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceQuery {
+    /// Only services that are (or are not) linked into the enabled directory.
+    pub enabled: Option<bool>,
+    /// Only services in one of these states; empty means any state.
+    pub states: Vec<RuntimeStateKind>,
+    /// Only names matching this glob; `*` and `?` are the only wildcards.
+    pub name_glob: Option<String>,
+    /// Only services whose definition does (or does not) have a `log/` service.
+    pub has_logger: Option<bool>,
+    /// Only services that have been running for at least this long.
+    pub min_uptime: Option<Duration>,
+    /// Fill in [`ServiceInfo::needs_restart`], reading the memory maps of every running
+    /// service listed.
+    pub check_upgrades: bool,
+}
+
+impl ServiceQuery {
+    /// Checks that need no status lookup, so rejected services are never queried.
+    fn matches_definition(&self, manager: &ServiceManager, name: &str) -> bool {
+        if let Some(glob) = &self.name_glob
+            && !glob_matches(glob.as_bytes(), name.as_bytes())
+        {
+            return false;
+        }
+        if self
+            .enabled
+            .is_some_and(|enabled| manager.backend.is_enabled(name) != enabled)
+        {
+            return false;
+        }
+        self.has_logger.is_none_or(|has_logger| {
+            manager.definitions_dir.join(name).join("log").is_dir() == has_logger
+        })
+    }
+
+    /// Definition checks for an entry that has no definition on disk.
+    fn matches_broken_link(&self, info: &ServiceInfo) -> bool {
+        self.name_glob
+            .as_ref()
+            .is_none_or(|glob| glob_matches(glob.as_bytes(), info.name.as_bytes()))
+            && self.enabled.is_none_or(|enabled| enabled)
+            && self.has_logger.is_none_or(|has_logger| !has_logger)
+            && self.matches_state(&info.runtime_state)
+    }
+
+    fn matches_state(&self, state: &ServiceRuntimeState) -> bool {
+        if !self.states.is_empty() && !self.states.contains(&state.kind()) {
+            return false;
+        }
+        self.min_uptime.is_none_or(
+            |min| matches!(state, ServiceRuntimeState::Running { uptime, .. } if *uptime >= min),
+        )
+    }
+}
+
+impl ServiceManager {
+    /// Services matching `query`, sorted by name.
+    ///
+    /// Name, enablement and logger filters run before the status of a service is read.
+    /// With a description cache, services without a description of their own get their
+    /// package's, looked up in one batch.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    pub fn list_services_filtered(&self, query: &ServiceQuery) -> Result<Vec<ServiceInfo>> {
+        let mut services = self
+            .iter_services_filtered(query.clone())?
+            .collect::<Result<Vec<_>>>()?;
+        self.fill_package_descriptions(&mut services);
+        self.save_finishes();
+        trace_debug!(listed = services.len(), "listed services");
+        Ok(services)
+    }
+
+    /// [`ServiceManager::list_services_filtered`] with the status of up to `jobs`
+    /// services read at once.
+    ///
+    /// The result is the same, in the same order; only the per-service `sv` calls and
+    /// file reads overlap, which is where a long listing spends its time.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    pub fn list_services_parallel(
+        &self,
+        query: &ServiceQuery,
+        jobs: NonZeroUsize,
+    ) -> Result<Vec<ServiceInfo>> {
+        let mut services = self
+            .iter_services_filtered(query.clone())?
+            .collect_parallel(jobs)?;
+        self.fill_package_descriptions(&mut services);
+        self.save_finishes();
+        trace_debug!(
+            listed = services.len(),
+            jobs = jobs.get(),
+            "listed services"
+        );
+        Ok(services)
+    }
+
+    /// All services, yielded one at a time as their status is read.
+    ///
+    /// Produces the same entries in the same order as [`ServiceManager::list_services`],
+    /// so a large listing can be shown while it is still being built.
+    pub fn iter_services(&self) -> Result<ServiceIter<'_>> {
+        self.iter_services_filtered(ServiceQuery::default())
+    }
+
+    /// The listing entry of a single service, without reading the status of any other.
+    ///
+    /// A dangling enablement link yields the same stand-in entry the listing shows.
+    pub fn service_info(&self, service: &str) -> Result<ServiceInfo> {
+        self.validate_service_name(service)?;
+        let broken = BrokenLink::probe(&self.enabled_dir.join(service));
+        let definition_path = self.definitions_dir.join(service);
+        if !definition_path.is_dir() {
+            return match broken {
+                Some(link) => Ok(link.to_service_info()),
+                None => Err(ServiceError::DefinitionMissing {
+                    service: service.to_string(),
+                    path: definition_path,
+                }),
+            };
+        }
+        let mut info =
+            self.build_service_info(service)?
+                .ok_or_else(|| ServiceError::DefinitionMissing {
+                    service: service.to_string(),
+                    path: definition_path,
+                })?;
+        info.runlevels = self.service_runlevels(service).unwrap_or_default();
+        info.needs_restart = info
+            .runtime_state
+            .pid()
+            .is_some_and(upgrade::runs_stale_files);
+        if broken.is_some() {
+            info.health = ServiceHealth::BrokenLink;
+        }
+        self.fill_package_descriptions(std::slice::from_mut(&mut info));
+        Ok(info)
+    }
+
+    /// Incremental form of [`ServiceManager::list_services_filtered`].
+    pub fn iter_services_filtered(&self, query: ServiceQuery) -> Result<ServiceIter<'_>> {
+        let mut names = self.backend.definition_names()?;
+        names.sort();
+        let (defined, dangling) = self
+            .broken_links()
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|link| names.binary_search(&link.service).is_ok());
+        Ok(ServiceIter {
+            manager: self,
+            query,
+            names: names.into_iter().peekable(),
+            dangling: Vec::into_iter(dangling).peekable(),
+            broken_definitions: defined,
+            runlevel_root: self.runlevels_dir(),
+            levels: self.runlevels().unwrap_or_default(),
+        })
+    }
+}
+
+/// Iterator returned by [`ServiceManager::iter_services`].
+///
+/// Definitions and the enabled directory are read up front; each step reads the status
+/// of one service. Links whose definition is gone come interleaved in name order.
+#[derive(Debug)]
+pub struct ServiceIter<'a> {
+    manager: &'a ServiceManager,
+    query: ServiceQuery,
+    names: Peekable<std::vec::IntoIter<String>>,
+    dangling: Peekable<std::vec::IntoIter<BrokenLink>>,
+    broken_definitions: Vec<BrokenLink>,
+    runlevel_root: Option<PathBuf>,
+    levels: Vec<String>,
+}
+
+impl ServiceIter<'_> {
+    fn next_definition(&self, name: &str) -> Result<Option<ServiceInfo>> {
+        if !self.query.matches_definition(self.manager, name) {
+            return Ok(None);
+        }
+        let Some(mut info) = self.manager.build_service_info(name)? else {
+            return Ok(None);
+        };
+        if !self.query.matches_state(&info.runtime_state) {
+            return Ok(None);
+        }
+        if let Some(root) = &self.runlevel_root {
+            info.runlevels = runlevel::runlevels_containing(root, &self.levels, name);
+        }
+        if self.query.check_upgrades {
+            info.needs_restart = info
+                .runtime_state
+                .pid()
+                .is_some_and(upgrade::runs_stale_files);
+        }
+        if self
+            .broken_definitions
+            .iter()
+            .any(|link| link.service == name)
+        {
+            info.health = ServiceHealth::BrokenLink;
+        }
+        Ok(Some(info))
+    }
+
+    /// The remaining entries, with definitions handed out to `jobs` workers in turn.
+    fn collect_parallel(mut self, jobs: NonZeroUsize) -> Result<Vec<ServiceInfo>> {
+        let names: Vec<String> = self.names.by_ref().collect();
+        let next = AtomicUsize::new(0);
+        let this = &self;
+        let mut results: Vec<(usize, Result<Option<ServiceInfo>>)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..jobs.get().min(names.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(name) = names.get(index) else {
+                                return done;
+                            };
+                            done.push((index, this.next_definition(name)));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        results.sort_unstable_by_key(|(index, _)| *index);
+
+        // Interleave dangling links as `next` does; the first failure in name order wins.
+        let mut services = Vec::new();
+        for (name, (_, result)) in names.iter().zip(results) {
+            while let Some(link) = self.dangling.next_if(|link| link.service < *name) {
+                let info = link.to_service_info();
+                if self.query.matches_broken_link(&info) {
+                    services.push(info);
+                }
+            }
+            services.extend(result?);
+        }
+        // Only dangling links are left, and those never fail.
+        services.extend(self.flatten());
+        Ok(services)
+    }
+}
+
+impl Iterator for ServiceIter<'_> {
+    type Item = Result<ServiceInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let link_first = match (self.names.peek(), self.dangling.peek()) {
+                (Some(name), Some(link)) => link.service < *name,
+                (None, Some(_)) => true,
+                (_, None) => false,
+            };
+            if link_first {
+                let info = self.dangling.next()?.to_service_info();
+                if self.query.matches_broken_link(&info) {
+                    return Some(Ok(info));
+                }
+                continue;
+            }
+            let name = self.names.next()?;
+            match self.next_definition(&name) {
+                Ok(Some(info)) => return Some(Ok(info)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.names.len() + self.dangling.len()))
+    }
+}
+
+/// Shell-style match of the whole name against `*` / `?` wildcards.
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RuntimeStateKind, ServiceQuery, glob_matches};
+    use crate::testutil::FakeRunit;
+    use crate::{DescriptionCache, ServiceError, ServiceHealth, ServiceManager};
+    use std::num::NonZeroUsize;
+    use std::time::SystemTime;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches(b"*", b"sshd"));
+        assert!(glob_matches(b"s*d", b"sshd"));
+        assert!(glob_matches(b"agetty-tty?", b"agetty-tty1"));
+        assert!(!glob_matches(b"agetty-tty?", b"agetty-tty10"));
+        assert!(!glob_matches(b"*d", b"cron"));
+    }
+
+    #[test]
+    fn filters_before_reading_status() {
+        let root = std::env::temp_dir().join(format!("runkit-query-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd/log")).unwrap();
+        std::fs::create_dir_all(root.join("sv/cron")).unwrap();
+        std::fs::create_dir_all(root.join("sv/dhcpcd")).unwrap();
+        std::fs::create_dir_all(root.join("service")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        let names = |query: &ServiceQuery| -> Vec<String> {
+            let services = manager.list_services_filtered(query).unwrap();
+            services.into_iter().map(|info| info.name).collect()
+        };
+        let logged = ServiceQuery {
+            has_logger: Some(true),
+            ..ServiceQuery::default()
+        };
+        assert_eq!(names(&logged), ["sshd"]);
+        let globbed = ServiceQuery {
+            name_glob: Some("*d".to_string()),
+            enabled: Some(false),
+            states: vec![RuntimeStateKind::Unknown],
+            ..ServiceQuery::default()
+        };
+        assert_eq!(names(&globbed), ["dhcpcd", "sshd"]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn iterates_in_listing_order() {
+        let tree = FakeRunit::new("iter");
+        tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        tree.add_service("acpid", "#!/bin/sh\nexec acpid -f\n");
+        tree.enable("sshd");
+        tree.set_running("sshd", 812, SystemTime::now());
+        std::os::unix::fs::symlink(
+            tree.definitions_dir().join("dhcpcd"),
+            tree.enabled_dir().join("dhcpcd"),
+        )
+        .unwrap();
+
+        let manager = tree.manager();
+        let mut services = manager.iter_services().unwrap();
+        assert_eq!(services.size_hint(), (0, Some(3)));
+        let first = services.next().unwrap().unwrap();
+        assert_eq!(first.name, "acpid");
+        assert_eq!(services.size_hint(), (0, Some(2)));
+        let rest: Vec<_> = services.map(|info| info.unwrap()).collect();
+        assert_eq!(rest[0].name, "dhcpcd");
+        assert_eq!(rest[0].health, ServiceHealth::BrokenLink);
+        assert_eq!(rest[1].name, "sshd");
+
+        let listed = manager.list_services().unwrap();
+        let names: Vec<_> = listed.iter().map(|info| info.name.as_str()).collect();
+        assert_eq!(names, ["acpid", "dhcpcd", "sshd"]);
+    }
+
+    #[test]
+    fn lists_in_parallel_like_sequentially() {
+        let tree = FakeRunit::new("parallel");
+        for name in ["acpid", "cronie", "sshd", "udevd", "zramen"] {
+            tree.add_service(name, "#!/bin/sh\nexec sleep 1000\n");
+            tree.enable(name);
+            tree.set_down(name, SystemTime::now());
+        }
+        tree.set_running("sshd", 812, SystemTime::now());
+        std::os::unix::fs::symlink(
+            tree.definitions_dir().join("dhcpcd"),
+            tree.enabled_dir().join("dhcpcd"),
+        )
+        .unwrap();
+
+        let manager = tree.manager();
+        let names = |services: Vec<crate::ServiceInfo>| -> Vec<String> {
+            services.into_iter().map(|info| info.name).collect()
+        };
+        let sequential = names(manager.list_services().unwrap());
+        assert_eq!(sequential.len(), 6);
+        for jobs in [1, 2, 16] {
+            let jobs = NonZeroUsize::new(jobs).unwrap();
+            let parallel = manager
+                .list_services_parallel(&ServiceQuery::default(), jobs)
+                .unwrap();
+            assert_eq!(names(parallel), sequential);
+        }
+        let running = ServiceQuery {
+            states: vec![RuntimeStateKind::Running],
+            ..ServiceQuery::default()
+        };
+        let two = NonZeroUsize::new(2).unwrap();
+        assert_eq!(
+            names(manager.list_services_parallel(&running, two).unwrap()),
+            ["sshd"]
+        );
+    }
+
+    #[test]
+    fn lists_package_descriptions_from_the_cache() {
+        let tree = FakeRunit::new("described");
+        let sshd = tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        tree.add_service("cronie", "#!/bin/sh\nexec crond -n\n");
+        tree.write_file("cronie", "description", "Local cron notes");
+        let mut cache = DescriptionCache::new();
+        cache.insert("sshd", &sshd, Some("The OpenBSD Secure Shell".into()));
+        let manager = tree.manager().with_description_cache(cache);
+
+        let one = NonZeroUsize::new(1).unwrap();
+        for services in [
+            manager.list_services().unwrap(),
+            manager
+                .list_services_parallel(&ServiceQuery::default(), one)
+                .unwrap(),
+        ] {
+            let descriptions: Vec<_> = services
+                .iter()
+                .map(|info| (info.name.as_str(), info.description.as_deref()))
+                .collect();
+            assert_eq!(
+                descriptions,
+                [
+                    ("cronie", Some("Local cron notes")),
+                    ("sshd", Some("The OpenBSD Secure Shell")),
+                ]
+            );
+        }
+        assert_eq!(
+            manager.service_info("sshd").unwrap().description.as_deref(),
+            Some("The OpenBSD Secure Shell")
+        );
+    }
+
+    #[test]
+    fn reads_one_service_like_the_listing() {
+        let tree = FakeRunit::new("one");
+        tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        tree.enable("sshd");
+        tree.set_running("sshd", 812, SystemTime::now());
+        std::os::unix::fs::symlink(
+            tree.definitions_dir().join("dhcpcd"),
+            tree.enabled_dir().join("dhcpcd"),
+        )
+        .unwrap();
+
+        let manager = tree.manager();
+        let listed = manager.list_services().unwrap();
+        let sshd = manager.service_info("sshd").unwrap();
+        assert_eq!(sshd.runtime_state.kind(), listed[1].runtime_state.kind());
+        assert!(sshd.enabled);
+        let dhcpcd = manager.service_info("dhcpcd").unwrap();
+        assert_eq!(dhcpcd.health, ServiceHealth::BrokenLink);
+        assert!(matches!(
+            manager.service_info("acpid"),
+            Err(ServiceError::DefinitionMissing { .. })
+        ));
+    }
+}
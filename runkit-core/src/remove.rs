@@ -0,0 +1,139 @@
+//! Deleting service definitions, with the checks that keep it from breaking a package
+//! or a running service.
+use crate::describe::parse_owned_by;
+use crate::supervise::{SupervisePresence, read_supervise_status};
+use crate::{Result, ServiceError, ServiceManager};
+use std::path::Path;
+use std::process::Command;
+
+impl ServiceManager {
+    /// Delete a service definition, optionally archiving it first.
+    ///
+    /// Refuses with [`ServiceError::RemovalRefused`] while the service is enabled in
+    /// any runlevel, still supervised or running, or when a package owns its scripts;
+    /// the package manager would put it back or fail on the next update. With `archive`,
+    /// the definition is written to that gzip-compressed tarball before it is removed.
+    pub fn remove_service(&self, service: &str, archive: Option<&Path>) -> Result<()> {
+        self.validate_service_name(service)?;
+        let definition = self.definitions_dir.join(service);
+        if !definition.is_dir() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: definition,
+            });
+        }
+        let refuse = |reason: String| ServiceError::RemovalRefused {
+            service: service.to_string(),
+            reason,
+        };
+
+        if std::fs::symlink_metadata(self.enabled_dir.join(service)).is_ok() {
+            return Err(refuse("it is enabled; disable it first".to_string()));
+        }
+        let runlevels = self.service_runlevels(service).unwrap_or_default();
+        if !runlevels.is_empty() {
+            return Err(refuse(format!(
+                "it is linked into runlevel {}",
+                runlevels.join(", ")
+            )));
+        }
+        if SupervisePresence::read(&definition).runsv_running {
+            return Err(refuse("runsv is still supervising it".to_string()));
+        }
+        if let Ok(status) = read_supervise_status(&definition)
+            && status.pid.is_some()
+        {
+            return Err(refuse("it is still running; stop it first".to_string()));
+        }
+        if let Some(package) = owning_package(&definition) {
+            return Err(refuse(format!("it belongs to package {package}")));
+        }
+
+        if let Some(archive) = archive {
+            archive_definition(&self.definitions_dir, service, archive)?;
+        }
+        std::fs::remove_dir_all(&definition).map_err(|err| ServiceError::from_io(&definition, err))
+    }
+}
+
+/// The package that installed the definition's scripts, according to `xbps-query -o`.
+/// Systems without xbps have no owner to protect.
+fn owning_package(definition: &Path) -> Option<String> {
+    let scripts: Vec<_> = ["run", "finish", "check", "log/run"]
+        .into_iter()
+        .map(|script| definition.join(script))
+        .filter(|path| path.exists())
+        .collect();
+    if scripts.is_empty() {
+        return None;
+    }
+    let output = Command::new("xbps-query")
+        .arg("-o")
+        .args(&scripts)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let owners = parse_owned_by(&String::from_utf8_lossy(&output.stdout));
+    owners.into_values().next()
+}
+
+/// Write `service` from `definitions_dir` to a gzip tarball, leaving out the runtime
+/// state runsv keeps in `supervise/`.
+fn archive_definition(definitions_dir: &Path, service: &str, archive: &Path) -> Result<()> {
+    let output = Command::new("tar")
+        .arg("-czf")
+        .arg(archive)
+        .arg(format!("--exclude={service}/supervise"))
+        .arg(format!("--exclude={service}/log/supervise"))
+        .arg("-C")
+        .arg(definitions_dir)
+        .arg(service)
+        .output()
+        .map_err(|err| ServiceError::from_io("tar", err))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let _ = std::fs::remove_file(archive);
+    Err(ServiceError::SystemCommand {
+        command: "tar".to_string(),
+        message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ServiceError;
+    use crate::testutil::FakeRunit;
+    use std::time::SystemTime;
+
+    #[test]
+    fn removes_only_disabled_and_stopped_services() {
+        let tree = FakeRunit::new("remove");
+        tree.add_service("app", "#!/bin/sh\nexec sleep 1000\n");
+        tree.enable("app");
+        tree.set_running("app", 812, SystemTime::now());
+        let manager = tree.manager();
+
+        assert!(matches!(
+            manager.remove_service("app", None),
+            Err(ServiceError::RemovalRefused { .. })
+        ));
+        std::fs::remove_file(tree.enabled_dir().join("app")).unwrap();
+        assert!(matches!(
+            manager.remove_service("app", None),
+            Err(ServiceError::RemovalRefused { reason, .. }) if reason.contains("running")
+        ));
+
+        tree.set_down("app", SystemTime::now());
+        let archive = tree.root().join("app.tar.gz");
+        manager.remove_service("app", Some(&archive)).unwrap();
+        assert!(!tree.definitions_dir().join("app").exists());
+        assert!(archive.is_file());
+        assert!(matches!(
+            manager.remove_service("app", None),
+            Err(ServiceError::DefinitionMissing { .. })
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+//! runsvdir runlevels: one directory of service links per level under `/etc/runit/runsvdir`.
+use crate::{Result, ServiceError, ServiceManager, validate_service_name};
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+
+impl ServiceManager {
+    /// Use an explicit runlevel root instead of deriving it from the enabled directory.
+    pub fn with_runlevels_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.runlevels_dir = Some(dir.into());
+        self
+    }
+
+    /// Directory holding one subdirectory per runlevel.
+    ///
+    /// When not configured explicitly it is derived from the enabled directory: on Void
+    /// `/var/service` is a symlink into `/etc/runit/runsvdir/<level>`, so the parent of its
+    /// resolved target is the runlevel root. A plain enabled directory has no runlevels.
+    pub fn runlevels_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.runlevels_dir {
+            return Some(dir.clone());
+        }
+
+        let meta = std::fs::symlink_metadata(&self.enabled_dir).ok()?;
+        if !meta.file_type().is_symlink() {
+            return None;
+        }
+        let resolved = std::fs::canonicalize(&self.enabled_dir).ok()?;
+        resolved.parent().map(Path::to_path_buf)
+    }
+
+    /// Names of all runlevels, sorted. Symlinked levels such as `current` are skipped.
+    pub fn runlevels(&self) -> Result<Vec<String>> {
+        let Some(root) = self.runlevels_dir() else {
+            return Ok(Vec::new());
+        };
+
+        let read_dir = std::fs::read_dir(&root).map_err(|e| ServiceError::from_io(&root, e))?;
+        let mut levels = Vec::new();
+        for entry in read_dir {
+            let entry = entry.map_err(|e| ServiceError::from_io(&root, e))?;
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if let (true, Some(name)) = (is_dir, entry.file_name().to_str()) {
+                levels.push(name.to_string());
+            }
+        }
+        levels.sort();
+        Ok(levels)
+    }
+
+    /// Runlevels in which the service is linked.
+    pub fn service_runlevels(&self, service: &str) -> Result<Vec<String>> {
+        self.validate_service_name(service)?;
+        let levels = self.runlevels()?;
+        Ok(match self.runlevels_dir() {
+            Some(root) => runlevels_containing(&root, &levels, service),
+            None => Vec::new(),
+        })
+    }
+
+    /// Link the service into a specific runlevel.
+    pub fn enable_in(&self, service: &str, runlevel: &str) -> Result<()> {
+        self.validate_runnable_name(service)?;
+        let level_dir = self.runlevel_path(runlevel)?;
+        self.ensure_not_masked(service)?;
+
+        let src = self.definitions_dir.join(service);
+        if !src.exists() {
+            return Err(ServiceError::DefinitionMissing {
+                service: service.to_string(),
+                path: src,
+            });
+        }
+
+        let dest = level_dir.join(service);
+        if std::fs::symlink_metadata(&dest).is_ok() {
+            return Err(ServiceError::AlreadyEnabled(service.to_string()));
+        }
+
+        unix_fs::symlink(&src, &dest).map_err(|err| ServiceError::from_io(&dest, err))
+    }
+
+    /// Remove the service link from a specific runlevel.
+    pub fn disable_in(&self, service: &str, runlevel: &str) -> Result<()> {
+        self.validate_service_name(service)?;
+        let dest = self.runlevel_path(runlevel)?.join(service);
+        if std::fs::symlink_metadata(&dest).is_err() {
+            return Err(ServiceError::NotEnabled(service.to_string()));
+        }
+
+        std::fs::remove_file(&dest).map_err(|err| ServiceError::from_io(&dest, err))
+    }
+
+    /// Directory of a runlevel [`ServiceManager::runlevels`] lists; anything else, `..`
+    /// included, is an unknown runlevel.
+    pub(crate) fn runlevel_path(&self, runlevel: &str) -> Result<PathBuf> {
+        let unknown = || ServiceError::UnknownRunlevel(runlevel.to_string());
+        validate_service_name(runlevel).map_err(|_| unknown())?;
+        let root = self.runlevels_dir().ok_or_else(unknown)?;
+        if !self.runlevels()?.iter().any(|level| level == runlevel) {
+            return Err(unknown());
+        }
+        Ok(root.join(runlevel))
+    }
+}
+
+pub(crate) fn runlevels_containing(root: &Path, levels: &[String], service: &str) -> Vec<String> {
+    levels
+        .iter()
+        .filter(|level| std::fs::symlink_metadata(root.join(level).join(service)).is_ok())
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ServiceError, ServiceManager};
+
+    #[test]
+    fn links_service_into_named_runlevel() {
+        let root = std::env::temp_dir().join(format!("runkit-runlevel-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd")).unwrap();
+        std::fs::create_dir_all(root.join("runsvdir/default")).unwrap();
+        std::fs::create_dir_all(root.join("runsvdir/single")).unwrap();
+        std::os::unix::fs::symlink("default", root.join("runsvdir/current")).unwrap();
+
+        let manager = ServiceManager::new(root.join("sv"), root.join("runsvdir/default"))
+            .with_runlevels_dir(root.join("runsvdir"));
+        assert_eq!(manager.runlevels().unwrap(), ["default", "single"]);
+
+        manager.enable_in("sshd", "single").unwrap();
+        assert_eq!(manager.service_runlevels("sshd").unwrap(), ["single"]);
+        assert!(matches!(
+            manager.enable_in("sshd", "rescue"),
+            Err(ServiceError::UnknownRunlevel(level)) if level == "rescue"
+        ));
+
+        for outside in [".", "..", "current"] {
+            assert!(matches!(
+                manager.enable_in("sshd", outside),
+                Err(ServiceError::UnknownRunlevel(level)) if level == outside
+            ));
+        }
+        assert!(!root.join("sshd").exists());
+
+        manager.disable_in("sshd", "single").unwrap();
+        assert!(manager.service_runlevels("sshd").unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,288 @@
+//! Writing new service definitions from a structured description.
+use crate::{EnvDir, Result, ServiceError, ServiceManager};
+use std::fmt::Write as _;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What the generated `run` (and optional `log/run`) script should do.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunScriptSpec {
+    command: String,
+    user: Option<String>,
+    working_dir: Option<PathBuf>,
+    redirect_stderr: bool,
+    logger: bool,
+    env: Vec<(String, String)>,
+    finish: Option<String>,
+}
+
+impl RunScriptSpec {
+    /// `command` is the foreground command line, exec'd from the script.
+    pub fn new(command: impl Into<String>) -> Self {
+        RunScriptSpec {
+            command: command.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Drop privileges with `chpst -u` before running the command.
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Change into this directory before running the command.
+    pub fn with_working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Send stderr to the logger along with stdout.
+    pub fn with_stderr_redirect(mut self, redirect: bool) -> Self {
+        self.redirect_stderr = redirect;
+        self
+    }
+
+    /// Add a `log/run` script that hands output to svlogd in `log/main`.
+    pub fn with_logger(mut self, logger: bool) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Set a variable through an `env/` directory read by `chpst -e`.
+    pub fn with_env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add a `finish` script, which runsv runs after the command exits with its exit
+    /// code (`-1` when killed) and signal as arguments, before restarting it.
+    pub fn with_finish(mut self, script: impl Into<String>) -> Self {
+        self.finish = Some(script.into());
+        self
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn working_dir(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    pub fn finish(&self) -> Option<&str> {
+        self.finish.as_deref()
+    }
+
+    pub fn has_logger(&self) -> bool {
+        self.logger
+    }
+
+    /// Contents of the `run` script.
+    pub fn render_run(&self) -> String {
+        let mut script = String::from("#!/bin/sh\n");
+        if self.redirect_stderr {
+            script.push_str("exec 2>&1\n");
+        }
+        // runsv starts the script inside the definition, so `env` is relative to it until
+        // the script changes directory.
+        let env_dir = match (self.env.is_empty(), &self.working_dir) {
+            (true, _) => None,
+            (false, None) => Some("env"),
+            (false, Some(_)) => {
+                script.push_str("env_dir=\"$PWD/env\"\n");
+                Some("\"$env_dir\"")
+            }
+        };
+        if let Some(dir) = &self.working_dir {
+            let _ = writeln!(
+                script,
+                "cd {} || exit 1",
+                shell_quote(&dir.to_string_lossy())
+            );
+        }
+        script.push_str("exec ");
+        if env_dir.is_some() || self.user.is_some() {
+            script.push_str("chpst ");
+        }
+        if let Some(env_dir) = env_dir {
+            let _ = write!(script, "-e {env_dir} ");
+        }
+        if let Some(user) = &self.user {
+            let _ = write!(script, "-u {} ", shell_quote(user));
+        }
+        script.push_str(self.command.trim());
+        script.push('\n');
+        script
+    }
+
+    /// Contents of the `log/run` script; `tail_logs` reads the `main/current` it produces.
+    pub fn render_log_run(&self) -> String {
+        "#!/bin/sh\n[ -d main ] || mkdir -p main\nexec svlogd -tt main\n".to_string()
+    }
+}
+
+impl ServiceManager {
+    /// Write a new definition under the definitions directory.
+    ///
+    /// The files are staged in a hidden directory, syntax-checked with `sh -n`, and only
+    /// then renamed into place, so a failed call never leaves a half-written service behind.
+    /// The service is not enabled.
+    pub fn create_service(&self, service: &str, spec: &RunScriptSpec) -> Result<PathBuf> {
+        self.validate_service_name(service)?;
+        if spec.command.trim().is_empty() {
+            return Err(ServiceError::InvalidRunScript {
+                service: service.to_string(),
+                message: "command is empty".to_string(),
+            });
+        }
+
+        let target = self.definitions_dir.join(service);
+        if std::fs::symlink_metadata(&target).is_ok() {
+            return Err(ServiceError::DefinitionExists {
+                service: service.to_string(),
+                path: target,
+            });
+        }
+
+        let staging = self.definitions_dir.join(format!(".{service}.new"));
+        let _ = std::fs::remove_dir_all(&staging);
+        let result = write_definition(&staging, service, spec).and_then(|()| {
+            std::fs::rename(&staging, &target).map_err(|err| ServiceError::from_io(&target, err))
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_dir_all(&staging);
+        }
+        result.map(|()| target)
+    }
+}
+
+fn write_definition(dir: &Path, service: &str, spec: &RunScriptSpec) -> Result<()> {
+    std::fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+    // runsvdir and the tools reading the definition may not run as the creator.
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755))
+        .map_err(|err| ServiceError::from_io(dir, err))?;
+    write_script(&dir.join("run"), service, &spec.render_run())?;
+    if let Some(finish) = &spec.finish {
+        write_script(&dir.join("finish"), service, finish)?;
+    }
+
+    let env = EnvDir::new(dir.join("env"));
+    for (name, value) in &spec.env {
+        env.set(name, value)?;
+    }
+
+    if spec.logger {
+        let log_dir = dir.join("log");
+        std::fs::create_dir(&log_dir).map_err(|err| ServiceError::from_io(&log_dir, err))?;
+        write_script(&log_dir.join("run"), service, &spec.render_log_run())?;
+    }
+    Ok(())
+}
+
+fn write_script(path: &Path, service: &str, contents: &str) -> Result<()> {
+    std::fs::write(path, contents).map_err(|err| ServiceError::from_io(path, err))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .map_err(|err| ServiceError::from_io(path, err))?;
+    check_syntax(path, service)
+}
+
+pub(crate) fn check_syntax(path: &Path, service: &str) -> Result<()> {
+    let output = Command::new("sh")
+        .arg("-n")
+        .arg(path)
+        .output()
+        .map_err(|err| ServiceError::from_io("sh", err))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(ServiceError::InvalidRunScript {
+        service: service.to_string(),
+        message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+fn shell_quote(value: &str) -> String {
+    let safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@".contains(c));
+    if safe {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RunScriptSpec;
+    use crate::{ServiceError, ServiceManager};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn renders_run_script() {
+        let spec = RunScriptSpec::new("/usr/bin/foo -f")
+            .with_user("_foo")
+            .with_working_dir("/var/lib/foo dir")
+            .with_stderr_redirect(true);
+        assert_eq!(
+            spec.render_run(),
+            "#!/bin/sh\nexec 2>&1\ncd '/var/lib/foo dir' || exit 1\nexec chpst -u _foo /usr/bin/foo -f\n"
+        );
+
+        let spec = RunScriptSpec::new("/usr/bin/foo").with_env("FOO_OPTS", "-v");
+        assert_eq!(
+            spec.render_run(),
+            "#!/bin/sh\nexec chpst -e env /usr/bin/foo\n"
+        );
+        let spec = spec.with_working_dir("/srv").with_user("_foo");
+        assert_eq!(
+            spec.render_run(),
+            "#!/bin/sh\nenv_dir=\"$PWD/env\"\ncd /srv || exit 1\nexec chpst -e \"$env_dir\" -u _foo /usr/bin/foo\n"
+        );
+    }
+
+    #[test]
+    fn creates_executable_definition() {
+        let root = std::env::temp_dir().join(format!("runkit-scaffold-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        let spec = RunScriptSpec::new("sleep 1000")
+            .with_logger(true)
+            .with_env("GREETING", "hello");
+        let path = manager.create_service("sleeper", &spec).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path.join("env/GREETING")).unwrap(),
+            "hello\n"
+        );
+        let mode = std::fs::metadata(path.join("run"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(path.join("log/run").is_file());
+
+        assert!(matches!(
+            manager.create_service("sleeper", &spec),
+            Err(ServiceError::DefinitionExists { .. })
+        ));
+        assert!(matches!(
+            manager.create_service("broken", &RunScriptSpec::new("echo \"unterminated")),
+            Err(ServiceError::InvalidRunScript { .. })
+        ));
+        assert!(!root.join("sv/broken").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,69 @@
+//! System-wide versus per-user service trees.
+use crate::{DEFAULT_SERVICE_DIR, Result, ServiceError, ServiceManager, service_dir_from_env};
+use std::path::PathBuf;
+
+/// Which runsvdir a [`ServiceManager`] operates on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ServiceScope {
+    /// `/etc/sv` and `/var/service` (or `$SVDIR`); changes need root.
+    #[default]
+    System,
+    /// A runsvdir owned by the invoking user: definitions in `~/.config/runit/sv`
+    /// and supervised links in `~/.local/run/service`.
+    User,
+}
+
+impl ServiceScope {
+    /// Definitions and enabled directories for this scope.
+    pub fn dirs(self) -> Result<(PathBuf, PathBuf)> {
+        match self {
+            ServiceScope::System => {
+                Ok((PathBuf::from(DEFAULT_SERVICE_DIR), service_dir_from_env()))
+            }
+            ServiceScope::User => {
+                let home = std::env::var_os("HOME")
+                    .filter(|home| !home.is_empty())
+                    .map(PathBuf::from)
+                    .ok_or_else(|| {
+                        ServiceError::Other("HOME is not set; cannot locate user services".into())
+                    })?;
+                let config = std::env::var_os("XDG_CONFIG_HOME")
+                    .filter(|dir| !dir.is_empty())
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| home.join(".config"));
+                Ok((config.join("runit/sv"), home.join(".local/run/service")))
+            }
+        }
+    }
+}
+
+impl ServiceManager {
+    /// Manager for the system tree or the invoking user's own runsvdir.
+    pub fn for_scope(scope: ServiceScope) -> Result<Self> {
+        let (definitions_dir, enabled_dir) = scope.dirs()?;
+        let mut manager = Self::new(definitions_dir, enabled_dir);
+        manager.scope = scope;
+        if scope == ServiceScope::User {
+            // socklog only collects the system's syslog.
+            manager.socklog_dir = None;
+        }
+        Ok(manager)
+    }
+
+    pub fn scope(&self) -> ServiceScope {
+        self.scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceScope;
+    use crate::ServiceManager;
+
+    #[test]
+    fn defaults_to_system_scope() {
+        assert_eq!(ServiceManager::default().scope(), ServiceScope::System);
+        let (definitions, _) = ServiceScope::System.dirs().unwrap();
+        assert_eq!(definitions, std::path::Path::new("/etc/sv"));
+    }
+}
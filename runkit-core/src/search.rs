@@ -0,0 +1,196 @@
+//! Streaming search over a service's current and rotated log files.
+use crate::logs::open_log_reader;
+use crate::{
+    LogFileKind, LogSeverity, Result, ServiceError, ServiceLogEntry, ServiceManager,
+    parse_svlogd_line,
+};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::io::{BufRead, ErrorKind};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Filters for [`ServiceManager::search_logs`]. The default matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+    /// Only entries whose message matches.
+    pub regex: Option<Regex>,
+    /// Keep the entries `regex` does not match instead.
+    pub invert: bool,
+    /// Only entries logged at or after this time.
+    pub since: Option<SystemTime>,
+    /// Only entries logged at or before this time.
+    pub until: Option<SystemTime>,
+    /// Only entries at least this severe.
+    pub min_severity: Option<LogSeverity>,
+    /// Keep at most this many of the newest matches.
+    pub limit: Option<usize>,
+    /// Read only the newest this many rotated files besides `current`; all by default.
+    pub rotations: Option<usize>,
+}
+
+impl LogQuery {
+    /// Entries without a timestamp cannot be placed in time and always pass the bounds.
+    pub fn matches(&self, entry: &ServiceLogEntry) -> bool {
+        if let Some(at) = entry.timestamp() {
+            if self.since.is_some_and(|since| at < since) {
+                return false;
+            }
+            if self.until.is_some_and(|until| at > until) {
+                return false;
+            }
+        }
+        if self.min_severity.is_some_and(|min| entry.severity < min) {
+            return false;
+        }
+        self.regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(&entry.message) != self.invert)
+    }
+}
+
+impl ServiceManager {
+    /// Matching entries from every log file, oldest first.
+    ///
+    /// Files are read line by line, and rotated files whose rotation stamp shows they
+    /// end before `since` (or that start after `until`) are skipped unopened.
+    pub fn search_logs(&self, service: &str, query: &LogQuery) -> Result<Vec<ServiceLogEntry>> {
+        let mut files = self.log_files(service)?;
+        if let Some(rotations) = query.rotations {
+            // Rotated files sort before `current`, oldest first.
+            let rotated = files
+                .iter()
+                .filter(|file| file.kind != LogFileKind::Current)
+                .count();
+            files.drain(..rotated.saturating_sub(rotations));
+        }
+        let limit = query.limit.unwrap_or(usize::MAX);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut matches = VecDeque::new();
+
+        let mut previous_rotation: Option<SystemTime> = None;
+        for file in &files {
+            let rotated_at = file
+                .rotated_unix
+                .map(|unix| to_system_time(unix, file.rotated_nanos.unwrap_or(0)));
+            let starts_after_until = previous_rotation
+                .zip(query.until)
+                .is_some_and(|(start, until)| start > until);
+            if starts_after_until {
+                break;
+            }
+            previous_rotation = rotated_at;
+            let ends_before_since = rotated_at
+                .zip(query.since)
+                .is_some_and(|(end, since)| end < since);
+            if ends_before_since {
+                continue;
+            }
+
+            let reader = match open_log_reader(&file.path) {
+                Ok(reader) => reader,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(ServiceError::from_io(&file.path, err)),
+            };
+            for line in reader.split(b'\n') {
+                let line = line.map_err(|err| ServiceError::from_io(&file.path, err))?;
+                let mut entry = parse_svlogd_line(&line);
+                self.classify_entries(std::slice::from_mut(&mut entry));
+                if query.matches(&entry) {
+                    if matches.len() == limit {
+                        matches.pop_front();
+                    }
+                    matches.push_back(entry);
+                }
+            }
+        }
+
+        Ok(matches.into_iter().collect())
+    }
+}
+
+fn to_system_time(unix: i64, nanos: u32) -> SystemTime {
+    UNIX_EPOCH + Duration::new(unix.max(0) as u64, nanos.min(999_999_999))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogQuery;
+    use crate::{LogSeverity, ServiceManager};
+    use regex::Regex;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn filters_by_pattern_and_time() {
+        let root = std::env::temp_dir().join(format!("runkit-search-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let log_dir = root.join("sv/app/log/main");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        std::fs::write(
+            log_dir.join("@400000006500000000000000.s"),
+            "@400000006400000000000000 error: early\n",
+        )
+        .unwrap();
+        std::fs::write(
+            log_dir.join("current"),
+            "@400000006580000000000000 error: middle\n@400000006580000100000000 ok\n@400000006700000000000000 error: late\n",
+        )
+        .unwrap();
+
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        // Labels are `2^62 + 10 + unix`, so each stamp sits ten seconds before its hex value.
+        let query = LogQuery {
+            regex: Some(Regex::new("^error").unwrap()),
+            invert: false,
+            since: Some(UNIX_EPOCH + Duration::from_secs(0x6500_0001)),
+            until: Some(UNIX_EPOCH + Duration::from_secs(0x6600_0000)),
+            min_severity: None,
+            limit: None,
+            rotations: None,
+        };
+        let messages: Vec<_> = manager
+            .search_logs("app", &query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, ["error: middle"]);
+
+        let newest = LogQuery {
+            limit: Some(1),
+            ..LogQuery::default()
+        };
+        let entries = manager.search_logs("app", &newest).unwrap();
+        assert_eq!(entries[0].message, "error: late");
+
+        let current_only = LogQuery {
+            rotations: Some(0),
+            ..LogQuery::default()
+        };
+        assert_eq!(manager.search_logs("app", &current_only).unwrap().len(), 3);
+        assert_eq!(
+            manager
+                .search_logs("app", &LogQuery::default())
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let errors = LogQuery {
+            min_severity: Some(LogSeverity::Error),
+            ..LogQuery::default()
+        };
+        assert_eq!(manager.search_logs("app", &errors).unwrap().len(), 3);
+
+        let quiet = LogQuery {
+            regex: Some(Regex::new("^error").unwrap()),
+            invert: true,
+            ..LogQuery::default()
+        };
+        let entries = manager.search_logs("app", &quiet).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "ok");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
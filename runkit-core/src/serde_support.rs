@@ -0,0 +1,198 @@
+//! Wire-format helpers for the `serde` feature.
+use crate::{LogSeverity, ServiceLogEntry};
+use serde::Deserialize;
+
+/// `Duration` as whole seconds; sub-second precision is dropped.
+pub(crate) mod seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+/// `Option<Duration>` as whole seconds or `null`.
+pub(crate) mod option_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(duration) => serializer.serialize_some(&duration.as_secs()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|secs| secs.map(Duration::from_secs))
+    }
+}
+
+/// `SystemTime` as whole seconds since the Unix epoch.
+pub(crate) mod unix_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub fn serialize<S: Serializer>(value: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        let secs = value
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        serializer.serialize_u64(secs)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        u64::deserialize(deserializer).map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    }
+}
+
+/// `Option<SystemTime>` as whole seconds since the Unix epoch or `null`.
+pub(crate) mod option_unix_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(time) => super::unix_seconds::serialize(time, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        Option::<u64>::deserialize(deserializer)
+            .map(|secs| secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+}
+
+/// Placeholder for timestamps missing from older runkitd output.
+pub(crate) fn unix_epoch() -> std::time::SystemTime {
+    std::time::UNIX_EPOCH
+}
+
+/// `Duration` as whole milliseconds.
+pub(crate) mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::try_from(value.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+/// Paths as strings, replacing invalid UTF-8 instead of failing.
+pub(crate) mod lossy_path {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::{Path, PathBuf};
+
+    pub fn serialize<S: Serializer>(value: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string_lossy())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        String::deserialize(deserializer).map(PathBuf::from)
+    }
+}
+
+/// `Option<PathBuf>` as a lossy string or `null`.
+pub(crate) mod option_lossy_path {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::PathBuf;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(path) => super::lossy_path::serialize(path, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        Option::<String>::deserialize(deserializer).map(|path| path.map(PathBuf::from))
+    }
+}
+
+/// Serialized shape of [`ServiceLogEntry`].
+#[derive(Deserialize)]
+pub(crate) struct LogEntryWire {
+    unix_seconds: Option<i64>,
+    nanos: Option<u32>,
+    raw: Option<String>,
+    message: String,
+    #[serde(default)]
+    severity: LogSeverity,
+}
+
+impl From<LogEntryWire> for ServiceLogEntry {
+    fn from(wire: LogEntryWire) -> Self {
+        ServiceLogEntry {
+            timestamp_unix: wire.unix_seconds,
+            timestamp_nanos: wire.nanos,
+            timestamp_raw: wire.raw,
+            message_bytes: wire.message.clone().into_bytes(),
+            message: wire.message,
+            severity: wire.severity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LogSeverity, ServiceLogEntry, ServiceRuntimeState};
+    use serde_json::json;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn keeps_runkitd_wire_format() {
+        let state = ServiceRuntimeState::Down {
+            since: Duration::from_millis(4_900),
+            normally_up: true,
+            started_at: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            json!({
+                "state": "down",
+                "since_seconds": 4,
+                "normally_up": true,
+                "started_at_unix": 1_700_000_000,
+            })
+        );
+
+        let entry: ServiceLogEntry = serde_json::from_value(json!({
+            "unix_seconds": 1_700_000_000,
+            "nanos": 5,
+            "raw": null,
+            "message": "listening",
+            "severity": "warning",
+        }))
+        .unwrap();
+        assert_eq!(entry.message_bytes, b"listening");
+        assert_eq!(entry.severity, LogSeverity::Warning);
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(value["unix_seconds"], 1_700_000_000);
+        assert!(value.get("message_bytes").is_none());
+    }
+}
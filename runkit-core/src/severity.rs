@@ -0,0 +1,126 @@
+//! Keyword-based severity classification for log lines.
+use crate::{Result, ServiceError, ServiceLogEntry, ServiceManager};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::sync::Arc;
+
+/// Severity derived from a log message. Ordered from least to most severe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum LogSeverity {
+    Debug,
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+/// Ordered pattern list; the first rule whose pattern matches decides the severity.
+#[derive(Debug, Clone)]
+pub struct SeverityRules {
+    rules: Vec<(Regex, LogSeverity)>,
+    fallback: LogSeverity,
+}
+
+static DEFAULT_RULES: Lazy<SeverityRules> = Lazy::new(|| SeverityRules {
+    rules: vec![
+        (
+            Regex::new(r"(?i)\b(error|err|fatal|crit(ical)?|panic|emerg|alert|fail(ed|ure)?)\b")
+                .unwrap(),
+            LogSeverity::Error,
+        ),
+        (
+            Regex::new(r"(?i)\bwarn(ing)?\b").unwrap(),
+            LogSeverity::Warning,
+        ),
+        (
+            Regex::new(r"(?i)\b(debug|trace)\b").unwrap(),
+            LogSeverity::Debug,
+        ),
+    ],
+    fallback: LogSeverity::Info,
+});
+
+impl Default for SeverityRules {
+    /// Common syslog-style keywords: error/fatal/fail, warn, debug/trace; info otherwise.
+    fn default() -> Self {
+        DEFAULT_RULES.clone()
+    }
+}
+
+impl SeverityRules {
+    /// An empty rule set that classifies everything as `fallback`.
+    pub fn new(fallback: LogSeverity) -> Self {
+        SeverityRules {
+            rules: Vec::new(),
+            fallback,
+        }
+    }
+
+    /// Append a rule; it is consulted after the rules added before it.
+    pub fn with_rule(mut self, pattern: &str, severity: LogSeverity) -> Result<Self> {
+        let regex = Regex::new(pattern).map_err(|err| ServiceError::Other(Box::new(err)))?;
+        self.rules.push((regex, severity));
+        Ok(self)
+    }
+
+    pub fn classify(&self, message: &str) -> LogSeverity {
+        self.rules
+            .iter()
+            .find(|(regex, _)| regex.is_match(message))
+            .map_or(self.fallback, |(_, severity)| *severity)
+    }
+}
+
+/// Classification applied while parsing, before any custom rules.
+pub(crate) fn default_severity(message: &str) -> LogSeverity {
+    DEFAULT_RULES.classify(message)
+}
+
+impl ServiceManager {
+    /// Classify log entries with custom rules instead of the built-in keywords.
+    pub fn with_severity_rules(mut self, rules: SeverityRules) -> Self {
+        self.severity_rules = Some(Arc::new(rules));
+        self
+    }
+
+    /// Re-derive `severity` when custom rules are configured.
+    pub(crate) fn classify_entries(&self, entries: &mut [ServiceLogEntry]) {
+        if let Some(rules) = &self.severity_rules {
+            for entry in entries {
+                entry.severity = rules.classify(&entry.message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogSeverity, SeverityRules};
+
+    #[test]
+    fn classifies_by_keyword() {
+        let rules = SeverityRules::default();
+        assert_eq!(
+            rules.classify("connection failed: timeout"),
+            LogSeverity::Error
+        );
+        assert_eq!(
+            rules.classify("WARNING: disk almost full"),
+            LogSeverity::Warning
+        );
+        assert_eq!(rules.classify("debug: polling"), LogSeverity::Debug);
+        assert_eq!(rules.classify("listening on :22"), LogSeverity::Info);
+        assert_eq!(rules.classify("no errors found"), LogSeverity::Info);
+
+        let custom = SeverityRules::new(LogSeverity::Debug)
+            .with_rule("^E ", LogSeverity::Error)
+            .unwrap();
+        assert_eq!(custom.classify("E boom"), LogSeverity::Error);
+        assert_eq!(custom.classify("anything"), LogSeverity::Debug);
+    }
+}
@@ -0,0 +1,245 @@
+//! Exporting the set of enabled services and re-creating it elsewhere.
+use crate::{Result, ServiceError, ServiceManager};
+
+/// One enabled service and the runlevels that link it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnabledService {
+    pub name: String,
+    /// Empty when the manager has no runlevel root; the service is then linked straight
+    /// into the enabled directory.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub runlevels: Vec<String>,
+}
+
+/// Enabled services of one machine, sorted by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnabledSnapshot {
+    pub services: Vec<EnabledService>,
+}
+
+/// Whether [`ServiceManager::apply_enabled`] only reports what it would do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DryRun(pub bool);
+
+/// Link that [`ServiceManager::apply_enabled`] created or would create.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotLink {
+    pub service: String,
+    pub runlevel: Option<String>,
+}
+
+/// Outcome of [`ServiceManager::apply_enabled`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApplyReport {
+    pub linked: Vec<SnapshotLink>,
+    pub unchanged: Vec<SnapshotLink>,
+    /// Services in the snapshot without a definition on this machine.
+    pub missing: Vec<String>,
+    /// Services in the snapshot that are masked here and were left disabled.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub masked: Vec<String>,
+}
+
+impl ServiceManager {
+    /// Record which services are enabled, per runlevel when runlevels are in use.
+    pub fn export_enabled(&self) -> Result<EnabledSnapshot> {
+        let mut services = Vec::new();
+        for name in self.backend.definition_names()? {
+            if self.validate_service_name(&name).is_err() {
+                continue;
+            }
+            let runlevels = self.service_runlevels(&name)?;
+            let enabled = if self.runlevels_dir().is_some() {
+                !runlevels.is_empty()
+            } else {
+                self.backend.is_enabled(&name)
+            };
+            if enabled {
+                services.push(EnabledService { name, runlevels });
+            }
+        }
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(EnabledSnapshot { services })
+    }
+
+    /// Create the links recorded in `snapshot` that do not exist yet.
+    ///
+    /// Services enabled here but absent from the snapshot are left alone, so applying a
+    /// snapshot never stops anything. An unknown runlevel fails the whole call before any
+    /// link is created.
+    pub fn apply_enabled(
+        &self,
+        snapshot: &EnabledSnapshot,
+        dry_run: DryRun,
+    ) -> Result<ApplyReport> {
+        let known_levels = self.runlevels()?;
+        for entry in &snapshot.services {
+            self.validate_service_name(&entry.name)?;
+            if let Some(level) = entry
+                .runlevels
+                .iter()
+                .find(|level| !known_levels.contains(level))
+            {
+                return Err(ServiceError::UnknownRunlevel(level.clone()));
+            }
+        }
+
+        let mut report = ApplyReport::default();
+        for entry in &snapshot.services {
+            if !self.definitions_dir.join(&entry.name).is_dir() {
+                report.missing.push(entry.name.clone());
+                continue;
+            }
+            if self.is_masked(&entry.name) {
+                report.masked.push(entry.name.clone());
+                continue;
+            }
+
+            if entry.runlevels.is_empty() {
+                let link = SnapshotLink {
+                    service: entry.name.clone(),
+                    runlevel: None,
+                };
+                if self.backend.is_enabled(&entry.name) {
+                    report.unchanged.push(link);
+                } else {
+                    if !dry_run.0 {
+                        self.enable(&entry.name)?;
+                    }
+                    report.linked.push(link);
+                }
+                continue;
+            }
+
+            let current = self.service_runlevels(&entry.name)?;
+            for level in &entry.runlevels {
+                let link = SnapshotLink {
+                    service: entry.name.clone(),
+                    runlevel: Some(level.clone()),
+                };
+                if current.contains(level) {
+                    report.unchanged.push(link);
+                } else {
+                    if !dry_run.0 {
+                        self.enable_in(&entry.name, level)?;
+                    }
+                    report.linked.push(link);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Remove the links of services this machine enables but `snapshot` does not, so a
+    /// following [`ServiceManager::apply_enabled`] reproduces the snapshot exactly.
+    ///
+    /// Returns the links removed, or that would be. Running services are not stopped;
+    /// runsvdir stops supervising them on its next scan.
+    pub fn prune_enabled(
+        &self,
+        snapshot: &EnabledSnapshot,
+        dry_run: DryRun,
+    ) -> Result<Vec<SnapshotLink>> {
+        let mut removed = Vec::new();
+        for current in self.export_enabled()?.services {
+            let wanted = snapshot
+                .services
+                .iter()
+                .find(|entry| entry.name == current.name);
+            if current.runlevels.is_empty() {
+                if wanted.is_none() {
+                    if !dry_run.0 {
+                        self.disable(&current.name)?;
+                    }
+                    removed.push(SnapshotLink {
+                        service: current.name,
+                        runlevel: None,
+                    });
+                }
+                continue;
+            }
+            for level in &current.runlevels {
+                if wanted.is_some_and(|entry| entry.runlevels.contains(level)) {
+                    continue;
+                }
+                if !dry_run.0 {
+                    self.disable_in(&current.name, level)?;
+                }
+                removed.push(SnapshotLink {
+                    service: current.name.clone(),
+                    runlevel: Some(level.clone()),
+                });
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DryRun, EnabledService, EnabledSnapshot};
+    use crate::ServiceManager;
+
+    #[test]
+    fn round_trips_enabled_set_between_trees() {
+        let root = std::env::temp_dir().join(format!("runkit-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in [
+            "a/sv/sshd",
+            "a/sv/cron",
+            "a/service",
+            "b/sv/sshd",
+            "b/service",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let source = ServiceManager::new(root.join("a/sv"), root.join("a/service"));
+        source.enable("sshd").unwrap();
+        source.enable("cron").unwrap();
+
+        let snapshot = source.export_enabled().unwrap();
+        assert_eq!(
+            snapshot,
+            EnabledSnapshot {
+                services: vec![
+                    EnabledService {
+                        name: "cron".into(),
+                        runlevels: Vec::new()
+                    },
+                    EnabledService {
+                        name: "sshd".into(),
+                        runlevels: Vec::new()
+                    },
+                ]
+            }
+        );
+
+        let target = ServiceManager::new(root.join("b/sv"), root.join("b/service"));
+        let plan = target.apply_enabled(&snapshot, DryRun(true)).unwrap();
+        assert_eq!(plan.linked.len(), 1);
+        assert_eq!(plan.missing, ["cron"]);
+        assert!(!target.backend().is_enabled("sshd"));
+
+        target.apply_enabled(&snapshot, DryRun(false)).unwrap();
+        assert!(target.backend().is_enabled("sshd"));
+        let again = target.apply_enabled(&snapshot, DryRun(false)).unwrap();
+        assert!(again.linked.is_empty());
+        assert_eq!(again.unchanged.len(), 1);
+
+        // Pruning against an empty snapshot unlinks what the source enabled.
+        let empty = EnabledSnapshot::default();
+        let pruned = source.prune_enabled(&empty, DryRun(true)).unwrap();
+        assert_eq!(pruned.len(), 2);
+        assert!(source.backend().is_enabled("sshd"));
+        source.prune_enabled(&snapshot, DryRun(false)).unwrap();
+        assert!(source.backend().is_enabled("cron"));
+        source.prune_enabled(&empty, DryRun(false)).unwrap();
+        assert!(!source.backend().is_enabled("cron"));
+        assert!(!source.backend().is_enabled("sshd"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
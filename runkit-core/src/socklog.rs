@@ -0,0 +1,282 @@
+//! Entries that services sent to syslog, as collected by socklog into svlogd directories.
+use crate::{Result, ServiceError, ServiceLogEntry, ServiceManager, read_svlogd_tail};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Where Void's `socklog-unix` and `socklog-klog` services keep one directory per facility.
+pub const SOCKLOG_DIR: &str = "/var/log/socklog";
+
+/// Lines read from the end of each facility's `current` before filtering by service.
+const SCAN_LINES: usize = 10_000;
+
+/// The `facility.level: program[pid]: message` shape socklog writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyslogLine<'a> {
+    pub facility: &'a str,
+    pub level: &'a str,
+    /// Syslog tag, without the `[pid]` suffix.
+    pub program: Option<&'a str>,
+    pub pid: Option<u32>,
+    pub message: &'a str,
+}
+
+impl<'a> SyslogLine<'a> {
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let (priority, rest) = line.split_once(": ")?;
+        let (facility, level) = priority.split_once('.')?;
+        if facility.is_empty() || facility.contains(char::is_whitespace) {
+            return None;
+        }
+
+        let tagged = rest.split_once(": ").and_then(|(tag, message)| {
+            if tag.contains(char::is_whitespace) {
+                return None;
+            }
+            let (program, pid) = match tag.strip_suffix(']').and_then(|t| t.split_once('[')) {
+                Some((program, pid)) => (program, pid.parse().ok()),
+                None => (tag, None),
+            };
+            Some((program, pid, message))
+        });
+        let (program, pid, message) = match tagged {
+            Some((program, pid, message)) => (Some(program), pid, message),
+            None => (None, None, rest),
+        };
+        Some(SyslogLine {
+            facility,
+            level,
+            program,
+            pid,
+            message,
+        })
+    }
+}
+
+impl ServiceManager {
+    /// Read syslog entries from this socklog directory instead of [`SOCKLOG_DIR`].
+    pub fn with_socklog_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.socklog_dir = Some(dir.into());
+        self
+    }
+
+    /// socklog root consulted for services without their own logger; `None` in user scope.
+    pub fn socklog_dir(&self) -> Option<&Path> {
+        self.socklog_dir.as_deref()
+    }
+
+    /// Facility directories that have a `current` file, sorted.
+    pub fn socklog_facilities(&self) -> Result<Vec<String>> {
+        let Some(root) = self.socklog_dir() else {
+            return Ok(Vec::new());
+        };
+        let read_dir = match std::fs::read_dir(root) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ServiceError::from_io(root, err)),
+        };
+        let mut facilities: Vec<String> = read_dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join("current").is_file())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .collect();
+        facilities.sort();
+        Ok(facilities)
+    }
+
+    /// Newest `limit` socklog entries attributed to the service, oldest first.
+    ///
+    /// A line belongs to the service when its syslog tag is the service name or the
+    /// program its `run` script execs, or when it sits in a facility directory named after
+    /// the service. Lines socklog copies into several facility directories appear once.
+    pub fn tail_socklog(&self, service: &str, limit: usize) -> Result<Vec<ServiceLogEntry>> {
+        self.validate_service_name(service)?;
+        let Some(root) = self.socklog_dir() else {
+            return Ok(Vec::new());
+        };
+        let program = self
+            .read_run_script(service)
+            .ok()
+            .and_then(|script| run_script_program(&script));
+
+        let mut entries = Vec::new();
+        for facility in self.socklog_facilities()? {
+            let path = root.join(&facility).join("current");
+            let lines = match read_svlogd_tail(&path, SCAN_LINES) {
+                Ok(lines) => lines,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(ServiceError::from_io(&path, err)),
+            };
+            for entry in lines {
+                let entry = with_human_timestamp(entry);
+                let tag = SyslogLine::parse(&entry.message).and_then(|line| line.program);
+                let matches = facility == service
+                    || tag.is_some_and(|tag| tag == service || Some(tag) == program.as_deref());
+                if matches {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        entries.sort_by_key(|entry| (entry.timestamp_unix, entry.timestamp_nanos));
+        entries.dedup_by(|a, b| a.timestamp_raw == b.timestamp_raw && a.message == b.message);
+        let skip = entries.len().saturating_sub(limit);
+        let mut entries = entries.split_off(skip);
+        self.classify_entries(&mut entries);
+        Ok(entries)
+    }
+}
+
+/// Program a run script finally execs, skipping `chpst`, `env` and similar wrappers.
+pub(crate) fn run_script_program(script: &str) -> Option<String> {
+    let command = run_script_command(script)?;
+    let name = Path::new(&command).file_name()?.to_str()?;
+    Some(name.to_string())
+}
+
+/// First word of the last `exec` line that is not a wrapper, as written in the script.
+pub(crate) fn run_script_command(script: &str) -> Option<String> {
+    let line = script
+        .lines()
+        .map(str::trim)
+        .rfind(|line| line.starts_with("exec "))?;
+    let mut words = line.split_whitespace().skip(1);
+    while let Some(word) = words.next() {
+        match word {
+            "chpst" | "env" | "nice" | "ionice" | "setsid" | "sudo" => {}
+            // chpst options that take a value.
+            "-u" | "-U" | "-b" | "-e" | "-/" | "-n" | "-l" | "-L" | "-m" | "-d" | "-o" | "-p"
+            | "-f" | "-c" | "-r" | "-t" => {
+                words.next();
+            }
+            flag if flag.starts_with('-') => {}
+            redirect if redirect.contains('>') || redirect.contains('<') => {}
+            assignment if assignment.contains('=') => {}
+            program => return Some(program.to_string()),
+        }
+    }
+    None
+}
+
+/// Fill in the timestamp of lines stamped by `svlogd -tt` (`2024-01-05_10:11:12.34567`)
+/// or `-ttt` (`2024-01-05T10:11:12.34567`), which are UTC.
+fn with_human_timestamp(mut entry: ServiceLogEntry) -> ServiceLogEntry {
+    if entry.timestamp_raw.is_some() {
+        return entry;
+    }
+    let Some((stamp, rest)) = entry.message.split_once(' ') else {
+        return entry;
+    };
+    let Some((unix, nanos)) = parse_human_stamp(stamp) else {
+        return entry;
+    };
+    let stamp = stamp.to_string();
+    let rest = rest.trim_start().to_string();
+    let skip = entry.message.len() - rest.len();
+    entry.message_bytes = entry.message_bytes.get(skip..).unwrap_or_default().to_vec();
+    entry.message = rest;
+    entry.timestamp_unix = Some(unix);
+    entry.timestamp_nanos = Some(nanos);
+    entry.timestamp_raw = Some(stamp);
+    entry
+}
+
+fn parse_human_stamp(stamp: &str) -> Option<(i64, u32)> {
+    let (date, time) = stamp.split_once(['_', 'T'])?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (clock, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut clock = clock.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    let digits: String = fraction.chars().take(9).collect();
+    let nanos = if digits.is_empty() {
+        0
+    } else {
+        digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32)
+    };
+    let days = days_from_civil(year, month, day);
+    Some((days * 86_400 + hour * 3600 + minute * 60 + second, nanos))
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyslogLine, parse_human_stamp, run_script_program};
+    use crate::ServiceManager;
+
+    #[test]
+    fn parses_syslog_lines() {
+        let line = SyslogLine::parse("daemon.info: sshd[812]: Accepted publickey").unwrap();
+        assert_eq!(line.facility, "daemon");
+        assert_eq!(line.level, "info");
+        assert_eq!(line.program, Some("sshd"));
+        assert_eq!(line.pid, Some(812));
+        assert_eq!(line.message, "Accepted publickey");
+
+        let kernel = SyslogLine::parse("kern.warn: usb 1-1: reset high-speed device").unwrap();
+        assert_eq!(kernel.program, None);
+        assert!(SyslogLine::parse("plain output").is_none());
+    }
+
+    #[test]
+    fn parses_human_stamps() {
+        assert_eq!(
+            parse_human_stamp("2024-01-05_10:11:12.34567"),
+            Some((1_704_449_472, 345_670_000))
+        );
+        assert_eq!(parse_human_stamp("1970-01-01T00:00:01"), Some((1, 0)));
+        assert_eq!(parse_human_stamp("daemon.info:"), None);
+    }
+
+    #[test]
+    fn finds_exec_target() {
+        let script =
+            "#!/bin/sh\nexec 2>&1\nexec chpst -u _dhcpcd:_dhcpcd env LC_ALL=C /usr/bin/dhcpcd -B\n";
+        assert_eq!(run_script_program(script).as_deref(), Some("dhcpcd"));
+    }
+
+    #[test]
+    fn attributes_socklog_entries_to_services() {
+        let root = std::env::temp_dir().join(format!("runkit-socklog-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/cronie")).unwrap();
+        std::fs::write(root.join("sv/cronie/run"), "#!/bin/sh\nexec crond -n\n").unwrap();
+        for facility in ["cron", "everything"] {
+            std::fs::create_dir_all(root.join("socklog").join(facility)).unwrap();
+            std::fs::write(
+                root.join("socklog").join(facility).join("current"),
+                "2024-01-05_10:11:12.00000 cron.info: crond[90]: started\n\
+                 2024-01-05_10:11:13.00000 daemon.info: sshd[91]: listening\n\
+                 2024-01-05_10:12:00.00000 cron.info: crond[90]: job ran\n",
+            )
+            .unwrap();
+        }
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"))
+            .with_socklog_dir(root.join("socklog"));
+
+        let entries = manager.tail_socklog("cronie", 10).unwrap();
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cron.info: crond[90]: started",
+                "cron.info: crond[90]: job ran"
+            ]
+        );
+        assert_eq!(entries[0].timestamp_unix, Some(1_704_449_472));
+        assert_eq!(manager.tail_socklog("cronie", 1).unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
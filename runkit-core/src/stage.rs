@@ -0,0 +1,185 @@
+//! Which runit stage the system is in and which runlevel is active.
+use crate::ServiceManager;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Directory holding runit's stage scripts `1`, `2` and `3`.
+pub const RUNIT_DIR: &str = "/etc/runit";
+
+/// runit's boot stages: one-time setup, normal operation under runsvdir, shutdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RunitStage {
+    Boot,
+    Running,
+    Shutdown,
+}
+
+impl RunitStage {
+    pub fn number(self) -> u8 {
+        match self {
+            RunitStage::Boot => 1,
+            RunitStage::Running => 2,
+            RunitStage::Shutdown => 3,
+        }
+    }
+
+    /// Decide the stage from the command lines of running processes. runit runs the
+    /// stage scripts as `/etc/runit/N`; stage 3 wins because stage 2 processes can
+    /// linger while it runs.
+    pub fn from_cmdlines<'a>(
+        runit_dir: &Path,
+        cmdlines: impl IntoIterator<Item = &'a [String]>,
+    ) -> Option<Self> {
+        let script = |n: u8| runit_dir.join(n.to_string());
+        let mut seen_boot = false;
+        let mut seen_running = false;
+        for argv in cmdlines {
+            // The script may be exec'd directly or run through a shell.
+            let runs = |n: u8| argv.iter().take(2).any(|arg| Path::new(arg) == script(n));
+            if runs(3) {
+                return Some(RunitStage::Shutdown);
+            }
+            seen_boot |= runs(1);
+            seen_running |= runs(2)
+                || argv
+                    .first()
+                    .is_some_and(|arg| Path::new(arg).file_name().is_some_and(|n| n == "runsvdir"));
+        }
+        if seen_boot {
+            Some(RunitStage::Boot)
+        } else if seen_running {
+            Some(RunitStage::Running)
+        } else {
+            None
+        }
+    }
+}
+
+/// Snapshot of the init system's own state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemStage {
+    /// `None` when no runit stage process could be identified, e.g. in a container.
+    pub stage: Option<RunitStage>,
+    /// Runlevel the enabled directory currently points at.
+    pub runlevel: Option<String>,
+    /// runit has been asked to halt or reboot (`/run/runit/stopit` is executable).
+    pub shutdown_requested: bool,
+    /// Modification times of the stage 1, 2 and 3 scripts.
+    pub stage_scripts: [Option<SystemTime>; 3],
+}
+
+impl SystemStage {
+    pub fn is_shutting_down(&self) -> bool {
+        self.stage == Some(RunitStage::Shutdown) || self.shutdown_requested
+    }
+
+    pub fn is_single_user(&self) -> bool {
+        self.runlevel.as_deref() == Some("single")
+    }
+}
+
+impl ServiceManager {
+    /// Name of the runlevel the enabled directory resolves into, such as `default`.
+    pub fn current_runlevel(&self) -> Option<String> {
+        let root = self.runlevels_dir()?;
+        let resolved = std::fs::canonicalize(&self.enabled_dir).ok()?;
+        let root = std::fs::canonicalize(root).ok()?;
+        if resolved.parent()? != root {
+            return None;
+        }
+        resolved.file_name()?.to_str().map(str::to_string)
+    }
+
+    /// Report the runit stage, active runlevel and stage script timestamps.
+    pub fn system_stage(&self) -> SystemStage {
+        let runit_dir = Path::new(RUNIT_DIR);
+        let cmdlines = process_cmdlines();
+        let stage = RunitStage::from_cmdlines(runit_dir, cmdlines.iter().map(Vec::as_slice));
+        let shutdown_requested = std::fs::metadata("/run/runit/stopit")
+            .is_ok_and(|meta| meta.permissions().mode() & 0o100 != 0);
+        let modified = |n: u8| {
+            std::fs::metadata(runit_dir.join(n.to_string()))
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+        SystemStage {
+            stage,
+            runlevel: self.current_runlevel(),
+            shutdown_requested,
+            stage_scripts: [modified(1), modified(2), modified(3)],
+        }
+    }
+}
+
+pub(crate) fn process_cmdlines() -> Vec<Vec<String>> {
+    let Ok(read_dir) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    read_dir
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .filter_map(|entry| std::fs::read(entry.path().join("cmdline")).ok())
+        .map(|raw| {
+            raw.split(|&b| b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RunitStage;
+    use crate::ServiceManager;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn identifies_stage_from_processes() {
+        let dir = Path::new("/etc/runit");
+        let running = [
+            argv(&["runit"]),
+            argv(&["runsvdir", "-P", "/run/runit/service"]),
+        ];
+        assert_eq!(
+            RunitStage::from_cmdlines(dir, running.iter().map(Vec::as_slice)),
+            Some(RunitStage::Running)
+        );
+        let stopping = [argv(&["runsvdir"]), argv(&["/bin/sh", "/etc/runit/3"])];
+        assert_eq!(
+            RunitStage::from_cmdlines(dir, stopping.iter().map(Vec::as_slice)),
+            Some(RunitStage::Shutdown)
+        );
+        assert_eq!(RunitStage::from_cmdlines(dir, std::iter::empty()), None);
+    }
+
+    #[test]
+    fn resolves_current_runlevel() {
+        let root = std::env::temp_dir().join(format!("runkit-stage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("runsvdir/single")).unwrap();
+        std::fs::create_dir_all(root.join("sv")).unwrap();
+        symlink(root.join("runsvdir/single"), root.join("service")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        assert_eq!(manager.current_runlevel().as_deref(), Some("single"));
+        assert!(manager.system_stage().is_single_user());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
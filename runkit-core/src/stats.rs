@@ -0,0 +1,233 @@
+//! Resource usage of a supervised process tree, read from `/proc`.
+use crate::{Result, ServiceError, ServiceManager};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Totals over a service's main process and all of its descendants.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServiceStats {
+    /// Pid of the process runsv started.
+    pub pid: u32,
+    /// Number of processes in the tree, including `pid`.
+    pub processes: usize,
+    pub rss_bytes: u64,
+    /// User plus system time consumed by live processes in the tree.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::millis"))]
+    pub cpu_time: Duration,
+    /// `cpu_time` as a share of one CPU over the main process's lifetime.
+    pub cpu_percent: f64,
+    pub threads: u64,
+    /// `None` when `/proc/<pid>/fd` is not readable, usually for other users' processes.
+    pub open_fds: Option<u64>,
+}
+
+/// The fields of `/proc/<pid>/stat` used here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ProcStat {
+    ppid: u32,
+    /// utime + stime, in clock ticks.
+    cpu_ticks: u64,
+    threads: u64,
+    /// Start time after boot, in clock ticks.
+    pub(crate) start_ticks: u64,
+    rss_pages: u64,
+}
+
+impl ProcStat {
+    /// The command name is in parentheses and may contain spaces, so fields are counted
+    /// from the last `)`.
+    fn parse(line: &str) -> Option<Self> {
+        let rest = &line[line.rfind(')')? + 1..];
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        // `fields[0]` is field 3 (state) in proc(5) numbering.
+        let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+        Some(ProcStat {
+            ppid: u32::try_from(field(4)?).ok()?,
+            cpu_ticks: field(14)? + field(15)?,
+            threads: field(20)?,
+            start_ticks: field(22)?,
+            rss_pages: field(24)?,
+        })
+    }
+}
+
+impl ServiceManager {
+    /// Resource usage of a running service; `None` when it is not running.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    pub fn stats(&self, service: &str) -> Result<Option<ServiceStats>> {
+        let Some(pid) = self.status(service)?.pid() else {
+            return Ok(None);
+        };
+        match ServiceStats::for_pid(pid) {
+            Ok(stats) => Ok(Some(stats)),
+            // The process exited between the status read and the /proc walk.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(ServiceError::from_io(format!("/proc/{pid}"), err)),
+        }
+    }
+}
+
+impl ServiceStats {
+    /// Collect usage for `pid` and its descendants.
+    pub fn for_pid(pid: u32) -> io::Result<Self> {
+        let proc_root = Path::new("/proc");
+        let main = read_stat(proc_root, pid)?;
+        Ok(ProcTable::read(proc_root)?.stats(pid, main))
+    }
+
+    /// Usage of several process trees from a single walk of `/proc`. Pids that are no
+    /// longer running are left out.
+    pub fn for_pids(pids: &[u32]) -> io::Result<HashMap<u32, Self>> {
+        let table = ProcTable::read(Path::new("/proc"))?;
+        Ok(pids
+            .iter()
+            .filter_map(|&pid| Some((pid, table.stats(pid, *table.procs.get(&pid)?))))
+            .collect())
+    }
+}
+
+/// Every process's stat line, indexed by parent.
+struct ProcTable<'a> {
+    proc_root: &'a Path,
+    procs: HashMap<u32, ProcStat>,
+    children: HashMap<u32, Vec<u32>>,
+    ticks_per_sec: u64,
+    page_size: u64,
+    uptime: Option<f64>,
+}
+
+impl<'a> ProcTable<'a> {
+    fn read(proc_root: &'a Path) -> io::Result<Self> {
+        let mut procs = HashMap::new();
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        for entry in std::fs::read_dir(proc_root)?.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            // Processes can vanish while we walk /proc.
+            if let Ok(stat) = read_stat(proc_root, pid) {
+                children.entry(stat.ppid).or_default().push(pid);
+                procs.insert(pid, stat);
+            }
+        }
+        Ok(ProcTable {
+            proc_root,
+            procs,
+            children,
+            ticks_per_sec: sysconf(libc::_SC_CLK_TCK).unwrap_or(100),
+            page_size: sysconf(libc::_SC_PAGESIZE).unwrap_or(4096),
+            uptime: read_uptime(proc_root),
+        })
+    }
+
+    /// Totals for `pid`, whose own stat is `main`, and every descendant in the table.
+    fn stats(&self, pid: u32, main: ProcStat) -> ServiceStats {
+        let mut tree = vec![(pid, main)];
+        let mut index = 0;
+        while index < tree.len() {
+            for kid in self.children.get(&tree[index].0).into_iter().flatten() {
+                if *kid != pid
+                    && let Some(stat) = self.procs.get(kid)
+                {
+                    tree.push((*kid, *stat));
+                }
+            }
+            index += 1;
+        }
+
+        let cpu_ticks: u64 = tree.iter().map(|(_, stat)| stat.cpu_ticks).sum();
+        let cpu_time = Duration::from_millis(cpu_ticks * 1000 / self.ticks_per_sec);
+
+        let lifetime = self
+            .uptime
+            .map(|uptime| uptime - main.start_ticks as f64 / self.ticks_per_sec as f64)
+            .unwrap_or(0.0);
+        let cpu_percent = if lifetime > 0.0 {
+            cpu_time.as_secs_f64() / lifetime * 100.0
+        } else {
+            0.0
+        };
+
+        let open_fds = tree
+            .iter()
+            .map(|(pid, _)| count_fds(self.proc_root, *pid))
+            .sum::<Option<u64>>();
+
+        ServiceStats {
+            pid,
+            processes: tree.len(),
+            rss_bytes: tree.iter().map(|(_, stat)| stat.rss_pages).sum::<u64>() * self.page_size,
+            cpu_time,
+            cpu_percent,
+            threads: tree.iter().map(|(_, stat)| stat.threads).sum(),
+            open_fds,
+        }
+    }
+}
+
+pub(crate) fn read_stat(proc_root: &Path, pid: u32) -> io::Result<ProcStat> {
+    let line = std::fs::read_to_string(proc_root.join(pid.to_string()).join("stat"))?;
+    ProcStat::parse(&line).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected /proc/{pid}/stat format"),
+        )
+    })
+}
+
+fn read_uptime(proc_root: &Path) -> Option<f64> {
+    let uptime = std::fs::read_to_string(proc_root.join("uptime")).ok()?;
+    uptime.split_whitespace().next()?.parse().ok()
+}
+
+fn count_fds(proc_root: &Path, pid: u32) -> Option<u64> {
+    let entries = std::fs::read_dir(proc_root.join(pid.to_string()).join("fd")).ok()?;
+    Some(entries.count() as u64)
+}
+
+pub(crate) fn sysconf(name: libc::c_int) -> Option<u64> {
+    // SAFETY: sysconf only reads the name and has no memory side effects.
+    let value = unsafe { libc::sysconf(name) };
+    u64::try_from(value).ok().filter(|value| *value > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProcStat, ServiceStats};
+
+    #[test]
+    fn parses_stat_with_spaces_in_comm() {
+        let line = "4321 (my (odd) daemon) S 1 4321 4321 0 -1 4194560 120 0 0 0 \
+                    25 15 0 0 20 0 3 0 5000 10485760 2560 18446744073709551615";
+        let stat = ProcStat::parse(line).expect("stat line should parse");
+        assert_eq!(stat.ppid, 1);
+        assert_eq!(stat.cpu_ticks, 40);
+        assert_eq!(stat.threads, 3);
+        assert_eq!(stat.start_ticks, 5000);
+        assert_eq!(stat.rss_pages, 2560);
+    }
+
+    #[test]
+    fn reads_own_process() {
+        let stats = ServiceStats::for_pid(std::process::id()).unwrap();
+        assert!(stats.processes >= 1);
+        assert!(stats.threads >= 1);
+        assert!(stats.rss_bytes > 0);
+        assert!(stats.open_fds.is_some_and(|fds| fds >= 3));
+    }
+
+    #[test]
+    fn reads_several_trees_at_once() {
+        let own = std::process::id();
+        let stats = ServiceStats::for_pids(&[own, u32::MAX]).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert!(stats[&own].processes >= 1);
+    }
+}
@@ -0,0 +1,123 @@
+//! Lenient status queries that keep `sv` warnings apart from fatal errors.
+use crate::{Result, ServiceError, ServiceManager, ServiceRuntimeState};
+use std::fmt;
+
+/// Non-fatal diagnostic printed by `sv` alongside a usable status line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusWarning {
+    pub message: String,
+}
+
+impl fmt::Display for StatusWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Runtime state together with whatever warnings were emitted while reading it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusReport {
+    pub state: ServiceRuntimeState,
+    pub warnings: Vec<StatusWarning>,
+}
+
+impl StatusReport {
+    pub fn new(state: ServiceRuntimeState) -> Self {
+        StatusReport {
+            state,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Build a report from raw `sv status` output.
+    ///
+    /// `warning:` lines on stdout and anything on stderr become warnings; the call only
+    /// fails when no status line remains or the line cannot be parsed at all.
+    pub fn from_sv_output(service: &str, stdout: &str, stderr: &str) -> Result<Self> {
+        let mut warnings = Vec::new();
+        let mut status_line = None;
+        for line in stdout.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if line.starts_with("warning:") {
+                warnings.push(StatusWarning {
+                    message: line.to_string(),
+                });
+            } else if status_line.is_none() {
+                status_line = Some(line);
+            }
+        }
+        warnings.extend(
+            stderr
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(|line| StatusWarning {
+                    message: line.to_string(),
+                }),
+        );
+
+        let unusable = |message: String| ServiceError::from_sv_message(service, message);
+        let Some(line) = status_line else {
+            let detail = warnings
+                .iter()
+                .map(|w| w.message.as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(unusable(if detail.is_empty() {
+                "sv status returned no output".to_string()
+            } else {
+                detail
+            }));
+        };
+        // `fail:` is sv reporting that it could not read the service at all.
+        if line.starts_with("fail:") {
+            return Err(unusable(line.to_string()));
+        }
+
+        Ok(StatusReport {
+            state: ServiceRuntimeState::from_sv_status(line),
+            warnings,
+        })
+    }
+}
+
+impl ServiceManager {
+    /// Like [`ServiceManager::status`], but harmless `sv` diagnostics are returned as
+    /// warnings instead of failing the call.
+    pub fn status_lenient(&self, service: &str) -> Result<StatusReport> {
+        self.validate_service_name(service)?;
+        self.backend.status_lenient(service)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatusReport;
+    use crate::{ServiceError, ServiceRuntimeState};
+
+    #[test]
+    fn keeps_warnings_next_to_state() {
+        let report = StatusReport::from_sv_output(
+            "sshd",
+            "warning: sshd: unable to open supervise/ok: access denied\nrun: sshd: (pid 812) 40s\n",
+            "sv: locale not supported\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            report.state,
+            ServiceRuntimeState::Running { pid: 812, .. }
+        ));
+        assert_eq!(report.warnings.len(), 2);
+
+        assert!(matches!(
+            StatusReport::from_sv_output(
+                "ghost",
+                "fail: ghost: unable to change to service directory\n",
+                ""
+            ),
+            Err(ServiceError::SvCommand { .. })
+        ));
+        assert!(StatusReport::from_sv_output("sshd", "", "sv: fatal\n").is_err());
+    }
+}
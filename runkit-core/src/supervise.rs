@@ -0,0 +1,311 @@
+//! Direct readers for the `supervise/` state that `runsv` maintains per service.
+use crate::{ServiceRuntimeState, Tai64N, Transition};
+use std::fs::OpenOptions;
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Size of the binary `supervise/status` record written by runsv.
+pub const SUPERVISE_STATUS_LEN: usize = 20;
+
+/// Process state reported by runsv in the last byte of `supervise/status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisedProcessState {
+    Down,
+    Running,
+    Finishing,
+}
+
+/// Direction runsv was last asked to move the service in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WantState {
+    Up,
+    Down,
+}
+
+/// Decoded contents of a `supervise/status` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuperviseStatus {
+    pub changed_tai64: u64,
+    pub changed_nanos: u32,
+    pub pid: Option<u32>,
+    pub paused: bool,
+    pub want: Option<WantState>,
+    pub term_sent: bool,
+    pub state: SupervisedProcessState,
+}
+
+impl SuperviseStatus {
+    /// Decode the 20-byte record: TAI64N change time, little-endian pid, then
+    /// the paused, want, term and state flag bytes.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < SUPERVISE_STATUS_LEN {
+            return None;
+        }
+
+        let changed_tai64 = u64::from_be_bytes(bytes[0..8].try_into().ok()?);
+        let changed_nanos = u32::from_be_bytes(bytes[8..12].try_into().ok()?);
+        let pid = u32::from_le_bytes(bytes[12..16].try_into().ok()?);
+        let want = match bytes[17] {
+            b'u' => Some(WantState::Up),
+            b'd' => Some(WantState::Down),
+            _ => None,
+        };
+        let state = match bytes[19] {
+            1 => SupervisedProcessState::Running,
+            2 => SupervisedProcessState::Finishing,
+            _ => SupervisedProcessState::Down,
+        };
+
+        Some(SuperviseStatus {
+            changed_tai64,
+            changed_nanos,
+            pid: if pid == 0 { None } else { Some(pid) },
+            paused: bytes[16] != 0,
+            want,
+            term_sent: bytes[18] != 0,
+            state,
+        })
+    }
+
+    /// Encode the record the way runsv writes it; the inverse of [`SuperviseStatus::parse`].
+    pub fn to_bytes(&self) -> [u8; SUPERVISE_STATUS_LEN] {
+        let mut bytes = [0u8; SUPERVISE_STATUS_LEN];
+        bytes[0..8].copy_from_slice(&self.changed_tai64.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.changed_nanos.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.pid.unwrap_or(0).to_le_bytes());
+        bytes[16] = u8::from(self.paused);
+        bytes[17] = match self.want {
+            Some(WantState::Up) => b'u',
+            Some(WantState::Down) => b'd',
+            None => 0,
+        };
+        bytes[18] = u8::from(self.term_sent);
+        bytes[19] = match self.state {
+            SupervisedProcessState::Down => 0,
+            SupervisedProcessState::Running => 1,
+            SupervisedProcessState::Finishing => 2,
+        };
+        bytes
+    }
+
+    /// Wall-clock time of the last state change.
+    pub fn changed_at(&self) -> SystemTime {
+        Tai64N {
+            label: self.changed_tai64,
+            nanos: self.changed_nanos,
+        }
+        .to_system_time()
+    }
+
+    /// Time spent in the current state, measured against the system clock.
+    pub fn elapsed(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.changed_at())
+            .unwrap_or_default()
+    }
+
+    /// Transition runsv is working towards, using the same rule as `sv status`
+    /// when it prints `want up` / `want down`, except that a process started with
+    /// `sv once` is not reported as stopping; see [`SuperviseStatus::runs_once`].
+    pub fn transition(&self) -> Option<Transition> {
+        match (self.pid, self.want) {
+            (None, Some(WantState::Up)) => Some(Transition::Starting),
+            (Some(_), Some(WantState::Down)) if self.term_sent => Some(Transition::Stopping),
+            _ => None,
+        }
+    }
+
+    /// The process was started with `sv once`: runsv wants the service down but has
+    /// not signalled it, so it keeps running and is not restarted once it exits.
+    pub fn runs_once(&self) -> bool {
+        self.state == SupervisedProcessState::Running
+            && self.pid.is_some()
+            && self.want == Some(WantState::Down)
+            && !self.term_sent
+    }
+
+    /// Map the raw record onto the same model `sv status` parsing produces.
+    pub fn to_runtime_state(&self, normally_up: bool) -> ServiceRuntimeState {
+        let elapsed = Duration::from_secs(self.elapsed().as_secs());
+        match (self.state, self.pid) {
+            (SupervisedProcessState::Running, Some(pid)) if self.paused => {
+                ServiceRuntimeState::Paused {
+                    pid,
+                    uptime: elapsed,
+                    started_at: self.changed_at(),
+                }
+            }
+            (SupervisedProcessState::Running, Some(pid)) => ServiceRuntimeState::Running {
+                pid,
+                uptime: elapsed,
+                started_at: self.changed_at(),
+            },
+            _ => ServiceRuntimeState::Down {
+                since: elapsed,
+                normally_up,
+                started_at: self.changed_at(),
+            },
+        }
+    }
+}
+
+/// Where runsv keeps a service's state and whether it is attending to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SupervisePresence {
+    /// `supervise/` with symlinks resolved, e.g. `/run/runit/supervise.sshd` on Void.
+    pub dir: Option<PathBuf>,
+    /// The `supervise/ok` FIFO runsv creates on startup exists.
+    pub has_ok_pipe: bool,
+    /// Some runsv holds the read end of `supervise/ok`.
+    pub runsv_running: bool,
+}
+
+impl SupervisePresence {
+    pub(crate) fn read(service_dir: &Path) -> Self {
+        let Ok(dir) = std::fs::canonicalize(service_dir.join("supervise")) else {
+            return SupervisePresence::default();
+        };
+        let ok = dir.join("ok");
+        let has_ok_pipe = std::fs::metadata(&ok).is_ok_and(|meta| meta.file_type().is_fifo());
+        SupervisePresence {
+            runsv_running: has_ok_pipe && has_reader(&ok),
+            has_ok_pipe,
+            dir: Some(dir),
+        }
+    }
+}
+
+/// Whether some runsv holds the read end of the `supervise/ok` FIFO.
+pub(crate) fn has_reader(fifo: &Path) -> bool {
+    match OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(fifo)
+    {
+        Ok(_) => true,
+        // ENXIO is the "no reader" answer; anything else (permissions, a regular file)
+        // says nothing about runsv, so do not report it as stale.
+        Err(err) => err.raw_os_error() != Some(libc::ENXIO),
+    }
+}
+
+/// Read `supervise/status` below a supervised service directory.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "trace", err(level = "debug"))
+)]
+pub(crate) fn read_supervise_status(service_dir: &Path) -> io::Result<SuperviseStatus> {
+    let bytes = std::fs::read(service_dir.join("supervise/status"))?;
+    SuperviseStatus::parse(&bytes).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "expected {SUPERVISE_STATUS_LEN} bytes, found {}",
+                bytes.len()
+            ),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SuperviseStatus, SupervisedProcessState, WantState};
+    use crate::{ServiceRuntimeState, Transition};
+
+    fn record(tai: u64, pid: u32, want: u8, state: u8) -> [u8; 20] {
+        let mut bytes = [0u8; 20];
+        bytes[0..8].copy_from_slice(&tai.to_be_bytes());
+        bytes[12..16].copy_from_slice(&pid.to_le_bytes());
+        bytes[17] = want;
+        bytes[19] = state;
+        bytes
+    }
+
+    #[test]
+    fn parses_running_record() {
+        let bytes = record(0x4000_0000_0000_000a, 4321, b'u', 1);
+        let status = SuperviseStatus::parse(&bytes).expect("record should parse");
+        assert_eq!(status.to_bytes(), bytes);
+        assert_eq!(status.pid, Some(4321));
+        assert_eq!(status.want, Some(WantState::Up));
+        assert_eq!(status.state, SupervisedProcessState::Running);
+        assert_eq!(status.transition(), None);
+        match status.to_runtime_state(true) {
+            ServiceRuntimeState::Running { pid, .. } => assert_eq!(pid, 4321),
+            other => panic!("unexpected state: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_paused_process() {
+        let mut bytes = record(0x4000_0000_0000_000a, 4321, b'u', 1);
+        bytes[16] = 1;
+        let status = SuperviseStatus::parse(&bytes).expect("record should parse");
+        assert!(status.paused);
+        assert!(matches!(
+            status.to_runtime_state(true),
+            ServiceRuntimeState::Paused { pid: 4321, .. }
+        ));
+    }
+
+    #[test]
+    fn reports_pending_transition() {
+        let mut bytes = record(0x4000_0000_0000_000a, 99, b'd', 1);
+        bytes[18] = 1;
+        let stopping = SuperviseStatus::parse(&bytes).expect("record should parse");
+        assert_eq!(stopping.transition(), Some(Transition::Stopping));
+        assert!(!stopping.runs_once());
+        let once = SuperviseStatus::parse(&record(0x4000_0000_0000_000a, 99, b'd', 1))
+            .expect("record should parse");
+        assert_eq!(once.transition(), None);
+        assert!(once.runs_once());
+        let starting = SuperviseStatus::parse(&record(0x4000_0000_0000_000a, 0, b'u', 0))
+            .expect("record should parse");
+        assert_eq!(starting.transition(), Some(Transition::Starting));
+    }
+
+    #[test]
+    fn reports_whether_runsv_holds_the_ok_pipe() {
+        use super::SupervisePresence;
+        use crate::testutil::FakeRunit;
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let tree = FakeRunit::new("presence");
+        tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        tree.enable("sshd");
+        let service = tree.enabled_dir().join("sshd");
+        assert_eq!(
+            SupervisePresence::read(&service),
+            SupervisePresence::default()
+        );
+
+        let supervise = tree.root().join("run/supervise.sshd");
+        std::fs::create_dir_all(&supervise).unwrap();
+        std::os::unix::fs::symlink(&supervise, tree.definitions_dir().join("sshd/supervise"))
+            .unwrap();
+        let ok = supervise.join("ok");
+        let path = CString::new(ok.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+        let presence = SupervisePresence::read(&service);
+        assert_eq!(presence.dir, std::fs::canonicalize(&supervise).ok());
+        assert!(presence.has_ok_pipe);
+        assert!(!presence.runsv_running);
+
+        // Holding the read end is what runsv does.
+        let _reader = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&ok)
+            .unwrap();
+        assert!(SupervisePresence::read(&service).runsv_running);
+    }
+
+    #[test]
+    fn rejects_short_record() {
+        assert!(SuperviseStatus::parse(&[0u8; 12]).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//! State changes through `sv -w N -v`, which waits for runsv to confirm the result.
+use crate::{Result, ServiceError, ServiceManager};
+use std::process::Command;
+use std::time::Duration;
+
+/// How long `sv` waits for a state change unless configured otherwise; matches `sv`'s own default.
+pub const DEFAULT_SV_TIMEOUT: Duration = Duration::from_secs(7);
+
+/// First word of an `sv -v` report line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SvVerdict {
+    Ok,
+    Timeout,
+    Fail,
+}
+
+fn parse_verdict(stdout: &str) -> Option<SvVerdict> {
+    let line = stdout.lines().find(|line| !line.trim().is_empty())?;
+    match line.split(':').next()?.trim() {
+        "ok" => Some(SvVerdict::Ok),
+        "timeout" => Some(SvVerdict::Timeout),
+        "fail" | "warning" => Some(SvVerdict::Fail),
+        _ => None,
+    }
+}
+
+impl ServiceManager {
+    /// Wait at most `timeout` for `start`, `stop` and `restart` to take effect.
+    pub fn with_sv_timeout(mut self, timeout: Duration) -> Self {
+        self.sv_timeout = timeout;
+        self
+    }
+
+    pub fn sv_timeout(&self) -> Duration {
+        self.sv_timeout
+    }
+
+    /// Bring the service up and wait until runsv reports it running. Any recorded
+    /// [`crate::DownReason`] no longer applies and is removed.
+    pub fn start(&self, service: &str) -> Result<()> {
+        self.sv_verified("up", service)?;
+        let _ = self.clear_down_reason(service);
+        Ok(())
+    }
+
+    /// Take the service down and wait until runsv reports it stopped.
+    pub fn stop(&self, service: &str) -> Result<()> {
+        self.sv_verified("down", service)
+    }
+
+    /// Restart the service and wait until it is running again.
+    pub fn restart(&self, service: &str) -> Result<()> {
+        self.sv_verified("restart", service)
+    }
+
+    /// Run `sv -w <timeout> -v <command> <service>`.
+    ///
+    /// An expired wait is reported as [`ServiceError::Timeout`] so callers can tell a
+    /// slow service from one that refused the command.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), err))]
+    fn sv_verified(&self, command: &str, service: &str) -> Result<()> {
+        self.validate_runnable_name(service)?;
+        // sv only understands whole seconds; never round a short timeout down to zero.
+        let seconds = self.sv_timeout.as_secs().max(1);
+        let output = Command::new(&self.sv_command)
+            .env("SVDIR", &self.enabled_dir)
+            .arg("-w")
+            .arg(seconds.to_string())
+            .arg("-v")
+            .arg(command)
+            .arg(service)
+            .output()
+            .map_err(|err| ServiceError::from_io(&self.sv_command, err))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        trace_debug!(status = %output.status, stdout = %stdout, "sv finished");
+        match parse_verdict(&stdout) {
+            Some(SvVerdict::Ok) if output.status.success() => {
+                self.record_request(service);
+                Ok(())
+            }
+            Some(SvVerdict::Timeout) => Err(ServiceError::Timeout {
+                service: service.to_string(),
+                timeout: Duration::from_secs(seconds),
+            }),
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let message = [stdout.trim(), stderr.trim()]
+                    .into_iter()
+                    .find(|text| !text.is_empty())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("sv {command} failed ({})", output.status));
+                Err(ServiceError::from_sv_message(service, message))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SvVerdict, parse_verdict};
+
+    #[test]
+    fn reads_verbose_sv_verdicts() {
+        assert_eq!(
+            parse_verdict("ok: run: sshd: (pid 812) 1s\n"),
+            Some(SvVerdict::Ok)
+        );
+        assert_eq!(
+            parse_verdict("timeout: down: sshd: 7s, normally up, want up\n"),
+            Some(SvVerdict::Timeout)
+        );
+        assert_eq!(
+            parse_verdict(
+                "fail: ghost: unable to change to service directory: file does not exist\n"
+            ),
+            Some(SvVerdict::Fail)
+        );
+        assert_eq!(parse_verdict(""), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! The `config` file svlogd reads from each log directory.
+use crate::definition::write_atomic;
+use crate::{Result, ServiceError, ServiceManager};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Whether a pattern selects or drops lines, and for which output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFilterKind {
+    /// `+pattern`: log matching lines.
+    Include,
+    /// `-pattern`: drop matching lines.
+    Exclude,
+    /// `epattern`: also copy matching lines to stderr.
+    IncludeStderr,
+    /// `Epattern`: stop copying matching lines to stderr.
+    ExcludeStderr,
+}
+
+impl LogFilterKind {
+    fn marker(self) -> char {
+        match self {
+            LogFilterKind::Include => '+',
+            LogFilterKind::Exclude => '-',
+            LogFilterKind::IncludeStderr => 'e',
+            LogFilterKind::ExcludeStderr => 'E',
+        }
+    }
+}
+
+/// One `+`/`-`/`e`/`E` line; svlogd applies them in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    pub kind: LogFilterKind,
+    pub pattern: String,
+}
+
+/// Parsed svlogd `config`. Unset fields fall back to svlogd's own defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SvlogdConfig {
+    /// `s`: rotate `current` once it reaches this many bytes.
+    pub max_size: Option<u64>,
+    /// `n`: keep at most this many rotated files.
+    pub max_files: Option<u32>,
+    /// `N`: keep at least this many rotated files when the disk is full.
+    pub min_files: Option<u32>,
+    /// `t`: rotate `current` after it has been written to for this long.
+    pub rotate_after: Option<Duration>,
+    /// `p`: prefix added to every logged line.
+    pub prefix: Option<String>,
+    /// `!`: processor run over each rotated file.
+    pub processor: Option<String>,
+    pub filters: Vec<LogFilter>,
+    /// Lines svlogd understands but this type does not model (`u`/`U`), kept verbatim.
+    pub other: Vec<String>,
+}
+
+impl SvlogdConfig {
+    pub fn parse(contents: &str) -> Self {
+        let mut config = SvlogdConfig::default();
+        for line in contents.lines() {
+            let Some(directive) = line.chars().next() else {
+                continue;
+            };
+            let arg = &line[directive.len_utf8()..];
+            match directive {
+                's' => config.max_size = arg.trim().parse().ok(),
+                'n' => config.max_files = arg.trim().parse().ok(),
+                'N' => config.min_files = arg.trim().parse().ok(),
+                't' => config.rotate_after = arg.trim().parse().ok().map(Duration::from_secs),
+                'p' => config.prefix = Some(arg.to_string()),
+                '!' => config.processor = Some(arg.to_string()),
+                '+' | '-' | 'e' | 'E' => config.filters.push(LogFilter {
+                    kind: match directive {
+                        '+' => LogFilterKind::Include,
+                        '-' => LogFilterKind::Exclude,
+                        'e' => LogFilterKind::IncludeStderr,
+                        _ => LogFilterKind::ExcludeStderr,
+                    },
+                    pattern: arg.to_string(),
+                }),
+                _ => config.other.push(line.to_string()),
+            }
+        }
+        config
+    }
+}
+
+impl fmt::Display for SvlogdConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(size) = self.max_size {
+            writeln!(f, "s{size}")?;
+        }
+        if let Some(count) = self.max_files {
+            writeln!(f, "n{count}")?;
+        }
+        if let Some(count) = self.min_files {
+            writeln!(f, "N{count}")?;
+        }
+        if let Some(timeout) = self.rotate_after {
+            writeln!(f, "t{}", timeout.as_secs())?;
+        }
+        if let Some(processor) = &self.processor {
+            writeln!(f, "!{processor}")?;
+        }
+        if let Some(prefix) = &self.prefix {
+            writeln!(f, "p{prefix}")?;
+        }
+        for line in &self.other {
+            writeln!(f, "{line}")?;
+        }
+        for filter in &self.filters {
+            writeln!(f, "{}{}", filter.kind.marker(), filter.pattern)?;
+        }
+        Ok(())
+    }
+}
+
+impl ServiceManager {
+    /// svlogd settings from the service's `log/main/config`; default when there is none.
+    pub fn svlogd_config(&self, service: &str) -> Result<SvlogdConfig> {
+        let path = self.svlogd_config_path(service)?;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(SvlogdConfig::parse(&contents)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(SvlogdConfig::default()),
+            Err(err) => Err(ServiceError::from_io(&path, err)),
+        }
+    }
+
+    /// Replace `log/main/config`. svlogd rereads it when the logger gets a HUP.
+    pub fn write_svlogd_config(&self, service: &str, config: &SvlogdConfig) -> Result<()> {
+        let path = self.svlogd_config_path(service)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| ServiceError::from_io(dir, err))?;
+        }
+        write_atomic(&path, config.to_string().as_bytes(), 0o644, false, |_| {
+            Ok(())
+        })
+    }
+
+    fn svlogd_config_path(&self, service: &str) -> Result<PathBuf> {
+        let log_dir = self.definition_file(service, "log")?;
+        if !log_dir.is_dir() {
+            return Err(ServiceError::LogUnavailable(service.to_string()));
+        }
+        Ok(log_dir.join("main/config"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogFilterKind, SvlogdConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn parses_and_serializes_directives() {
+        let config =
+            SvlogdConfig::parse("s1048576\nn5\nt86400\npsshd: \n-*debug*\n+*\nu10.0.0.1\n");
+        assert_eq!(config.max_size, Some(1_048_576));
+        assert_eq!(config.max_files, Some(5));
+        assert_eq!(config.rotate_after, Some(Duration::from_secs(86_400)));
+        assert_eq!(config.prefix.as_deref(), Some("sshd: "));
+        assert_eq!(config.filters.len(), 2);
+        assert_eq!(config.filters[0].kind, LogFilterKind::Exclude);
+        assert_eq!(config.filters[0].pattern, "*debug*");
+        assert_eq!(config.other, ["u10.0.0.1"]);
+
+        assert_eq!(SvlogdConfig::parse(&config.to_string()), config);
+    }
+}
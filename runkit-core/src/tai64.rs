@@ -0,0 +1,153 @@
+//! TAI64N labels as written by runit and svlogd, and their conversion to wall-clock time.
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Label of the TAI64 epoch (1970-01-01 00:00:00 TAI).
+pub const TAI64_EPOCH_LABEL: u64 = 1 << 62;
+
+/// runit stores `2^62 + 10 + unix seconds`: it assumes the 1972 TAI-UTC offset of ten
+/// seconds and ignores later leap seconds (see `tai_unix` in runit).
+pub(crate) const RUNIT_TAI64_UNIX_OFFSET: u64 = TAI64_EPOCH_LABEL + 10;
+
+/// Unix times at which TAI-UTC changed, with the new offset (from the IERS leap second list).
+const LEAP_SECONDS: &[(u64, u64)] = &[
+    (63_072_000, 10),
+    (78_796_800, 11),
+    (94_694_400, 12),
+    (126_230_400, 13),
+    (157_766_400, 14),
+    (189_302_400, 15),
+    (220_924_800, 16),
+    (252_460_800, 17),
+    (283_996_800, 18),
+    (315_532_800, 19),
+    (362_793_600, 20),
+    (394_329_600, 21),
+    (425_865_600, 22),
+    (489_024_000, 23),
+    (567_993_600, 24),
+    (631_152_000, 25),
+    (662_688_000, 26),
+    (709_948_800, 27),
+    (741_484_800, 28),
+    (773_020_800, 29),
+    (820_454_400, 30),
+    (867_715_200, 31),
+    (915_148_800, 32),
+    (1_136_073_600, 33),
+    (1_230_768_000, 34),
+    (1_341_100_800, 35),
+    (1_435_708_800, 36),
+    (1_483_228_800, 37),
+];
+
+/// A TAI64N label: 64-bit second label plus nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tai64N {
+    pub label: u64,
+    pub nanos: u32,
+}
+
+impl Tai64N {
+    /// Parse the 24 hex digits svlogd writes after `@`.
+    pub fn parse(stamp: &str) -> Option<Self> {
+        if stamp.len() != 24 || !stamp.is_ascii() {
+            return None;
+        }
+        let label = u64::from_str_radix(&stamp[..16], 16).ok()?;
+        let nanos = u32::from_str_radix(&stamp[16..], 16).ok()?;
+        Some(Tai64N { label, nanos })
+    }
+
+    /// Decode the 12-byte big-endian form used in `supervise/status`.
+    pub fn from_bytes(bytes: [u8; 12]) -> Self {
+        let (label, nanos) = bytes.split_at(8);
+        Tai64N {
+            label: u64::from_be_bytes(label.try_into().expect("8 bytes")),
+            nanos: u32::from_be_bytes(nanos.try_into().expect("4 bytes")),
+        }
+    }
+
+    /// Label for `time` the way runit and svlogd compute it.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Tai64N {
+            label: RUNIT_TAI64_UNIX_OFFSET + since_epoch.as_secs(),
+            nanos: since_epoch.subsec_nanos(),
+        }
+    }
+
+    /// Wall-clock time under runit's convention; the inverse of [`Tai64N::from_system_time`].
+    pub fn to_system_time(self) -> SystemTime {
+        let secs = self.label.saturating_sub(RUNIT_TAI64_UNIX_OFFSET);
+        UNIX_EPOCH + Duration::new(secs, self.nanos.min(999_999_999))
+    }
+
+    /// Label for `time` on the real TAI scale, counting every leap second since 1972.
+    pub fn from_utc(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let unix = since_epoch.as_secs();
+        Tai64N {
+            label: TAI64_EPOCH_LABEL + unix + tai_minus_utc(unix),
+            nanos: since_epoch.subsec_nanos(),
+        }
+    }
+
+    /// Wall-clock time of a label on the real TAI scale. A label inside a leap
+    /// second maps to the second after it.
+    pub fn to_utc(self) -> SystemTime {
+        let tai = self.label.saturating_sub(TAI64_EPOCH_LABEL);
+        let offset = LEAP_SECONDS
+            .iter()
+            .rev()
+            .find(|(start, offset)| tai >= start + offset)
+            .map_or(10, |(_, offset)| *offset);
+        UNIX_EPOCH + Duration::new(tai.saturating_sub(offset), self.nanos.min(999_999_999))
+    }
+}
+
+impl fmt::Display for Tai64N {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}{:08x}", self.label, self.nanos)
+    }
+}
+
+/// TAI-UTC in seconds at a Unix time (10 before 1972).
+pub fn tai_minus_utc(unix: u64) -> u64 {
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(start, _)| unix >= *start)
+        .map_or(10, |(_, offset)| *offset)
+}
+
+/// Hex label for `time` as svlogd would write it, without the leading `@`.
+pub fn encode_tai64n(time: SystemTime) -> String {
+    Tai64N::from_system_time(time).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tai64N, encode_tai64n, tai_minus_utc};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn round_trips_runit_labels() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        let stamp = encode_tai64n(time);
+        assert_eq!(stamp, "400000006553f10a075bcd15");
+        assert_eq!(Tai64N::parse(&stamp).unwrap().to_system_time(), time);
+    }
+
+    #[test]
+    fn applies_leap_seconds() {
+        assert_eq!(tai_minus_utc(0), 10);
+        assert_eq!(tai_minus_utc(1_483_228_799), 36);
+        assert_eq!(tai_minus_utc(1_483_228_800), 37);
+
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let label = Tai64N::from_utc(time);
+        assert_eq!(label.label - (1 << 62), 1_700_000_037);
+        assert_eq!(label.to_utc(), time);
+    }
+}
@@ -0,0 +1,213 @@
+//! Parameterized services: a `name@` template directory stamped out as `name@param`.
+use crate::{Result, ServiceError, ServiceInfo, ServiceManager};
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Separates the template name from the instance parameter, as in `wpa_supplicant@wlan0`.
+pub const INSTANCE_SEPARATOR: char = '@';
+
+/// Placeholder replaced by the instance parameter in every template file.
+pub const INSTANCE_PLACEHOLDER: &str = "%i";
+
+/// Split `agetty@tty1` into `("agetty", "tty1")`; `None` for plain names and templates.
+pub fn split_instance(name: &str) -> Option<(&str, &str)> {
+    let (template, param) = name.split_once(INSTANCE_SEPARATOR)?;
+    (!template.is_empty() && !param.is_empty() && !param.contains(INSTANCE_SEPARATOR))
+        .then_some((template, param))
+}
+
+/// Instances in `services` keyed by template, each group in listing order.
+pub fn group_instances(services: &[ServiceInfo]) -> BTreeMap<&str, Vec<&ServiceInfo>> {
+    let mut groups: BTreeMap<&str, Vec<&ServiceInfo>> = BTreeMap::new();
+    for info in services {
+        if let Some(template) = info.template.as_deref() {
+            groups.entry(template).or_default().push(info);
+        }
+    }
+    groups
+}
+
+pub(crate) fn is_template_dir_name(name: &str) -> bool {
+    name.len() > 1 && name.ends_with(INSTANCE_SEPARATOR)
+}
+
+impl ServiceManager {
+    /// Names of the templates under the definitions directory, without the trailing `@`.
+    pub fn templates(&self) -> Result<Vec<String>> {
+        let mut templates: Vec<String> = self
+            .backend
+            .definition_names()?
+            .into_iter()
+            .filter_map(|name| {
+                is_template_dir_name(&name).then(|| name[..name.len() - 1].to_string())
+            })
+            .collect();
+        templates.sort();
+        Ok(templates)
+    }
+
+    /// Definitions created from `template`, sorted.
+    pub fn instances(&self, template: &str) -> Result<Vec<String>> {
+        let mut instances: Vec<String> = self
+            .backend
+            .definition_names()?
+            .into_iter()
+            .filter(|name| split_instance(name).is_some_and(|(base, _)| base == template))
+            .collect();
+        instances.sort();
+        Ok(instances)
+    }
+
+    /// Create the `template@param` definition from the `template@` directory.
+    ///
+    /// Files are copied with their permissions and every `%i` replaced by `param`; runsv
+    /// state such as `supervise/` is skipped. Like [`ServiceManager::create_service`] the
+    /// copy is staged and renamed into place, and the instance is not enabled.
+    pub fn create_instance(&self, template: &str, param: &str) -> Result<PathBuf> {
+        let service = format!("{template}{INSTANCE_SEPARATOR}{param}");
+        if split_instance(&service) != Some((template, param)) {
+            return Err(ServiceError::InvalidServiceName(service));
+        }
+        self.validate_service_name(&service)?;
+
+        let source = self
+            .definitions_dir
+            .join(format!("{template}{INSTANCE_SEPARATOR}"));
+        if !source.is_dir() {
+            return Err(ServiceError::DefinitionMissing {
+                service: format!("{template}{INSTANCE_SEPARATOR}"),
+                path: source,
+            });
+        }
+
+        let target = self.definitions_dir.join(&service);
+        if std::fs::symlink_metadata(&target).is_ok() {
+            return Err(ServiceError::DefinitionExists {
+                service,
+                path: target,
+            });
+        }
+
+        let staging = self.definitions_dir.join(format!(".{service}.new"));
+        let _ = std::fs::remove_dir_all(&staging);
+        let result = copy_template(&source, &staging, param).and_then(|()| {
+            std::fs::rename(&staging, &target).map_err(|err| ServiceError::from_io(&target, err))
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_dir_all(&staging);
+        }
+        result.map(|()| target)
+    }
+}
+
+fn copy_template(source: &Path, dest: &Path, param: &str) -> Result<()> {
+    std::fs::create_dir(dest).map_err(|err| ServiceError::from_io(dest, err))?;
+    let read_dir = std::fs::read_dir(source).map_err(|err| ServiceError::from_io(source, err))?;
+    for entry in read_dir {
+        let entry = entry.map_err(|err| ServiceError::from_io(source, err))?;
+        let from = entry.path();
+        let to = dest.join(entry.file_name());
+        if entry.file_name() == "supervise" {
+            continue;
+        }
+
+        let meta =
+            std::fs::symlink_metadata(&from).map_err(|err| ServiceError::from_io(&from, err))?;
+        if meta.is_dir() {
+            copy_template(&from, &to, param)?;
+        } else if meta.file_type().is_symlink() {
+            let target =
+                std::fs::read_link(&from).map_err(|err| ServiceError::from_io(&from, err))?;
+            std::os::unix::fs::symlink(target, &to)
+                .map_err(|err| ServiceError::from_io(&to, err))?;
+        } else {
+            let contents = std::fs::read(&from).map_err(|err| ServiceError::from_io(&from, err))?;
+            let contents = match String::from_utf8(contents) {
+                Ok(text) => text.replace(INSTANCE_PLACEHOLDER, param).into_bytes(),
+                Err(err) => err.into_bytes(),
+            };
+            std::fs::write(&to, contents).map_err(|err| ServiceError::from_io(&to, err))?;
+            std::fs::set_permissions(
+                &to,
+                std::fs::Permissions::from_mode(meta.permissions().mode()),
+            )
+            .map_err(|err| ServiceError::from_io(&to, err))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_instance;
+    use crate::testutil::FakeRunit;
+    use crate::{ServiceError, ServiceManager};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn splits_instance_names() {
+        assert_eq!(
+            split_instance("wpa_supplicant@wlan0"),
+            Some(("wpa_supplicant", "wlan0"))
+        );
+        assert_eq!(split_instance("agetty@"), None);
+        assert_eq!(split_instance("sshd"), None);
+    }
+
+    #[test]
+    fn stamps_out_instances_from_template() {
+        let root = std::env::temp_dir().join(format!("runkit-template-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let template = root.join("sv/agetty@");
+        std::fs::create_dir_all(template.join("supervise")).unwrap();
+        std::fs::write(template.join("run"), "#!/bin/sh\nexec agetty %i 38400\n").unwrap();
+        std::fs::set_permissions(template.join("run"), std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+
+        let path = manager.create_instance("agetty", "tty1").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path.join("run")).unwrap(),
+            "#!/bin/sh\nexec agetty tty1 38400\n"
+        );
+        let mode = std::fs::metadata(path.join("run"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(!path.join("supervise").exists());
+
+        manager.create_instance("agetty", "tty2").unwrap();
+        assert_eq!(manager.templates().unwrap(), ["agetty"]);
+        assert_eq!(
+            manager.instances("agetty").unwrap(),
+            ["agetty@tty1", "agetty@tty2"]
+        );
+        assert!(matches!(
+            manager.create_instance("agetty", "tty1"),
+            Err(ServiceError::DefinitionExists { .. })
+        ));
+        assert!(matches!(
+            manager.create_instance("getty", "tty1"),
+            Err(ServiceError::DefinitionMissing { .. })
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn refuses_to_run_the_template_itself() {
+        let tree = FakeRunit::new("template-dir");
+        tree.add_service("agetty@", "#!/bin/sh\nexec agetty %i 38400\n");
+        let manager = tree.manager();
+        manager.create_instance("agetty", "tty1").unwrap();
+
+        let invalid = |result: crate::Result<()>| matches!(result, Err(ServiceError::InvalidServiceName(name)) if name == "agetty@");
+        assert!(invalid(manager.enable("agetty@")));
+        assert!(invalid(manager.start("agetty@")));
+        assert!(invalid(manager.once("agetty@")));
+        assert!(manager.control("agetty@").is_err());
+        assert!(!tree.enabled_dir().join("agetty@").exists());
+        manager.enable("agetty@tty1").unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+//! Throwaway runit trees for tests that cannot rely on root or a live runsvdir.
+//!
+//! Enabled by the `testutil` feature:
+//!
+//! ```no_run
+//! # use runkit_core::testutil::{FakeRunit, SvScript};
+//! # use std::time::SystemTime;
+//! let tree = FakeRunit::new("listing");
+//! tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+//! tree.enable("sshd");
+//! tree.set_running("sshd", 812, SystemTime::now());
+//! tree.install_sv(&SvScript::new().respond("-w 7 -v up sshd", "ok: run: sshd: (pid 812) 1s\n"));
+//! let services = tree.manager().list_services().unwrap();
+//! ```
+use crate::{ServiceManager, SuperviseStatus, SupervisedProcessState, Tai64N, WantState};
+use std::os::unix::fs::{PermissionsExt, symlink};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+static NEXT_TREE: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary root holding `etc/sv` and `var/service`, removed again on drop.
+#[derive(Debug)]
+pub struct FakeRunit {
+    root: PathBuf,
+}
+
+impl FakeRunit {
+    /// Create an empty tree under the system temp directory. `label` only makes the
+    /// directory name easier to recognise.
+    pub fn new(label: &str) -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "runkit-testutil-{label}-{}-{}",
+            std::process::id(),
+            NEXT_TREE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        let tree = FakeRunit { root };
+        create_dir(&tree.definitions_dir());
+        create_dir(&tree.enabled_dir());
+        tree
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn definitions_dir(&self) -> PathBuf {
+        self.root.join("etc/sv")
+    }
+
+    pub fn enabled_dir(&self) -> PathBuf {
+        self.root.join("var/service")
+    }
+
+    /// socklog root of the tree's manager, so the host's syslog never leaks into a test.
+    pub fn socklog_dir(&self) -> PathBuf {
+        self.root.join("var/log/socklog")
+    }
+
+    /// Where [`FakeRunit::install_sv`] puts the `sv` stub.
+    pub fn sv_path(&self) -> PathBuf {
+        self.root.join("bin/sv")
+    }
+
+    /// Manager over this tree, using the `sv` stub once one is installed.
+    pub fn manager(&self) -> ServiceManager {
+        let manager = ServiceManager::new(self.definitions_dir(), self.enabled_dir())
+            .with_socklog_dir(self.socklog_dir());
+        if self.sv_path().is_file() {
+            manager.with_sv_command(self.sv_path())
+        } else {
+            manager
+        }
+    }
+
+    /// Add a definition with an executable `run` script and return its directory.
+    pub fn add_service(&self, name: &str, run_script: &str) -> PathBuf {
+        let dir = self.definitions_dir().join(name);
+        create_dir(&dir);
+        write_executable(&dir.join("run"), run_script);
+        dir
+    }
+
+    /// Give the definition a `log/` service running svlogd into `log/main`.
+    pub fn add_logger(&self, name: &str) -> PathBuf {
+        let dir = self.definitions_dir().join(name).join("log");
+        create_dir(&dir.join("main"));
+        write_executable(&dir.join("run"), "#!/bin/sh\nexec svlogd -tt main\n");
+        dir
+    }
+
+    /// Write a file inside the definition, e.g. `down`, `check` or `conf`.
+    pub fn write_file(&self, name: &str, file: &str, contents: &str) -> PathBuf {
+        let path = self.definitions_dir().join(name).join(file);
+        if let Some(parent) = path.parent() {
+            create_dir(parent);
+        }
+        std::fs::write(&path, contents).expect("write service file");
+        path
+    }
+
+    /// Link the definition into the enabled directory.
+    pub fn enable(&self, name: &str) {
+        symlink(
+            self.definitions_dir().join(name),
+            self.enabled_dir().join(name),
+        )
+        .expect("link service");
+    }
+
+    /// Store a `supervise/status` record for an enabled service, as runsv would. Pass
+    /// `<name>/log` to set the state of its logger.
+    pub fn write_status(&self, name: &str, status: &SuperviseStatus) {
+        let dir = self.definitions_dir().join(name).join("supervise");
+        create_dir(&dir);
+        std::fs::write(dir.join("status"), status.to_bytes()).expect("write supervise status");
+    }
+
+    /// Record the service as running under `pid` since `since`.
+    pub fn set_running(&self, name: &str, pid: u32, since: SystemTime) {
+        self.write_status(
+            name,
+            &status(since, Some(pid), SupervisedProcessState::Running),
+        );
+    }
+
+    /// Record the service as down since `since`, with runsv told to keep it down.
+    pub fn set_down(&self, name: &str, since: SystemTime) {
+        let mut down = status(since, None, SupervisedProcessState::Down);
+        down.want = Some(WantState::Down);
+        self.write_status(name, &down);
+    }
+
+    /// Install an `sv` stub that answers according to `script` and logs each call.
+    pub fn install_sv(&self, script: &SvScript) -> PathBuf {
+        let path = self.sv_path();
+        if let Some(parent) = path.parent() {
+            create_dir(parent);
+        }
+        write_executable(&path, &script.render(&self.calls_path()));
+        path
+    }
+
+    /// Arguments of every `sv` stub invocation so far, one string per call.
+    pub fn sv_calls(&self) -> Vec<String> {
+        std::fs::read_to_string(self.calls_path())
+            .map(|calls| calls.lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    fn calls_path(&self) -> PathBuf {
+        self.root.join("sv.calls")
+    }
+}
+
+impl Drop for FakeRunit {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Canned `sv` replies keyed by the exact argument list, joined with single spaces.
+///
+/// Calls without a reply print a `fail:` line to stderr and exit 1.
+#[derive(Debug, Clone, Default)]
+pub struct SvScript {
+    replies: Vec<SvReply>,
+}
+
+#[derive(Debug, Clone)]
+struct SvReply {
+    args: String,
+    stdout: String,
+    stderr: String,
+    exit_code: i32,
+}
+
+impl SvScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Print `stdout` and exit successfully when called with `args`.
+    pub fn respond(mut self, args: &str, stdout: &str) -> Self {
+        self.replies.push(SvReply {
+            args: args.to_string(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+        });
+        self
+    }
+
+    /// Print `stderr` and exit with `exit_code` when called with `args`.
+    pub fn fail(mut self, args: &str, stderr: &str, exit_code: i32) -> Self {
+        self.replies.push(SvReply {
+            args: args.to_string(),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+            exit_code,
+        });
+        self
+    }
+
+    fn render(&self, calls: &Path) -> String {
+        let mut script = format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$*\" >> {}\ncase \"$*\" in\n",
+            shell_quote(&calls.to_string_lossy())
+        );
+        for reply in &self.replies {
+            script.push_str(&format!(
+                "{})\n    printf '%s' {}\n    printf '%s' {} >&2\n    exit {}\n    ;;\n",
+                shell_quote(&reply.args),
+                shell_quote(&reply.stdout),
+                shell_quote(&reply.stderr),
+                reply.exit_code
+            ));
+        }
+        script.push_str(
+            "*)\n    echo \"fail: $*: no scripted reply\" >&2\n    exit 1\n    ;;\nesac\n",
+        );
+        script
+    }
+}
+
+fn status(since: SystemTime, pid: Option<u32>, state: SupervisedProcessState) -> SuperviseStatus {
+    let changed = Tai64N::from_system_time(since);
+    SuperviseStatus {
+        changed_tai64: changed.label,
+        changed_nanos: changed.nanos,
+        pid,
+        paused: false,
+        want: None,
+        term_sent: false,
+        state,
+    }
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn create_dir(path: &Path) {
+    std::fs::create_dir_all(path).expect("create test directory");
+}
+
+fn write_executable(path: &Path, contents: &str) {
+    std::fs::write(path, contents).expect("write script");
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .expect("make script executable");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FakeRunit, SvScript};
+    use crate::{ServiceError, ServiceRuntimeState};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn builds_a_listable_tree_with_scripted_sv() {
+        let tree = FakeRunit::new("selftest");
+        tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        tree.add_logger("sshd");
+        tree.add_service("ntpd", "#!/bin/sh\nexec ntpd\n");
+        tree.enable("sshd");
+        tree.enable("ntpd");
+        let since = SystemTime::now() - Duration::from_secs(30);
+        tree.set_running("sshd", 812, since);
+        tree.set_running("sshd/log", 813, since);
+        tree.set_down("ntpd", since);
+        tree.install_sv(
+            &SvScript::new()
+                .respond("-w 7 -v up ntpd", "ok: run: ntpd: (pid 900) 0s\n")
+                .fail(
+                    "-w 7 -v down sshd",
+                    "warning: sshd: unable to open supervise/ok\n",
+                    1,
+                ),
+        );
+
+        let manager = tree.manager();
+        let services = manager.list_services().unwrap();
+        assert_eq!(services.len(), 2);
+        assert!(
+            services[1]
+                .log_state
+                .as_ref()
+                .is_some_and(|log| log.is_running())
+        );
+        assert!(matches!(
+            services[1].runtime_state,
+            ServiceRuntimeState::Running { pid: 812, .. }
+        ));
+        assert!(services[0].runtime_state.is_down());
+
+        manager.start("ntpd").unwrap();
+        assert!(matches!(
+            manager.stop("sshd"),
+            Err(ServiceError::SvCommand { .. })
+        ));
+        assert_eq!(tree.sv_calls(), ["-w 7 -v up ntpd", "-w 7 -v down sshd"]);
+
+        let root = tree.root().to_path_buf();
+        drop(tree);
+        assert!(!root.exists());
+    }
+}
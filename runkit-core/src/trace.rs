@@ -0,0 +1,13 @@
+//! Diagnostics through `tracing` when the `tracing` feature is enabled.
+//!
+//! Spans come from `#[cfg_attr(feature = "tracing", tracing::instrument)]` on the functions
+//! themselves; `trace_debug!` expands to nothing without the feature, so event call sites
+//! stay free of `cfg` attributes. Only pass values that are used elsewhere too, or they
+//! turn into unused variables.
+
+macro_rules! trace_debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
@@ -0,0 +1,418 @@
+//! Translating systemd `.service` units into runit definitions, for software that only
+//! ships a unit file.
+use crate::{INSTANCE_PLACEHOLDER, Result, RunScriptSpec, ServiceError};
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// A unit translated into a [`RunScriptSpec`], together with what could not be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitConversion {
+    pub spec: RunScriptSpec,
+    /// One note per directive that was dropped or only approximated.
+    pub warnings: Vec<String>,
+}
+
+/// What `Restart=` asks for after the main process exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Restart {
+    Always,
+    No,
+    OnSuccess,
+    OnFailure,
+    OnAbnormal,
+}
+
+impl UnitConversion {
+    /// Translate the contents of a `.service` unit.
+    ///
+    /// `ExecStart`, `User`, `Group`, `Environment`, `WorkingDirectory`, `Restart` and
+    /// `RestartSec` carry over; output always goes to svlogd, the closest thing to the
+    /// journal. Restart policies other than `always` become a `finish` script that
+    /// takes the service down instead. Fails without an `ExecStart` to run.
+    pub fn parse(unit: &str) -> Result<Self> {
+        let mut warnings = Vec::new();
+        let mut exec_start: Vec<String> = Vec::new();
+        let mut user = None;
+        let mut group = None;
+        let mut env = Vec::new();
+        let mut working_dir = None;
+        // systemd's default.
+        let mut restart = Restart::No;
+        let mut restart_sec = None;
+        let mut kind = String::from("simple");
+
+        let mut section = String::new();
+        for line in logical_lines(unit) {
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                section = name.to_string();
+                continue;
+            }
+            if section != "Service" {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                // An empty assignment resets the list, as in drop-ins.
+                "ExecStart" if value.is_empty() => exec_start.clear(),
+                "ExecStart" => exec_start.push(value.to_string()),
+                "User" => user = Some(value.to_string()),
+                "Group" => group = Some(value.to_string()),
+                "Environment" => {
+                    for word in split_words(value) {
+                        match word.split_once('=') {
+                            Some((name, value)) if !name.is_empty() => {
+                                env.push((name.to_string(), value.to_string()));
+                            }
+                            _ => warnings
+                                .push(format!("Environment= entry `{word}` is not NAME=VALUE")),
+                        }
+                    }
+                }
+                "WorkingDirectory" => {
+                    let dir = value.strip_prefix('-').unwrap_or(value);
+                    if dir.starts_with('~') {
+                        warnings.push(format!(
+                            "WorkingDirectory={value} depends on the user's home and was dropped"
+                        ));
+                    } else if !dir.is_empty() {
+                        working_dir = Some(dir.to_string());
+                    }
+                }
+                "Restart" => {
+                    restart = match value {
+                        "always" | "on-watchdog" => Restart::Always,
+                        "no" => Restart::No,
+                        "on-success" => Restart::OnSuccess,
+                        "on-failure" => Restart::OnFailure,
+                        "on-abnormal" | "on-abort" => Restart::OnAbnormal,
+                        other => {
+                            warnings.push(format!("Restart={other} is unknown; restarting always"));
+                            Restart::Always
+                        }
+                    };
+                }
+                "RestartSec" => match parse_timespan(value) {
+                    Some(delay) => restart_sec = Some(delay),
+                    None => warnings.push(format!("RestartSec={value} is not a time span")),
+                },
+                "Type" => kind = value.to_string(),
+                _ => warnings.push(format!("{key}= has no runit equivalent and was dropped")),
+            }
+        }
+
+        if exec_start.len() > 1 {
+            warnings.push(format!(
+                "{} ExecStart= lines; only the last one runs",
+                exec_start.len()
+            ));
+        }
+        let Some(exec) = exec_start.pop() else {
+            return Err(ServiceError::InvalidUnit(
+                "the [Service] section has no ExecStart=".to_string(),
+            ));
+        };
+        let command = exec_command(&exec, &mut warnings);
+        for name in env.iter().map(|(name, _)| name) {
+            if command.contains(&format!("${name}")) || command.contains(&format!("${{{name}}}")) {
+                warnings.push(format!(
+                    "ExecStart= expands ${name}, which chpst only sets for the command, not \
+                     for the run script's shell"
+                ));
+            }
+        }
+        match kind.as_str() {
+            "simple" | "exec" | "notify" | "notify-reload" | "dbus" | "idle" => {}
+            "forking" => warnings.push(
+                "Type=forking: runsv supervises the process it starts, so the command has to \
+                 stay in the foreground"
+                    .to_string(),
+            ),
+            "oneshot" => warnings.push(
+                "Type=oneshot: runsv treats the command as a long-running service".to_string(),
+            ),
+            other => warnings.push(format!("Type={other} is unknown")),
+        }
+
+        let mut spec = RunScriptSpec::new(command)
+            .with_logger(true)
+            .with_stderr_redirect(true);
+        match (user, group) {
+            (Some(user), Some(group)) => spec = spec.with_user(format!("{user}:{group}")),
+            (Some(user), None) => spec = spec.with_user(user),
+            (None, Some(group)) => {
+                warnings.push(format!("Group={group} without User= was dropped"));
+            }
+            (None, None) => {}
+        }
+        if let Some(dir) = working_dir {
+            spec = spec.with_working_dir(dir);
+        }
+        for (name, value) in env {
+            spec = spec.with_env(name, value);
+        }
+        if let Some(finish) = render_finish(restart, restart_sec) {
+            spec = spec.with_finish(finish);
+        }
+        Ok(UnitConversion { spec, warnings })
+    }
+}
+
+/// Lines with comments dropped and `\` continuations joined.
+fn logical_lines(unit: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut pending = String::new();
+    for line in unit.lines() {
+        let line = line.trim();
+        if pending.is_empty() && (line.starts_with('#') || line.starts_with(';')) {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(head) => {
+                pending.push_str(head);
+                pending.push(' ');
+            }
+            None => {
+                pending.push_str(line);
+                if !pending.is_empty() {
+                    lines.push(std::mem::take(&mut pending));
+                }
+            }
+        }
+    }
+    if !pending.trim().is_empty() {
+        lines.push(pending);
+    }
+    lines
+}
+
+/// The command line for the run script: prefixes stripped, specifiers checked.
+fn exec_command(exec: &str, warnings: &mut Vec<String>) -> String {
+    let mut command = exec;
+    let mut custom_argv0 = false;
+    while let Some(first) = command.chars().next() {
+        match first {
+            '-' | '+' | '!' | ':' | '|' => {}
+            '@' => custom_argv0 = true,
+            _ => break,
+        }
+        command = &command[first.len_utf8()..];
+    }
+    let mut command = command.trim().to_string();
+    if custom_argv0 {
+        // `@/usr/bin/foo foo-name args`: the second word is argv[0] only.
+        let mut words = command.splitn(3, char::is_whitespace);
+        let program = words.next().unwrap_or_default();
+        let _argv0 = words.next();
+        let rest = words.next().unwrap_or_default();
+        warnings.push("ExecStart= sets argv[0] with `@`, which was dropped".to_string());
+        command = format!("{program} {rest}").trim().to_string();
+    }
+
+    let mut specifiers = Vec::new();
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        match chars.next() {
+            Some('%') | None => {}
+            Some(specifier) if format!("%{specifier}") == INSTANCE_PLACEHOLDER => {}
+            Some(specifier) if !specifiers.contains(&specifier) => specifiers.push(specifier),
+            Some(_) => {}
+        }
+    }
+    for specifier in specifiers {
+        warnings.push(format!(
+            "ExecStart= uses the specifier %{specifier}, which was left as is"
+        ));
+    }
+    command.replace("%%", "%")
+}
+
+/// Words of an `Environment=` value, honoring quotes and backslash escapes.
+fn split_words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                if let Some(escaped) = chars.next() {
+                    word.push(escaped);
+                }
+                in_word = true;
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (c, Some(open)) if c == open => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (c, _) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// A systemd time span such as `5`, `500ms` or `1min 30s`.
+fn parse_timespan(value: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut any = false;
+    for token in value.split_whitespace() {
+        let split = token
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(token.len());
+        let (number, unit) = token.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let seconds = match unit {
+            "" | "s" | "sec" | "second" | "seconds" => 1.0,
+            "ms" | "msec" => 0.001,
+            "m" | "min" | "minute" | "minutes" => 60.0,
+            "h" | "hr" | "hour" | "hours" => 3600.0,
+            _ => return None,
+        };
+        total += Duration::try_from_secs_f64(number * seconds).ok()?;
+        any = true;
+    }
+    any.then_some(total)
+}
+
+/// A `finish` script implementing `restart` and the delay before the next start, or
+/// `None` when runsv's own behavior already matches.
+fn render_finish(restart: Restart, delay: Option<Duration>) -> Option<String> {
+    // runsv passes the exit code, or -1 when a signal ended the process.
+    let stop = match restart {
+        Restart::Always => None,
+        Restart::No => Some("exec sv down ."),
+        Restart::OnSuccess => Some("[ \"$1\" = 0 ] || exec sv down ."),
+        Restart::OnFailure => Some("[ \"$1\" = 0 ] && exec sv down ."),
+        Restart::OnAbnormal => Some("[ \"$1\" = -1 ] || exec sv down ."),
+    };
+    // runsv already waits a second between starts.
+    let delay = delay
+        .map(|delay| delay.as_secs() + u64::from(delay.subsec_nanos() > 0))
+        .filter(|seconds| *seconds > 1);
+    if stop.is_none() && delay.is_none() {
+        return None;
+    }
+    let mut script = String::from("#!/bin/sh\n");
+    if let Some(stop) = stop {
+        script.push_str(stop);
+        script.push('\n');
+    }
+    if let Some(seconds) = delay {
+        let _ = writeln!(script, "exec sleep {seconds}");
+    }
+    Some(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnitConversion;
+    use crate::ServiceError;
+
+    #[test]
+    fn converts_service_section() {
+        let unit = "\
+[Unit]
+Description=Example daemon
+
+[Service]
+Type=simple
+User=_example
+Group=_example
+WorkingDirectory=-/var/lib/example
+Environment=\"EXAMPLE_OPTS=-v -x\" LANG=C
+ExecStart=/usr/bin/exampled \\
+    --foreground --config /etc/example.conf
+Restart=on-failure
+RestartSec=5s
+PIDFile=/run/example.pid
+
+[Install]
+WantedBy=multi-user.target
+";
+        let conversion = UnitConversion::parse(unit).unwrap();
+        let spec = &conversion.spec;
+        assert_eq!(
+            spec.command(),
+            "/usr/bin/exampled  --foreground --config /etc/example.conf"
+        );
+        assert_eq!(spec.user(), Some("_example:_example"));
+        assert_eq!(
+            spec.working_dir(),
+            Some(std::path::Path::new("/var/lib/example"))
+        );
+        assert_eq!(
+            spec.env(),
+            [
+                ("EXAMPLE_OPTS".to_string(), "-v -x".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+            ]
+        );
+        assert!(spec.has_logger());
+        assert_eq!(
+            spec.finish(),
+            Some("#!/bin/sh\n[ \"$1\" = 0 ] && exec sv down .\nexec sleep 5\n")
+        );
+        assert_eq!(
+            conversion.warnings,
+            ["PIDFile= has no runit equivalent and was dropped"]
+        );
+    }
+
+    #[test]
+    fn notes_what_runit_cannot_express() {
+        let unit = "\
+[Service]
+Type=forking
+ExecStart=-@/usr/sbin/legacyd legacy -d %n --id %i
+Environment=PORT=80
+ExecStart=
+ExecStart=/usr/sbin/legacyd -p $PORT 100%%
+Restart=always
+";
+        let conversion = UnitConversion::parse(unit).unwrap();
+        assert_eq!(conversion.spec.command(), "/usr/sbin/legacyd -p $PORT 100%");
+        assert_eq!(conversion.spec.finish(), None);
+        assert_eq!(conversion.warnings.len(), 2);
+        assert!(conversion.warnings[0].contains("$PORT"));
+        assert!(conversion.warnings[1].contains("Type=forking"));
+
+        let conversion =
+            UnitConversion::parse("[Service]\nExecStart=@/usr/sbin/legacyd legacy -d %n --id %i\n")
+                .unwrap();
+        assert_eq!(conversion.spec.command(), "/usr/sbin/legacyd -d %n --id %i");
+        assert_eq!(
+            conversion.spec.finish(),
+            Some("#!/bin/sh\nexec sv down .\n")
+        );
+        assert!(conversion.warnings.iter().any(|w| w.contains("argv[0]")));
+        assert!(conversion.warnings.iter().any(|w| w.contains("%n")));
+        assert!(!conversion.warnings.iter().any(|w| w.contains("%i")));
+
+        assert!(matches!(
+            UnitConversion::parse("[Unit]\nDescription=nothing\n"),
+            Err(ServiceError::InvalidUnit(_))
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+//! Services still running code that a package upgrade has replaced on disk.
+use crate::{Result, ServiceError, ServiceManager};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// Mappings that never correspond to an installed file.
+const IGNORED_PREFIXES: &[&str] = &["/memfd:", "/dev/", "/SYSV", "/run/", "/tmp/", "/var/tmp/"];
+
+/// Executable mappings in `/proc/<pid>/maps` contents whose file has been deleted,
+/// the way `xcheckrestart` finds them. Sorted and without duplicates.
+pub fn deleted_mappings(maps: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = maps
+        .lines()
+        .filter_map(|line| {
+            // address perms offset dev inode pathname; the path itself may contain spaces.
+            let mut fields = line.splitn(6, char::is_whitespace);
+            let perms = fields.nth(1)?;
+            let path = fields.nth(3)?.trim_start().strip_suffix(DELETED_SUFFIX)?;
+            let relevant = perms.contains('x')
+                && path.starts_with('/')
+                && !IGNORED_PREFIXES
+                    .iter()
+                    .any(|prefix| path.starts_with(prefix));
+            relevant.then(|| PathBuf::from(path))
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Deleted executable and libraries used by `pid`.
+pub(crate) fn stale_files_of(proc_root: &Path, pid: u32) -> io::Result<Vec<PathBuf>> {
+    let dir = proc_root.join(pid.to_string());
+    let mut stale = deleted_mappings(&std::fs::read_to_string(dir.join("maps"))?);
+    if let Ok(exe) = std::fs::read_link(dir.join("exe"))
+        && let Some(path) = exe.to_str().and_then(|p| p.strip_suffix(DELETED_SUFFIX))
+    {
+        let path = PathBuf::from(path);
+        if !stale.contains(&path) {
+            stale.insert(0, path);
+        }
+    }
+    Ok(stale)
+}
+
+/// Whether `pid` still runs from a replaced file; false when it cannot be read.
+pub(crate) fn runs_stale_files(pid: u32) -> bool {
+    stale_files_of(Path::new("/proc"), pid).is_ok_and(|files| !files.is_empty())
+}
+
+impl ServiceManager {
+    /// Replaced files the service's main process still runs from; empty when it is
+    /// current or not running.
+    pub fn stale_files(&self, service: &str) -> Result<Vec<PathBuf>> {
+        let Some(pid) = self.status(service)?.pid() else {
+            return Ok(Vec::new());
+        };
+        match stale_files_of(Path::new("/proc"), pid) {
+            Ok(files) => Ok(files),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(ServiceError::from_io(format!("/proc/{pid}/maps"), err)),
+        }
+    }
+
+    /// Whether the service must be restarted to pick up upgraded binaries or libraries.
+    pub fn needs_restart(&self, service: &str) -> Result<bool> {
+        self.stale_files(service).map(|files| !files.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::deleted_mappings;
+    use std::path::PathBuf;
+
+    #[test]
+    fn finds_deleted_executable_mappings() {
+        let maps = "\
+55d0c0a00000-55d0c0a20000 r-xp 00002000 fd:01 1311 /usr/bin/sshd
+7f1c2a000000-7f1c2a1b0000 r-xp 00028000 fd:01 2201 /usr/lib/libcrypto.so.3 (deleted)
+7f1c2a400000-7f1c2a410000 r--p 00000000 fd:01 2202 /usr/lib/libz.so.1.3 (deleted)
+7f1c2a600000-7f1c2a610000 r-xp 00000000 00:01 4101 /memfd:jit (deleted)
+7f1c2a800000-7f1c2a810000 r-xp 00000000 fd:01 2203 /usr/lib/my lib.so (deleted)
+7f1c2a900000-7f1c2a910000 r-xp 00028000 fd:01 2201 /usr/lib/libcrypto.so.3 (deleted)
+7ffd1c000000-7ffd1c021000 rw-p 00000000 00:00 0 [stack]
+";
+        assert_eq!(
+            deleted_mappings(maps),
+            [
+                PathBuf::from("/usr/lib/libcrypto.so.3"),
+                PathBuf::from("/usr/lib/my lib.so"),
+            ]
+        );
+    }
+}
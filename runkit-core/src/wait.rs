@@ -0,0 +1,285 @@
+//! Blocking waits for a service to reach a wanted runtime state.
+use crate::{Result, ServiceError, ServiceManager, ServiceRuntimeState};
+use std::time::{Duration, Instant};
+
+/// Upper bound between two status checks. Status changes seen by a
+/// [`crate::ServiceWatcher`] wake the wait earlier.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+impl ServiceManager {
+    /// Block until `predicate` accepts the service's runtime state and return that state.
+    ///
+    /// The state is checked once up front, then again whenever runsv rewrites
+    /// `supervise/status` or every quarter second at the latest, so it also works with
+    /// custom backends. Fails with [`ServiceError::Timeout`] when `timeout` passes first.
+    pub fn wait_for<F>(
+        &self,
+        service: &str,
+        predicate: F,
+        timeout: Duration,
+    ) -> Result<ServiceRuntimeState>
+    where
+        F: Fn(&ServiceRuntimeState) -> bool,
+    {
+        self.validate_service_name(service)?;
+        let deadline = Instant::now() + timeout;
+        // Without a watcher (missing directories, inotify limits) plain polling still works.
+        let mut watcher = self.watch().ok();
+
+        loop {
+            let state = self.backend.status(service)?;
+            if predicate(&state) {
+                return Ok(state);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ServiceError::Timeout {
+                    service: service.to_string(),
+                    timeout,
+                });
+            }
+            let nap = POLL_INTERVAL.min(deadline - now);
+            match watcher.as_mut() {
+                Some(watcher) => {
+                    watcher.wait(nap)?;
+                }
+                None => std::thread::sleep(nap),
+            }
+        }
+    }
+
+    /// Restart through `supervise/control` and confirm that a new process came up.
+    ///
+    /// Returns the pid of the new process. Fails with [`ServiceError::Timeout`] when the
+    /// service is not running under a different pid within `timeout`, e.g. because the
+    /// run script keeps exiting.
+    pub fn restart_verified(&self, service: &str, timeout: Duration) -> Result<u32> {
+        let previous = match self.status(service)? {
+            ServiceRuntimeState::Running { pid, .. } => Some(pid),
+            _ => None,
+        };
+        self.control(service)?.restart()?;
+
+        let state = self.wait_for(
+            service,
+            |state| matches!(state, ServiceRuntimeState::Running { pid, .. } if Some(*pid) != previous),
+            timeout,
+        )?;
+        self.record_request(service);
+        match state {
+            ServiceRuntimeState::Running { pid, .. } => Ok(pid),
+            _ => unreachable!("wait_for only returns states accepted by the predicate"),
+        }
+    }
+
+    /// [`ServiceManager::wait_for`] until the service is running.
+    pub fn wait_until_running(
+        &self,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<ServiceRuntimeState> {
+        self.wait_for(service, ServiceRuntimeState::is_running, timeout)
+    }
+
+    /// Enable the service, bring it up as soon as runsvdir supervises it and wait until
+    /// it runs. An already enabled service is just started.
+    ///
+    /// runsvdir rescans the enabled directory every five seconds, so `timeout` has to
+    /// cover that as well as the service starting.
+    pub fn enable_now(&self, service: &str, timeout: Duration) -> Result<ServiceRuntimeState> {
+        let deadline = Instant::now() + timeout;
+        match self.enable(service) {
+            Ok(()) | Err(ServiceError::AlreadyEnabled(_)) => {}
+            Err(err) => return Err(err),
+        }
+        let control = self.control(service)?;
+        loop {
+            match control.up() {
+                Ok(()) => break,
+                Err(ServiceError::NotSupervised(_) | ServiceError::SupervisorNotRunning(_))
+                    if Instant::now() < deadline =>
+                {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                Err(ServiceError::NotSupervised(_) | ServiceError::SupervisorNotRunning(_)) => {
+                    return Err(ServiceError::Timeout {
+                        service: service.to_string(),
+                        timeout,
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        let state =
+            self.wait_until_running(service, deadline.saturating_duration_since(Instant::now()))?;
+        self.record_request(service);
+        Ok(state)
+    }
+
+    /// Take the service down, wait until it has stopped and only then disable it.
+    ///
+    /// Returns the state it stopped in, or `None` when no runsv was supervising it and
+    /// there was nothing to stop.
+    pub fn disable_now(
+        &self,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<Option<ServiceRuntimeState>> {
+        let state = match self.control(service)?.down() {
+            Ok(()) => Some(self.wait_until_stopped(service, timeout)?),
+            Err(ServiceError::NotSupervised(_) | ServiceError::SupervisorNotRunning(_)) => None,
+            Err(err) => return Err(err),
+        };
+        if state.is_some() {
+            self.record_request(service);
+        }
+        self.disable(service)?;
+        Ok(state)
+    }
+
+    /// [`ServiceManager::wait_for`] until the service is down or has failed.
+    pub fn wait_until_stopped(
+        &self,
+        service: &str,
+        timeout: Duration,
+    ) -> Result<ServiceRuntimeState> {
+        self.wait_for(
+            service,
+            |state| state.is_down() || state.is_failed(),
+            timeout,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::FakeRunit;
+    use crate::{
+        Result, ServiceBackend, ServiceError, ServiceLogEntry, ServiceManager, ServiceRuntimeState,
+    };
+    use std::ffi::CString;
+    use std::fs::OpenOptions;
+    use std::io::Read;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::{Duration, SystemTime};
+
+    /// Reports the service down for the first two checks, then running.
+    #[derive(Debug, Default)]
+    struct SlowStart {
+        checks: AtomicU32,
+    }
+
+    impl ServiceBackend for SlowStart {
+        fn definition_names(&self) -> Result<Vec<String>> {
+            Ok(vec!["sshd".into()])
+        }
+
+        fn is_enabled(&self, _service: &str) -> bool {
+            true
+        }
+
+        fn status(&self, _service: &str) -> Result<ServiceRuntimeState> {
+            let check = self.checks.fetch_add(1, Ordering::SeqCst);
+            if check < 2 {
+                Ok(ServiceRuntimeState::Down {
+                    since: Duration::ZERO,
+                    normally_up: true,
+                    started_at: SystemTime::now(),
+                })
+            } else {
+                Ok(ServiceRuntimeState::Running {
+                    pid: if check < 4 { 42 } else { 43 },
+                    uptime: Duration::ZERO,
+                    started_at: SystemTime::now(),
+                })
+            }
+        }
+
+        fn tail_logs(&self, _service: &str, _limit: usize) -> Result<Vec<ServiceLogEntry>> {
+            Ok(Vec::new())
+        }
+
+        fn enable(&self, _service: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn disable(&self, _service: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn waits_until_predicate_holds_or_times_out() {
+        let manager = ServiceManager::new("/nonexistent/sv", "/nonexistent/service")
+            .with_backend(SlowStart::default());
+        let state = manager
+            .wait_until_running("sshd", Duration::from_secs(5))
+            .unwrap();
+        assert!(matches!(
+            state,
+            ServiceRuntimeState::Running { pid: 42, .. }
+        ));
+
+        assert!(matches!(
+            manager.wait_until_stopped("sshd", Duration::from_millis(50)),
+            Err(ServiceError::Timeout { service, .. }) if service == "sshd"
+        ));
+    }
+
+    #[test]
+    fn verified_restart_waits_for_new_pid() {
+        let root = std::env::temp_dir().join(format!("runkit-restart-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let supervise = root.join("service/sshd/supervise");
+        std::fs::create_dir_all(&supervise).unwrap();
+        let control = supervise.join("control");
+        let fifo = CString::new(control.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&control)
+            .unwrap();
+
+        let backend = SlowStart::default();
+        backend.checks.store(2, Ordering::SeqCst);
+        let manager =
+            ServiceManager::new(root.join("sv"), root.join("service")).with_backend(backend);
+        assert_eq!(
+            manager
+                .restart_verified("sshd", Duration::from_secs(5))
+                .unwrap(),
+            43
+        );
+
+        let mut sent = String::new();
+        reader.read_to_string(&mut sent).unwrap();
+        assert_eq!(sent, "tcu");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn enable_now_needs_a_supervisor() {
+        let tree = FakeRunit::new("enable-now");
+        tree.add_service("sshd", "#!/bin/sh\nexec sshd -D\n");
+        let manager = tree.manager();
+
+        assert!(matches!(
+            manager.enable_now("sshd", Duration::from_millis(300)),
+            Err(ServiceError::Timeout { service, .. }) if service == "sshd"
+        ));
+        assert!(tree.enabled_dir().join("sshd").exists());
+
+        // Nothing supervises the link yet, so there is nothing to stop.
+        assert!(
+            manager
+                .disable_now("sshd", Duration::from_millis(300))
+                .unwrap()
+                .is_none()
+        );
+        assert!(!tree.enabled_dir().join("sshd").exists());
+    }
+}
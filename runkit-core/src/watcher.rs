@@ -0,0 +1,254 @@
+//! Event-driven change notification for the service tree.
+use crate::inotify::{Inotify, InotifyEvent};
+use crate::{Result, ServiceError, ServiceManager, validate_service_name};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Something changed for one service.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ServiceEvent {
+    /// A definition directory appeared in the definitions directory.
+    DefinitionAdded(String),
+    /// A definition directory was removed or renamed away.
+    DefinitionRemoved(String),
+    /// A link appeared in the enabled directory.
+    Enabled(String),
+    /// A link disappeared from the enabled directory.
+    Disabled(String),
+    /// runsv rewrote `supervise/status`.
+    StatusChanged(String),
+}
+
+impl ServiceEvent {
+    pub fn service(&self) -> &str {
+        match self {
+            ServiceEvent::DefinitionAdded(name)
+            | ServiceEvent::DefinitionRemoved(name)
+            | ServiceEvent::Enabled(name)
+            | ServiceEvent::Disabled(name)
+            | ServiceEvent::StatusChanged(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WatchTarget {
+    Definitions,
+    Enabled,
+    Supervise(String),
+}
+
+const DIR_MASK: u32 = libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+// runsv writes `status.new` and renames it over `status`.
+const SUPERVISE_MASK: u32 = libc::IN_MOVED_TO | libc::IN_CLOSE_WRITE;
+
+/// Emits [`ServiceEvent`]s from inotify watches on the definitions directory, the
+/// enabled directory and each enabled service's `supervise/` directory.
+#[derive(Debug)]
+pub struct ServiceWatcher {
+    inotify: Inotify,
+    definitions_dir: PathBuf,
+    enabled_dir: PathBuf,
+    watches: HashMap<i32, WatchTarget>,
+    /// Enabled services whose `supervise/` does not exist yet (runsv not started).
+    unwatched: HashSet<String>,
+    pending: VecDeque<ServiceEvent>,
+}
+
+impl ServiceWatcher {
+    pub fn new(manager: &ServiceManager) -> Result<Self> {
+        let inotify = Inotify::new().map_err(|err| ServiceError::from_io("inotify", err))?;
+        let mut watcher = ServiceWatcher {
+            inotify,
+            definitions_dir: manager.definitions_dir().to_path_buf(),
+            enabled_dir: manager.enabled_dir().to_path_buf(),
+            watches: HashMap::new(),
+            unwatched: HashSet::new(),
+            pending: VecDeque::new(),
+        };
+
+        for (dir, target) in [
+            (watcher.definitions_dir.clone(), WatchTarget::Definitions),
+            (watcher.enabled_dir.clone(), WatchTarget::Enabled),
+        ] {
+            let wd = watcher
+                .inotify
+                .add_watch(&dir, DIR_MASK)
+                .map_err(|err| ServiceError::from_io(&dir, err))?;
+            watcher.watches.insert(wd, target);
+        }
+
+        let enabled = std::fs::read_dir(&watcher.enabled_dir)
+            .map_err(|err| ServiceError::from_io(&watcher.enabled_dir, err))?;
+        for entry in enabled.flatten() {
+            if let Some(name) = entry.file_name().to_str() {
+                watcher.unwatched.insert(name.to_string());
+            }
+        }
+        watcher.watch_supervise_dirs();
+        Ok(watcher)
+    }
+
+    /// Events that happened since the last call, without blocking.
+    pub fn poll(&mut self) -> Result<Vec<ServiceEvent>> {
+        let events = self
+            .inotify
+            .read_events()
+            .map_err(|err| ServiceError::from_io("inotify", err))?;
+        for event in events {
+            self.handle(event);
+        }
+        self.watch_supervise_dirs();
+        Ok(self.pending.drain(..).collect())
+    }
+
+    /// Block until at least one event arrives or `timeout` passes; empty on timeout.
+    pub fn wait(&mut self, timeout: Duration) -> Result<Vec<ServiceEvent>> {
+        let events = self.poll()?;
+        if !events.is_empty() || !self.inotify.wait(timeout) {
+            return Ok(events);
+        }
+        self.poll()
+    }
+
+    fn handle(&mut self, event: InotifyEvent) {
+        if event.mask & libc::IN_IGNORED != 0 {
+            self.watches.remove(&event.wd);
+            return;
+        }
+        let Some(target) = self.watches.get(&event.wd).cloned() else {
+            return;
+        };
+        let name = event
+            .name
+            .as_ref()
+            .and_then(|name| name.to_str())
+            .map(str::to_string);
+        let created = event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0;
+
+        match (target, name) {
+            (WatchTarget::Supervise(service), Some(file)) if file == "status" => {
+                self.push(ServiceEvent::StatusChanged(service));
+            }
+            (WatchTarget::Supervise(_), _) => {}
+            (_, None) => {}
+            (_, Some(name)) if name.starts_with('.') || validate_service_name(&name).is_err() => {}
+            (WatchTarget::Definitions, Some(name)) if created => {
+                if self.definitions_dir.join(&name).is_dir() {
+                    self.push(ServiceEvent::DefinitionAdded(name));
+                }
+            }
+            (WatchTarget::Definitions, Some(name)) => {
+                self.push(ServiceEvent::DefinitionRemoved(name));
+            }
+            (WatchTarget::Enabled, Some(name)) if created => {
+                self.unwatched.insert(name.clone());
+                self.push(ServiceEvent::Enabled(name));
+            }
+            (WatchTarget::Enabled, Some(name)) => {
+                self.unwatch_supervise(&name);
+                self.push(ServiceEvent::Disabled(name));
+            }
+        }
+    }
+
+    fn push(&mut self, event: ServiceEvent) {
+        // Collapse bursts such as runsv rewriting status several times in a row.
+        if !self.pending.contains(&event) {
+            self.pending.push_back(event);
+        }
+    }
+
+    fn watch_supervise_dirs(&mut self) {
+        let names: Vec<String> = self.unwatched.iter().cloned().collect();
+        for name in names {
+            let dir = supervise_dir(&self.enabled_dir, &name);
+            if let Ok(wd) = self.inotify.add_watch(&dir, SUPERVISE_MASK) {
+                self.watches
+                    .insert(wd, WatchTarget::Supervise(name.clone()));
+                self.unwatched.remove(&name);
+            }
+        }
+    }
+
+    fn unwatch_supervise(&mut self, service: &str) {
+        self.unwatched.remove(service);
+        let target = WatchTarget::Supervise(service.to_string());
+        let wds: Vec<i32> = self
+            .watches
+            .iter()
+            .filter(|(_, watched)| **watched == target)
+            .map(|(wd, _)| *wd)
+            .collect();
+        for wd in wds {
+            self.inotify.rm_watch(wd);
+            self.watches.remove(&wd);
+        }
+    }
+}
+
+impl Iterator for ServiceWatcher {
+    type Item = Result<ServiceEvent>;
+
+    /// Blocks until the next event.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            match self.wait(Duration::from_secs(60)) {
+                Ok(events) => self.pending.extend(events),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl ServiceManager {
+    /// Start watching this manager's directories for changes.
+    pub fn watch(&self) -> Result<ServiceWatcher> {
+        ServiceWatcher::new(self)
+    }
+}
+
+fn supervise_dir(enabled_dir: &Path, service: &str) -> PathBuf {
+    enabled_dir.join(service).join("supervise")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceEvent;
+    use crate::ServiceManager;
+    use std::time::Duration;
+
+    #[test]
+    fn reports_definition_and_status_changes() {
+        let root = std::env::temp_dir().join(format!("runkit-watch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sv/sshd/supervise")).unwrap();
+        std::fs::create_dir_all(root.join("service")).unwrap();
+        let manager = ServiceManager::new(root.join("sv"), root.join("service"));
+        let mut watcher = manager.watch().unwrap();
+
+        std::fs::create_dir(root.join("sv/cron")).unwrap();
+        manager.enable("sshd").unwrap();
+        let events = watcher.wait(Duration::from_secs(1)).unwrap();
+        assert!(events.contains(&ServiceEvent::DefinitionAdded("cron".to_string())));
+        assert!(events.contains(&ServiceEvent::Enabled("sshd".to_string())));
+
+        std::fs::write(root.join("sv/sshd/supervise/status.new"), [0u8; 20]).unwrap();
+        std::fs::rename(
+            root.join("sv/sshd/supervise/status.new"),
+            root.join("sv/sshd/supervise/status"),
+        )
+        .unwrap();
+        let events = watcher.wait(Duration::from_secs(1)).unwrap();
+        assert_eq!(events, [ServiceEvent::StatusChanged("sshd".to_string())]);
+
+        manager.disable("sshd").unwrap();
+        let events = watcher.wait(Duration::from_secs(1)).unwrap();
+        assert_eq!(events, [ServiceEvent::Disabled("sshd".to_string())]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,15 +1,48 @@
-use runkit_core::{DesiredState, ServiceInfo, ServiceRuntimeState};
+use crate::daemon::{DaemonClient, DaemonError};
+use crate::formatting;
+use runkit_core::{
+    CheckOutcome, LogUsage, ProcessInfo, ServiceInfo, ServiceLogEntry, ServiceManager, ServiceOp,
+    ServiceQuery, ServiceScope, ServiceStats, SystemStage,
+};
 use serde::Deserialize;
 use serde_json::Value;
 use std::env;
 use std::path::PathBuf;
 use std::process::Command;
-use std::time::Duration;
+
+/// Newest runkitd response format this build understands.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Failure identifiers runkitd reports in `error_code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidName,
+    DefinitionMissing,
+    AlreadyEnabled,
+    NotEnabled,
+    SvFailure,
+    Io,
+    SupervisorNotRunning,
+    CheckFailed,
+    Timeout,
+    NotSupervised,
+    PermissionDenied,
+    Masked,
+    SystemCommand,
+    RemovalRefused,
+    /// Anything this build does not know yet.
+    #[serde(other)]
+    Other,
+}
 
 #[derive(Clone)]
 pub struct ActionDispatcher {
     helper_path: PathBuf,
     use_pkexec: bool,
+    user_scope: bool,
+    probe: Option<ServiceManager>,
+    daemon: Option<DaemonClient>,
 }
 
 impl Default for ActionDispatcher {
@@ -22,40 +55,162 @@ impl Default for ActionDispatcher {
             .or_else(|_| env::var("RUNKIT_HELPER_NO_PKEXEC"))
             .map(|value| value == "0" || value.eq_ignore_ascii_case("false"))
             .unwrap_or(true);
+        let user_scope = env::var("RUNKIT_USER_SCOPE")
+            .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let probe = if user_scope {
+            ServiceManager::for_scope(ServiceScope::User).ok()
+        } else {
+            Some(ServiceManager::detect())
+        };
 
         ActionDispatcher {
             helper_path,
             use_pkexec,
+            user_scope,
+            probe,
+            // The daemon serves the system scope only.
+            daemon: DaemonClient::from_env().filter(|_| !user_scope),
         }
     }
 }
 
 impl ActionDispatcher {
+    /// Runs a command that only reads state. Without pkexec the helper gets `--read-only`,
+    /// so runkitd itself refuses anything that is not a read.
+    fn query(
+        &self,
+        privileged: bool,
+        action: &str,
+        service: Option<&str>,
+        extra: &[&str],
+    ) -> Result<DaemonProcessResponse, String> {
+        self.execute(privileged, true, action, service, extra)
+    }
+
     fn execute(
         &self,
         privileged: bool,
+        read_only: bool,
         action: &str,
         service: Option<&str>,
         extra: &[&str],
     ) -> Result<DaemonProcessResponse, String> {
-        let use_pkexec = privileged && self.use_pkexec;
-        execute_helper(self.helper_path.clone(), use_pkexec, action, service, extra)
+        if let Some(daemon) = &self.daemon {
+            let mut params: Vec<&str> = service.into_iter().collect();
+            params.extend_from_slice(extra);
+            match daemon.call(action, &params) {
+                Ok(result) => {
+                    let response = serde_json::from_value(result)
+                        .map_err(|err| format!("Failed to decode runkitd response: {err}"))
+                        .and_then(check_protocol)?;
+                    // The daemon decides what needs root; whatever it refuses goes
+                    // through the helper, and pkexec where privileged.
+                    if response.error_code != Some(ErrorCode::PermissionDenied) {
+                        return Ok(response);
+                    }
+                }
+                Err(DaemonError::Rejected(message)) => return Err(message),
+                // Not running (any more); spawn the helper as usual.
+                Err(DaemonError::Unavailable(_)) => {}
+            }
+        }
+        // User services belong to the caller, so they never need elevation.
+        let use_pkexec = privileged && self.use_pkexec && !self.user_scope;
+        execute_helper(
+            self.helper_path.clone(),
+            use_pkexec,
+            self.user_scope,
+            read_only,
+            action,
+            service,
+            extra,
+        )
+        .and_then(check_protocol)
+    }
+
+    pub fn user_scope(&self) -> bool {
+        self.user_scope
+    }
+
+    /// The in-process manager, when `readable` says it sees everything runkitd would.
+    /// Reading directly spares an authentication prompt and a process spawn.
+    fn direct(
+        &self,
+        readable: impl FnOnce(&ServiceManager) -> runkit_core::Result<bool>,
+    ) -> Option<&ServiceManager> {
+        self.probe
+            .as_ref()
+            .filter(|probe| readable(probe).unwrap_or(false))
+    }
+
+    /// Whether the action has to go through pkexec; skipped when the user can already
+    /// write the service's control files or the enabled directory.
+    fn needs_elevation(&self, action: &str, service: &str) -> bool {
+        let Some(capability) = self
+            .probe
+            .as_ref()
+            .and_then(|probe| probe.can_control(service).ok())
+        else {
+            return true;
+        };
+        match action {
+            "enable" => !capability.allows(ServiceOp::Enable),
+            "disable" => !capability.allows(ServiceOp::Disable),
+            // Checks and logger maintenance may touch more than the control FIFO.
+            "start" | "stop" | "restart" | "reload" | "once" | "pause" | "cont" | "signal" => {
+                !capability.control
+            }
+            _ => true,
+        }
     }
 
-    pub fn run(&self, action: &str, service: &str) -> Result<String, String> {
-        let response = self.execute(true, action, Some(service), &[])?;
+    pub fn run(&self, action: &str, service: &str, extra: &[&str]) -> Result<String, String> {
+        let privileged = self.needs_elevation(action, service);
+        let response = self.execute(privileged, false, action, Some(service), extra)?;
+        // Check results carry the script's output; show it beneath the summary.
+        let check_output = match (action, &response.data) {
+            ("check", Some(data)) => serde_json::from_value::<CheckOutcome>(data.clone())
+                .ok()
+                .and_then(|outcome| formatting::check_output(&outcome)),
+            _ => None,
+        };
+        let with_output = |message: String| match &check_output {
+            Some(output) => format!("{message}\n{output}"),
+            None => message,
+        };
         match response.status.as_str() {
-            "ok" => Ok(response
-                .message
-                .unwrap_or_else(|| format!("{action} command completed for {service}"))),
-            _ => Err(response
-                .message
-                .unwrap_or_else(|| format!("runkitd reported failure for {service}"))),
+            "ok" => Ok(with_output(response.message.unwrap_or_else(|| {
+                format!("{action} command completed for {service}")
+            }))),
+            _ => {
+                let message = with_output(
+                    response
+                        .message
+                        .unwrap_or_else(|| format!("runkitd reported failure for {service}")),
+                );
+                Err(match response.error_code.and_then(formatting::error_hint) {
+                    Some(hint) => format!("{message}\n{hint}"),
+                    None => message,
+                })
+            }
         }
     }
 
     pub fn fetch_services(&self, privileged: bool) -> Result<Vec<ServiceInfo>, String> {
-        let response = self.execute(privileged, "list", None, &[])?;
+        // Any failure here, say a supervisor restarted meanwhile, goes to the helper.
+        // The restart banner needs to know which services an upgrade left stale.
+        let query = ServiceQuery {
+            check_upgrades: true,
+            ..ServiceQuery::default()
+        };
+        if let Some(probe) = self.direct(|probe| Ok(probe.can_read_states()))
+            && let Ok(services) = probe.list_services_filtered(&query)
+        {
+            return Ok(services);
+        }
+        let response = self.query(privileged, "list", None, &["--check-upgrades"])?;
         if response.status.as_str() != "ok" {
             return Err(response
                 .message
@@ -66,16 +221,42 @@ impl ActionDispatcher {
             .data
             .ok_or_else(|| "runkitd returned no service data".to_string())?;
 
-        let snapshots: Vec<ServiceSnapshot> = serde_json::from_value(data)
-            .map_err(|err| format!("Failed to decode runkitd response: {err}"))?;
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd response: {err}"))
+    }
+
+    /// Status of one service, so an action does not re-list every service.
+    pub fn fetch_service(&self, service: &str) -> Result<ServiceInfo, String> {
+        if let Some(probe) = self.direct(|probe| probe.can_read_state(service))
+            && let Ok(info) = probe.service_info(service)
+        {
+            return Ok(info);
+        }
+        // Unprivileged on purpose: a failure falls back to a full refresh.
+        let response = self.query(false, "status", Some(service), &[])?;
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to read the status of {service}")));
+        }
+
+        let data = response
+            .data
+            .ok_or_else(|| "runkitd returned no service data".to_string())?;
 
-        Ok(snapshots.into_iter().map(ServiceInfo::from).collect())
+        serde_json::from_value(data)
+            .map_err(|err| format!("Failed to decode runkitd response: {err}"))
     }
 
     pub fn fetch_logs(&self, service: &str, lines: usize) -> Result<Vec<LogEntry>, String> {
+        if let Some(probe) = self.direct(|probe| probe.can_read_logs(service))
+            && let Ok(entries) = probe.tail_logs(service, lines.max(1))
+        {
+            return Ok(entries.into_iter().map(LogEntry::from).collect());
+        }
         let limit_arg = lines.max(1).to_string();
         let extra_args = ["--lines", limit_arg.as_str()];
-        let response = self.execute(false, "logs", Some(service), &extra_args)?;
+        let response = self.query(false, "logs", Some(service), &extra_args)?;
 
         if response.status.as_str() != "ok" {
             return Err(response
@@ -87,14 +268,67 @@ impl ActionDispatcher {
             .data
             .ok_or_else(|| "runkitd returned no log data".to_string())?;
 
-        let entries: Vec<LogEntrySnapshot> = serde_json::from_value(data)
+        let entries: Vec<ServiceLogEntry> = serde_json::from_value(data)
             .map_err(|err| format!("Failed to decode runkitd logs response: {err}"))?;
 
         Ok(entries.into_iter().map(LogEntry::from).collect())
     }
 
+    pub fn fetch_stats(&self, service: &str) -> Result<Option<ServiceStats>, String> {
+        let response = self.query(false, "stats", Some(service), &[])?;
+
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to read usage for {service}")));
+        }
+
+        match response.data {
+            Some(data) => serde_json::from_value(data)
+                .map_err(|err| format!("Failed to decode runkitd stats response: {err}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn fetch_process(&self, service: &str) -> Result<Option<ProcessInfo>, String> {
+        let response = self.query(false, "process", Some(service), &[])?;
+
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to inspect the process of {service}")));
+        }
+
+        match response.data {
+            Some(data) => serde_json::from_value(data)
+                .map_err(|err| format!("Failed to decode runkitd process response: {err}")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn fetch_log_usage(&self, service: &str) -> Result<Option<LogUsage>, String> {
+        let response = self.query(false, "log-usage", Some(service), &[])?;
+
+        if response.status.as_str() != "ok" {
+            return Err(response
+                .message
+                .unwrap_or_else(|| format!("runkitd failed to inspect logs for {service}")));
+        }
+
+        match response.data {
+            Some(data) => serde_json::from_value(data)
+                .map_err(|err| format!("Failed to decode runkitd log usage response: {err}")),
+            None => Ok(None),
+        }
+    }
+
+    /// Stage and runlevel of the local system, read without going through runkitd.
+    pub fn system_stage(&self) -> Option<SystemStage> {
+        self.probe.as_ref().map(ServiceManager::system_stage)
+    }
+
     pub fn fetch_description(&self, service: &str) -> Result<Option<String>, String> {
-        let response = self.execute(false, "describe", Some(service), &[])?;
+        let response = self.query(false, "describe", Some(service), &[])?;
 
         if response.status.as_str() != "ok" {
             return Err(response
@@ -116,6 +350,8 @@ impl ActionDispatcher {
 fn execute_helper(
     helper_path: PathBuf,
     use_pkexec: bool,
+    user_scope: bool,
+    read_only: bool,
     action: &str,
     service: Option<&str>,
     extra: &[&str],
@@ -127,6 +363,14 @@ fn execute_helper(
     } else {
         Command::new(&helper_path)
     };
+    if user_scope {
+        command.arg("--user");
+    }
+    // Without pkexec the helper has no more rights than the GUI; for reads, make sure it
+    // cannot change anything either.
+    if !use_pkexec && read_only {
+        command.arg("--read-only");
+    }
     command.arg(action);
     if let Some(service) = service {
         command.arg(service);
@@ -188,11 +432,29 @@ fn execute_helper(
 
 #[derive(Debug, Deserialize)]
 struct DaemonProcessResponse {
+    /// Absent from helpers that predate versioned responses.
+    #[serde(default)]
+    protocol_version: u32,
     status: String,
+    #[serde(default, alias = "error")]
+    error_code: Option<ErrorCode>,
     message: Option<String>,
     data: Option<Value>,
 }
 
+/// Refuse responses in a format newer than this build understands, rather than
+/// misreading them.
+fn check_protocol(response: DaemonProcessResponse) -> Result<DaemonProcessResponse, String> {
+    if response.protocol_version > PROTOCOL_VERSION {
+        return Err(format!(
+            "runkitd uses response format {}, but this version of runkit only understands \
+             up to {PROTOCOL_VERSION}; update runkit",
+            response.protocol_version
+        ));
+    }
+    Ok(response)
+}
+
 fn parse_response(data: &str) -> Result<DaemonProcessResponse, serde_json::Error> {
     if data.is_empty() {
         Err(serde_json::Error::io(std::io::Error::new(
@@ -204,16 +466,6 @@ fn parse_response(data: &str) -> Result<DaemonProcessResponse, serde_json::Error
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct ServiceSnapshot {
-    name: String,
-    definition_path: String,
-    enabled: bool,
-    desired_state: SnapshotDesiredState,
-    runtime_state: SnapshotRuntimeState,
-    description: Option<String>,
-}
-
 #[derive(Clone, Debug)]
 pub struct LogEntry {
     pub unix_seconds: Option<i64>,
@@ -222,21 +474,13 @@ pub struct LogEntry {
     pub message: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct LogEntrySnapshot {
-    unix_seconds: Option<i64>,
-    nanos: Option<u32>,
-    raw: Option<String>,
-    message: String,
-}
-
-impl From<LogEntrySnapshot> for LogEntry {
-    fn from(snapshot: LogEntrySnapshot) -> Self {
+impl From<ServiceLogEntry> for LogEntry {
+    fn from(entry: ServiceLogEntry) -> Self {
         LogEntry {
-            unix_seconds: snapshot.unix_seconds,
-            nanos: snapshot.nanos,
-            raw: snapshot.raw,
-            message: snapshot.message,
+            unix_seconds: entry.timestamp_unix,
+            nanos: entry.timestamp_nanos,
+            raw: entry.timestamp_raw,
+            message: entry.message,
         }
     }
 }
@@ -245,84 +489,3 @@ impl From<LogEntrySnapshot> for LogEntry {
 struct DescriptionSnapshot {
     description: Option<String>,
 }
-
-impl From<ServiceSnapshot> for ServiceInfo {
-    fn from(snapshot: ServiceSnapshot) -> Self {
-        ServiceInfo {
-            name: snapshot.name,
-            definition_path: PathBuf::from(snapshot.definition_path),
-            enabled: snapshot.enabled,
-            desired_state: snapshot.desired_state.into(),
-            runtime_state: snapshot.runtime_state.into(),
-            description: snapshot.description,
-        }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum SnapshotDesiredState {
-    AutoStart,
-    Manual,
-}
-
-impl From<SnapshotDesiredState> for DesiredState {
-    fn from(value: SnapshotDesiredState) -> Self {
-        match value {
-            SnapshotDesiredState::AutoStart => DesiredState::AutoStart,
-            SnapshotDesiredState::Manual => DesiredState::Manual,
-        }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(tag = "state", rename_all = "snake_case")]
-enum SnapshotRuntimeState {
-    Running {
-        pid: u32,
-        uptime_seconds: u64,
-    },
-    Down {
-        since_seconds: u64,
-        normally_up: bool,
-    },
-    Failed {
-        pid: u32,
-        uptime_seconds: u64,
-        exit_code: i32,
-    },
-    Unknown {
-        raw: String,
-    },
-}
-
-impl From<SnapshotRuntimeState> for ServiceRuntimeState {
-    fn from(value: SnapshotRuntimeState) -> Self {
-        match value {
-            SnapshotRuntimeState::Running {
-                pid,
-                uptime_seconds,
-            } => ServiceRuntimeState::Running {
-                pid,
-                uptime: Duration::from_secs(uptime_seconds),
-            },
-            SnapshotRuntimeState::Down {
-                since_seconds,
-                normally_up,
-            } => ServiceRuntimeState::Down {
-                since: Duration::from_secs(since_seconds),
-                normally_up,
-            },
-            SnapshotRuntimeState::Failed {
-                pid,
-                uptime_seconds,
-                exit_code,
-            } => ServiceRuntimeState::Failed {
-                pid,
-                uptime: Duration::from_secs(uptime_seconds),
-                exit_code,
-            },
-            SnapshotRuntimeState::Unknown { raw } => ServiceRuntimeState::Unknown { raw },
-        }
-    }
-}
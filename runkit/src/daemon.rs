@@ -0,0 +1,148 @@
+//! Client for a `runkitd serve` instance, which saves a pkexec round trip per action.
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::env;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const DEFAULT_SOCKET: &str = "/run/runkitd.sock";
+
+/// Longest a single request may take; `restart` and `check` wait for the service.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug)]
+pub enum DaemonError {
+    /// The daemon could not be reached; the caller should fall back to spawning runkitd.
+    Unavailable(io::Error),
+    /// The daemon refused the request itself.
+    Rejected(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct DaemonClient {
+    socket: PathBuf,
+    connection: Arc<Mutex<Connection>>,
+}
+
+#[derive(Debug, Default)]
+struct Connection {
+    stream: Option<UnixStream>,
+    next_id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+impl DaemonClient {
+    /// `RUNKITD_SOCKET` names the socket, an empty value disables the daemon; otherwise
+    /// the default socket is used when it exists.
+    pub fn from_env() -> Option<Self> {
+        let socket = match env::var_os("RUNKITD_SOCKET") {
+            Some(path) if path.is_empty() => return None,
+            Some(path) => PathBuf::from(path),
+            None => {
+                let path = PathBuf::from(DEFAULT_SOCKET);
+                if !path.exists() {
+                    return None;
+                }
+                path
+            }
+        };
+        Some(DaemonClient {
+            socket,
+            connection: Arc::default(),
+        })
+    }
+
+    /// Run one helper command on the daemon and return its response object. A broken
+    /// connection is re-established once before giving up.
+    pub fn call(&self, method: &str, params: &[&str]) -> Result<Value, DaemonError> {
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        connection.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": connection.next_id,
+            "method": method,
+            "params": params,
+        });
+        let bytes =
+            serde_json::to_vec(&request).map_err(|err| DaemonError::Rejected(err.to_string()))?;
+
+        let reused = connection.stream.is_some();
+        let mut result = self.exchange(&mut connection, &bytes);
+        // The daemon may have restarted since the last request; only then is a resend
+        // safe, since the request never reached a live peer.
+        if reused && result.as_ref().is_err_and(is_stale_connection) {
+            connection.stream = None;
+            result = self.exchange(&mut connection, &bytes);
+        }
+        let reply = match result {
+            Ok(reply) => reply,
+            Err(err) => {
+                connection.stream = None;
+                return Err(match err.kind() {
+                    // The request may still be running; repeating it through pkexec could
+                    // run it twice.
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut => DaemonError::Rejected(format!(
+                        "runkitd did not answer within {}s",
+                        REQUEST_TIMEOUT.as_secs()
+                    )),
+                    _ => DaemonError::Unavailable(err),
+                });
+            }
+        };
+        let response: RpcResponse = serde_json::from_slice(&reply)
+            .map_err(|err| DaemonError::Rejected(format!("invalid daemon response: {err}")))?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(DaemonError::Rejected(error.message)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(DaemonError::Rejected(
+                "daemon response carried no result".to_string(),
+            )),
+        }
+    }
+
+    fn exchange(&self, connection: &mut Connection, request: &[u8]) -> io::Result<Vec<u8>> {
+        if connection.stream.is_none() {
+            let stream = UnixStream::connect(&self.socket)?;
+            stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+            connection.stream = Some(stream);
+        }
+        let stream = connection
+            .stream
+            .as_mut()
+            .ok_or_else(|| io::Error::from(ErrorKind::NotConnected))?;
+        let len = u32::try_from(request.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "request too large"))?;
+        stream.write_all(&len.to_be_bytes())?;
+        stream.write_all(request)?;
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let mut reply = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut reply)?;
+        Ok(reply)
+    }
+}
+
+fn is_stale_connection(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::UnexpectedEof
+    )
+}
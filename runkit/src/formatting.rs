@@ -1,13 +1,39 @@
-use crate::actions::LogEntry;
+use crate::actions::{ErrorCode, LogEntry};
 use gtk4::glib;
 use humantime::format_duration;
-use runkit_core::{DesiredState, ServiceInfo, ServiceRuntimeState};
+use runkit_core::{
+    CheckOutcome, DesiredState, LogUsage, ProcessInfo, RunitStage, ServiceHealth, ServiceInfo,
+    ServiceRuntimeState, ServiceStats, SystemStage, Transition,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub fn runtime_state_short(service: &ServiceInfo) -> String {
+    match service.pending {
+        Some(Transition::Starting) => return "Starting…".to_string(),
+        Some(Transition::Stopping) => return "Stopping…".to_string(),
+        None => {}
+    }
+
+    if service.health == ServiceHealth::BrokenLink {
+        return "Broken link".to_string();
+    }
+
+    if service.flapping.is_some() {
+        return "Restarting rapidly".to_string();
+    }
+
+    if service.masked {
+        return "Masked".to_string();
+    }
+
     if matches!(&service.runtime_state, ServiceRuntimeState::Running { .. }) {
         return "Running".to_string();
     }
 
+    if service.runtime_state.is_paused() {
+        return "Paused".to_string();
+    }
+
     if !service.enabled {
         return "Disabled".to_string();
     }
@@ -22,20 +48,59 @@ pub fn runtime_state_short(service: &ServiceInfo) -> String {
         }
         ServiceRuntimeState::Failed { .. } => "Error".to_string(),
         ServiceRuntimeState::Unknown { .. } => "Unavailable".to_string(),
-        ServiceRuntimeState::Running { .. } => unreachable!(),
+        ServiceRuntimeState::Running { .. } | ServiceRuntimeState::Paused { .. } => {
+            unreachable!()
+        }
     }
 }
 
 pub fn runtime_state_detail(service: &ServiceInfo) -> String {
-    match &service.runtime_state {
-        ServiceRuntimeState::Running { pid, uptime } => format!(
-            "Running (PID {pid}) for {}",
-            format_duration(*uptime).to_string()
+    if service.health == ServiceHealth::BrokenLink {
+        return format!(
+            "Enabled, but {} no longer exists; disable it to remove the stale link",
+            service.definition_path.display()
+        );
+    }
+
+    if let Some(flapping) = service.flapping {
+        return format!(
+            "Crash loop: {} restarts in the last {}; check the logs",
+            flapping.restarts,
+            format_duration(flapping.window)
+        );
+    }
+
+    let detail = match &service.runtime_state {
+        ServiceRuntimeState::Running { pid, uptime, .. } => {
+            let uptime = live_duration(&service.runtime_state, *uptime);
+            let detail = format!(
+                "Running (PID {pid}) for {}",
+                format_duration(uptime).to_string()
+            );
+            let detail = if service.run_once {
+                format!("{detail}; started once, runit will not restart it")
+            } else {
+                detail
+            };
+            if logger_stopped(service) {
+                format!("{detail}; its logger is not running")
+            } else {
+                detail
+            }
+        }
+        ServiceRuntimeState::Paused { pid, uptime, .. } => format!(
+            "Paused (PID {pid}) after running for {}; continue it to resume",
+            format_duration(live_duration(&service.runtime_state, *uptime))
         ),
-        ServiceRuntimeState::Down { since, normally_up } => {
-            let downtime = format_duration(*since).to_string();
+        ServiceRuntimeState::Down {
+            since, normally_up, ..
+        } => {
+            let downtime =
+                format_duration(live_duration(&service.runtime_state, *since)).to_string();
             if !service.enabled {
                 format!("Disabled; last ran {downtime} ago")
+            } else if !is_auto_start(service.desired_state) {
+                format!("Stopped {downtime} ago; a down file keeps it from starting at boot")
             } else if *normally_up {
                 format!("Stopped {downtime} ago; runit will restart automatically")
             } else {
@@ -56,17 +121,60 @@ pub fn runtime_state_detail(service: &ServiceInfo) -> String {
             }
         }
         ServiceRuntimeState::Unknown { .. } => {
-            if service.enabled {
+            if service.enabled && service.supervise_dir.is_none() {
+                "Not supervised yet; runsvdir has not started a runsv for it".to_string()
+            } else if service.enabled && !service.runsv_running {
+                "Not supervised; its runsv has exited, check that runsvdir is running".to_string()
+            } else if service.enabled {
                 "Status unavailable; runit did not report details".to_string()
             } else {
                 "Disabled; service directory is not linked to /var/service".to_string()
             }
         }
+    };
+
+    let detail = match &service.down_reason {
+        Some(note) if !service.enabled || service.runtime_state.is_down() => {
+            format!("{detail}; taken down for: {note}")
+        }
+        _ => detail,
+    };
+
+    match service.finishes.first().filter(|finish| finish.is_crash()) {
+        Some(finish) => {
+            let ago = SystemTime::now()
+                .duration_since(finish.at)
+                .map(|elapsed| Duration::from_secs(elapsed.as_secs()))
+                .unwrap_or_default();
+            format!("{detail}; last {finish} {} ago", format_duration(ago))
+        }
+        None => detail,
+    }
+}
+
+fn logger_stopped(service: &ServiceInfo) -> bool {
+    matches!(
+        service.log_state,
+        Some(ServiceRuntimeState::Down { .. } | ServiceRuntimeState::Failed { .. })
+    )
+}
+
+/// Time in the current state as of now, rounded to whole seconds. Falls back to the
+/// snapshot when the helper sent no timestamp.
+fn live_duration(state: &ServiceRuntimeState, snapshot: Duration) -> Duration {
+    match state.started_at() {
+        Some(started_at) if started_at != UNIX_EPOCH => {
+            Duration::from_secs(state.elapsed().unwrap_or_default().as_secs())
+        }
+        _ => snapshot,
     }
 }
 
 pub fn list_row_subtitle(service: &ServiceInfo) -> String {
-    runtime_state_short(service)
+    match &service.template {
+        Some(template) => format!("{} · {template}@ instance", runtime_state_short(service)),
+        None => runtime_state_short(service),
+    }
 }
 
 pub fn is_running(state: &ServiceRuntimeState) -> bool {
@@ -78,10 +186,18 @@ pub fn is_auto_start(desired: DesiredState) -> bool {
 }
 
 pub fn status_level(service: &ServiceInfo) -> StatusLevel {
+    if service.flapping.is_some() || service.health == ServiceHealth::BrokenLink {
+        return StatusLevel::Critical;
+    }
+
     if matches!(&service.runtime_state, ServiceRuntimeState::Running { .. }) {
         return StatusLevel::Good;
     }
 
+    if service.runtime_state.is_paused() {
+        return StatusLevel::Warning;
+    }
+
     if !service.enabled {
         return StatusLevel::Neutral;
     }
@@ -96,8 +212,118 @@ pub fn status_level(service: &ServiceInfo) -> StatusLevel {
         }
         ServiceRuntimeState::Failed { .. } => StatusLevel::Critical,
         ServiceRuntimeState::Unknown { .. } => StatusLevel::Warning,
-        ServiceRuntimeState::Running { .. } => unreachable!(),
+        ServiceRuntimeState::Running { .. } | ServiceRuntimeState::Paused { .. } => {
+            unreachable!()
+        }
+    }
+}
+
+pub fn stats_summary(stats: &ServiceStats) -> String {
+    let mut parts = vec![
+        format!("{} RSS", glib::format_size(stats.rss_bytes)),
+        format!("{:.1}% CPU", stats.cpu_percent),
+        format!("{} threads", stats.threads),
+    ];
+    if let Some(fds) = stats.open_fds {
+        parts.push(format!("{fds} open files"));
+    }
+    if stats.processes > 1 {
+        parts.push(format!("{} processes", stats.processes));
     }
+    parts.join(" · ")
+}
+
+pub fn process_summary(process: &ProcessInfo) -> String {
+    let user = process
+        .user
+        .clone()
+        .unwrap_or_else(|| format!("uid {}", process.uid));
+    if process.command_line.is_empty() {
+        format!("Runs as {user}")
+    } else {
+        format!("{} · runs as {user}", process.command())
+    }
+}
+
+/// Banner text for system states that affect every service.
+pub fn system_notice(stage: &SystemStage) -> Option<String> {
+    if stage.is_shutting_down() {
+        Some("The system is shutting down; services are being stopped.".to_string())
+    } else if stage.stage == Some(RunitStage::Boot) {
+        Some("The system is still booting; services start once stage 2 begins.".to_string())
+    } else if stage.is_single_user() {
+        Some("Single-user mode: only services linked into the single runlevel run.".to_string())
+    } else {
+        None
+    }
+}
+
+/// Log directories beyond this size are worth pointing out.
+const LARGE_LOG_BYTES: u64 = 512 * 1024 * 1024;
+
+pub fn log_usage_warning(service: &str, usage: &LogUsage) -> Option<String> {
+    if usage.stalled {
+        Some(format!(
+            "The logger for {service} is not keeping up; restart the logger or check free disk space."
+        ))
+    } else if usage.total_bytes > LARGE_LOG_BYTES {
+        Some(format!(
+            "Logs for {service} take up {}; lower its svlogd size or file count.",
+            glib::format_size(usage.total_bytes)
+        ))
+    } else {
+        None
+    }
+}
+
+pub fn restart_notice(services: &[ServiceInfo]) -> Option<String> {
+    let stale: Vec<&str> = services
+        .iter()
+        .filter(|service| service.needs_restart)
+        .map(|service| service.name.as_str())
+        .collect();
+    match stale.as_slice() {
+        [] => None,
+        [name] => Some(format!(
+            "{name} is running upgraded files; restart it to apply the update."
+        )),
+        names => Some(format!(
+            "{} services are running upgraded files and need a restart: {}",
+            names.len(),
+            names.join(", ")
+        )),
+    }
+}
+
+/// Suggested next step for an error reported by runkitd.
+pub fn error_hint(code: ErrorCode) -> Option<&'static str> {
+    match code {
+        ErrorCode::NotSupervised => {
+            Some("runsvdir picks up new services within a few seconds; try again shortly.")
+        }
+        ErrorCode::SupervisorNotRunning => {
+            Some("Enable the service so runsvdir starts a supervisor for it.")
+        }
+        ErrorCode::PermissionDenied => {
+            Some("This action needs administrator rights; check that polkit is running.")
+        }
+        ErrorCode::Masked => {
+            Some("Unmask the service first; it was masked to keep it from starting.")
+        }
+        ErrorCode::Timeout => {
+            Some("The service is slow to change state; check its log for progress.")
+        }
+        _ => None,
+    }
+}
+
+/// Trimmed stdout and stderr of a check run, or `None` when it printed nothing.
+pub fn check_output(outcome: &CheckOutcome) -> Option<String> {
+    let lines: Vec<&str> = [outcome.stdout.trim(), outcome.stderr.trim()]
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
 }
 
 pub fn format_log_entry(entry: &LogEntry) -> String {
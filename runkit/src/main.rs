@@ -1,4 +1,5 @@
 mod actions;
+mod daemon;
 mod formatting;
 mod ui;
 
@@ -7,10 +8,9 @@ use gtk::glib::ControlFlow;
 use gtk::glib::{self, source::SourceId};
 use gtk4::{self as gtk, pango};
 use libadwaita::{self as adw, Application, prelude::*};
-use runkit_core::ServiceInfo;
+use runkit_core::{DescriptionCache, FinishHistory, RestartTracker, ServiceInfo};
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::io;
@@ -36,7 +36,9 @@ struct AppController {
     dispatcher: ActionDispatcher,
     model: Rc<RefCell<AppModel>>,
     widgets: ui::AppWidgets,
-    description_store: RefCell<DescriptionStore>,
+    description_cache: RefCell<DescriptionCache>,
+    restart_tracker: RefCell<RestartTracker>,
+    finish_history: RefCell<FinishHistory>,
     preferences_window: RefCell<Option<adw::PreferencesWindow>>,
     about_dialog: RefCell<Option<adw::MessageDialog>>,
     preferences: RefCell<UserPreferences>,
@@ -57,74 +59,6 @@ struct AppModel {
     pending_selection: Option<String>,
 }
 
-struct DescriptionStore {
-    path: Option<PathBuf>,
-    entries: HashMap<String, Option<String>>,
-}
-
-impl DescriptionStore {
-    fn load() -> Self {
-        let path = description_store_path();
-        let entries = path
-            .as_ref()
-            .and_then(|p| fs::read_to_string(p).ok())
-            .and_then(|data| serde_json::from_str(&data).ok())
-            .unwrap_or_default();
-        DescriptionStore { path, entries }
-    }
-
-    fn lookup(&self, service: &str) -> Option<Option<String>> {
-        self.entries.get(service).cloned()
-    }
-
-    fn ensure_present(&mut self, service: &str, description: &str) {
-        let trimmed = description.trim();
-        if trimmed.is_empty() {
-            return;
-        }
-        if self.entries.contains_key(service) {
-            return;
-        }
-        if let Err(err) = self.store(service, Some(trimmed.to_string())) {
-            eprintln!("Failed to persist description for {service}: {err}");
-        }
-    }
-
-    fn store(&mut self, service: &str, description: Option<String>) -> io::Result<()> {
-        let needs_write = match self.entries.get(service) {
-            Some(existing) if existing == &description => false,
-            _ => true,
-        };
-        if !needs_write {
-            return Ok(());
-        }
-
-        self.entries
-            .insert(service.to_string(), description.clone());
-        self.save()
-    }
-
-    fn save(&self) -> io::Result<()> {
-        let path = match &self.path {
-            Some(path) => path,
-            None => return Ok(()),
-        };
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let data = serde_json::to_string_pretty(&self.entries)
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-        fs::write(path, data)
-    }
-}
-
-fn description_store_path() -> Option<PathBuf> {
-    let mut base = config_root()?;
-    base.push("runkit");
-    base.push("services.json");
-    Some(base)
-}
-
 fn config_root() -> Option<PathBuf> {
     if let Some(dir) = env::var_os("RUNKIT_CONFIG_DIR") {
         return Some(PathBuf::from(dir));
@@ -221,12 +155,17 @@ impl AppController {
     fn new(app: &Application, dispatcher: ActionDispatcher) -> Rc<Self> {
         let preferences = load_user_preferences();
         let widgets = ui::AppWidgets::new(app, preferences.show_all_services);
-        let description_store = DescriptionStore::load();
+        if dispatcher.user_scope() {
+            widgets.show_user_scope();
+        }
+        let description_cache = DescriptionCache::load_default();
         let controller = Rc::new(Self {
             dispatcher,
             model: Rc::new(RefCell::new(AppModel::default())),
             widgets,
-            description_store: RefCell::new(description_store),
+            description_cache: RefCell::new(description_cache),
+            restart_tracker: RefCell::new(RestartTracker::default()),
+            finish_history: RefCell::new(FinishHistory::default()),
             preferences_window: RefCell::new(None),
             about_dialog: RefCell::new(None),
             preferences: RefCell::new(preferences),
@@ -277,7 +216,7 @@ impl AppController {
         let register_action = |button: &gtk::Button, action: &'static str| {
             let controller = Rc::clone(self);
             button.connect_clicked(move |_| {
-                controller.trigger_action(action);
+                controller.trigger_action(action, &[]);
             });
         };
 
@@ -285,10 +224,24 @@ impl AppController {
         register_action(&self.widgets.action_stop, "stop");
         register_action(&self.widgets.action_restart, "restart");
         register_action(&self.widgets.action_reload, "reload");
+        register_action(&self.widgets.action_once, "once");
+        register_action(&self.widgets.action_pause, "pause");
+        register_action(&self.widgets.action_cont, "cont");
         register_action(&self.widgets.action_enable, "enable");
         register_action(&self.widgets.action_disable, "disable");
         register_action(&self.widgets.action_check, "check");
 
+        {
+            let controller = Rc::clone(self);
+            self.widgets
+                .signal_action
+                .connect_activate(move |_, parameter| {
+                    if let Some(signal) = parameter.and_then(|value| value.str()) {
+                        controller.trigger_action("signal", &[signal]);
+                    }
+                });
+        }
+
         {
             let controller = Rc::clone(self);
             let popover = self.widgets.menu_popover.clone();
@@ -362,15 +315,11 @@ impl AppController {
                         model.description_error = None;
                     }
 
-                    if let Some(text) = service.description.as_deref() {
-                        self.description_store
-                            .borrow_mut()
-                            .ensure_present(&name, text);
-                    }
-
                     self.widgets.show_service_details(&service);
                     self.widgets.action_bar_set_enabled(true, Some(&service));
                     self.ensure_service_description(&service);
+                    self.refresh_stats(&service);
+                    self.refresh_log_usage(&service);
 
                     let remember_last = {
                         let prefs = self.preferences.borrow();
@@ -423,13 +372,19 @@ impl AppController {
         }
     }
 
-    fn update_services(self: &Rc<Self>, services: Vec<ServiceInfo>) {
+    fn update_services(self: &Rc<Self>, mut services: Vec<ServiceInfo>) {
         {
-            let mut store = self.description_store.borrow_mut();
-            for service in &services {
-                if let Some(description) = service.description.as_deref() {
-                    store.ensure_present(&service.name, description);
+            // runkitd sees every service only once, so crash loops are tracked across refreshes.
+            let mut tracker = self.restart_tracker.borrow_mut();
+            let mut finishes = self.finish_history.borrow_mut();
+            for service in services.iter_mut().filter(|service| service.enabled) {
+                let observed = tracker.observe(&service.name, &service.runtime_state);
+                service.flapping = service.flapping.or(observed);
+                for finish in &service.finishes {
+                    finishes.record(&service.name, *finish);
                 }
+                finishes.observe(&service.name, &service.runtime_state);
+                service.finishes = finishes.history(&service.name);
             }
         }
         let pending_selection = {
@@ -514,9 +469,9 @@ impl AppController {
         count
     }
 
-    fn trigger_action(self: &Rc<Self>, action: &'static str) {
+    fn trigger_action(self: &Rc<Self>, action: &'static str, extra: &[&str]) {
         if let Some(service_name) = self.widgets.current_service() {
-            match self.dispatcher.run(action, &service_name) {
+            match self.dispatcher.run(action, &service_name, extra) {
                 Ok(message) => {
                     let (entries_snapshot, notes_snapshot) = {
                         let mut model = self.model.borrow_mut();
@@ -535,7 +490,9 @@ impl AppController {
                     };
                     self.widgets
                         .show_activity(&service_name, &entries_snapshot, &notes_snapshot);
-                    self.request_refresh(true);
+                    // A pid change the user asked for is not a crash loop.
+                    self.restart_tracker.borrow_mut().forget(&service_name);
+                    self.request_service_refresh(&service_name);
                 }
                 Err(err) => {
                     let error_message = format!("Operation failed: {err}");
@@ -568,11 +525,56 @@ impl AppController {
         let result = self.dispatcher.fetch_services(true);
         self.widgets.show_loading(false);
         match result {
-            Ok(services) => self.update_services(services),
+            Ok(services) => {
+                let restart_notice = formatting::restart_notice(&services);
+                self.update_services(services);
+                if let Some(notice) = self
+                    .dispatcher
+                    .system_stage()
+                    .as_ref()
+                    .and_then(formatting::system_notice)
+                    .or(restart_notice)
+                {
+                    self.widgets.show_notice(&notice);
+                }
+            }
             Err(err) => self.widgets.show_error(&err),
         }
     }
 
+    /// Re-read only `service` after acting on it, falling back to a full refresh.
+    fn request_service_refresh(self: &Rc<Self>, service: &str) {
+        let mut info = match self.dispatcher.fetch_service(service) {
+            Ok(info) => info,
+            Err(_) => return self.request_refresh(true),
+        };
+        if info.enabled {
+            let mut tracker = self.restart_tracker.borrow_mut();
+            let mut finishes = self.finish_history.borrow_mut();
+            let observed = tracker.observe(&info.name, &info.runtime_state);
+            info.flapping = info.flapping.or(observed);
+            for finish in &info.finishes {
+                finishes.record(&info.name, *finish);
+            }
+            finishes.observe(&info.name, &info.runtime_state);
+            info.finishes = finishes.history(&info.name);
+        }
+        let replaced = {
+            let mut model = self.model.borrow_mut();
+            let existing = model
+                .services
+                .iter_mut()
+                .find(|existing| existing.name == info.name);
+            existing.map(|existing| *existing = info).is_some()
+        };
+        if !replaced {
+            return self.request_refresh(true);
+        }
+        self.widgets
+            .update_status_summary(&self.model.borrow().services);
+        self.render_service_list();
+    }
+
     fn request_logs(self: &Rc<Self>, service: String) {
         self.widgets.show_activity_loading(&service);
         let lines = self.preferences.borrow().log_lines.max(1) as usize;
@@ -1031,6 +1033,30 @@ impl AppController {
         dialog.present();
     }
 
+    fn refresh_stats(&self, service: &ServiceInfo) {
+        if !service.runtime_state.is_running() {
+            return;
+        }
+        // Usage and process details are nice-to-haves; failures leave the plain status line.
+        let stats = self.dispatcher.fetch_stats(&service.name).ok().flatten();
+        let process = self.dispatcher.fetch_process(&service.name).ok().flatten();
+        if stats.is_some() || process.is_some() {
+            self.widgets
+                .show_service_stats(service, stats.as_ref(), process.as_ref());
+        }
+    }
+
+    fn refresh_log_usage(&self, service: &ServiceInfo) {
+        if !service.has_logger {
+            return;
+        }
+        if let Ok(Some(usage)) = self.dispatcher.fetch_log_usage(&service.name)
+            && let Some(warning) = formatting::log_usage_warning(&service.name, &usage)
+        {
+            self.widgets.show_notice(&warning);
+        }
+    }
+
     fn ensure_service_description(self: &Rc<Self>, service: &ServiceInfo) {
         let name = service.name.clone();
 
@@ -1039,7 +1065,11 @@ impl AppController {
             return;
         }
 
-        if let Some(saved) = self.description_store.borrow().lookup(&name) {
+        let cached = self
+            .description_cache
+            .borrow()
+            .get(&name, &service.definition_path);
+        if let Some(saved) = cached {
             self.record_description(&name, saved);
             return;
         }
@@ -1047,12 +1077,12 @@ impl AppController {
         self.widgets.show_description_loading(&name);
         match self.dispatcher.fetch_description(&name) {
             Ok(description) => {
-                if let Err(err) = self
-                    .description_store
-                    .borrow_mut()
-                    .store(&name, description.clone())
                 {
-                    eprintln!("Failed to persist description for {name}: {err}");
+                    let mut cache = self.description_cache.borrow_mut();
+                    cache.insert(&name, &service.definition_path, description.clone());
+                    if let Err(err) = cache.save() {
+                        eprintln!("Failed to persist description for {name}: {err}");
+                    }
                 }
                 self.record_description(&name, description);
             }
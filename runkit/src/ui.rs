@@ -1,16 +1,17 @@
 use crate::actions::LogEntry;
 use crate::formatting::{
-    StatusLevel, format_log_entry, is_auto_start, is_running, list_row_subtitle,
-    runtime_state_detail, runtime_state_short, status_level,
+    StatusLevel, format_log_entry, is_running, list_row_subtitle, process_summary,
+    runtime_state_detail, runtime_state_short, stats_summary, status_level,
 };
 use gtk::{cairo, gdk, gio, glib, pango};
 use gtk4 as gtk;
 use libadwaita::{self as adw, prelude::*};
-use runkit_core::ServiceInfo;
+use runkit_core::{ProcessInfo, ServiceInfo, ServiceStats, SvSignal};
 use std::{f64::consts::PI, rc::Rc};
 
 pub struct AppWidgets {
     pub window: adw::ApplicationWindow,
+    window_title: adw::WindowTitle,
     pub search_entry: gtk::SearchEntry,
     pub service_filter_toggle: gtk::ToggleButton,
     pub list_box: gtk::ListBox,
@@ -18,9 +19,14 @@ pub struct AppWidgets {
     pub action_stop: gtk::Button,
     pub action_restart: gtk::Button,
     pub action_reload: gtk::Button,
+    pub action_once: gtk::Button,
+    pub action_pause: gtk::Button,
+    pub action_cont: gtk::Button,
     pub action_enable: gtk::Button,
     pub action_disable: gtk::Button,
     pub action_check: gtk::Button,
+    pub action_signal: gtk::MenuButton,
+    pub signal_action: gio::SimpleAction,
     detail_stack: gtk::Stack,
     detail_title: gtk::Label,
     detail_state_label: gtk::Label,
@@ -391,18 +397,42 @@ impl AppWidgets {
         let action_stop = gtk::Button::with_label("Stop");
         let action_restart = gtk::Button::with_label("Restart");
         let action_reload = gtk::Button::with_label("Reload");
+        let action_once = gtk::Button::builder()
+            .label("Run once")
+            .tooltip_text("Start without restarting it when it exits")
+            .build();
+        let action_pause = gtk::Button::with_label("Pause");
+        let action_cont = gtk::Button::with_label("Continue");
+        action_cont.set_visible(false);
         let action_enable = gtk::Button::with_label("Enable service");
         let action_disable = gtk::Button::with_label("Disable service");
         let action_check = gtk::Button::with_label("Run health check");
 
+        let signal_action = gio::SimpleAction::new("signal", Some(glib::VariantTy::STRING));
+        app.add_action(&signal_action);
+        let signal_menu = gio::Menu::new();
+        for signal in SvSignal::ALL {
+            signal_menu.append(
+                Some(&format!("SIG{signal}")),
+                Some(&format!("app.signal::{}", signal.name())),
+            );
+        }
+        let action_signal = gtk::MenuButton::builder()
+            .label("Send signal")
+            .menu_model(&signal_menu)
+            .build();
+
         let action_row_one = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
             .spacing(6)
             .build();
         action_row_one.append(&action_start);
         action_row_one.append(&action_stop);
+        action_row_one.append(&action_once);
         action_row_one.append(&action_restart);
         action_row_one.append(&action_reload);
+        action_row_one.append(&action_pause);
+        action_row_one.append(&action_cont);
 
         let action_row_two = gtk::Box::builder()
             .orientation(gtk::Orientation::Horizontal)
@@ -411,6 +441,7 @@ impl AppWidgets {
         action_row_two.append(&action_enable);
         action_row_two.append(&action_disable);
         action_row_two.append(&action_check);
+        action_row_two.append(&action_signal);
 
         let detail_title = gtk::Label::builder()
             .xalign(0.0)
@@ -516,6 +547,7 @@ impl AppWidgets {
 
         AppWidgets {
             window: window.clone(),
+            window_title,
             search_entry,
             service_filter_toggle,
             list_box,
@@ -523,9 +555,14 @@ impl AppWidgets {
             action_stop,
             action_restart,
             action_reload,
+            action_once,
+            action_pause,
+            action_cont,
             action_enable,
             action_disable,
             action_check,
+            action_signal,
+            signal_action,
             detail_stack,
             detail_title,
             detail_state_label,
@@ -543,6 +580,10 @@ impl AppWidgets {
         }
     }
 
+    pub fn show_user_scope(&self) {
+        self.window_title.set_subtitle("User services");
+    }
+
     pub fn show_loading(&self, active: bool) {
         self.loading_revealer.set_reveal_child(active);
         if active {
@@ -639,6 +680,18 @@ impl AppWidgets {
         configure_indicator(&self.detail_status_indicator, status_level(service));
     }
 
+    pub fn show_service_stats(
+        &self,
+        service: &ServiceInfo,
+        stats: Option<&ServiceStats>,
+        process: Option<&ProcessInfo>,
+    ) {
+        let mut lines = vec![runtime_state_detail(service)];
+        lines.extend(process.map(process_summary));
+        lines.extend(stats.map(stats_summary));
+        self.detail_state_label.set_label(&lines.join("\n"));
+    }
+
     pub fn show_placeholder(&self) {
         self.detail_stack.set_visible_child_name("placeholder");
         self.clear_activity();
@@ -683,10 +736,8 @@ impl AppWidgets {
         let running = service
             .map(|s| is_running(&s.runtime_state))
             .unwrap_or(false);
-        let autostart = service
-            .map(|s| is_auto_start(s.desired_state))
-            .unwrap_or(false);
         let service_enabled = service.map(|s| s.enabled).unwrap_or(false);
+        let paused = service.is_some_and(|s| s.runtime_state.is_paused());
 
         self.action_start
             .set_sensitive(enabled && service_enabled && !running);
@@ -695,9 +746,25 @@ impl AppWidgets {
         self.action_restart
             .set_sensitive(enabled && service_enabled);
         self.action_reload.set_sensitive(enabled && service_enabled);
+        self.action_once
+            .set_sensitive(enabled && service_enabled && !running && !paused);
+        // Only one of Pause and Continue applies at a time.
+        self.action_pause.set_visible(!paused);
+        self.action_pause
+            .set_sensitive(enabled && service_enabled && running);
+        self.action_cont.set_visible(paused);
+        self.action_cont.set_sensitive(enabled && service_enabled);
         self.action_check.set_sensitive(enabled && service_enabled);
-        self.action_enable.set_sensitive(enabled && !autostart);
-        self.action_disable.set_sensitive(enabled && autostart);
+        // Services without a check script have nothing for `sv check` to run.
+        self.action_check
+            .set_visible(service.is_none_or(|s| s.has_check));
+        self.action_signal
+            .set_sensitive(enabled && service_enabled && running);
+        let masked = service.is_some_and(|s| s.masked);
+        self.action_enable
+            .set_sensitive(enabled && !service_enabled && !masked);
+        self.action_disable
+            .set_sensitive(enabled && service_enabled);
     }
 
     pub fn update_status_summary(&self, services: &[ServiceInfo]) {
@@ -763,6 +830,13 @@ impl AppWidgets {
         self.banner.set_revealed(true);
     }
 
+    /// Persistent banner describing the system state; it has nothing to dismiss.
+    pub fn show_notice(&self, message: &str) {
+        self.banner.set_title(message);
+        self.banner.set_button_label(None);
+        self.banner.set_revealed(true);
+    }
+
     pub fn clear_activity(&self) {
         self.activity_label
             .set_text("Select a service to see recent activity.");
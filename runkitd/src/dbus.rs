@@ -0,0 +1,429 @@
+//! `tech.geektoshi.Runkit1` on the system bus, with every call authorized by polkit.
+//!
+//! Methods return the same JSON object the one-shot helper prints. Callers that polkit
+//! turns away get `org.freedesktop.DBus.Error.AccessDenied` instead. Status methods
+//! check [`READ_ACTION`], `Logs` [`LOGS_ACTION`], lifecycle methods [`CONTROL_ACTION`]
+//! and enablement methods [`MANAGE_ACTION`], so administrators can grant them
+//! separately. Logs often carry secrets, so reading them asks for authentication.
+//!
+//! Commands run on the blocking thread pool, so one waiting on `sv` or a check script
+//! does not hold up other callers. With an idle timeout runkitd leaves the bus once no
+//! call has been in flight for that long, and bus activation starts it again.
+use crate::serve::{Activity, parse_command, unavailable};
+use crate::{HelperContext, HelperError, HelperResponse};
+use runkit_core::{ServiceEvent, ServiceManager, ServiceWatcher};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::Value;
+use zbus::{Connection, fdo, interface};
+
+pub const BUS_NAME: &str = "tech.geektoshi.Runkit1";
+pub const OBJECT_PATH: &str = "/tech/geektoshi/Runkit1";
+
+pub const READ_ACTION: &str = "tech.geektoshi.Runkit.read";
+pub const LOGS_ACTION: &str = "tech.geektoshi.Runkit.logs";
+pub const CONTROL_ACTION: &str = "tech.geektoshi.Runkit.control";
+pub const MANAGE_ACTION: &str = "tech.geektoshi.Runkit.manage";
+
+/// How often the watcher wakes up when nothing changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Runkit1 {
+    context: Arc<HelperContext>,
+    activity: Arc<Activity>,
+}
+
+/// Counts a method call as activity, including a polkit prompt, until it returns.
+struct Busy<'a>(&'a Activity);
+
+impl<'a> Busy<'a> {
+    fn start(activity: &'a Activity) -> Self {
+        activity.connected();
+        Busy(activity)
+    }
+}
+
+impl Drop for Busy<'_> {
+    fn drop(&mut self) {
+        self.0.disconnected();
+    }
+}
+
+impl Runkit1 {
+    /// Authorize the caller for `action`, then run `method` as if it came from the
+    /// command line.
+    async fn call(
+        &self,
+        connection: &Connection,
+        header: &Header<'_>,
+        action: &str,
+        method: &str,
+        params: &[&str],
+    ) -> fdo::Result<String> {
+        let _busy = Busy::start(&self.activity);
+        authorize(connection, header, action).await?;
+        let command = parse_command(method, params)
+            .map_err(|err| fdo::Error::InvalidArgs(err.render().to_string().trim().to_string()))?;
+        if let Some(reason) = unavailable(&command) {
+            return Err(fdo::Error::NotSupported(reason.to_string()));
+        }
+        let context = Arc::clone(&self.context);
+        let response = blocking::unblock(move || match context.run(command) {
+            Ok(outcome) => HelperResponse::ok_with(outcome),
+            Err(err) => HelperResponse::error(&err),
+        })
+        .await;
+        serde_json::to_string(&response).map_err(|err| fdo::Error::Failed(err.to_string()))
+    }
+
+    /// Run a lifecycle or enablement method and announce the service's new state.
+    async fn act(
+        &self,
+        connection: &Connection,
+        header: &Header<'_>,
+        emitter: &SignalEmitter<'_>,
+        action: &str,
+        method: &str,
+        service: &str,
+    ) -> fdo::Result<String> {
+        let response = self
+            .call(connection, header, action, method, &[service])
+            .await?;
+        let context = Arc::clone(&self.context);
+        let service_name = service.to_string();
+        let state = blocking::unblock(move || context.manager.status(&service_name)).await;
+        if let Ok(state) = state {
+            Runkit1::state_changed(emitter, service, state.kind().name()).await?;
+        }
+        Ok(response)
+    }
+}
+
+#[interface(name = "tech.geektoshi.Runkit1")]
+impl Runkit1 {
+    /// Every service with its current status, as `runkitd list` prints it.
+    async fn list_services(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> fdo::Result<String> {
+        self.call(connection, &header, READ_ACTION, "list", &[])
+            .await
+    }
+
+    /// One service as `runkitd status` prints it.
+    async fn status(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.call(connection, &header, READ_ACTION, "status", &[&service])
+            .await
+    }
+
+    /// The newest `lines` log entries of a service.
+    async fn logs(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        service: String,
+        lines: u32,
+    ) -> fdo::Result<String> {
+        let lines = lines.to_string();
+        self.call(
+            connection,
+            &header,
+            LOGS_ACTION,
+            "logs",
+            &[&service, "--lines", &lines],
+        )
+        .await
+    }
+
+    async fn start(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            CONTROL_ACTION,
+            "start",
+            &service,
+        )
+        .await
+    }
+
+    async fn stop(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            CONTROL_ACTION,
+            "stop",
+            &service,
+        )
+        .await
+    }
+
+    async fn restart(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            CONTROL_ACTION,
+            "restart",
+            &service,
+        )
+        .await
+    }
+
+    async fn reload(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            CONTROL_ACTION,
+            "reload",
+            &service,
+        )
+        .await
+    }
+
+    async fn once(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            CONTROL_ACTION,
+            "once",
+            &service,
+        )
+        .await
+    }
+
+    async fn pause(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            CONTROL_ACTION,
+            "pause",
+            &service,
+        )
+        .await
+    }
+
+    async fn cont(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            CONTROL_ACTION,
+            "cont",
+            &service,
+        )
+        .await
+    }
+
+    async fn enable(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            MANAGE_ACTION,
+            "enable",
+            &service,
+        )
+        .await
+    }
+
+    async fn disable(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        service: String,
+    ) -> fdo::Result<String> {
+        self.act(
+            connection,
+            &header,
+            &emitter,
+            MANAGE_ACTION,
+            "disable",
+            &service,
+        )
+        .await
+    }
+
+    /// The service's runtime state changed; `state` is `running`, `paused`, `down`,
+    /// `failed` or `unknown`.
+    #[zbus(signal)]
+    async fn state_changed(
+        emitter: &SignalEmitter<'_>,
+        service: &str,
+        state: &str,
+    ) -> zbus::Result<()>;
+
+    /// The service was linked into or removed from the enabled directory.
+    #[zbus(signal)]
+    async fn enabled_changed(
+        emitter: &SignalEmitter<'_>,
+        service: &str,
+        enabled: bool,
+    ) -> zbus::Result<()>;
+
+    /// A service definition appeared.
+    #[zbus(signal)]
+    async fn service_added(emitter: &SignalEmitter<'_>, service: &str) -> zbus::Result<()>;
+
+    /// A service definition disappeared.
+    #[zbus(signal)]
+    async fn service_removed(emitter: &SignalEmitter<'_>, service: &str) -> zbus::Result<()>;
+}
+
+/// Ask polkit whether the sender of `header` may perform `action`, letting it prompt
+/// for authentication.
+async fn authorize(connection: &Connection, header: &Header<'_>, action: &str) -> fdo::Result<()> {
+    const ALLOW_USER_INTERACTION: u32 = 1;
+
+    let sender = header
+        .sender()
+        .ok_or_else(|| fdo::Error::AccessDenied("caller has no bus name".to_string()))?;
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender.as_str()))]),
+    );
+    let details: HashMap<&str, &str> = HashMap::new();
+    let reply = connection
+        .call_method(
+            Some("org.freedesktop.PolicyKit1"),
+            "/org/freedesktop/PolicyKit1/Authority",
+            Some("org.freedesktop.PolicyKit1.Authority"),
+            "CheckAuthorization",
+            &(subject, action, details, ALLOW_USER_INTERACTION, ""),
+        )
+        .await?;
+    let (authorized, _challenge, _details): (bool, bool, HashMap<String, String>) =
+        reply.body().deserialize()?;
+    if authorized {
+        Ok(())
+    } else {
+        Err(fdo::Error::AccessDenied(format!(
+            "not authorized for {action}"
+        )))
+    }
+}
+
+/// Own [`BUS_NAME`] on the system bus and serve until the process is killed or no call
+/// came in for `idle_timeout`, turning filesystem changes into signals.
+pub fn serve(context: HelperContext, idle_timeout: Option<Duration>) -> Result<(), HelperError> {
+    let dbus_error = |err: zbus::Error| HelperError::Other(format!("D-Bus: {err}"));
+    let manager: ServiceManager = context.manager.clone();
+    let mut watcher = ServiceWatcher::new(&manager)?;
+    let activity = Arc::new(Activity::default());
+    let connection = zbus::blocking::connection::Builder::system()
+        .and_then(|builder| builder.name(BUS_NAME))
+        .and_then(|builder| {
+            builder.serve_at(
+                OBJECT_PATH,
+                Runkit1 {
+                    context: Arc::new(context),
+                    activity: Arc::clone(&activity),
+                },
+            )
+        })
+        .and_then(|builder| builder.build())
+        .map_err(dbus_error)?;
+    let interface = connection
+        .object_server()
+        .interface::<_, Runkit1>(OBJECT_PATH)
+        .map_err(dbus_error)?;
+    let emitter = interface.signal_emitter();
+    loop {
+        let wait = match idle_timeout {
+            Some(idle) => match activity.idle_for() {
+                // Dropping the connection releases the name for the next activation.
+                Some(elapsed) if elapsed >= idle => return Ok(()),
+                Some(elapsed) => (idle - elapsed).min(WATCH_INTERVAL),
+                None => idle.min(WATCH_INTERVAL),
+            },
+            None => WATCH_INTERVAL,
+        };
+        for event in watcher.wait(wait)? {
+            let emitted = match &event {
+                ServiceEvent::DefinitionAdded(service) => {
+                    zbus::block_on(Runkit1::service_added(emitter, service))
+                }
+                ServiceEvent::DefinitionRemoved(service) => {
+                    zbus::block_on(Runkit1::service_removed(emitter, service))
+                }
+                ServiceEvent::Enabled(service) => {
+                    zbus::block_on(Runkit1::enabled_changed(emitter, service, true))
+                }
+                ServiceEvent::Disabled(service) => {
+                    zbus::block_on(Runkit1::enabled_changed(emitter, service, false))
+                }
+                ServiceEvent::StatusChanged(service) => match manager.status(service) {
+                    Ok(state) => zbus::block_on(Runkit1::state_changed(
+                        emitter,
+                        service,
+                        state.kind().name(),
+                    )),
+                    Err(_) => Ok(()),
+                },
+            };
+            emitted.map_err(dbus_error)?;
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Diagnostics about runkitd's own operation, written to stderr or to syslog (socklog
+//! on Void) so failures under pkexec or the daemon can be traced after the fact.
+//!
+//! The level comes from `--log-level`, then `RUNKIT_LOG`, then `log_level` in
+//! [`CONFIG_FILE`]; the destination from `--log-target`, then `log_target`. Stdout stays
+//! reserved for the JSON response either way.
+use clap::ValueEnum;
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use tracing::{Level, Metadata};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+pub const CONFIG_FILE: &str = "/etc/runkit/runkitd.conf";
+
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// `LOG_DAEMON`.
+const FACILITY: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogTarget {
+    Stderr,
+    Syslog,
+}
+
+#[derive(Debug, Default)]
+struct Config {
+    level: Option<String>,
+    target: Option<LogTarget>,
+}
+
+impl Config {
+    /// `key = value` lines; `#` starts a comment and unknown keys are ignored.
+    fn load(path: &Path) -> Self {
+        let mut config = Config::default();
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return config;
+        };
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "log_level" => config.level = Some(value.to_string()),
+                "log_target" => config.target = LogTarget::from_str(value, true).ok(),
+                _ => {}
+            }
+        }
+        config
+    }
+}
+
+/// Install the global subscriber.
+pub fn init(level: Option<&str>, target: Option<LogTarget>) {
+    let config = Config::load(Path::new(CONFIG_FILE));
+    let filter = level
+        .map(EnvFilter::try_new)
+        .or_else(|| std::env::var("RUNKIT_LOG").ok().map(EnvFilter::try_new))
+        .or_else(|| config.level.as_deref().map(EnvFilter::try_new))
+        .and_then(Result::ok)
+        .unwrap_or_else(|| EnvFilter::new("warn"));
+    let syslog = match target.or(config.target) {
+        Some(LogTarget::Syslog) => Syslog::connect(),
+        _ => None,
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+    match syslog {
+        // syslogd stamps messages itself.
+        Some(syslog) => builder
+            .with_writer(syslog)
+            .with_ansi(false)
+            .without_time()
+            .init(),
+        None => builder.with_writer(io::stderr).init(),
+    }
+}
+
+struct Syslog {
+    socket: UnixDatagram,
+    pid: u32,
+}
+
+impl Syslog {
+    fn connect() -> Option<Self> {
+        let socket = UnixDatagram::unbound().ok()?;
+        socket.connect(SYSLOG_SOCKET).ok()?;
+        Some(Syslog {
+            socket,
+            pid: std::process::id(),
+        })
+    }
+}
+
+impl<'a> MakeWriter<'a> for Syslog {
+    type Writer = SyslogMessage<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SyslogMessage::new(self, 6)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        let severity = match *meta.level() {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            _ => 7,
+        };
+        SyslogMessage::new(self, severity)
+    }
+}
+
+/// One formatted event, sent as a single datagram when dropped.
+struct SyslogMessage<'a> {
+    syslog: &'a Syslog,
+    buffer: Vec<u8>,
+}
+
+impl<'a> SyslogMessage<'a> {
+    fn new(syslog: &'a Syslog, severity: u8) -> Self {
+        let header = format!("<{}>runkitd[{}]: ", FACILITY * 8 + severity, syslog.pid);
+        SyslogMessage {
+            syslog,
+            buffer: header.into_bytes(),
+        }
+    }
+}
+
+impl Write for SyslogMessage<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SyslogMessage<'_> {
+    fn drop(&mut self) {
+        if self.buffer.last() == Some(&b'\n') {
+            self.buffer.pop();
+        }
+        // Nowhere left to report a lost log line.
+        let _ = self.syslog.socket.send(&self.buffer);
+    }
+}
@@ -1,54 +1,454 @@
-use clap::{Parser, Subcommand};
+#[cfg(feature = "dbus")]
+mod dbus;
+#[cfg(feature = "tracing")]
+mod logging;
+mod metrics;
+mod output;
+mod serve;
+mod timespec;
+mod varlink;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use output::OutputFormat;
+use regex::Regex;
 use runkit_core::{
-    DesiredState, ServiceInfo, ServiceLogEntry, ServiceManager, ServiceRuntimeState,
+    CheckOutcome, ControlCommand, DescriptionCache, DryRun, EnabledSnapshot, EnvValue,
+    EventJournal, FinishHistory, JournalQuery, LogQuery, PowerAction, RunScriptSpec,
+    RuntimeStateKind, ServiceEvent, ServiceManager, ServiceQuery, ServiceScope, ServiceStats,
+    ServiceWatcher, SvSignal, SystemControl, UnitConversion,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-use std::os::unix::fs as unix_fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::{ErrorKind, Write};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Privileged daemon for the Runkit GUI", long_about = None)]
 struct Cli {
+    /// Operate on the invoking user's runsvdir instead of the system one.
+    #[arg(long, global = true)]
+    user: bool,
+    /// Record state changes in `$XDG_STATE_HOME/runkit/events.log`.
+    #[arg(long, global = true)]
+    journal: bool,
+    /// Log runkitd's own operation at this level or `tracing` filter, e.g. `debug`.
+    #[cfg(feature = "tracing")]
+    #[arg(long, global = true, value_name = "FILTER")]
+    log_level: Option<String>,
+    /// Where runkitd's own log goes; syslog falls back to stderr without `/dev/log`.
+    #[cfg(feature = "tracing")]
+    #[arg(long, global = true, value_enum)]
+    log_target: Option<logging::LogTarget>,
+    /// Refuse commands that change anything; frontends pass it when they run the
+    /// helper without elevation just to read state.
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// How to print the response; `table` and, with the `yaml` feature, `yaml` are
+    /// meant for reading in a terminal.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: OutputFormat,
+    /// Serve `tech.geektoshi.Runkit1` on the system bus instead of running a command.
+    #[arg(long)]
+    dbus: bool,
+    /// With `--dbus`, leave the bus after this many seconds without a method call.
+    #[arg(long, value_name = "SECS", requires = "dbus")]
+    idle_timeout: Option<u64>,
     #[command(subcommand)]
-    command: HelperCommand,
+    command: Option<HelperCommand>,
 }
 
 #[derive(Subcommand, Debug)]
 enum HelperCommand {
     /// Start a service and ensure it keeps running.
-    Start { service: String },
+    Start {
+        service: String,
+        /// Seconds to wait for `sv` to confirm the service is up; fails with the
+        /// `timeout` error code when it is not.
+        #[arg(long, alias = "wait", value_name = "SECS")]
+        timeout: Option<u64>,
+    },
     /// Stop a service and keep it down.
-    Stop { service: String },
-    /// Restart a service.
-    Restart { service: String },
+    Stop {
+        service: String,
+        /// Seconds to wait for `sv` to confirm the service is down; fails with the
+        /// `timeout` error code when it is not.
+        #[arg(long, alias = "wait", value_name = "SECS")]
+        timeout: Option<u64>,
+        /// Note why the service is being stopped; shown until it is started again.
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Restart a service and wait until it runs under a new pid.
+    Restart {
+        service: String,
+        /// Seconds to wait for the new process.
+        #[arg(long, default_value_t = 7, value_name = "SECS")]
+        timeout: u64,
+    },
+    /// Block until a service reaches a state; exits with status 10 when it does not in time.
+    Wait {
+        service: String,
+        /// running, paused, down, failed or unknown.
+        #[arg(long, default_value = "running", value_parser = parse_state)]
+        state: RuntimeStateKind,
+        /// Seconds to wait before giving up.
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+    },
     /// Reload a service's configuration.
     Reload { service: String },
-    /// Run the service's check script.
-    Check { service: String },
+    /// Run the service's check script and report its output.
+    Check {
+        service: String,
+        /// Seconds before the check script is killed.
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+    },
     /// Run a service once and exit.
     Once { service: String },
+    /// Suspend a running service with SIGSTOP, like `sv pause`.
+    Pause { service: String },
+    /// Resume a paused service with SIGCONT, like `sv cont`.
+    Cont { service: String },
     /// Enable a service (auto-start on boot).
-    Enable { service: String },
+    Enable {
+        service: String,
+        /// Link into this runlevel instead of the active one.
+        #[arg(long)]
+        runlevel: Option<String>,
+        /// Start the service right away and report its state once it runs.
+        #[arg(long, conflicts_with = "runlevel")]
+        now: bool,
+        /// With `--now`, seconds to wait for runsvdir to pick the service up and start it.
+        #[arg(long, default_value_t = 15)]
+        timeout: u64,
+    },
     /// Disable a service (stop auto-start).
-    Disable { service: String },
+    Disable {
+        service: String,
+        /// Unlink from this runlevel instead of the active one.
+        #[arg(long)]
+        runlevel: Option<String>,
+        /// Note why the service is being disabled; shown until it is enabled again.
+        #[arg(long)]
+        reason: Option<String>,
+        /// Take the service down and wait until it stopped before unlinking it.
+        #[arg(long, conflicts_with = "runlevel")]
+        stop: bool,
+        /// With `--stop`, seconds to wait for the service to go down.
+        #[arg(long, default_value_t = 7)]
+        timeout: u64,
+    },
+    /// List the available runlevels.
+    Runlevels,
     /// Fetch service description without loading logs or status.
     Describe { service: String },
     /// List all available services with their current status.
-    List,
+    List {
+        /// Only services in this state; repeat for several.
+        #[arg(long, value_parser = parse_state)]
+        state: Vec<RuntimeStateKind>,
+        /// Only services linked into the enabled directory.
+        #[arg(long)]
+        enabled_only: bool,
+        /// Only services whose name matches this glob (`*` and `?`).
+        #[arg(long, value_name = "GLOB")]
+        name_glob: Option<String>,
+        /// Add CPU, memory and process counts of running services under `stats`.
+        #[arg(long)]
+        with_stats: bool,
+        /// Flag running services whose binaries or libraries an upgrade replaced.
+        #[arg(long)]
+        check_upgrades: bool,
+        /// Read the status of this many services at once; defaults to the number of
+        /// CPUs.
+        #[arg(long, value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+    },
+    /// Report one service as `list` would, without reading any other.
+    Status { service: String },
+    /// Restart the service's svlogd logger.
+    RestartLogger { service: String },
+    /// Make the service's svlogd logger rotate its current file.
+    RotateLogs { service: String },
+    /// Report resource usage of a running service.
+    Stats { service: String },
+    /// Show the command line, user and start time of a running service.
+    Process { service: String },
+    /// List services that repository packages provide but that are not installed.
+    Installable,
+    /// Show journaled state changes, oldest first.
+    Events {
+        /// Only changes of this service.
+        service: Option<String>,
+        /// Only changes at or after this Unix time.
+        #[arg(long)]
+        since: Option<u64>,
+        /// Show at most this many of the newest changes.
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// List recent exits of a service with their exit code or signal, as seen by this
+    /// and earlier runs.
+    FinishHistory { service: String },
+    /// Report the size and rotation state of a service's log directory.
+    LogUsage { service: String },
+    /// Create `TEMPLATE@PARAM` from the `TEMPLATE@` definition.
+    Instantiate { template: String, param: String },
+    /// Write a new service definition; it is not enabled.
+    Create {
+        service: String,
+        /// Foreground command line the run script execs.
+        #[arg(long, value_name = "CMDLINE")]
+        exec: String,
+        /// Add a `log/run` script and send stdout and stderr to svlogd.
+        #[arg(long)]
+        logger: bool,
+        /// Run the command as this user through `chpst -u`.
+        #[arg(long)]
+        user: Option<String>,
+        /// Set a variable through the service's `env/` directory; repeatable.
+        #[arg(long = "env", value_name = "K=V", value_parser = parse_env_var)]
+        env: Vec<(String, String)>,
+    },
+    /// Write a new service definition translated from a systemd `.service` unit.
+    ConvertUnit {
+        file: PathBuf,
+        /// Name of the new service; defaults to the unit's, so `foo@.service` becomes
+        /// the template `foo@`.
+        #[arg(long)]
+        name: Option<String>,
+        /// Only print the scripts that would be written.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete a disabled, stopped service definition that no package owns.
+    Remove {
+        service: String,
+        /// Save the definition to this gzip-compressed tarball first.
+        #[arg(long, value_name = "PATH")]
+        archive: Option<PathBuf>,
+    },
+    /// Disable a service and prevent it from being enabled again.
+    Mask { service: String },
+    /// Allow a masked service to be enabled again.
+    Unmask { service: String },
+    /// Show or change the variables `chpst -e env` loads for a service.
+    Env {
+        service: String,
+        /// Set a variable; repeatable.
+        #[arg(long, value_name = "K=V", value_parser = parse_env_var)]
+        set: Vec<(String, String)>,
+        /// Remove a variable so the inherited value applies again; repeatable.
+        #[arg(long, value_name = "K")]
+        unset: Vec<String>,
+        /// Only list the variables; the default without `--set` or `--unset`.
+        #[arg(long, conflicts_with_all = ["set", "unset"])]
+        list: bool,
+    },
+    /// Show or change the variables in a service's `conf` file, keeping its comments.
+    Conf {
+        service: String,
+        /// Assign a variable, e.g. `--set OPTS="-4 -p 2222"`; repeatable.
+        #[arg(long, value_name = "K=V", value_parser = parse_env_var)]
+        set: Vec<(String, String)>,
+        /// Drop every assignment to a variable; repeatable.
+        #[arg(long, value_name = "K")]
+        unset: Vec<String>,
+        /// Print the value of one variable instead of all of them.
+        #[arg(long, value_name = "K", conflicts_with_all = ["set", "unset"])]
+        get: Option<String>,
+    },
+    /// Print the set of enabled services as JSON; `import-enabled` reads it back.
+    ExportEnabled,
+    /// Re-create the enabled set saved by `export-enabled` in a file.
+    ImportEnabled {
+        file: PathBuf,
+        /// Also disable services that are enabled here but not in the file.
+        #[arg(long)]
+        prune: bool,
+        /// Only report which links would be created or removed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Re-create enablement links from a snapshot read on stdin.
+    ApplyEnabled {
+        /// Only report which links would be created.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Run the operations in a JSON array read on stdin and report each one's result.
+    ///
+    /// Every operation is `{"method": "restart", "params": ["sshd"]}`, the subcommand
+    /// and the arguments that would follow it on the command line.
+    Batch,
+    /// Report the runit stage and the active runlevel.
+    System,
+    /// Halt the machine through runit's stage 3.
+    Poweroff,
+    /// Reboot the machine through runit's stage 3.
+    Reboot,
+    /// Make runsvdir supervise another runlevel.
+    SwitchRunlevel { runlevel: String },
+    /// Report leftover supervise state and dead supervisors.
+    Doctor,
+    /// Lint a service definition: missing or non-executable scripts, syntax errors,
+    /// programs that are not installed and dangling symlinks.
+    Verify {
+        #[arg(required_unless_present = "all")]
+        service: Option<String>,
+        /// Lint every definition and the enabled directory.
+        #[arg(long, conflicts_with = "service")]
+        all: bool,
+    },
+    /// Remove enablement links whose service definition no longer exists.
+    PruneLinks,
+    /// Deliver a signal through the control FIFO, as `sv` would.
+    Signal {
+        service: String,
+        /// hup, alarm, interrupt, quit, usr1, usr2, term, kill, pause or cont; the
+        /// SIG-prefixed names and sv's `1`/`2` work too.
+        #[arg(value_parser = parse_signal)]
+        signal: SvSignal,
+    },
+    /// Keep running and answer length-prefixed JSON-RPC requests on a Unix socket.
+    Serve {
+        #[arg(long, default_value = serve::DEFAULT_SOCKET)]
+        socket: PathBuf,
+        /// Let members of this group connect; otherwise only root can.
+        #[arg(long)]
+        group: Option<String>,
+        #[command(flatten)]
+        activation: serve::Activation,
+    },
+    /// Keep running and expose service states in Prometheus format at `/metrics`.
+    Metrics {
+        #[arg(long, default_value = metrics::DEFAULT_LISTEN)]
+        listen: SocketAddr,
+    },
+    /// Keep running and answer `io.runkit` varlink calls on a Unix socket.
+    Varlink {
+        #[arg(long, default_value = varlink::DEFAULT_SOCKET)]
+        socket: PathBuf,
+        /// Let members of this group connect; otherwise only root can.
+        #[arg(long)]
+        group: Option<String>,
+        #[command(flatten)]
+        activation: serve::Activation,
+    },
+    /// Keep running and print a JSON object per line for every service that is added,
+    /// removed, enabled, disabled or changes state.
+    Watch {
+        /// Only report this service; repeatable.
+        #[arg(long)]
+        service: Vec<String>,
+    },
+    /// Print a completion script for `shell`, for packagers.
+    #[command(hide = true)]
+    GenerateCompletions { shell: clap_complete::Shell },
+    /// Print the manpage, or write one per subcommand into `--out-dir`, for packagers.
+    #[command(hide = true)]
+    GenerateMan {
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
     /// Tail logs for a service.
     Logs {
         service: String,
         #[arg(long, default_value_t = 200)]
         lines: usize,
+        /// Keep running and print entries as they are written, one JSON object per line.
+        #[arg(long)]
+        follow: bool,
+        /// Only entries logged at or after this RFC 3339 time or age (`15m`, `2h`).
+        #[arg(long, value_parser = timespec::parse_time, conflicts_with = "follow")]
+        since: Option<SystemTime>,
+        /// Only entries logged at or before this RFC 3339 time or age.
+        #[arg(long, value_parser = timespec::parse_time, conflicts_with = "follow")]
+        until: Option<SystemTime>,
+        /// Only entries whose message matches this regular expression.
+        #[arg(long, value_parser = Regex::new)]
+        grep: Option<Regex>,
+        /// Only entries that do not match `--grep`.
+        #[arg(long, requires = "grep")]
+        invert: bool,
+        /// Read every rotated file as well as `current`.
+        #[arg(long, conflicts_with_all = ["follow", "rotations"])]
+        all: bool,
+        /// Read the newest N rotated files as well as `current`.
+        #[arg(long, value_name = "N", conflicts_with = "follow")]
+        rotations: Option<usize>,
     },
 }
 
+impl HelperCommand {
+    /// Commands that only read state. They need no elevation wherever the files they
+    /// read are readable to the caller, and they are all `--read-only` lets through.
+    /// Socket clients of any uid may run them, so nothing here opens a file the caller
+    /// names: root would read it for them.
+    fn is_read_only(&self) -> bool {
+        match self {
+            HelperCommand::Wait { .. }
+            | HelperCommand::Runlevels
+            | HelperCommand::Describe { .. }
+            | HelperCommand::List { .. }
+            | HelperCommand::Status { .. }
+            | HelperCommand::Stats { .. }
+            | HelperCommand::Process { .. }
+            | HelperCommand::Installable
+            | HelperCommand::Events { .. }
+            | HelperCommand::FinishHistory { .. }
+            | HelperCommand::LogUsage { .. }
+            | HelperCommand::ExportEnabled
+            | HelperCommand::System
+            | HelperCommand::Doctor
+            | HelperCommand::Verify { .. }
+            | HelperCommand::Metrics { .. }
+            | HelperCommand::Watch { .. }
+            | HelperCommand::GenerateCompletions { .. }
+            | HelperCommand::GenerateMan { .. }
+            | HelperCommand::Logs { .. } => true,
+            HelperCommand::Env { set, unset, .. } | HelperCommand::Conf { set, unset, .. } => {
+                set.is_empty() && unset.is_empty()
+            }
+            _ => false,
+        }
+    }
+
+    /// Commands that keep running or write straight to stdout instead of producing one
+    /// response. Only the command line runs them; sockets, D-Bus and batches refuse them.
+    pub(crate) fn is_streaming(&self) -> bool {
+        matches!(
+            self,
+            HelperCommand::Serve { .. }
+                | HelperCommand::Varlink { .. }
+                | HelperCommand::Metrics { .. }
+                | HelperCommand::Watch { .. }
+                | HelperCommand::Logs { follow: true, .. }
+                | HelperCommand::GenerateCompletions { .. }
+                | HelperCommand::GenerateMan { .. }
+        )
+    }
+}
+
+/// Why a [`HelperCommand::is_streaming`] command is refused outside the command line.
+pub(crate) const STREAMING: &str =
+    "this command keeps running or prints to stdout and is only available on the command line";
+
+/// Version of the response format. It only changes when existing fields change meaning
+/// or go away; new fields can appear without a bump.
+const PROTOCOL_VERSION: u32 = 1;
+
 #[derive(Debug, Serialize)]
 struct HelperResponse {
+    protocol_version: u32,
     status: ResponseStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<ErrorCode>,
     message: Option<String>,
     data: Option<Value>,
 }
@@ -60,24 +460,65 @@ enum ResponseStatus {
     Error,
 }
 
+/// Stable identifier of a failure, for frontends that offer remediation hints instead of
+/// matching on messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ErrorCode {
+    InvalidName,
+    DefinitionMissing,
+    AlreadyEnabled,
+    NotEnabled,
+    SvFailure,
+    Io,
+    SupervisorNotRunning,
+    CheckFailed,
+    Timeout,
+    NotSupervised,
+    PermissionDenied,
+    Masked,
+    SystemCommand,
+    RemovalRefused,
+    Other,
+}
+
 impl HelperResponse {
     fn ok_with(outcome: CommandOutcome) -> Self {
         Self {
+            protocol_version: PROTOCOL_VERSION,
             status: ResponseStatus::Ok,
+            error_code: None,
             message: outcome.message,
             data: outcome.data,
         }
     }
 
-    fn error(message: impl Into<String>) -> Self {
+    fn error(err: &HelperError) -> Self {
         Self {
+            protocol_version: PROTOCOL_VERSION,
             status: ResponseStatus::Error,
-            message: Some(message.into()),
-            data: None,
+            error_code: Some(err.code()),
+            message: Some(err.to_string()),
+            data: err.data(),
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct BatchOperation {
+    method: String,
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchResult {
+    method: String,
+    params: Vec<String>,
+    #[serde(flatten)]
+    response: HelperResponse,
+}
+
 #[derive(Debug)]
 struct CommandOutcome {
     message: Option<String>,
@@ -107,6 +548,14 @@ enum HelperError {
     AlreadyEnabled(String),
     #[error("service is not enabled: {0}")]
     NotEnabled(String),
+    #[error("runsv is not running for {0}; is the service enabled?")]
+    SupervisorNotRunning(String),
+    #[error("{0} is not supervised yet; runsvdir has not started runsv for it")]
+    NotSupervised(String),
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("{0} is masked; unmask it before enabling")]
+    Masked(String),
     #[error("command `{command}` failed for {service}: {message}")]
     SvFailure {
         command: String,
@@ -120,6 +569,18 @@ enum HelperError {
         source: std::io::Error,
     },
     #[error("{0}")]
+    Timeout(String),
+    #[error("{command} failed: {message}")]
+    SystemCommand { command: String, message: String },
+    #[error("refusing to remove {service}: {reason}")]
+    RemovalRefused { service: String, reason: String },
+    #[error("health check failed for {service}: {summary}")]
+    CheckFailed {
+        service: String,
+        summary: String,
+        outcome: Box<CheckOutcome>,
+    },
+    #[error("{0}")]
     Other(String),
 }
 
@@ -132,9 +593,45 @@ impl HelperError {
             HelperError::NotEnabled(_) => 5,
             HelperError::SvFailure { .. } => 6,
             HelperError::Io { .. } => 7,
+            HelperError::SupervisorNotRunning(_) => 8,
+            HelperError::CheckFailed { .. } => 9,
+            HelperError::Timeout(_) => 10,
+            HelperError::NotSupervised(_) => 11,
+            HelperError::PermissionDenied(_) => 12,
+            HelperError::Masked(_) => 13,
+            HelperError::SystemCommand { .. } => 14,
+            HelperError::RemovalRefused { .. } => 15,
             HelperError::Other(_) => 1,
         }
     }
+
+    fn code(&self) -> ErrorCode {
+        match self {
+            HelperError::InvalidService(_) => ErrorCode::InvalidName,
+            HelperError::DefinitionMissing { .. } => ErrorCode::DefinitionMissing,
+            HelperError::AlreadyEnabled(_) => ErrorCode::AlreadyEnabled,
+            HelperError::NotEnabled(_) => ErrorCode::NotEnabled,
+            HelperError::SvFailure { .. } => ErrorCode::SvFailure,
+            HelperError::Io { .. } => ErrorCode::Io,
+            HelperError::SupervisorNotRunning(_) => ErrorCode::SupervisorNotRunning,
+            HelperError::CheckFailed { .. } => ErrorCode::CheckFailed,
+            HelperError::Timeout(_) => ErrorCode::Timeout,
+            HelperError::NotSupervised(_) => ErrorCode::NotSupervised,
+            HelperError::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            HelperError::Masked(_) => ErrorCode::Masked,
+            HelperError::SystemCommand { .. } => ErrorCode::SystemCommand,
+            HelperError::RemovalRefused { .. } => ErrorCode::RemovalRefused,
+            HelperError::Other(_) => ErrorCode::Other,
+        }
+    }
+
+    /// Structured detail attached to the error response.
+    fn data(&self) -> Option<Value> {
+        match self {
+            HelperError::CheckFailed { outcome, .. } => serde_json::to_value(outcome).ok(),
+            _ => None,
+        }
+    }
 }
 
 impl From<runkit_core::ServiceError> for HelperError {
@@ -152,269 +649,1131 @@ impl From<runkit_core::ServiceError> for HelperError {
             runkit_core::ServiceError::LogUnavailable(service) => {
                 HelperError::Other(format!("log stream unavailable for {service}"))
             }
+            runkit_core::ServiceError::DefinitionMissing { service, path } => {
+                HelperError::DefinitionMissing { service, path }
+            }
+            err @ (runkit_core::ServiceError::DefinitionExists { .. }
+            | runkit_core::ServiceError::InvalidRunScript { .. }
+            | runkit_core::ServiceError::InvalidUnit(_)) => HelperError::Other(err.to_string()),
+            runkit_core::ServiceError::AlreadyEnabled(service) => {
+                HelperError::AlreadyEnabled(service)
+            }
+            runkit_core::ServiceError::NotEnabled(service) => HelperError::NotEnabled(service),
+            runkit_core::ServiceError::SupervisorNotRunning(service) => {
+                HelperError::SupervisorNotRunning(service)
+            }
+            runkit_core::ServiceError::Masked(service) => HelperError::Masked(service),
+            runkit_core::ServiceError::NotSupervised(service) => {
+                HelperError::NotSupervised(service)
+            }
+            runkit_core::ServiceError::PermissionDenied(detail) => {
+                HelperError::PermissionDenied(detail)
+            }
+            runkit_core::ServiceError::SystemCommand { command, message } => {
+                HelperError::SystemCommand { command, message }
+            }
+            runkit_core::ServiceError::UnknownRunlevel(runlevel) => {
+                HelperError::Other(format!("unknown runlevel: {runlevel}"))
+            }
+            runkit_core::ServiceError::RemovalRefused { service, reason } => {
+                HelperError::RemovalRefused { service, reason }
+            }
+            err @ runkit_core::ServiceError::Timeout { .. } => {
+                HelperError::Timeout(err.to_string())
+            }
+            err @ (runkit_core::ServiceError::MissingCheckScript(_)
+            | runkit_core::ServiceError::DependencyCycle(_)
+            | runkit_core::ServiceError::InvalidMetadata { .. }) => {
+                HelperError::Other(err.to_string())
+            }
             runkit_core::ServiceError::Other(err) => HelperError::Other(err.to_string()),
         }
     }
 }
 
 fn main() {
-    let response = execute();
+    let cli = Cli::parse();
+    // Generators print their artifact instead of a JSON response.
+    let generated = match &cli.command {
+        Some(HelperCommand::GenerateCompletions { shell }) => Some(print_completions(*shell)),
+        Some(HelperCommand::GenerateMan { out_dir }) => Some(generate_man(out_dir.as_deref())),
+        _ => None,
+    };
+    match generated {
+        Some(Ok(())) => return,
+        Some(Err(err)) => emit_and_exit(HelperResponse::error(&err), cli.format, err.exit_code()),
+        None => {}
+    }
+    #[cfg(feature = "tracing")]
+    logging::init(cli.log_level.as_deref(), cli.log_target);
+    let format = cli.format;
+    let response = execute(cli);
     match response {
         Ok(outcome) => {
-            emit_and_exit(HelperResponse::ok_with(outcome), 0);
+            emit_and_exit(HelperResponse::ok_with(outcome), format, 0);
         }
         Err(err) => {
-            emit_and_exit(HelperResponse::error(err.to_string()), err.exit_code());
+            emit_and_exit(HelperResponse::error(&err), format, err.exit_code());
         }
     }
 }
 
-fn emit_and_exit(response: HelperResponse, exit_code: i32) -> ! {
-    let output = serde_json::to_string(&response).unwrap_or_else(|_| {
+fn emit_and_exit(response: HelperResponse, format: OutputFormat, exit_code: i32) -> ! {
+    let output = output::render(&response, format).unwrap_or_else(|_| {
         "{\"status\":\"error\",\"message\":\"failed to serialize runkitd response\"}".to_string()
     });
     println!("{}", output);
     std::process::exit(exit_code);
 }
 
-fn execute() -> Result<CommandOutcome, HelperError> {
-    let cli = Cli::parse();
-    let context = HelperContext::default();
-    context.run(cli.command)
+fn execute(cli: Cli) -> Result<CommandOutcome, HelperError> {
+    let manager = if cli.user {
+        ServiceManager::for_scope(ServiceScope::User)?
+    } else {
+        ServiceManager::detect()
+    }
+    .with_description_cache(DescriptionCache::load_default())
+    .with_finish_history(FinishHistory::load_default());
+    let manager = match EventJournal::open_default() {
+        Some(journal) if cli.journal => manager.with_event_journal(journal),
+        _ => manager,
+    };
+    let context = HelperContext {
+        manager,
+        user_scope: cli.user,
+        read_only: cli.read_only,
+    };
+    let command = match (cli.dbus, cli.command) {
+        (false, Some(command)) => command,
+        (true, None) => return serve_dbus(context, cli.idle_timeout),
+        (true, Some(_)) => {
+            return Err(HelperError::Other(
+                "--dbus cannot be combined with a command".to_string(),
+            ));
+        }
+        (false, None) => Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "a command is required unless --dbus is given",
+            )
+            .exit(),
+    };
+    match command {
+        HelperCommand::Serve {
+            socket,
+            group,
+            activation,
+        } => {
+            serve::serve(&context, &socket, group.as_deref(), activation)?;
+            Ok(CommandOutcome::message("runkitd stopped serving"))
+        }
+        HelperCommand::Metrics { listen } => {
+            metrics::serve(&context.manager, listen)?;
+            Ok(CommandOutcome::message("runkitd stopped exporting metrics"))
+        }
+        HelperCommand::Varlink {
+            socket,
+            group,
+            activation,
+        } => {
+            varlink::serve(&context, &socket, group.as_deref(), activation)?;
+            Ok(CommandOutcome::message("runkitd stopped serving varlink"))
+        }
+        HelperCommand::Logs {
+            service,
+            follow: true,
+            grep,
+            invert,
+            ..
+        } => {
+            let query = LogQuery {
+                regex: grep,
+                invert,
+                ..LogQuery::default()
+            };
+            follow_logs(&context.manager, &service, &query)
+        }
+        HelperCommand::Watch { service } => watch(&context.manager, &service),
+        command => context.run(command),
+    }
 }
 
-#[derive(Debug)]
-struct HelperContext {
-    manager: ServiceManager,
+fn print_completions(shell: clap_complete::Shell) -> Result<(), HelperError> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "runkitd", &mut script);
+    print_generated(&script)
 }
 
-impl Default for HelperContext {
-    fn default() -> Self {
-        HelperContext {
-            manager: ServiceManager::default(),
+/// Print the manpage of runkitd itself, or write every page into `out_dir`.
+fn generate_man(out_dir: Option<&Path>) -> Result<(), HelperError> {
+    let command = Cli::command();
+    let Some(dir) = out_dir else {
+        let mut page = Vec::new();
+        clap_mangen::Man::new(command)
+            .render(&mut page)
+            .map_err(|err| HelperError::Other(err.to_string()))?;
+        return print_generated(&page);
+    };
+    clap_mangen::generate_to(command, dir).map_err(|source| HelperError::Io {
+        path: dir.to_path_buf(),
+        source,
+    })
+}
+
+/// Write generated output; a reader that stops early, like `head`, is not an error.
+fn print_generated(bytes: &[u8]) -> Result<(), HelperError> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => {
+            Err(HelperError::Other(format!("writing to stdout: {err}")))
         }
+        _ => Ok(()),
     }
 }
 
-impl HelperContext {
-    fn run(&self, command: HelperCommand) -> Result<CommandOutcome, HelperError> {
-        match command {
-            HelperCommand::Start { service } => self.call_sv("up", &service),
-            HelperCommand::Stop { service } => self.call_sv("down", &service),
-            HelperCommand::Restart { service } => self.call_sv("restart", &service),
-            HelperCommand::Reload { service } => self.call_sv("reload", &service),
-            HelperCommand::Check { service } => self.call_sv("check", &service),
-            HelperCommand::Once { service } => self.call_sv("once", &service),
-            HelperCommand::Enable { service } => self.enable(&service),
-            HelperCommand::Disable { service } => self.disable(&service),
-            HelperCommand::Describe { service } => self.describe(&service),
-            HelperCommand::List => self.list(),
-            HelperCommand::Logs { service, lines } => self.logs(&service, lines),
-        }
+/// How often `watch` wakes up when nothing changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Split a `--env K=V` argument.
+fn parse_env_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got `{arg}`")),
     }
+}
 
-    fn call_sv(&self, subcommand: &str, service: &str) -> Result<CommandOutcome, HelperError> {
-        self.manager.validate_service_name(service)?;
-        let mut command = Command::new(self.manager.sv_command_path());
-        command.arg(subcommand).arg(service);
+/// Parse a `--state` argument.
+fn parse_state(name: &str) -> Result<RuntimeStateKind, String> {
+    RuntimeStateKind::from_name(name).ok_or_else(|| format!("unknown state `{name}`"))
+}
 
-        let output = command.output().map_err(|err| HelperError::Io {
-            path: self.manager.sv_command_path().to_path_buf(),
-            source: err,
-        })?;
+/// Parse a `signal` argument.
+fn parse_signal(name: &str) -> Result<SvSignal, String> {
+    SvSignal::from_name(name).ok_or_else(|| format!("unknown signal `{name}`"))
+}
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-            return Err(HelperError::SvFailure {
-                command: subcommand.to_string(),
-                service: service.to_string(),
-                message: if stderr.is_empty() {
-                    format!("exit status {}", output.status)
-                } else {
-                    stderr
-                },
-            });
+/// Print entries matching `query` as svlogd writes them, one JSON object per line,
+/// until the reader closes stdout. A failure after streaming started ends the stream
+/// with the usual error response.
+fn follow_logs(
+    manager: &ServiceManager,
+    service: &str,
+    query: &LogQuery,
+) -> Result<CommandOutcome, HelperError> {
+    let follower = manager.follow_logs(service)?;
+    let mut stdout = std::io::stdout().lock();
+    for entry in follower {
+        let entry = entry?;
+        if !query.matches(&entry) {
+            continue;
+        }
+        let line =
+            serde_json::to_string(&entry).map_err(|err| HelperError::Other(err.to_string()))?;
+        match writeln!(stdout, "{line}").and_then(|()| stdout.flush()) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::BrokenPipe => std::process::exit(0),
+            Err(err) => return Err(HelperError::Other(format!("writing to stdout: {err}"))),
         }
+    }
+    Ok(CommandOutcome::message(format!(
+        "{service} has no more log entries"
+    )))
+}
 
-        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        Ok(CommandOutcome::message(if stdout.is_empty() {
-            format!("{subcommand} command executed for {service}")
-        } else {
-            stdout
-        }))
+/// Print every change the watcher reports, one JSON object per line, until the reader
+/// closes stdout or watching fails.
+fn watch(manager: &ServiceManager, services: &[String]) -> Result<CommandOutcome, HelperError> {
+    let mut watcher = ServiceWatcher::new(manager)?;
+    let mut stdout = std::io::stdout().lock();
+    loop {
+        for event in watcher.wait(WATCH_INTERVAL)? {
+            if !services.is_empty() && !services.iter().any(|name| name == event.service()) {
+                continue;
+            }
+            let line = watch_event(manager, &event).to_string();
+            match writeln!(stdout, "{line}").and_then(|()| stdout.flush()) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::BrokenPipe => std::process::exit(0),
+                Err(err) => return Err(HelperError::Other(format!("writing to stdout: {err}"))),
+            }
+        }
     }
+}
 
-    fn enable(&self, service: &str) -> Result<CommandOutcome, HelperError> {
-        self.manager.validate_service_name(service)?;
-        let src = self.manager.definitions_dir().join(service);
-        if !src.exists() {
-            return Err(HelperError::DefinitionMissing {
-                service: service.to_string(),
-                path: src,
+fn watch_event(manager: &ServiceManager, event: &ServiceEvent) -> Value {
+    let service = event.service();
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let kind = match event {
+        ServiceEvent::DefinitionAdded(_) => "added",
+        ServiceEvent::DefinitionRemoved(_) => "removed",
+        ServiceEvent::Enabled(_) => "enabled",
+        ServiceEvent::Disabled(_) => "disabled",
+        ServiceEvent::StatusChanged(_) => {
+            // The record may be gone again by the time it is read.
+            let state = manager.status(service).ok();
+            return json!({
+                "event": "state_changed",
+                "service": service,
+                "time": time,
+                "state": state.as_ref().map(|state| state.kind().name()),
+                "pid": state.as_ref().and_then(|state| state.pid()),
             });
         }
+    };
+    json!({ "event": kind, "service": service, "time": time })
+}
+
+#[cfg(feature = "dbus")]
+fn serve_dbus(
+    context: HelperContext,
+    idle_timeout: Option<u64>,
+) -> Result<CommandOutcome, HelperError> {
+    dbus::serve(context, idle_timeout.map(Duration::from_secs))?;
+    Ok(CommandOutcome::message("runkitd left the system bus"))
+}
+
+#[cfg(not(feature = "dbus"))]
+fn serve_dbus(
+    _context: HelperContext,
+    _idle_timeout: Option<u64>,
+) -> Result<CommandOutcome, HelperError> {
+    Err(HelperError::Other(
+        "runkitd was built without D-Bus support; rebuild with --features dbus".to_string(),
+    ))
+}
+
+#[derive(Debug, Default)]
+struct HelperContext {
+    manager: ServiceManager,
+    user_scope: bool,
+    read_only: bool,
+}
 
-        let dest = self.manager.enabled_dir().join(service);
-        if dest.exists() {
-            return Err(HelperError::AlreadyEnabled(service.to_string()));
+impl HelperContext {
+    fn run(&self, command: HelperCommand) -> Result<CommandOutcome, HelperError> {
+        let read_only = command.is_read_only();
+        #[cfg(feature = "tracing")]
+        if read_only {
+            tracing::debug!(?command, "running");
+        } else {
+            tracing::info!(?command, "running");
         }
+        if self.read_only && !read_only {
+            return Err(HelperError::PermissionDenied(
+                "runkitd was started with --read-only".to_string(),
+            ));
+        }
+        if command.is_streaming() {
+            return Err(HelperError::Other(STREAMING.to_string()));
+        }
+        let result = self.dispatch(command);
+        #[cfg(feature = "tracing")]
+        if let Err(err) = &result {
+            tracing::warn!(code = ?err.code(), "{err}");
+        }
+        result
+    }
 
-        unix_fs::symlink(&src, &dest).map_err(|err| HelperError::Io {
-            path: dest.clone(),
-            source: err,
-        })?;
+    fn dispatch(&self, command: HelperCommand) -> Result<CommandOutcome, HelperError> {
+        match command {
+            HelperCommand::Start {
+                service,
+                timeout: Some(seconds),
+            } => {
+                let manager = self
+                    .manager
+                    .clone()
+                    .with_sv_timeout(Duration::from_secs(seconds));
+                manager.start(&service)?;
+                Ok(CommandOutcome::message(format!("{service} is up")))
+            }
+            HelperCommand::Start { service, .. } => {
+                let outcome = self.control("up", &service, &[ControlCommand::Up])?;
+                self.manager.clear_down_reason(&service)?;
+                Ok(outcome)
+            }
+            HelperCommand::Stop {
+                service,
+                timeout: Some(seconds),
+                reason,
+            } => {
+                let manager = self
+                    .manager
+                    .clone()
+                    .with_sv_timeout(Duration::from_secs(seconds));
+                manager.stop(&service)?;
+                self.note_down_reason(&service, reason.as_deref())?;
+                Ok(CommandOutcome::message(format!("{service} is down")))
+            }
+            HelperCommand::Stop {
+                service, reason, ..
+            } => {
+                let outcome = self.control("down", &service, &[ControlCommand::Down])?;
+                self.note_down_reason(&service, reason.as_deref())?;
+                Ok(outcome)
+            }
+            HelperCommand::Restart { service, timeout } => {
+                self.restart(&service, Duration::from_secs(timeout))
+            }
+            HelperCommand::Wait {
+                service,
+                state,
+                timeout,
+            } => {
+                let reached = self.manager.wait_for(
+                    &service,
+                    |current| current.kind() == state,
+                    Duration::from_secs(timeout),
+                )?;
+                Ok(CommandOutcome::with(
+                    Some(format!("{service} is {}", state.name())),
+                    serde_json::to_value(reached).ok(),
+                ))
+            }
+            HelperCommand::Reload { service } => {
+                self.control("reload", &service, &[ControlCommand::Hangup])
+            }
+            HelperCommand::Check { service, timeout } => {
+                self.check(&service, Duration::from_secs(timeout))
+            }
+            HelperCommand::Once { service } => {
+                self.manager.once(&service)?;
+                Ok(CommandOutcome::message(format!(
+                    "{service} started once; it will not be restarted"
+                )))
+            }
+            HelperCommand::Pause { service } => {
+                self.manager.pause(&service)?;
+                Ok(CommandOutcome::message(format!("{service} paused")))
+            }
+            HelperCommand::Cont { service } => {
+                self.manager.cont(&service)?;
+                Ok(CommandOutcome::message(format!("{service} resumed")))
+            }
+            HelperCommand::Enable {
+                service,
+                now: true,
+                timeout,
+                ..
+            } => {
+                let state = self
+                    .manager
+                    .enable_now(&service, Duration::from_secs(timeout))?;
+                Ok(CommandOutcome::with(
+                    Some(format!("Enabled and started service {service}")),
+                    serde_json::to_value(state).ok(),
+                ))
+            }
+            HelperCommand::Enable {
+                service, runlevel, ..
+            } => self.enable(&service, runlevel.as_deref()),
+            HelperCommand::Disable {
+                service,
+                reason,
+                stop: true,
+                timeout,
+                ..
+            } => {
+                let state = self
+                    .manager
+                    .disable_now(&service, Duration::from_secs(timeout))?;
+                self.note_down_reason(&service, reason.as_deref())?;
+                let message = match state {
+                    Some(_) => format!("Stopped and disabled service {service}"),
+                    None => format!("Disabled service {service}; it was not supervised"),
+                };
+                Ok(CommandOutcome::with(
+                    Some(message),
+                    serde_json::to_value(state).ok(),
+                ))
+            }
+            HelperCommand::Disable {
+                service,
+                runlevel,
+                reason,
+                ..
+            } => {
+                let outcome = self.disable(&service, runlevel.as_deref())?;
+                self.note_down_reason(&service, reason.as_deref())?;
+                Ok(outcome)
+            }
+            HelperCommand::Runlevels => self.runlevels(),
+            HelperCommand::Describe { service } => self.describe(&service),
+            HelperCommand::List {
+                state,
+                enabled_only,
+                name_glob,
+                with_stats,
+                check_upgrades,
+                jobs,
+            } => self.list(
+                &ServiceQuery {
+                    enabled: enabled_only.then_some(true),
+                    states: state,
+                    name_glob,
+                    check_upgrades,
+                    ..ServiceQuery::default()
+                },
+                with_stats,
+                jobs,
+            ),
+            HelperCommand::Status { service } => {
+                let info = self.manager.service_info(&service)?;
+                let data = serde_json::to_value(info)
+                    .map_err(|err| HelperError::Other(err.to_string()))?;
+                Ok(CommandOutcome::with(None, Some(data)))
+            }
+            HelperCommand::RestartLogger { service } => {
+                self.manager.restart_logger(&service)?;
+                Ok(CommandOutcome::message(format!(
+                    "logger restarted for {service}"
+                )))
+            }
+            HelperCommand::RotateLogs { service } => {
+                self.manager.rotate_logs(&service)?;
+                Ok(CommandOutcome::message(format!(
+                    "log rotation requested for {service}"
+                )))
+            }
+            HelperCommand::Stats { service } => self.stats(&service),
+            HelperCommand::Process { service } => {
+                let info = self.manager.process_info(&service)?;
+                let message = info.is_none().then(|| format!("{service} is not running"));
+                Ok(CommandOutcome::with(
+                    message,
+                    serde_json::to_value(info).ok(),
+                ))
+            }
+            HelperCommand::Installable => {
+                let services = self.manager.installable_services()?;
+                let message = services
+                    .is_empty()
+                    .then(|| "every packaged service is already installed".to_string());
+                Ok(CommandOutcome::with(
+                    message,
+                    serde_json::to_value(services).ok(),
+                ))
+            }
+            HelperCommand::Events {
+                service,
+                since,
+                limit,
+            } => {
+                let query = JournalQuery {
+                    service,
+                    since: since.map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds)),
+                    limit,
+                };
+                let Some(journal) = EventJournal::open_default() else {
+                    return Err(HelperError::Other(
+                        "cannot locate the event journal; set XDG_STATE_HOME or HOME".into(),
+                    ));
+                };
+                let events = journal
+                    .events(&query)
+                    .map_err(|err| runkit_core::ServiceError::from_io(journal.path(), err))?;
+                Ok(CommandOutcome::with(
+                    None,
+                    serde_json::to_value(events).ok(),
+                ))
+            }
+            HelperCommand::FinishHistory { service } => {
+                let finishes = self.manager.finish_history(&service)?;
+                let message = finishes
+                    .is_empty()
+                    .then(|| format!("no exits of {service} observed"));
+                Ok(CommandOutcome::with(
+                    message,
+                    serde_json::to_value(finishes).ok(),
+                ))
+            }
+            HelperCommand::LogUsage { service } => {
+                let usage = self.manager.log_usage(&service)?;
+                let message = usage
+                    .is_none()
+                    .then(|| format!("{service} has no svlogd logs"));
+                Ok(CommandOutcome::with(
+                    message,
+                    serde_json::to_value(usage).ok(),
+                ))
+            }
+            HelperCommand::Instantiate { template, param } => {
+                let path = self.manager.create_instance(&template, &param)?;
+                Ok(CommandOutcome::with(
+                    Some(format!("created {template}@{param}")),
+                    Some(json!({ "path": path.display().to_string() })),
+                ))
+            }
+            HelperCommand::Create {
+                service,
+                exec,
+                logger,
+                user,
+                env,
+            } => {
+                let mut spec = RunScriptSpec::new(exec)
+                    .with_logger(logger)
+                    .with_stderr_redirect(logger);
+                if let Some(user) = user {
+                    spec = spec.with_user(user);
+                }
+                for (name, value) in env {
+                    spec = spec.with_env(name, value);
+                }
+                let path = self.manager.create_service(&service, &spec)?;
+                Ok(CommandOutcome::with(
+                    Some(format!("created {service}")),
+                    Some(json!({ "path": path.display().to_string() })),
+                ))
+            }
+            HelperCommand::ConvertUnit {
+                file,
+                name,
+                dry_run,
+            } => {
+                let unit = std::fs::read_to_string(&file).map_err(|source| HelperError::Io {
+                    path: file.clone(),
+                    source,
+                })?;
+                let file_name = file
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let service = match name {
+                    Some(name) => name,
+                    None => file_name
+                        .strip_suffix(".service")
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string)
+                        .ok_or_else(|| {
+                            HelperError::Other(format!(
+                                "{file_name} is not a .service unit; pass --name"
+                            ))
+                        })?,
+                };
+                let UnitConversion { spec, warnings } = UnitConversion::parse(&unit)?;
+                if dry_run {
+                    let env: serde_json::Map<String, Value> = spec
+                        .env()
+                        .iter()
+                        .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+                        .collect();
+                    return Ok(CommandOutcome::with(
+                        None,
+                        Some(json!({
+                            "service": service,
+                            "run": spec.render_run(),
+                            "finish": spec.finish(),
+                            "log": spec.has_logger().then(|| spec.render_log_run()),
+                            "env": env,
+                            "warnings": warnings,
+                        })),
+                    ));
+                }
+                let path = self.manager.create_service(&service, &spec)?;
+                Ok(CommandOutcome::with(
+                    Some(format!("created {service} from {file_name}")),
+                    Some(json!({
+                        "service": service,
+                        "path": path.display().to_string(),
+                        "warnings": warnings,
+                    })),
+                ))
+            }
+            HelperCommand::Env {
+                service,
+                set,
+                unset,
+                ..
+            } => {
+                let env = self.manager.env_dir(&service)?;
+                env.update(&set, &unset)?;
+                // An empty file unsets the variable for the service, shown as `null`.
+                let vars: serde_json::Map<String, Value> = env
+                    .list()?
+                    .into_iter()
+                    .map(|(name, value)| match value {
+                        EnvValue::Set(value) => (name, Value::String(value)),
+                        EnvValue::Unset => (name, Value::Null),
+                    })
+                    .collect();
+                let message = (!set.is_empty() || !unset.is_empty()).then(|| {
+                    format!("updated the environment of {service}; restart it to apply the change")
+                });
+                Ok(CommandOutcome::with(message, Some(Value::Object(vars))))
+            }
+            HelperCommand::Conf {
+                service,
+                get: Some(key),
+                ..
+            } => {
+                let conf = self.manager.conf(&service)?;
+                Ok(CommandOutcome::with(
+                    None,
+                    Some(json!({ key.as_str(): conf.get(&key) })),
+                ))
+            }
+            HelperCommand::Conf {
+                service,
+                set,
+                unset,
+                get: None,
+            } => {
+                let mut conf = self.manager.conf(&service)?;
+                let changed = !set.is_empty() || !unset.is_empty();
+                for (key, value) in &set {
+                    conf.set(key, value)?;
+                }
+                for key in &unset {
+                    conf.remove(key);
+                }
+                if changed {
+                    self.manager.write_conf(&service, &conf)?;
+                }
+                let vars: serde_json::Map<String, Value> = conf
+                    .keys()
+                    .into_iter()
+                    .map(|key| (key.to_string(), json!(conf.get(key))))
+                    .collect();
+                let message = changed.then(|| {
+                    format!("updated the conf file of {service}; restart it to apply the change")
+                });
+                Ok(CommandOutcome::with(message, Some(Value::Object(vars))))
+            }
+            HelperCommand::Remove { service, archive } => {
+                self.manager.remove_service(&service, archive.as_deref())?;
+                let message = match &archive {
+                    Some(archive) => {
+                        format!("removed {service}; archived to {}", archive.display())
+                    }
+                    None => format!("removed {service}"),
+                };
+                Ok(CommandOutcome::message(message))
+            }
+            HelperCommand::Mask { service } => {
+                self.manager.mask(&service)?;
+                self.mask_outcome(&service, format!("{service} masked"))
+            }
+            HelperCommand::Unmask { service } => {
+                self.manager.unmask(&service)?;
+                self.mask_outcome(&service, format!("{service} unmasked"))
+            }
+            HelperCommand::ExportEnabled => {
+                let snapshot = self.manager.export_enabled()?;
+                Ok(CommandOutcome::with(
+                    None,
+                    serde_json::to_value(&snapshot).ok(),
+                ))
+            }
+            HelperCommand::ImportEnabled {
+                file,
+                prune,
+                dry_run,
+            } => {
+                let io_error = |source| HelperError::Io {
+                    path: file.clone(),
+                    source,
+                };
+                let contents = std::fs::read(&file).map_err(io_error)?;
+                // `export-enabled` prints a whole response; a bare snapshot works as well.
+                let mut value: Value = serde_json::from_slice(&contents)
+                    .map_err(|err| HelperError::Other(format!("invalid snapshot file: {err}")))?;
+                if let Some(data) = value.get_mut("data").map(Value::take) {
+                    value = data;
+                }
+                let snapshot: EnabledSnapshot = serde_json::from_value(value)
+                    .map_err(|err| HelperError::Other(format!("invalid snapshot file: {err}")))?;
+                self.apply_snapshot(&snapshot, dry_run, prune)
+            }
+            HelperCommand::ApplyEnabled { dry_run } => {
+                let snapshot: EnabledSnapshot = serde_json::from_reader(std::io::stdin().lock())
+                    .map_err(|err| {
+                        HelperError::Other(format!("invalid snapshot on stdin: {err}"))
+                    })?;
+                self.apply_snapshot(&snapshot, dry_run, false)
+            }
+            HelperCommand::Batch => self.batch(),
+            HelperCommand::System => {
+                let stage = self.manager.system_stage();
+                Ok(CommandOutcome::with(
+                    None,
+                    serde_json::to_value(&stage).ok(),
+                ))
+            }
+            HelperCommand::Poweroff => self.power(PowerAction::Poweroff),
+            HelperCommand::Reboot => self.power(PowerAction::Reboot),
+            HelperCommand::SwitchRunlevel { runlevel } => {
+                self.system_control()?
+                    .switch_runlevel(&self.manager, &runlevel)?;
+                Ok(CommandOutcome::message(format!(
+                    "switched to runlevel {runlevel}"
+                )))
+            }
+            HelperCommand::Doctor => {
+                let report = self.manager.audit()?;
+                let message = if report.is_clean() {
+                    "no problems found".to_string()
+                } else {
+                    report
+                        .findings
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                Ok(CommandOutcome::with(
+                    Some(message),
+                    serde_json::to_value(&report).ok(),
+                ))
+            }
+            HelperCommand::Verify { service, .. } => {
+                let report = match service.as_deref() {
+                    Some(service) => self.manager.lint(service)?,
+                    None => self.manager.lint_all()?,
+                };
+                let message = if report.is_clean() {
+                    "no problems found".to_string()
+                } else {
+                    report
+                        .findings
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                Ok(CommandOutcome::with(
+                    Some(message),
+                    serde_json::to_value(&report).ok(),
+                ))
+            }
+            HelperCommand::PruneLinks => {
+                let removed = self.manager.prune_broken_links()?;
+                let names: Vec<&str> = removed.iter().map(|link| link.service.as_str()).collect();
+                Ok(CommandOutcome::with(
+                    Some(format!("removed {} broken link(s)", removed.len())),
+                    Some(json!(names)),
+                ))
+            }
+            HelperCommand::Signal { service, signal } => {
+                self.manager.signal(&service, signal)?;
+                Ok(CommandOutcome::with(
+                    Some(format!("SIG{signal} sent to {service}")),
+                    Some(json!({
+                        "service": service,
+                        "signal": signal.name(),
+                        "command": char::from(signal.control_command().as_byte()).to_string(),
+                    })),
+                ))
+            }
+            HelperCommand::Logs {
+                service,
+                lines,
+                follow: false,
+                since,
+                until,
+                grep,
+                invert,
+                all,
+                rotations,
+            } => {
+                let filtered = since.is_some() || until.is_some() || grep.is_some();
+                if !filtered && !all && rotations.is_none() {
+                    return self.logs(&service, lines);
+                }
+                let query = LogQuery {
+                    regex: grep,
+                    invert,
+                    since,
+                    until,
+                    limit: Some(lines),
+                    rotations,
+                    ..LogQuery::default()
+                };
+                self.search_logs(&service, &query)
+            }
+            HelperCommand::Logs { follow: true, .. }
+            | HelperCommand::Serve { .. }
+            | HelperCommand::Varlink { .. }
+            | HelperCommand::Metrics { .. }
+            | HelperCommand::Watch { .. }
+            | HelperCommand::GenerateCompletions { .. }
+            | HelperCommand::GenerateMan { .. } => {
+                unreachable!("streaming commands are refused before dispatch")
+            }
+        }
+    }
 
+    fn control(
+        &self,
+        label: &str,
+        service: &str,
+        commands: &[ControlCommand],
+    ) -> Result<CommandOutcome, HelperError> {
+        self.manager.send_control(service, commands)?;
         Ok(CommandOutcome::message(format!(
-            "Enabled service {service}"
+            "{label} command executed for {service}"
         )))
     }
 
-    fn disable(&self, service: &str) -> Result<CommandOutcome, HelperError> {
-        self.manager.validate_service_name(service)?;
-        let dest = self.manager.enabled_dir().join(service);
-        if !dest.exists() {
-            return Err(HelperError::NotEnabled(service.to_string()));
+    fn note_down_reason(&self, service: &str, reason: Option<&str>) -> Result<(), HelperError> {
+        if let Some(reason) = reason.filter(|reason| !reason.trim().is_empty()) {
+            self.manager.set_down_reason(service, reason)?;
         }
+        Ok(())
+    }
 
-        std::fs::remove_file(&dest).map_err(|err| HelperError::Io {
-            path: dest.clone(),
-            source: err,
-        })?;
+    fn restart(&self, service: &str, timeout: Duration) -> Result<CommandOutcome, HelperError> {
+        let pid = self.manager.restart_verified(service, timeout)?;
+        Ok(CommandOutcome::with(
+            Some(format!("{service} restarted (pid {pid})")),
+            Some(json!({ "pid": pid })),
+        ))
+    }
 
-        Ok(CommandOutcome::message(format!(
-            "Disabled service {service}"
-        )))
+    fn check(&self, service: &str, timeout: Duration) -> Result<CommandOutcome, HelperError> {
+        let outcome = self.manager.run_check(service, timeout)?;
+        if !outcome.passed() {
+            let millis = outcome.duration.as_millis();
+            let summary = match outcome.exit_code {
+                _ if outcome.timed_out => format!("timed out after {}s", timeout.as_secs()),
+                Some(code) => format!("exit status {code} after {millis} ms"),
+                None => format!("killed by a signal after {millis} ms"),
+            };
+            return Err(HelperError::CheckFailed {
+                service: service.to_string(),
+                summary,
+                outcome: Box::new(outcome),
+            });
+        }
+        Ok(CommandOutcome::with(
+            Some(format!(
+                "check passed for {service} in {} ms",
+                outcome.duration.as_millis()
+            )),
+            serde_json::to_value(&outcome).ok(),
+        ))
     }
 
-    fn list(&self) -> Result<CommandOutcome, HelperError> {
-        let services = self.manager.list_services()?;
-        let snapshots: Vec<ServiceSnapshot> = services.iter().map(ServiceSnapshot::from).collect();
-        let data =
-            serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
+    fn enable(&self, service: &str, runlevel: Option<&str>) -> Result<CommandOutcome, HelperError> {
+        match runlevel {
+            Some(runlevel) => {
+                self.manager.enable_in(service, runlevel)?;
+                Ok(CommandOutcome::message(format!(
+                    "Enabled service {service} in runlevel {runlevel}"
+                )))
+            }
+            None => {
+                self.manager.enable(service)?;
+                Ok(CommandOutcome::message(format!(
+                    "Enabled service {service}"
+                )))
+            }
+        }
+    }
+
+    fn disable(
+        &self,
+        service: &str,
+        runlevel: Option<&str>,
+    ) -> Result<CommandOutcome, HelperError> {
+        match runlevel {
+            Some(runlevel) => {
+                self.manager.disable_in(service, runlevel)?;
+                Ok(CommandOutcome::message(format!(
+                    "Disabled service {service} in runlevel {runlevel}"
+                )))
+            }
+            None => {
+                self.manager.disable(service)?;
+                Ok(CommandOutcome::message(format!(
+                    "Disabled service {service}"
+                )))
+            }
+        }
+    }
+
+    fn runlevels(&self) -> Result<CommandOutcome, HelperError> {
+        let runlevels = self.manager.runlevels()?;
+        Ok(CommandOutcome::with(None, Some(json!(runlevels))))
+    }
+
+    fn list(
+        &self,
+        query: &ServiceQuery,
+        with_stats: bool,
+        jobs: Option<NonZeroUsize>,
+    ) -> Result<CommandOutcome, HelperError> {
+        // `sv status` runs once per enabled service; overlapping them keeps a full
+        // listing from taking as long as all of them back to back.
+        let jobs = jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN));
+        let services = self.manager.list_services_parallel(query, jobs)?;
+        let mut data =
+            serde_json::to_value(&services).map_err(|err| HelperError::Other(err.to_string()))?;
+        if with_stats {
+            let pids: Vec<u32> = services
+                .iter()
+                .filter_map(|info| info.runtime_state.pid())
+                .collect();
+            let stats = ServiceStats::for_pids(&pids).map_err(|source| HelperError::Io {
+                path: PathBuf::from("/proc"),
+                source,
+            })?;
+            // Services that are not running, or exited meanwhile, get `null`.
+            if let Value::Array(entries) = &mut data {
+                for (entry, info) in entries.iter_mut().zip(&services) {
+                    let usage = info.runtime_state.pid().and_then(|pid| stats.get(&pid));
+                    if let Value::Object(entry) = entry {
+                        entry.insert("stats".to_string(), json!(usage));
+                    }
+                }
+            }
+        }
         Ok(CommandOutcome::with(None, Some(data)))
     }
 
     fn logs(&self, service: &str, lines: usize) -> Result<CommandOutcome, HelperError> {
         let entries = self.manager.tail_logs(service, lines)?;
-        let snapshots: Vec<LogEntrySnapshot> =
-            entries.into_iter().map(LogEntrySnapshot::from).collect();
         let data =
-            serde_json::to_value(snapshots).map_err(|err| HelperError::Other(err.to_string()))?;
+            serde_json::to_value(entries).map_err(|err| HelperError::Other(err.to_string()))?;
         Ok(CommandOutcome::with(None, Some(data)))
     }
 
-    fn describe(&self, service: &str) -> Result<CommandOutcome, HelperError> {
-        let description = self.manager.service_description(service)?;
-        let data = json!({
-            "service": service,
-            "description": description,
-        });
+    /// Entries matching `query` across `current` and the rotated files.
+    fn search_logs(&self, service: &str, query: &LogQuery) -> Result<CommandOutcome, HelperError> {
+        if self.manager.log_dir(service)?.is_none() {
+            return Err(runkit_core::ServiceError::LogUnavailable(service.to_string()).into());
+        }
+        let entries = self.manager.search_logs(service, query)?;
+        let data =
+            serde_json::to_value(entries).map_err(|err| HelperError::Other(err.to_string()))?;
         Ok(CommandOutcome::with(None, Some(data)))
     }
-}
 
-#[derive(Debug, Serialize)]
-struct ServiceSnapshot {
-    name: String,
-    definition_path: String,
-    enabled: bool,
-    desired_state: SnapshotDesiredState,
-    runtime_state: SnapshotRuntimeState,
-    description: Option<String>,
-}
+    fn stats(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let stats = self.manager.stats(service)?;
+        let message = stats.is_none().then(|| format!("{service} is not running"));
+        let data =
+            serde_json::to_value(stats).map_err(|err| HelperError::Other(err.to_string()))?;
+        Ok(CommandOutcome::with(message, Some(data)))
+    }
 
-impl From<&ServiceInfo> for ServiceSnapshot {
-    fn from(info: &ServiceInfo) -> Self {
-        ServiceSnapshot {
-            name: info.name.clone(),
-            definition_path: info.definition_path.to_string_lossy().to_string(),
-            enabled: info.enabled,
-            desired_state: SnapshotDesiredState::from(info.desired_state),
-            runtime_state: SnapshotRuntimeState::from(&info.runtime_state),
-            description: info.description.clone(),
+    /// Link what `snapshot` records and, with `prune`, unlink what it does not.
+    fn apply_snapshot(
+        &self,
+        snapshot: &EnabledSnapshot,
+        dry_run: bool,
+        prune: bool,
+    ) -> Result<CommandOutcome, HelperError> {
+        let unlinked = if prune {
+            self.manager.prune_enabled(snapshot, DryRun(dry_run))?
+        } else {
+            Vec::new()
+        };
+        let report = self.manager.apply_enabled(snapshot, DryRun(dry_run))?;
+        let verb = if dry_run { "would link" } else { "linked" };
+        let mut message = format!("{verb} {} service(s)", report.linked.len());
+        if prune {
+            let verb = if dry_run { "would unlink" } else { "unlinked" };
+            message.push_str(&format!(", {verb} {}", unlinked.len()));
+        }
+        if !report.missing.is_empty() {
+            message.push_str(&format!(
+                "; no definition for {}",
+                report.missing.join(", ")
+            ));
         }
+        let mut data = serde_json::to_value(&report).ok();
+        if prune && let Some(Value::Object(data)) = &mut data {
+            data.insert("unlinked".to_string(), json!(unlinked));
+        }
+        Ok(CommandOutcome::with(Some(message), data))
     }
-}
 
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "snake_case")]
-enum SnapshotDesiredState {
-    AutoStart,
-    Manual,
-}
+    /// Run every operation in order, carrying on past failures, so one authorization
+    /// covers all of them.
+    fn batch(&self) -> Result<CommandOutcome, HelperError> {
+        let operations: Vec<BatchOperation> = serde_json::from_reader(std::io::stdin().lock())
+            .map_err(|err| HelperError::Other(format!("invalid batch on stdin: {err}")))?;
+        let results: Vec<BatchResult> = operations
+            .into_iter()
+            .map(|operation| {
+                let response = match self.run_batched(&operation) {
+                    Ok(outcome) => HelperResponse::ok_with(outcome),
+                    Err(err) => HelperResponse::error(&err),
+                };
+                BatchResult {
+                    method: operation.method,
+                    params: operation.params,
+                    response,
+                }
+            })
+            .collect();
+        let succeeded = results
+            .iter()
+            .filter(|result| matches!(result.response.status, ResponseStatus::Ok))
+            .count();
+        Ok(CommandOutcome::with(
+            Some(format!(
+                "{succeeded} of {} operation(s) succeeded",
+                results.len()
+            )),
+            serde_json::to_value(&results).ok(),
+        ))
+    }
 
-impl From<DesiredState> for SnapshotDesiredState {
-    fn from(value: DesiredState) -> Self {
-        match value {
-            DesiredState::AutoStart => SnapshotDesiredState::AutoStart,
-            DesiredState::Manual => SnapshotDesiredState::Manual,
+    fn run_batched(&self, operation: &BatchOperation) -> Result<CommandOutcome, HelperError> {
+        let params: Vec<&str> = operation.params.iter().map(String::as_str).collect();
+        let command = serve::parse_command(&operation.method, &params)
+            .map_err(|err| HelperError::Other(err.render().to_string().trim().to_string()))?;
+        // Whatever the socket refuses needs stdin or stdout to itself, which a batch has not.
+        if let Some(reason) = serve::unavailable(&command) {
+            return Err(HelperError::Other(format!(
+                "{} cannot be part of a batch: {reason}",
+                operation.method
+            )));
         }
+        self.run(command)
     }
-}
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "state", rename_all = "snake_case")]
-enum SnapshotRuntimeState {
-    Running {
-        pid: u32,
-        uptime_seconds: u64,
-    },
-    Down {
-        since_seconds: u64,
-        normally_up: bool,
-    },
-    Failed {
-        pid: u32,
-        uptime_seconds: u64,
-        exit_code: i32,
-    },
-    Unknown {
-        raw: String,
-    },
-}
+    fn mask_outcome(&self, service: &str, message: String) -> Result<CommandOutcome, HelperError> {
+        let desired_state = self.manager.desired_state(service)?;
+        Ok(CommandOutcome::with(
+            Some(message),
+            Some(json!({
+                "service": service,
+                "masked": self.manager.is_masked(service),
+                "desired_state": desired_state,
+            })),
+        ))
+    }
 
-impl From<&ServiceRuntimeState> for SnapshotRuntimeState {
-    fn from(value: &ServiceRuntimeState) -> Self {
-        match value {
-            ServiceRuntimeState::Running { pid, uptime } => SnapshotRuntimeState::Running {
-                pid: *pid,
-                uptime_seconds: uptime.as_secs(),
-            },
-            ServiceRuntimeState::Down { since, normally_up } => SnapshotRuntimeState::Down {
-                since_seconds: since.as_secs(),
-                normally_up: *normally_up,
-            },
-            ServiceRuntimeState::Failed {
-                pid,
-                uptime,
-                exit_code,
-            } => SnapshotRuntimeState::Failed {
-                pid: *pid,
-                uptime_seconds: uptime.as_secs(),
-                exit_code: *exit_code,
-            },
-            ServiceRuntimeState::Unknown { raw } => {
-                SnapshotRuntimeState::Unknown { raw: raw.clone() }
-            }
+    /// Power and runlevel actions affect the whole machine, so `--user` never gets them.
+    fn system_control(&self) -> Result<SystemControl, HelperError> {
+        if self.user_scope {
+            return Err(HelperError::PermissionDenied(
+                "system actions are not available in user scope".to_string(),
+            ));
         }
+        Ok(SystemControl::new())
     }
-}
 
-#[derive(Debug, Serialize)]
-struct LogEntrySnapshot {
-    unix_seconds: Option<i64>,
-    nanos: Option<u32>,
-    raw: Option<String>,
-    message: String,
-}
+    fn power(&self, action: PowerAction) -> Result<CommandOutcome, HelperError> {
+        self.system_control()?.power(action)?;
+        Ok(CommandOutcome::with(
+            Some(format!("{action} requested")),
+            Some(json!({ "action": action })),
+        ))
+    }
 
-impl From<ServiceLogEntry> for LogEntrySnapshot {
-    fn from(entry: ServiceLogEntry) -> Self {
-        LogEntrySnapshot {
-            unix_seconds: entry.timestamp_unix,
-            nanos: entry.timestamp_nanos,
-            raw: entry.timestamp_raw,
-            message: entry.message,
-        }
+    fn describe(&self, service: &str) -> Result<CommandOutcome, HelperError> {
+        let description = self.manager.service_description(service)?;
+        let data = json!({
+            "service": service,
+            "description": description,
+        });
+        Ok(CommandOutcome::with(None, Some(data)))
     }
 }
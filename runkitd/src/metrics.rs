@@ -0,0 +1,316 @@
+//! Prometheus exporter: `GET /metrics` answers with the state of every service in the
+//! text exposition format, read fresh on each scrape.
+use crate::HelperError;
+use runkit_core::{RuntimeStateKind, ServiceInfo, ServiceManager, ServiceRuntimeState};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:9477";
+
+/// Scrapers that stall are dropped rather than holding up the next one. Reading the
+/// whole request has to fit in this, however slowly it trickles in.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Request line and headers together; a scrape needs far less.
+const MAX_REQUEST_BYTES: u64 = 8192;
+
+const STATES: [RuntimeStateKind; 5] = [
+    RuntimeStateKind::Running,
+    RuntimeStateKind::Paused,
+    RuntimeStateKind::Down,
+    RuntimeStateKind::Failed,
+    RuntimeStateKind::Unknown,
+];
+
+/// Counters that live as long as the exporter.
+#[derive(Debug, Default)]
+struct Exporter {
+    /// Last pid seen per service and how often it changed.
+    restarts: HashMap<String, (Option<u32>, u64)>,
+    scrapes: u64,
+    scrape_errors: u64,
+}
+
+/// Answer scrapes on `listen` until the process is killed. Requests are served one at
+/// a time, which is plenty for a scraper every few seconds.
+pub fn serve(manager: &ServiceManager, listen: SocketAddr) -> Result<(), HelperError> {
+    let listener = TcpListener::bind(listen)
+        .map_err(|err| HelperError::Other(format!("cannot listen on {listen}: {err}")))?;
+    let mut exporter = Exporter::default();
+    for stream in listener.incoming() {
+        // One scraper hanging up early must not stop the exporter.
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let _ = answer(&mut exporter, manager, stream);
+    }
+    Ok(())
+}
+
+fn answer(exporter: &mut Exporter, manager: &ServiceManager, stream: TcpStream) -> io::Result<()> {
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let request = Deadline {
+        stream: stream.try_clone()?,
+        at: Instant::now() + CLIENT_TIMEOUT,
+    };
+    let mut reader = BufReader::new(request.take(MAX_REQUEST_BYTES));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers; nothing in them changes the answer.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+    let path = path.split('?').next().unwrap_or(path);
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            exporter.render(manager),
+        ),
+        ("GET", "/") => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            "<html><body><a href=\"/metrics\">Metrics</a></body></html>\n".to_string(),
+        ),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "only GET is supported\n".to_string(),
+        ),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// The client side of a request, failing once `at` has passed.
+struct Deadline {
+    stream: TcpStream,
+    at: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.at.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Exporter {
+    fn render(&mut self, manager: &ServiceManager) -> String {
+        let started = Instant::now();
+        self.scrapes += 1;
+        let services = match manager.list_services() {
+            Ok(services) => services,
+            Err(_) => {
+                self.scrape_errors += 1;
+                Vec::new()
+            }
+        };
+        for info in &services {
+            self.observe(info);
+        }
+
+        let mut out = String::new();
+        family(
+            &mut out,
+            "runkit_service_up",
+            "gauge",
+            "Whether the service is running (1) or not (0).",
+        );
+        for info in &services {
+            let up = u8::from(info.runtime_state.is_running());
+            sample(
+                &mut out,
+                "runkit_service_up",
+                &[("service", &info.name)],
+                up,
+            );
+        }
+
+        family(
+            &mut out,
+            "runkit_service_state",
+            "gauge",
+            "Runtime state of the service; 1 for the current state, 0 for the others.",
+        );
+        for info in &services {
+            let current = info.runtime_state.kind();
+            for state in STATES {
+                sample(
+                    &mut out,
+                    "runkit_service_state",
+                    &[("service", &info.name), ("state", state.name())],
+                    u8::from(state == current),
+                );
+            }
+        }
+
+        family(
+            &mut out,
+            "runkit_service_enabled",
+            "gauge",
+            "Whether the service is linked into the enabled directory.",
+        );
+        for info in &services {
+            let enabled = u8::from(info.enabled);
+            sample(
+                &mut out,
+                "runkit_service_enabled",
+                &[("service", &info.name)],
+                enabled,
+            );
+        }
+
+        family(
+            &mut out,
+            "runkit_service_uptime_seconds",
+            "gauge",
+            "Seconds the current process has been running; 0 when it is not.",
+        );
+        for info in &services {
+            let uptime = match &info.runtime_state {
+                ServiceRuntimeState::Running { uptime, .. }
+                | ServiceRuntimeState::Paused { uptime, .. } => uptime.as_secs(),
+                _ => 0,
+            };
+            sample(
+                &mut out,
+                "runkit_service_uptime_seconds",
+                &[("service", &info.name)],
+                uptime,
+            );
+        }
+
+        family(
+            &mut out,
+            "runkit_service_restarts_total",
+            "counter",
+            "Times the service came back under a new pid since the exporter started.",
+        );
+        for info in &services {
+            let restarts = self.restarts.get(&info.name).map_or(0, |(_, count)| *count);
+            sample(
+                &mut out,
+                "runkit_service_restarts_total",
+                &[("service", &info.name)],
+                restarts,
+            );
+        }
+
+        family(
+            &mut out,
+            "runkit_exporter_scrapes_total",
+            "counter",
+            "Scrapes answered.",
+        );
+        sample(&mut out, "runkit_exporter_scrapes_total", &[], self.scrapes);
+        family(
+            &mut out,
+            "runkit_exporter_scrape_errors_total",
+            "counter",
+            "Scrapes for which the services could not be listed.",
+        );
+        sample(
+            &mut out,
+            "runkit_exporter_scrape_errors_total",
+            &[],
+            self.scrape_errors,
+        );
+        family(
+            &mut out,
+            "runkit_exporter_scrape_duration_seconds",
+            "gauge",
+            "Time spent reading service state for this scrape.",
+        );
+        sample(
+            &mut out,
+            "runkit_exporter_scrape_duration_seconds",
+            &[],
+            started.elapsed().as_secs_f64(),
+        );
+        out
+    }
+
+    /// Count a restart whenever a service runs under a different pid than last time.
+    fn observe(&mut self, info: &ServiceInfo) {
+        let pid = info.runtime_state.pid();
+        let (last, count) = self.restarts.entry(info.name.clone()).or_default();
+        if let (Some(previous), Some(pid)) = (*last, pid)
+            && previous != pid
+        {
+            *count += 1;
+        }
+        if pid.is_some() {
+            *last = pid;
+        }
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{key}=\"{}\"", escape_label(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CLIENT_TIMEOUT, Exporter, MAX_REQUEST_BYTES, answer};
+    use runkit_core::ServiceManager;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Instant;
+
+    #[test]
+    fn answers_once_the_request_is_too_long() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        // Headers that never end: without the cap the server would wait for more.
+        client.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        let padding = b"X-Padding: runkit\r\n".repeat(MAX_REQUEST_BYTES as usize / 16);
+        client.write_all(&padding).unwrap();
+
+        let manager = ServiceManager::new("/nonexistent/sv", "/nonexistent/service");
+        let started = Instant::now();
+        answer(&mut Exporter::default(), &manager, server).unwrap();
+        assert!(started.elapsed() < CLIENT_TIMEOUT);
+    }
+}
@@ -0,0 +1,229 @@
+//! Rendering a response for a terminal instead of a frontend. JSON stays the default
+//! and the only format the GUI and the sockets speak.
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// Aligned columns; services get one row each, other data falls back to keys and
+    /// values.
+    Table,
+}
+
+/// Reads one cell from the JSON of a `ServiceInfo`.
+type Cell = fn(&Value) -> String;
+
+/// Columns of a service row, by header.
+const SERVICE_COLUMNS: [(&str, Cell); 6] = [
+    ("SERVICE", |info| text(&info["name"])),
+    ("STATE", service_state),
+    ("ENABLED", |info| yes_no(&info["enabled"])),
+    ("PID", |info| text(&info["runtime_state"]["pid"])),
+    ("UPTIME", |info| {
+        uptime(&info["runtime_state"]["uptime_seconds"])
+    }),
+    ("DESCRIPTION", |info| text(&info["description"])),
+];
+
+/// `response` as `format`. `message` and `data` are what a table shows; a failure
+/// becomes a single `error:` line.
+pub fn render(response: &impl Serialize, format: OutputFormat) -> Result<String, String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string(response).map_err(|err| err.to_string()),
+        #[cfg(feature = "yaml")]
+        OutputFormat::Yaml => serde_yaml::to_string(response).map_err(|err| err.to_string()),
+        OutputFormat::Table => {
+            let response = serde_json::to_value(response).map_err(|err| err.to_string())?;
+            Ok(render_table(&response))
+        }
+    }
+}
+
+fn render_table(response: &Value) -> String {
+    let message = response["message"].as_str();
+    if response["status"] == "error" {
+        return format!("error: {}", message.unwrap_or("unknown failure"));
+    }
+    let table = match &response["data"] {
+        Value::Null => None,
+        Value::Array(entries) if entries.is_empty() => None,
+        Value::Array(entries) if entries.iter().all(is_service) => {
+            let rows = entries
+                .iter()
+                .map(|info| SERVICE_COLUMNS.iter().map(|(_, cell)| cell(info)).collect())
+                .collect();
+            Some(align(
+                SERVICE_COLUMNS.map(|(header, _)| header).to_vec(),
+                rows,
+            ))
+        }
+        Value::Object(_) if is_service(&response["data"]) => {
+            Some(service_details(&response["data"]))
+        }
+        Value::Array(entries) if entries.iter().all(Value::is_object) => {
+            let headers: Vec<&str> = entries[0]
+                .as_object()
+                .into_iter()
+                .flat_map(|entry| entry.keys().map(String::as_str))
+                .collect();
+            let rows = entries
+                .iter()
+                .map(|entry| headers.iter().map(|key| text(&entry[*key])).collect())
+                .collect();
+            let headers = headers.iter().map(|key| key.to_uppercase()).collect();
+            Some(align(headers, rows))
+        }
+        Value::Object(fields) => {
+            let rows = fields
+                .iter()
+                .map(|(key, value)| vec![format!("{key}:"), text(value)])
+                .collect();
+            Some(align_rows(rows))
+        }
+        other => Some(text(other)),
+    };
+    [message.map(str::to_string), table]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn is_service(value: &Value) -> bool {
+    value.get("name").is_some() && value.get("runtime_state").is_some()
+}
+
+/// One service as labelled lines, with what `list` leaves out.
+fn service_details(info: &Value) -> String {
+    let runlevels = match info["runlevels"].as_array() {
+        Some(runlevels) if !runlevels.is_empty() => {
+            runlevels.iter().map(text).collect::<Vec<_>>().join(", ")
+        }
+        _ => "-".to_string(),
+    };
+    let log = match &info["log_state"] {
+        Value::Null => "-".to_string(),
+        state => state_name(state),
+    };
+    let rows = vec![
+        vec!["Service:".to_string(), text(&info["name"])],
+        vec!["Description:".to_string(), text(&info["description"])],
+        vec!["State:".to_string(), service_state(info)],
+        vec!["PID:".to_string(), text(&info["runtime_state"]["pid"])],
+        vec![
+            "Uptime:".to_string(),
+            uptime(&info["runtime_state"]["uptime_seconds"]),
+        ],
+        vec!["Enabled:".to_string(), yes_no(&info["enabled"])],
+        vec!["Desired:".to_string(), text(&info["desired_state"])],
+        vec!["Runlevels:".to_string(), runlevels],
+        vec!["Logger:".to_string(), log],
+        vec!["Definition:".to_string(), text(&info["definition_path"])],
+    ];
+    align_rows(rows)
+}
+
+/// The runtime state, with what is going on around it in parentheses.
+fn service_state(info: &Value) -> String {
+    let mut state = state_name(&info["runtime_state"]);
+    let mut notes = Vec::new();
+    if let Some(pending) = info["pending"].as_str() {
+        notes.push(pending.to_string());
+    }
+    if info["masked"] == true {
+        notes.push("masked".to_string());
+    }
+    if !info["flapping"].is_null() {
+        notes.push("flapping".to_string());
+    }
+    if info["needs_restart"] == true {
+        notes.push("needs restart".to_string());
+    }
+    if !notes.is_empty() {
+        state = format!("{state} ({})", notes.join(", "));
+    }
+    state
+}
+
+fn state_name(state: &Value) -> String {
+    state["state"].as_str().unwrap_or("unknown").to_string()
+}
+
+fn yes_no(value: &Value) -> String {
+    if value == true { "yes" } else { "no" }.to_string()
+}
+
+/// Seconds as the two largest units, e.g. `3d 4h` or `5m 12s`.
+fn uptime(seconds: &Value) -> String {
+    let Some(seconds) = seconds.as_u64() else {
+        return "-".to_string();
+    };
+    let units = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+    let parts: Vec<String> = units
+        .iter()
+        .scan(seconds, |left, (suffix, size)| {
+            let count = *left / size;
+            *left %= size;
+            Some((count, suffix))
+        })
+        .skip_while(|(count, _)| *count == 0)
+        .take(2)
+        .map(|(count, suffix)| format!("{count}{suffix}"))
+        .collect();
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// A cell's text: strings bare, nothing as `-`, anything nested as compact JSON.
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(text) => text.replace('\n', " "),
+        other => other.to_string(),
+    }
+}
+
+fn align(headers: Vec<impl Into<String>>, rows: Vec<Vec<String>>) -> String {
+    let mut all = vec![headers.into_iter().map(Into::into).collect()];
+    all.extend(rows);
+    align_rows(all)
+}
+
+/// Pad every column but the last to its widest cell.
+fn align_rows(rows: Vec<Vec<String>>) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    rows.iter()
+        .map(|row| {
+            let mut line = String::new();
+            for (column, cell) in row.iter().enumerate() {
+                line.push_str(cell);
+                if column + 1 < row.len() {
+                    line.extend(std::iter::repeat_n(
+                        ' ',
+                        widths[column] - cell.chars().count() + 2,
+                    ));
+                }
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,571 @@
+//! Long-running mode answering requests on a Unix socket, so a frontend authenticates
+//! once instead of spawning the helper through pkexec for every action.
+//!
+//! Every frame is a 4-byte big-endian length followed by that many bytes of JSON. A
+//! request is a JSON-RPC 2.0 call whose `method` is a subcommand and whose `params` are
+//! the arguments that would follow it on the command line:
+//!
+//! ```json
+//! {"jsonrpc": "2.0", "id": 1, "method": "stop", "params": ["sshd", "--timeout", "5"]}
+//! ```
+//!
+//! The `result` is the same object the one-shot helper prints. Only requests that
+//! cannot be run at all get a JSON-RPC `error`.
+//!
+//! Instead of binding its socket, the daemon can take over one that a supervisor
+//! created: systemd-style `LISTEN_FDS` are picked up automatically, and `--listen-fd`
+//! names any other descriptor, such as `0` under inetd in `wait` mode. With
+//! `--idle-timeout` it exits once no client has been connected for that long, and the
+//! supervisor starts it again on the next connection.
+//!
+//! Only root may run commands that change anything. Other clients, which `--group`
+//! lets connect, are limited to the commands that only read state, so the socket never
+//! grants more than an unauthenticated `runkitd --read-only` would.
+use crate::{HelperCommand, HelperContext, HelperError, HelperResponse, STREAMING};
+use clap::error::ErrorKind as ClapErrorKind;
+use clap::{Args, Parser};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ffi::CString;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_SOCKET: &str = "/run/runkitd.sock";
+
+/// Frames above this size are refused rather than buffered.
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// First descriptor passed under the `LISTEN_FDS` protocol.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Connections beyond this many are closed right away instead of getting a thread.
+const MAX_CONNECTIONS: usize = 32;
+
+/// A client that sends nothing for this long is disconnected; the GUI reconnects on
+/// its next request.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// How a long-running mode gets its listening socket and when it gives up on it.
+#[derive(Args, Debug, Clone, Copy)]
+pub struct Activation {
+    /// Accept on this inherited listening socket instead of binding `--socket`.
+    #[arg(long, value_name = "FD")]
+    pub listen_fd: Option<RawFd>,
+    /// Exit after this many seconds without a connected client.
+    #[arg(long, value_name = "SECS")]
+    pub idle_timeout: Option<u64>,
+}
+
+/// Who is on the other end of a connection, as the kernel reports it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Peer {
+    uid: u32,
+}
+
+impl Peer {
+    fn of(stream: &UnixStream) -> io::Result<Self> {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: `cred` and `len` describe a valid buffer for SO_PEERCRED.
+        let status = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                (&mut cred as *mut libc::ucred).cast(),
+                &mut len,
+            )
+        };
+        if status != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Peer { uid: cred.uid })
+    }
+
+    /// Root may run anything; everyone else only what changes nothing.
+    pub(crate) fn authorize(self, command: &HelperCommand) -> Result<(), HelperError> {
+        if self.uid == 0 || command.is_read_only() {
+            return Ok(());
+        }
+        Err(HelperError::PermissionDenied(format!(
+            "uid {} may only read state over the socket; run the command through pkexec",
+            self.uid
+        )))
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "runkitd", no_binary_name = true)]
+struct RequestCli {
+    #[command(subcommand)]
+    command: HelperCommand,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<HelperResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+impl Response {
+    fn result(id: Value, result: HelperResponse) -> Self {
+        Response {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, code: i32, message: impl Into<String>) -> Self {
+        Response {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+/// Bind `socket` and answer requests until the process is killed or idles out. Without
+/// `group` only root may connect; with it, members of that group may as well.
+pub fn serve(
+    context: &HelperContext,
+    socket: &Path,
+    group: Option<&str>,
+    activation: Activation,
+) -> Result<(), HelperError> {
+    accept_loop(socket, group, activation, |stream, peer| {
+        // A client hanging up mid-frame is its own problem.
+        let _ = handle_connection(context, stream, peer);
+    })
+}
+
+/// Listen as `activation` asks and run `handle` on a thread per connection, for up to
+/// [`MAX_CONNECTIONS`] at once. Returns once the idle timeout passes with no client
+/// connected.
+pub(crate) fn accept_loop(
+    socket: &Path,
+    group: Option<&str>,
+    activation: Activation,
+    handle: impl Fn(UnixStream, Peer) + Sync,
+) -> Result<(), HelperError> {
+    let io_error = |source| HelperError::Io {
+        path: socket.to_path_buf(),
+        source,
+    };
+    let passed = listen_fds();
+    let inherited = activation.listen_fd.or(passed);
+    let listener = match inherited {
+        Some(fd) => inherit(fd)?,
+        None => bind(socket, group)?,
+    };
+    let idle_timeout = activation.idle_timeout.map(Duration::from_secs);
+    let activity = Activity::default();
+    let result = std::thread::scope(|scope| {
+        loop {
+            let wait = match idle_timeout {
+                Some(idle) => match activity.idle_for() {
+                    Some(elapsed) if elapsed >= idle => return Ok(()),
+                    Some(elapsed) => Some(idle - elapsed),
+                    // Recheck once the last client may have left.
+                    None => Some(idle),
+                },
+                None => None,
+            };
+            if !readable(&listener, wait).map_err(io_error)? {
+                continue;
+            }
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                // Another wakeup took the connection, or the client gave up already.
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::WouldBlock
+                            | ErrorKind::Interrupted
+                            | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(err) => return Err(io_error(err)),
+            };
+            if activity.connections() >= MAX_CONNECTIONS {
+                continue;
+            }
+            // Linux does not pass the listener's O_NONBLOCK on, but be explicit.
+            let prepared = stream
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_read_timeout(Some(CLIENT_TIMEOUT)))
+                .and_then(|()| stream.set_write_timeout(Some(CLIENT_TIMEOUT)))
+                .and_then(|()| Peer::of(&stream));
+            let Ok(peer) = prepared else {
+                continue;
+            };
+            activity.connected();
+            let (handle, activity) = (&handle, &activity);
+            scope.spawn(move || {
+                handle(stream, peer);
+                activity.disconnected();
+            });
+        }
+    });
+    // A socket we bound ourselves would only mislead clients once nobody answers it.
+    if inherited.is_none() && result.is_ok() {
+        let _ = std::fs::remove_file(socket);
+    }
+    result
+}
+
+/// Connected clients and when the last one left.
+#[derive(Debug)]
+pub(crate) struct Activity {
+    connections: AtomicUsize,
+    since: Mutex<Instant>,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Activity {
+            connections: AtomicUsize::new(0),
+            since: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl Activity {
+    fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn connected(&self) {
+        self.connections.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn disconnected(&self) {
+        let mut since = self
+            .since
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *since = Instant::now();
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// How long nobody has been connected; `None` while someone is.
+    pub(crate) fn idle_for(&self) -> Option<Duration> {
+        let since = self
+            .since
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        (self.connections.load(Ordering::SeqCst) == 0).then(|| since.elapsed())
+    }
+}
+
+/// Wait up to `timeout`, or forever, for a connection to accept.
+fn readable(listener: &UnixListener, timeout: Option<Duration>) -> io::Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: listener.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.map_or(-1, |timeout| {
+        // Round up so a sub-millisecond remainder does not spin.
+        i32::try_from(timeout.as_millis() + 1).unwrap_or(i32::MAX)
+    });
+    // SAFETY: `fd` is a single valid pollfd for the duration of the call.
+    match unsafe { libc::poll(&mut fd, 1, timeout) } {
+        -1 => {
+            let err = io::Error::last_os_error();
+            if err.kind() == ErrorKind::Interrupted {
+                Ok(false)
+            } else {
+                Err(err)
+            }
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+/// The socket passed under the `LISTEN_FDS` protocol, if it was meant for us. The
+/// variables are removed so that `sv`, check scripts and other children do not take
+/// the socket for theirs.
+fn listen_fds() -> Option<RawFd> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let count = std::env::var("LISTEN_FDS").ok();
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        // SAFETY: runkitd is still single-threaded; the accept loop starts afterwards.
+        unsafe { std::env::remove_var(name) };
+    }
+    let pid: u32 = pid?.parse().ok()?;
+    let count: u32 = count?.parse().ok()?;
+    (pid == std::process::id() && count >= 1).then_some(LISTEN_FDS_START)
+}
+
+/// Take ownership of an inherited descriptor after checking it is a listening socket.
+fn inherit(fd: RawFd) -> Result<UnixListener, HelperError> {
+    let mut accepting: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `accepting` and `len` describe a valid buffer for an int option.
+    let status = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTCONN,
+            (&mut accepting as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    if status != 0 || accepting == 0 {
+        return Err(HelperError::Other(format!(
+            "descriptor {fd} is not a listening socket"
+        )));
+    }
+    // SAFETY: the descriptor was handed to this process to own, and nothing else in it
+    // refers to it. Keeping it out of `sv` and other children is all fcntl changes.
+    let listener = unsafe {
+        libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        UnixListener::from_raw_fd(fd)
+    };
+    // Concurrent wakeups must not leave accept blocking.
+    listener
+        .set_nonblocking(true)
+        .map_err(|err| HelperError::Other(format!("descriptor {fd}: {err}")))?;
+    Ok(listener)
+}
+
+fn bind(socket: &Path, group: Option<&str>) -> Result<UnixListener, HelperError> {
+    let io_error = |source| HelperError::Io {
+        path: socket.to_path_buf(),
+        source,
+    };
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(HelperError::Other(format!(
+                "another runkitd is already listening on {}",
+                socket.display()
+            )));
+        }
+        std::fs::remove_file(socket).map_err(io_error)?;
+    }
+    let listener = UnixListener::bind(socket).map_err(io_error)?;
+    let mode = match group {
+        Some(group) => {
+            let gid = group_id(group)
+                .ok_or_else(|| HelperError::Other(format!("unknown group: {group}")))?;
+            std::os::unix::fs::chown(socket, None, Some(gid)).map_err(io_error)?;
+            0o660
+        }
+        None => 0o600,
+    };
+    std::fs::set_permissions(socket, std::fs::Permissions::from_mode(mode)).map_err(io_error)?;
+    listener.set_nonblocking(true).map_err(io_error)?;
+    Ok(listener)
+}
+
+fn group_id(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    // SAFETY: `name` is NUL-terminated and the returned entry is read before any other
+    // group lookup could overwrite it.
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        None
+    } else {
+        Some(unsafe { (*entry).gr_gid })
+    }
+}
+
+fn handle_connection(
+    context: &HelperContext,
+    mut stream: UnixStream,
+    peer: Peer,
+) -> io::Result<()> {
+    while let Some(frame) = read_frame(&mut stream)? {
+        let response = answer(context, peer, &frame);
+        let bytes = serde_json::to_vec(&response).map_err(io::Error::other)?;
+        write_frame(&mut stream, &bytes)?;
+    }
+    Ok(())
+}
+
+fn answer(context: &HelperContext, peer: Peer, frame: &[u8]) -> Response {
+    let value: Value = match serde_json::from_slice(frame) {
+        Ok(value) => value,
+        Err(err) => return Response::error(Value::Null, PARSE_ERROR, err.to_string()),
+    };
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request: Request = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(err) => return Response::error(id, INVALID_REQUEST, err.to_string()),
+    };
+    let params: Vec<&str> = request.params.iter().map(String::as_str).collect();
+    let command = match parse_command(&request.method, &params) {
+        Ok(command) => command,
+        Err(err) => {
+            let code = match err.kind() {
+                ClapErrorKind::InvalidSubcommand => METHOD_NOT_FOUND,
+                _ => INVALID_PARAMS,
+            };
+            return Response::error(request.id, code, err.render().to_string().trim());
+        }
+    };
+    if let Some(reason) = unavailable(&command) {
+        return Response::error(request.id, METHOD_NOT_FOUND, reason);
+    }
+    let result = match peer.authorize(&command).and_then(|()| context.run(command)) {
+        Ok(outcome) => HelperResponse::ok_with(outcome),
+        Err(err) => HelperResponse::error(&err),
+    };
+    Response::result(request.id, result)
+}
+
+/// Parse a subcommand and its arguments the way they would appear on the command line.
+pub(crate) fn parse_command(method: &str, params: &[&str]) -> Result<HelperCommand, clap::Error> {
+    let args = std::iter::once(method).chain(params.iter().copied());
+    RequestCli::try_parse_from(args).map(|cli| cli.command)
+}
+
+/// Commands that only make sense for a one-shot helper process.
+pub(crate) fn unavailable(command: &HelperCommand) -> Option<&'static str> {
+    match command {
+        _ if command.is_streaming() => Some(STREAMING),
+        HelperCommand::ApplyEnabled { .. } => {
+            Some("apply-enabled reads stdin and is not available over the socket")
+        }
+        HelperCommand::Batch => {
+            Some("batch reads stdin; send its operations as separate requests instead")
+        }
+        _ => None,
+    }
+}
+
+/// Read one length-prefixed frame; `None` once the peer has closed the connection.
+fn read_frame(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("frame of {len} bytes exceeds the {MAX_FRAME_LEN} byte limit"),
+        ));
+    }
+    let mut frame = vec![0; len as usize];
+    stream.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn write_frame(stream: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len())
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "response too large"))?;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(bytes)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_FRAME_LEN, Peer, parse_command, read_frame, unavailable, write_frame};
+    use crate::HelperError;
+    use std::io::{Cursor, ErrorKind};
+
+    #[test]
+    fn round_trips_frames() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, br#"{"method":"list"}"#).unwrap();
+        write_frame(&mut wire, b"").unwrap();
+        assert_eq!(&wire[..4], &17u32.to_be_bytes());
+
+        let mut reader = Cursor::new(wire);
+        assert_eq!(
+            read_frame(&mut reader).unwrap().unwrap(),
+            br#"{"method":"list"}"#
+        );
+        assert_eq!(read_frame(&mut reader).unwrap().unwrap(), b"");
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_oversized_and_truncated_frames() {
+        let mut oversized = Cursor::new((MAX_FRAME_LEN + 1).to_be_bytes().to_vec());
+        let err = read_frame(&mut oversized).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let mut at_limit = Cursor::new(MAX_FRAME_LEN.to_be_bytes().to_vec());
+        let err = read_frame(&mut at_limit).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+        let mut truncated = Cursor::new([0, 0, 0, 8, b'{'].to_vec());
+        let err = read_frame(&mut truncated).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn refuses_streaming_commands_and_writes_from_others() {
+        let command = |method: &str, params: &[&str]| parse_command(method, params).unwrap();
+        assert!(unavailable(&command("logs", &["sshd", "--follow"])).is_some());
+        assert!(unavailable(&command("watch", &[])).is_some());
+        assert!(unavailable(&command("batch", &[])).is_some());
+        assert!(unavailable(&command("logs", &["sshd"])).is_none());
+        assert!(parse_command("no-such-command", &[]).is_err());
+
+        let nobody = Peer { uid: 65534 };
+        assert!(nobody.authorize(&command("status", &["sshd"])).is_ok());
+        assert!(matches!(
+            nobody.authorize(&command("enable", &["sshd"])),
+            Err(HelperError::PermissionDenied(_))
+        ));
+        assert!(
+            Peer { uid: 0 }
+                .authorize(&command("enable", &["sshd"]))
+                .is_ok()
+        );
+        // Root would read the file for them.
+        let convert = command("convert-unit", &["/root/secret.service", "--dry-run"]);
+        assert!(nobody.authorize(&convert).is_err());
+        let import = command("import-enabled", &["/root/enabled.json", "--dry-run"]);
+        assert!(nobody.authorize(&import).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Points in time given on the command line: RFC 3339 timestamps or ages like `15m`.
+use std::time::{Duration, SystemTime};
+
+/// Parse `2024-05-01T12:00:00Z` or an age such as `90s`, `15m`, `1h30m` or `2d`, which
+/// counts back from now.
+pub(crate) fn parse_time(arg: &str) -> Result<SystemTime, String> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(arg) {
+        return Ok(SystemTime::from(datetime));
+    }
+    let age = parse_age(arg).ok_or_else(|| {
+        format!("expected an RFC 3339 timestamp or an age like 15m, 2h or 1d, got `{arg}`")
+    })?;
+    SystemTime::now()
+        .checked_sub(age)
+        .ok_or_else(|| format!("`{arg}` reaches back too far"))
+}
+
+/// Sum of `<number><unit>` parts with units `s`, `m`, `h`, `d` and `w`.
+fn parse_age(arg: &str) -> Option<Duration> {
+    let mut total = 0u64;
+    let mut rest = arg.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let (number, tail) = rest.split_at(digits);
+        let unit = tail.chars().next()?;
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let part = number.parse::<u64>().ok()?.checked_mul(seconds)?;
+        total = total.checked_add(part)?;
+        rest = &tail[unit.len_utf8()..];
+    }
+    Some(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_age, parse_time};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn parses_ages() {
+        assert_eq!(parse_age("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_age("15m"), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_age(" 1h30m "), Some(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_age("2d"), Some(Duration::from_secs(2 * 86_400)));
+        assert_eq!(parse_age("1w1s"), Some(Duration::from_secs(604_801)));
+        assert_eq!(parse_age("0s"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn rejects_malformed_ages() {
+        for arg in [
+            "", "  ", "15", "m", "15x", "1h 30m", "-5m", "1.5h", "15mm", "5µ",
+        ] {
+            assert_eq!(parse_age(arg), None, "{arg:?}");
+        }
+        assert_eq!(parse_age(&format!("{}w", u64::MAX)), None);
+        assert_eq!(parse_age(&format!("{}s1s", u64::MAX)), None);
+    }
+
+    #[test]
+    fn parses_timestamps_and_ages() {
+        let at = parse_time("2024-05-01T12:00:00Z").unwrap();
+        assert_eq!(
+            at.duration_since(SystemTime::UNIX_EPOCH).unwrap(),
+            Duration::from_secs(1_714_564_800)
+        );
+        let age = SystemTime::now()
+            .duration_since(parse_time("1h").unwrap())
+            .unwrap();
+        assert!(age.abs_diff(Duration::from_secs(3600)) < Duration::from_secs(60));
+        assert!(parse_time("yesterday").is_err());
+    }
+}
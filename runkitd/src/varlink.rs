@@ -0,0 +1,415 @@
+//! The `io.runkit` varlink interface, for tooling that prefers a typed, introspectable
+//! IPC over parsing the helper's JSON output.
+//!
+//! Messages are JSON objects terminated by a NUL byte. Every call is run exactly like
+//! the matching subcommand; failures come back as `io.runkit.CommandFailed` carrying the
+//! helper's `error_code`. `org.varlink.service` is implemented too, so generic clients
+//! such as `varlinkctl introspect` can discover the interface.
+use crate::serve::{Activation, Peer, accept_loop, parse_command, unavailable};
+use crate::{HelperContext, HelperError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+pub const DEFAULT_SOCKET: &str = "/run/io.runkit";
+
+const INTERFACE: &str = "io.runkit";
+const SERVICE_INTERFACE: &str = "org.varlink.service";
+
+/// Messages above this size are refused rather than buffered.
+const MAX_MESSAGE_LEN: u64 = 16 * 1024 * 1024;
+
+const DESCRIPTION: &str = "\
+# Manage runit services through runkitd. Methods behave like the runkitd
+# subcommands of the same name; `message` and `data` are what the command
+# line helper would print.
+interface io.runkit
+
+# Every service with its current status, as `runkitd list` prints it.
+method ListServices() -> (services: []object)
+
+# One service as `runkitd status` prints it.
+method Status(service: string) -> (info: object)
+
+# The newest log entries of a service; `lines` defaults to 200.
+method Logs(service: string, lines: ?int) -> (entries: []object)
+
+# Bring a service up, waiting up to `timeout` seconds for it when given.
+method Start(service: string, timeout: ?int) -> (message: ?string, data: ?object)
+
+# Take a service down, waiting up to `timeout` seconds for it when given.
+method Stop(
+  service: string,
+  timeout: ?int,
+  reason: ?string
+) -> (message: ?string, data: ?object)
+
+# Restart a service and wait up to `timeout` seconds for the new process.
+method Restart(service: string, timeout: ?int) -> (message: ?string, data: ?object)
+
+method Reload(service: string) -> (message: ?string, data: ?object)
+method Once(service: string) -> (message: ?string, data: ?object)
+method Pause(service: string) -> (message: ?string, data: ?object)
+method Cont(service: string) -> (message: ?string, data: ?object)
+
+# Link a service into the enabled directory.
+method Enable(service: string) -> (message: ?string, data: ?object)
+
+# Remove a service from the enabled directory.
+method Disable(service: string, reason: ?string) -> (message: ?string, data: ?object)
+
+# The command ran and failed; `code` is the `error_code` runkitd reports.
+error CommandFailed (code: string, message: string)
+";
+
+const SERVICE_DESCRIPTION: &str = "\
+# The Varlink Service Interface is provided by every varlink service. It
+# describes the service and the interfaces it implements.
+interface org.varlink.service
+
+# Get a list of all the interfaces a service provides and information
+# about the implementation.
+method GetInfo() -> (
+  vendor: string,
+  product: string,
+  version: string,
+  url: string,
+  interfaces: []string
+)
+
+# Get the description of an interface that is implemented by this service.
+method GetInterfaceDescription(interface: string) -> (description: string)
+
+# The requested interface was not found.
+error InterfaceNotFound (interface: string)
+
+# The requested method was not found
+error MethodNotFound (method: string)
+
+# The interface defines the requested method, but the service does not
+# implement it.
+error MethodNotImplemented (method: string)
+
+# One of the passed parameters is invalid.
+error InvalidParameter (parameter: string)
+";
+
+#[derive(Debug, Deserialize)]
+struct Call {
+    method: String,
+    #[serde(default)]
+    parameters: Option<Map<String, Value>>,
+    #[serde(default)]
+    oneway: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Reply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    parameters: Value,
+}
+
+impl Reply {
+    fn ok(parameters: Value) -> Self {
+        Reply {
+            error: None,
+            parameters,
+        }
+    }
+
+    fn error(error: impl Into<String>, parameters: Value) -> Self {
+        Reply {
+            error: Some(error.into()),
+            parameters,
+        }
+    }
+
+    fn invalid_parameter(parameter: &str) -> Self {
+        Reply::error(
+            "org.varlink.service.InvalidParameter",
+            json!({ "parameter": parameter }),
+        )
+    }
+}
+
+/// Answer calls until the process is killed or idles out, with the same access rules
+/// and activation options as `runkitd serve`.
+pub fn serve(
+    context: &HelperContext,
+    socket: &Path,
+    group: Option<&str>,
+    activation: Activation,
+) -> Result<(), HelperError> {
+    accept_loop(socket, group, activation, |stream, peer| {
+        // Malformed messages end the connection, as the protocol asks.
+        let _ = handle_connection(context, stream, peer);
+    })
+}
+
+fn handle_connection(context: &HelperContext, stream: UnixStream, peer: Peer) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    while let Some(message) = read_message(&mut reader)? {
+        let call: Call = serde_json::from_slice(&message)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        let reply = answer(context, peer, &call);
+        if call.oneway {
+            continue;
+        }
+        let mut bytes = serde_json::to_vec(&reply).map_err(io::Error::other)?;
+        bytes.push(0);
+        writer.write_all(&bytes)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Read one NUL-terminated message; `None` once the peer has closed the connection.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut message = Vec::new();
+    reader
+        .take(MAX_MESSAGE_LEN + 1)
+        .read_until(0, &mut message)?;
+    match message.pop() {
+        None => Ok(None),
+        Some(0) => Ok(Some(message)),
+        Some(_) if message.len() as u64 >= MAX_MESSAGE_LEN => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("message exceeds the {MAX_MESSAGE_LEN} byte limit"),
+        )),
+        Some(_) => Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "connection closed mid-message",
+        )),
+    }
+}
+
+fn answer(context: &HelperContext, peer: Peer, call: &Call) -> Reply {
+    let empty = Map::new();
+    let parameters = call.parameters.as_ref().unwrap_or(&empty);
+    let Some((interface, method)) = call.method.rsplit_once('.') else {
+        return Reply::error(
+            "org.varlink.service.MethodNotFound",
+            json!({ "method": call.method }),
+        );
+    };
+    let reply = match interface {
+        SERVICE_INTERFACE => answer_service(method, parameters),
+        INTERFACE => answer_runkit(context, peer, method, parameters),
+        _ => {
+            return Reply::error(
+                "org.varlink.service.InterfaceNotFound",
+                json!({ "interface": interface }),
+            );
+        }
+    };
+    reply.unwrap_or_else(|| {
+        Reply::error(
+            "org.varlink.service.MethodNotFound",
+            json!({ "method": call.method }),
+        )
+    })
+}
+
+fn answer_service(method: &str, parameters: &Map<String, Value>) -> Option<Reply> {
+    let reply = match method {
+        "GetInfo" => Reply::ok(json!({
+            "vendor": "Runkit",
+            "product": "runkitd",
+            "version": env!("CARGO_PKG_VERSION"),
+            "url": "https://github.com/letdown2491/runkit",
+            "interfaces": [SERVICE_INTERFACE, INTERFACE],
+        })),
+        "GetInterfaceDescription" => match parameters.get("interface").and_then(Value::as_str) {
+            Some(SERVICE_INTERFACE) => Reply::ok(json!({ "description": SERVICE_DESCRIPTION })),
+            Some(INTERFACE) => Reply::ok(json!({ "description": DESCRIPTION })),
+            Some(interface) => Reply::error(
+                "org.varlink.service.InterfaceNotFound",
+                json!({ "interface": interface }),
+            ),
+            None => Reply::invalid_parameter("interface"),
+        },
+        _ => return None,
+    };
+    Some(reply)
+}
+
+/// Build the command line for `method`, run it, and shape the outcome the way the
+/// interface description declares.
+fn answer_runkit(
+    context: &HelperContext,
+    peer: Peer,
+    method: &str,
+    parameters: &Map<String, Value>,
+) -> Option<Reply> {
+    let arguments = match arguments(method, parameters)? {
+        Ok(arguments) => arguments,
+        Err(parameter) => return Some(Reply::invalid_parameter(parameter)),
+    };
+    let (subcommand, params) = arguments.split_first()?;
+    let params: Vec<&str> = params.iter().map(String::as_str).collect();
+    let result = parse_command(subcommand, &params)
+        .map_err(|err| HelperError::Other(err.render().to_string().trim().to_string()))
+        .and_then(|command| match unavailable(&command) {
+            Some(reason) => Err(HelperError::Other(reason.to_string())),
+            None => peer.authorize(&command).and_then(|()| context.run(command)),
+        });
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            return Some(Reply::error(
+                "io.runkit.CommandFailed",
+                json!({ "code": err.code(), "message": err.to_string() }),
+            ));
+        }
+    };
+    let data = outcome.data.unwrap_or(Value::Null);
+    Some(Reply::ok(match method {
+        "ListServices" => json!({ "services": data }),
+        "Status" => json!({ "info": data }),
+        "Logs" => json!({ "entries": data }),
+        _ => json!({ "message": outcome.message, "data": data }),
+    }))
+}
+
+/// The subcommand and arguments `method` stands for, `None` for an unknown method, or
+/// the name of the parameter that is missing or has the wrong type.
+fn arguments(
+    method: &str,
+    parameters: &Map<String, Value>,
+) -> Option<Result<Vec<String>, &'static str>> {
+    let subcommand = match method {
+        "ListServices" => "list",
+        "Status" => "status",
+        "Logs" => "logs",
+        "Start" => "start",
+        "Stop" => "stop",
+        "Restart" => "restart",
+        "Reload" => "reload",
+        "Once" => "once",
+        "Pause" => "pause",
+        "Cont" => "cont",
+        "Enable" => "enable",
+        "Disable" => "disable",
+        _ => return None,
+    };
+    let mut arguments = vec![subcommand.to_string()];
+    if method == "ListServices" {
+        return Some(Ok(arguments));
+    }
+    match parameters.get("service").and_then(Value::as_str) {
+        Some(service) => arguments.push(service.to_string()),
+        None => return Some(Err("service")),
+    }
+    let options: &[(&'static str, &str)] = match method {
+        "Logs" => &[("lines", "--lines")],
+        "Start" | "Restart" => &[("timeout", "--timeout")],
+        "Stop" => &[("timeout", "--timeout"), ("reason", "--reason")],
+        "Disable" => &[("reason", "--reason")],
+        _ => &[],
+    };
+    for (name, flag) in options {
+        let value = match parameters.get(*name) {
+            None | Some(Value::Null) => continue,
+            Some(Value::String(value)) if *name == "reason" => value.clone(),
+            Some(Value::Number(value)) if *name != "reason" && value.is_u64() => value.to_string(),
+            Some(_) => return Some(Err(name)),
+        };
+        arguments.extend([flag.to_string(), value]);
+    }
+    Some(Ok(arguments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_MESSAGE_LEN, arguments, read_message};
+    use serde_json::{Map, Value, json};
+    use std::io::{BufReader, Cursor, ErrorKind, Read};
+
+    #[test]
+    fn splits_messages_on_nul() {
+        let mut reader = Cursor::new(b"{\"method\":\"a\"}\0{}\0".to_vec());
+        assert_eq!(
+            read_message(&mut reader).unwrap().unwrap(),
+            b"{\"method\":\"a\"}"
+        );
+        assert_eq!(read_message(&mut reader).unwrap().unwrap(), b"{}");
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+
+        let mut truncated = Cursor::new(b"{\"method\"".to_vec());
+        let err = read_message(&mut truncated).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rejects_messages_over_the_limit() {
+        let endless = std::io::repeat(b' ').take(MAX_MESSAGE_LEN + 16);
+        let err = read_message(&mut BufReader::new(endless)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    fn parameters(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn maps_parameters_to_arguments() {
+        let mapped = |method: &str, value: Value| arguments(method, &parameters(value));
+        assert_eq!(
+            mapped("ListServices", json!({})),
+            Some(Ok(vec!["list".into()]))
+        );
+        assert_eq!(
+            mapped("Logs", json!({ "service": "sshd", "lines": 20 })),
+            Some(Ok(["logs", "sshd", "--lines", "20"]
+                .map(String::from)
+                .to_vec()))
+        );
+        assert_eq!(
+            mapped(
+                "Stop",
+                json!({ "service": "sshd", "timeout": 3, "reason": "maintenance" })
+            ),
+            Some(Ok([
+                "stop",
+                "sshd",
+                "--timeout",
+                "3",
+                "--reason",
+                "maintenance"
+            ]
+            .map(String::from)
+            .to_vec()))
+        );
+        assert_eq!(
+            mapped("Start", json!({ "service": "sshd", "timeout": null })),
+            Some(Ok(vec!["start".into(), "sshd".into()]))
+        );
+        assert_eq!(mapped("Frobnicate", json!({})), None);
+    }
+
+    #[test]
+    fn names_the_bad_parameter() {
+        let mapped = |method: &str, value: Value| arguments(method, &parameters(value));
+        assert_eq!(mapped("Status", json!({})), Some(Err("service")));
+        assert_eq!(
+            mapped("Status", json!({ "service": 7 })),
+            Some(Err("service"))
+        );
+        assert_eq!(
+            mapped("Logs", json!({ "service": "sshd", "lines": -1 })),
+            Some(Err("lines"))
+        );
+        assert_eq!(
+            mapped("Disable", json!({ "service": "sshd", "reason": 1 })),
+            Some(Err("reason"))
+        );
+        assert_eq!(
+            mapped("Restart", json!({ "service": "sshd", "timeout": "5" })),
+            Some(Err("timeout"))
+        );
+    }
+}